@@ -43,8 +43,8 @@ impl FromStr for TrackMode {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "MODE1/2352" => Ok(Self::Mode1),
-            "MODE2/2352" => Ok(Self::Mode2),
+            "MODE1/2352" | "MODE1/2048" => Ok(Self::Mode1),
+            "MODE2/2352" | "MODE2/2336" => Ok(Self::Mode2),
             "AUDIO" => Ok(Self::Audio),
             _ => Err(format!("unsupported CD track type: {s}")),
         }
@@ -0,0 +1,208 @@
+//! Game identification from the disc's SYSTEM.CNF boot file name and license sector
+
+use crate::CdRomResult;
+use crate::iso9660::{self, IsoFilesystem, MODE_2_SECTOR_LEN};
+use crate::reader::CdRom;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::OnceLock;
+
+// The license string ("Licensed by Sony Computer Entertainment ...") is in the system area
+const LICENSE_SECTOR_LBA: u32 = 4;
+
+const GAME_DATABASE: &str = include_str!("discinfo/gamedb.tsv");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiscRegion {
+    NtscU,
+    NtscJ,
+    Pal,
+}
+
+impl DiscRegion {
+    /// Guess the region from a serial's prefix, e.g. `SLUS` or `SCES`
+    #[must_use]
+    pub fn from_serial(serial: &str) -> Option<Self> {
+        let prefix = serial.get(..4)?;
+        match prefix.to_ascii_uppercase().as_str() {
+            "SCUS" | "SLUS" | "PAPX" => Some(Self::NtscU),
+            "SCPS" | "SLPS" | "SLPM" | "SCPM" | "SIPS" => Some(Self::NtscJ),
+            "SCES" | "SLES" | "SCED" | "SLED" => Some(Self::Pal),
+            _ => None,
+        }
+    }
+
+    /// Determine the region from the license string in the disc's system area, which is what the
+    /// drive checks against the console's region. Returns `None` for unlicensed discs.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if track 1 is not a data track or if reading from the disc fails.
+    pub fn read_license(disc: &mut CdRom) -> CdRomResult<Option<Self>> {
+        let mut license = [0; MODE_2_SECTOR_LEN];
+        iso9660::read_mode_2_sector(disc, LICENSE_SECTOR_LBA, &mut license)?;
+        Ok(Self::from_license(&license))
+    }
+
+    /// The last character of the `SCEx` string that the drive reads from the disc's lead-in
+    #[must_use]
+    pub fn scex_letter(self) -> u8 {
+        match self {
+            Self::NtscU => b'A',
+            Self::NtscJ => b'I',
+            Self::Pal => b'E',
+        }
+    }
+
+    fn from_license(license: &[u8]) -> Option<Self> {
+        const LICENSES: &[(&[u8], DiscRegion)] = &[
+            (b"Entertainment Amer", DiscRegion::NtscU),
+            (b"Entertainment Euro", DiscRegion::Pal),
+            (b"Entertainment Inc", DiscRegion::NtscJ),
+        ];
+
+        LICENSES.iter().find_map(|&(text, region)| {
+            license.windows(text.len()).any(|window| window == text).then_some(region)
+        })
+    }
+}
+
+impl Display for DiscRegion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NtscU => write!(f, "NTSC-U"),
+            Self::NtscJ => write!(f, "NTSC-J"),
+            Self::Pal => write!(f, "PAL"),
+        }
+    }
+}
+
+/// Identifying information for a PS1 game disc. Every field is optional because homebrew and
+/// prototype discs often lack a license sector or a serial-style boot file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiscInfo {
+    /// Boot file path from SYSTEM.CNF, e.g. `cdrom:\SLUS_012.34;1`
+    pub boot_file: Option<String>,
+    /// Serial in the form printed on the disc label, e.g. `SLUS-01234`
+    pub serial: Option<String>,
+    pub region: Option<DiscRegion>,
+    /// Title from the bundled game database
+    pub title: Option<String>,
+}
+
+impl DiscInfo {
+    /// Identify the game on a disc. Read errors are logged and leave the corresponding fields
+    /// unset.
+    #[must_use]
+    pub fn read(disc: &mut CdRom) -> Self {
+        let mut info = Self::default();
+
+        match IsoFilesystem::open(disc) {
+            Ok(filesystem) => {
+                info.boot_file = read_boot_file(disc, &filesystem);
+                info.serial = info
+                    .boot_file
+                    .as_deref()
+                    .and_then(normalize_serial)
+                    .or_else(|| normalize_serial(filesystem.volume_id()));
+            }
+            Err(err) => log::warn!("Unable to read ISO 9660 filesystem from disc: {err}"),
+        }
+
+        info.region = match DiscRegion::read_license(disc) {
+            Ok(region) => region,
+            Err(err) => {
+                log::warn!("Unable to read license sector from disc: {err}");
+                None
+            }
+        };
+        info.region =
+            info.region.or_else(|| info.serial.as_deref().and_then(DiscRegion::from_serial));
+
+        info.title = info.serial.as_deref().and_then(lookup_title).map(String::from);
+
+        info
+    }
+}
+
+fn read_boot_file(disc: &mut CdRom, filesystem: &IsoFilesystem) -> Option<String> {
+    let contents = match filesystem.find(disc, "SYSTEM.CNF") {
+        Ok(Some(file)) => file.read(disc),
+        Ok(None) => return None,
+        Err(err) => Err(err),
+    };
+
+    let contents = match contents {
+        Ok(contents) => contents,
+        Err(err) => {
+            log::warn!("Unable to read SYSTEM.CNF from disc: {err}");
+            return None;
+        }
+    };
+
+    String::from_utf8_lossy(&contents).lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "BOOT").then(|| value.trim().into())
+    })
+}
+
+/// Convert a boot file name such as `cdrom:\SLUS_012.34;1` to the serial printed on the disc
+/// label (`SLUS-01234`). Returns `None` if the file name is not serial-shaped.
+#[must_use]
+pub fn normalize_serial(boot_file: &str) -> Option<String> {
+    let file_name = boot_file.rsplit(['\\', '/', ':']).next().unwrap_or(boot_file).trim();
+    let file_name = file_name.strip_suffix(";1").unwrap_or(file_name);
+
+    // Usually PPPP_NNN.NN, but some boot files use the label form PPPP-NNNNN
+    let (prefix, number) = file_name.split_at_checked(4)?;
+    let number = number.strip_prefix(['_', '-'])?;
+    let digits: String = match number.split_once('.') {
+        Some((high, low)) if high.len() == 3 && low.len() == 2 => [high, low].concat(),
+        None if number.len() == 5 => number.into(),
+        _ => return None,
+    };
+
+    if !prefix.bytes().all(|b| b.is_ascii_alphabetic())
+        || !digits.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    Some(format!("{}-{digits}", prefix.to_ascii_uppercase()))
+}
+
+/// Look up a game's title in the bundled game database by serial, e.g. `SCUS-94163`
+#[must_use]
+pub fn lookup_title(serial: &str) -> Option<&'static str> {
+    static DATABASE: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
+
+    let database = DATABASE.get_or_init(|| {
+        GAME_DATABASE
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once('\t'))
+            .collect()
+    });
+
+    database.get(serial).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serial_normalization() {
+        assert_eq!(normalize_serial(r"cdrom:\SLUS_012.34;1").as_deref(), Some("SLUS-01234"));
+        assert_eq!(normalize_serial("cdrom:SCES_008.67;1").as_deref(), Some("SCES-00867"));
+        assert_eq!(normalize_serial(r"cdrom:\GAME\slps_007.00").as_deref(), Some("SLPS-00700"));
+        assert_eq!(normalize_serial("SLUS-01234").as_deref(), Some("SLUS-01234"));
+        assert_eq!(normalize_serial(r"cdrom:\PSX.EXE;1"), None);
+    }
+
+    #[test]
+    fn game_database() {
+        assert_eq!(lookup_title("SCUS-94163"), Some("Final Fantasy VII (Disc 1)"));
+        assert_eq!(lookup_title("SLUS-99999"), None);
+    }
+}
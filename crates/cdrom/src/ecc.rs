@@ -0,0 +1,345 @@
+//! Reed-Solomon product code (RSPC) error correction for Mode 1 and Mode 2 Form 1 sectors, as
+//! specified in ECMA-130 Annex A
+//!
+//! The 2340 bytes following the sync pattern are treated as a matrix of 26 rows by 86 byte columns
+//! (43 little-endian 16-bit words, with the low and high bytes forming separate columns). Each
+//! column is a P codeword of 24 data bytes and 2 parity bytes, and each of the 52 diagonals is a Q
+//! codeword of 43 bytes plus 2 parity bytes. Both are RS codes over GF(2^8) that can correct a
+//! single byte error per codeword; alternating P and Q passes can correct more complex patterns.
+
+use crate::cue::TrackMode;
+use crate::reader::Mode2Form;
+use std::ops::Range;
+
+const HEADER_LOCATION: Range<usize> = 12..16;
+
+// Codeword indices are relative to the end of the sync pattern
+const ECC_DATA_OFFSET: usize = 12;
+
+const P_CODEWORDS: usize = 86;
+const P_CODEWORD_LEN: usize = 26;
+
+const Q_CODEWORDS: usize = 52;
+const Q_DATA_LEN: usize = 43;
+const Q_CODEWORD_LEN: usize = Q_DATA_LEN + 2;
+const Q_DATA_AREA_LEN: usize = P_CODEWORDS * P_CODEWORD_LEN;
+
+// Give up if alternating P and Q passes have not converged after this many rounds
+const MAX_CORRECTION_ROUNDS: usize = 4;
+
+// GF(2^8) with primitive polynomial x^8 + x^4 + x^3 + x^2 + 1
+const GF_POLYNOMIAL: u16 = 0x11D;
+
+const GF_EXP: [u8; 512] = {
+    let mut exp = [0; 512];
+    let mut value: u16 = 1;
+    let mut i = 0;
+    while i < 512 {
+        exp[i] = value as u8;
+        value <<= 1;
+        if value & 0x100 != 0 {
+            value ^= GF_POLYNOMIAL;
+        }
+        i += 1;
+    }
+    exp
+};
+
+const GF_LOG: [u8; 256] = {
+    let mut log = [0; 256];
+    let mut i = 0;
+    while i < 255 {
+        log[GF_EXP[i] as usize] = i as u8;
+        i += 1;
+    }
+    log
+};
+
+fn gf_mul_alpha(value: u8) -> u8 {
+    let reduction = if value & 0x80 != 0 { GF_POLYNOMIAL as u8 } else { 0 };
+    (value << 1) ^ reduction
+}
+
+fn gf_div(a: u8, b: u8) -> u8 {
+    if a == 0 {
+        return 0;
+    }
+
+    GF_EXP[usize::from(GF_LOG[usize::from(a)]) + 255 - usize::from(GF_LOG[usize::from(b)])]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Parity {
+    P,
+    Q,
+}
+
+impl Parity {
+    fn codewords(self) -> usize {
+        match self {
+            Self::P => P_CODEWORDS,
+            Self::Q => Q_CODEWORDS,
+        }
+    }
+
+    fn codeword_len(self) -> usize {
+        match self {
+            Self::P => P_CODEWORD_LEN,
+            Self::Q => Q_CODEWORD_LEN,
+        }
+    }
+
+    // Sector offset of byte i in the given codeword. Parity bytes are the last 2 bytes of each
+    // codeword
+    fn index(self, codeword: usize, i: usize) -> usize {
+        let offset = match self {
+            // P codewords are columns
+            Self::P => codeword + P_CODEWORDS * i,
+            // Q codewords are diagonals that wrap around the data and P parity, with Q parity after
+            Self::Q => match i {
+                Q_DATA_LEN => Q_DATA_AREA_LEN + codeword,
+                _ if i > Q_DATA_LEN => Q_DATA_AREA_LEN + Q_CODEWORDS + codeword,
+                _ => {
+                    ((codeword >> 1) * P_CODEWORDS + (codeword & 1) + (P_CODEWORDS + 2) * i)
+                        % Q_DATA_AREA_LEN
+                }
+            },
+        };
+        ECC_DATA_OFFSET + offset
+    }
+
+    // Returns S0 (sum of all bytes) and S1 (sum of bytes weighted by alpha^(n-1-i))
+    fn syndromes(self, sector: &[u8], codeword: usize) -> (u8, u8) {
+        (0..self.codeword_len()).fold((0, 0), |(s0, s1), i| {
+            let byte = sector[self.index(codeword, i)];
+            (s0 ^ byte, gf_mul_alpha(s1) ^ byte)
+        })
+    }
+
+    fn generate(self, sector: &mut [u8]) {
+        let len = self.codeword_len();
+        for codeword in 0..self.codewords() {
+            sector[self.index(codeword, len - 2)] = 0;
+            sector[self.index(codeword, len - 1)] = 0;
+
+            // Choose parity bytes p0 and p1 such that both syndromes are 0:
+            //   S0 ^ p0 ^ p1 = 0
+            //   S1 ^ alpha*p0 ^ p1 = 0
+            let (s0, s1) = self.syndromes(sector, codeword);
+            let p0 = gf_div(s0 ^ s1, 3);
+            sector[self.index(codeword, len - 2)] = p0;
+            sector[self.index(codeword, len - 1)] = s0 ^ p0;
+        }
+    }
+
+    fn is_valid(self, sector: &[u8]) -> bool {
+        (0..self.codewords()).all(|codeword| self.syndromes(sector, codeword) == (0, 0))
+    }
+
+    /// Correct single-byte errors in every codeword. Returns the number of corrected codewords and
+    /// the number of codewords with errors that could not be corrected.
+    fn correct(self, sector: &mut [u8]) -> (usize, usize) {
+        let len = self.codeword_len();
+
+        let mut corrected = 0;
+        let mut uncorrectable = 0;
+        for codeword in 0..self.codewords() {
+            let (s0, s1) = self.syndromes(sector, codeword);
+            if (s0, s1) == (0, 0) {
+                continue;
+            }
+
+            if s0 == 0 || s1 == 0 {
+                uncorrectable += 1;
+                continue;
+            }
+
+            // A single error e at position i produces S0 = e and S1 = e * alpha^(n-1-i)
+            let power = (255 + usize::from(GF_LOG[usize::from(s1)])
+                - usize::from(GF_LOG[usize::from(s0)]))
+                % 255;
+            if power >= len {
+                uncorrectable += 1;
+                continue;
+            }
+
+            sector[self.index(codeword, len - 1 - power)] ^= s0;
+            corrected += 1;
+        }
+
+        (corrected, uncorrectable)
+    }
+}
+
+/// Whether sectors of the given mode have P/Q parity. For Mode 2, this depends on the sector's
+/// submode.
+///
+/// # Panics
+///
+/// This function will panic if `sector`'s length is less than 2352.
+#[must_use]
+pub fn has_ecc(mode: TrackMode, sector: &[u8]) -> bool {
+    match mode {
+        TrackMode::Mode1 => true,
+        TrackMode::Mode2 => Mode2Form::parse(sector) == Mode2Form::One,
+        TrackMode::Audio => false,
+    }
+}
+
+/// Check the sector's P and Q parity. Sectors without parity are always valid.
+///
+/// # Panics
+///
+/// This function will panic if `sector`'s length is less than 2352.
+#[must_use]
+pub fn is_valid(mode: TrackMode, sector: &[u8]) -> bool {
+    if !has_ecc(mode, sector) {
+        return true;
+    }
+
+    with_ecc_header(mode, sector.to_vec(), |sector| {
+        Parity::P.is_valid(sector) && Parity::Q.is_valid(sector)
+    })
+}
+
+/// Attempt to correct errors in a sector using its P and Q parity. Returns whether the parity is
+/// valid after correction; if this returns `false`, the sector may have been partially modified.
+///
+/// # Panics
+///
+/// This function will panic if `sector`'s length is less than 2352.
+pub fn correct(mode: TrackMode, sector: &mut [u8]) -> bool {
+    if !has_ecc(mode, sector) {
+        return true;
+    }
+
+    with_ecc_header(mode, sector, |sector| {
+        for _ in 0..MAX_CORRECTION_ROUNDS {
+            let (p_corrected, p_uncorrectable) = Parity::P.correct(sector);
+            let (q_corrected, q_uncorrectable) = Parity::Q.correct(sector);
+
+            // Each pass can make the other pass's uncorrectable codewords correctable, so keep
+            // going until a round makes no progress
+            if p_corrected == 0 && q_corrected == 0 {
+                return p_uncorrectable == 0 && q_uncorrectable == 0;
+            }
+        }
+
+        Parity::P.is_valid(sector) && Parity::Q.is_valid(sector)
+    })
+}
+
+/// Recompute the sector's P and Q parity from its contents. This should be done after the EDC is
+/// up to date, because the parity covers the EDC. Does nothing for sectors without parity.
+///
+/// # Panics
+///
+/// This function will panic if `sector`'s length is less than 2352.
+pub fn regenerate(mode: TrackMode, sector: &mut [u8]) {
+    if !has_ecc(mode, sector) {
+        return;
+    }
+
+    generate(mode, sector);
+}
+
+// Compute parity without checking the submode, for callers that already know the sector's form
+pub(crate) fn generate(mode: TrackMode, sector: &mut [u8]) {
+    with_ecc_header(mode, sector, |sector| {
+        Parity::P.generate(sector);
+        Parity::Q.generate(sector);
+    });
+}
+
+// Mode 2 parity is computed as if the header were all zeros, so that the parity does not depend on
+// the sector's address
+fn with_ecc_header<S: AsMut<[u8]>, T>(
+    mode: TrackMode,
+    mut sector: S,
+    f: impl FnOnce(&mut [u8]) -> T,
+) -> T {
+    let sector = sector.as_mut();
+    if mode != TrackMode::Mode2 {
+        return f(sector);
+    }
+
+    let header: [u8; 4] = sector[HEADER_LOCATION].try_into().unwrap();
+    sector[HEADER_LOCATION].fill(0);
+    let result = f(sector);
+    sector[HEADER_LOCATION].copy_from_slice(&header);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_sector(mode: TrackMode) -> Vec<u8> {
+        let mut sector = vec![0; crate::BYTES_PER_SECTOR as usize];
+        sector[1..11].fill(0xFF);
+        sector[12..16].copy_from_slice(&[
+            0x00,
+            0x02,
+            0x16,
+            if mode == TrackMode::Mode1 { 1 } else { 2 },
+        ]);
+        for (i, byte) in sector[16..0x818].iter_mut().enumerate() {
+            *byte = (i * 7 + (i >> 8)) as u8;
+        }
+        if mode == TrackMode::Mode2 {
+            // Form 1 subheader
+            sector[16..24].copy_from_slice(&[0, 0, 8, 0, 0, 0, 8, 0]);
+        }
+
+        crate::reader::regenerate_edc(mode, &mut sector);
+        regenerate(mode, &mut sector);
+        sector
+    }
+
+    #[test]
+    fn regenerated_parity_is_valid() {
+        for mode in [TrackMode::Mode1, TrackMode::Mode2] {
+            let sector = test_sector(mode);
+            assert!(is_valid(mode, &sector));
+            assert!(sector[0x81C..0x930].iter().any(|&byte| byte != 0));
+        }
+    }
+
+    #[test]
+    fn mode_2_parity_ignores_header() {
+        let mut sector = test_sector(TrackMode::Mode2);
+        sector[12..15].copy_from_slice(&[0x12, 0x34, 0x56]);
+        assert!(is_valid(TrackMode::Mode2, &sector));
+    }
+
+    #[test]
+    fn corrects_errors() {
+        for mode in [TrackMode::Mode1, TrackMode::Mode2] {
+            let expected = test_sector(mode);
+
+            let mut sector = expected.clone();
+            sector[100] ^= 0x5A;
+            assert!(!is_valid(mode, &sector));
+            assert!(correct(mode, &mut sector));
+            assert_eq!(sector, expected);
+
+            // Multiple errors in the same column are corrected by Q and then P
+            let mut sector = expected.clone();
+            sector[100] ^= 0x01;
+            sector[100 + P_CODEWORDS] ^= 0x80;
+            sector[100 + 5 * P_CODEWORDS] ^= 0xFF;
+            sector[2000] ^= 0x33;
+            assert!(correct(mode, &mut sector));
+            assert_eq!(sector, expected);
+        }
+    }
+
+    #[test]
+    fn stripped_parity_is_not_correctable() {
+        let mut sector = test_sector(TrackMode::Mode1);
+        sector[0x81C..0x930].fill(0);
+        assert!(!is_valid(TrackMode::Mode1, &sector));
+        assert!(!correct(TrackMode::Mode1, &mut sector.clone()));
+    }
+}
@@ -0,0 +1,468 @@
+//! Read-only ISO 9660 filesystem support for data track 1, including the CD-ROM XA extensions
+//! used by PS1 discs
+
+use crate::cdtime::CdTime;
+use crate::cue::TrackType;
+use crate::reader::CdRom;
+use crate::{CdRomError, CdRomResult};
+use bincode::{Decode, Encode};
+
+/// Length of user data in a Mode 1 or Mode 2 Form 1 sector
+pub const USER_DATA_LEN: usize = 2048;
+
+/// Length of a Mode 2 sector after the sync and header bytes: 8 subheader bytes, followed by
+/// either 2048 data bytes + EDC/ECC (Form 1) or 2324 data bytes + EDC (Form 2)
+pub const MODE_2_SECTOR_LEN: usize = 2336;
+
+const SECTOR_HEADER_LEN: usize = 16;
+const MODE_2_SUBHEADER_LEN: usize = 8;
+
+// The primary volume descriptor is always at LBA 16; descriptors continue until a terminator
+const VOLUME_DESCRIPTORS_LBA: u32 = 16;
+const VOLUME_DESCRIPTOR_PRIMARY: u8 = 1;
+const VOLUME_DESCRIPTOR_TERMINATOR: u8 = 255;
+const STANDARD_IDENTIFIER: &[u8] = b"CD001";
+
+const PVD_VOLUME_ID: std::ops::Range<usize> = 40..72;
+const PVD_VOLUME_SPACE_SIZE: usize = 80;
+const PVD_ROOT_RECORD: usize = 156;
+
+const RECORD_MIN_LEN: usize = 33;
+const RECORD_FLAG_DIRECTORY: u8 = 1 << 1;
+
+// Guard against malformed directory sizes
+const MAX_DIRECTORY_LEN: u32 = 1024 * 1024;
+
+/// CD-ROM XA attributes, stored in the system use area of a directory record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct XaAttributes(u16);
+
+impl XaAttributes {
+    const SIGNATURE: &'static [u8] = b"XA";
+    const FORM_1: u16 = 1 << 11;
+    const FORM_2: u16 = 1 << 12;
+    const INTERLEAVED: u16 = 1 << 13;
+    const CDDA: u16 = 1 << 14;
+
+    fn parse(system_use: &[u8]) -> Option<Self> {
+        if system_use.len() < 14 || &system_use[6..8] != Self::SIGNATURE {
+            return None;
+        }
+
+        Some(Self(u16::from_be_bytes([system_use[4], system_use[5]])))
+    }
+
+    #[must_use]
+    pub fn raw(self) -> u16 {
+        self.0
+    }
+
+    #[must_use]
+    pub fn is_form_1(self) -> bool {
+        self.0 & Self::FORM_1 != 0
+    }
+
+    /// Form 2 files (e.g. XA audio and STR video) have 2324 bytes of data per sector instead of
+    /// 2048, and should be read using [`DirectoryEntry::read_mode_2_sectors`]
+    #[must_use]
+    pub fn is_form_2(self) -> bool {
+        self.0 & Self::FORM_2 != 0
+    }
+
+    #[must_use]
+    pub fn is_interleaved(self) -> bool {
+        self.0 & Self::INTERLEAVED != 0
+    }
+
+    #[must_use]
+    pub fn is_cdda(self) -> bool {
+        self.0 & Self::CDDA != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct DirectoryEntry {
+    /// File name with the version suffix (e.g. `;1`) removed
+    pub name: String,
+    pub lba: u32,
+    pub size: u32,
+    pub is_directory: bool,
+    pub xa_attributes: Option<XaAttributes>,
+}
+
+impl DirectoryEntry {
+    fn parse(record: &[u8]) -> Self {
+        let name_len = record[32] as usize;
+        let name = &record[RECORD_MIN_LEN..(RECORD_MIN_LEN + name_len).min(record.len())];
+
+        // The name is padded to an even record offset, and the system use area follows
+        let system_use_start = RECORD_MIN_LEN + name_len + (1 - name_len % 2);
+        let xa_attributes = record.get(system_use_start..).and_then(XaAttributes::parse);
+
+        Self {
+            name: String::from_utf8_lossy(strip_version(name)).into_owned(),
+            lba: read_u32_le(&record[2..]),
+            size: read_u32_le(&record[10..]),
+            is_directory: record[25] & RECORD_FLAG_DIRECTORY != 0,
+            xa_attributes,
+        }
+    }
+
+    /// Number of sectors occupied by this file or directory
+    #[must_use]
+    pub fn sector_count(&self) -> u32 {
+        self.size.div_ceil(USER_DATA_LEN as u32)
+    }
+
+    // LBA of the given sector within this entry, which can overflow if the entry is corrupt
+    fn sector_lba(&self, sector_offset: u32) -> CdRomResult<u32> {
+        self.lba
+            .checked_add(sector_offset)
+            .ok_or_else(|| CdRomError::IsoEntryOutOfRange { name: self.name.clone() })
+    }
+
+    /// Read the entire contents of a file.
+    ///
+    /// # Errors
+    ///
+    /// Will propagate any error encountered while reading from the disc.
+    pub fn read(&self, disc: &mut CdRom) -> CdRomResult<Vec<u8>> {
+        self.read_range(disc, 0, self.size)
+    }
+
+    /// Read up to `len` bytes starting at `offset` within a file. The returned data is shorter
+    /// than `len` if the range extends past the end of the file.
+    ///
+    /// # Errors
+    ///
+    /// Will propagate any error encountered while reading from the disc.
+    pub fn read_range(&self, disc: &mut CdRom, offset: u32, len: u32) -> CdRomResult<Vec<u8>> {
+        const SECTOR_LEN: u32 = USER_DATA_LEN as u32;
+
+        let end = offset.saturating_add(len).min(self.size);
+        let mut data = Vec::with_capacity(end.saturating_sub(offset) as usize);
+
+        let mut sector = [0; USER_DATA_LEN];
+        let mut position = offset;
+        while position < end {
+            read_user_data(disc, self.sector_lba(position / SECTOR_LEN)?, &mut sector)?;
+
+            let start = position % SECTOR_LEN;
+            let chunk_len = (SECTOR_LEN - start).min(end - position);
+            data.extend_from_slice(&sector[start as usize..(start + chunk_len) as usize]);
+            position += chunk_len;
+        }
+
+        Ok(data)
+    }
+
+    /// Read every sector of a file as a raw Mode 2 sector, including the subheader. This is the
+    /// only way to read the full contents of Form 2 files such as XA audio and STR video.
+    ///
+    /// # Errors
+    ///
+    /// Will propagate any error encountered while reading from the disc.
+    pub fn read_mode_2_sectors(
+        &self,
+        disc: &mut CdRom,
+    ) -> CdRomResult<Vec<[u8; MODE_2_SECTOR_LEN]>> {
+        (0..self.sector_count())
+            .map(|i| {
+                let mut sector = [0; MODE_2_SECTOR_LEN];
+                read_mode_2_sector(disc, self.sector_lba(i)?, &mut sector)?;
+                Ok(sector)
+            })
+            .collect()
+    }
+
+    /// List the entries in a directory. The `.` and `..` entries are skipped. Returns an empty
+    /// list if this entry is not a directory.
+    ///
+    /// # Errors
+    ///
+    /// Will propagate any error encountered while reading from the disc.
+    pub fn list(&self, disc: &mut CdRom) -> CdRomResult<Vec<DirectoryEntry>> {
+        if !self.is_directory {
+            return Ok(Vec::new());
+        }
+
+        let data = self.read_range(disc, 0, self.size.min(MAX_DIRECTORY_LEN))?;
+        Ok(parse_directory(&data))
+    }
+
+    /// Find a file or directory by path relative to this directory, e.g. `MOVIE\INTRO.STR;1`.
+    /// Matching is case-insensitive, and version suffixes are optional. Returns `None` if the
+    /// path does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Will propagate any error encountered while reading from the disc.
+    pub fn find(&self, disc: &mut CdRom, path: &str) -> CdRomResult<Option<DirectoryEntry>> {
+        let mut entry = self.clone();
+
+        for component in path.split(['\\', '/']).filter(|component| !component.is_empty()) {
+            let component = strip_version(component.as_bytes());
+            let Some(child) = entry
+                .list(disc)?
+                .into_iter()
+                .find(|child| child.name.as_bytes().eq_ignore_ascii_case(component))
+            else {
+                return Ok(None);
+            };
+
+            entry = child;
+        }
+
+        Ok(Some(entry))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IsoFilesystem {
+    volume_id: String,
+    volume_sectors: u32,
+    root: DirectoryEntry,
+}
+
+impl IsoFilesystem {
+    /// Read the primary volume descriptor from data track 1.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if track 1 is not a data track, if the disc does not have a primary
+    /// volume descriptor, or if reading from the disc fails.
+    pub fn open(disc: &mut CdRom) -> CdRomResult<Self> {
+        let mut sector = [0; USER_DATA_LEN];
+        for lba in VOLUME_DESCRIPTORS_LBA.. {
+            read_user_data(disc, lba, &mut sector)?;
+
+            if &sector[1..6] != STANDARD_IDENTIFIER {
+                break;
+            }
+
+            match sector[0] {
+                VOLUME_DESCRIPTOR_PRIMARY => {
+                    let volume_id = String::from_utf8_lossy(&sector[PVD_VOLUME_ID]);
+                    let volume_sectors = read_u32_le(&sector[PVD_VOLUME_SPACE_SIZE..]);
+
+                    return Ok(Self {
+                        volume_id: volume_id.trim_end_matches([' ', '\0']).into(),
+                        volume_sectors,
+                        root: DirectoryEntry::parse(&sector[PVD_ROOT_RECORD..]),
+                    });
+                }
+                VOLUME_DESCRIPTOR_TERMINATOR => break,
+                _ => {}
+            }
+        }
+
+        Err(CdRomError::IsoMissingVolumeDescriptor)
+    }
+
+    #[must_use]
+    pub fn volume_id(&self) -> &str {
+        &self.volume_id
+    }
+
+    /// Size of the filesystem in 2048-byte sectors
+    #[must_use]
+    pub fn volume_sectors(&self) -> u32 {
+        self.volume_sectors
+    }
+
+    #[must_use]
+    pub fn root(&self) -> &DirectoryEntry {
+        &self.root
+    }
+
+    /// Find a file or directory by absolute path, e.g. `\SLUS_000.01;1`. An empty path or `\`
+    /// returns the root directory.
+    ///
+    /// # Errors
+    ///
+    /// Will propagate any error encountered while reading from the disc.
+    pub fn find(&self, disc: &mut CdRom, path: &str) -> CdRomResult<Option<DirectoryEntry>> {
+        self.root.find(disc, path)
+    }
+}
+
+/// Read a raw sector from data track 1 by logical block address (LBA 0 = 00:02:00), excluding the
+/// 12 sync bytes and 4 header bytes
+fn read_track_1_sector(disc: &mut CdRom, lba: u32) -> CdRomResult<[u8; 2352]> {
+    let track = disc.cue().track(1);
+    if track.track_type != TrackType::Data {
+        return Err(CdRomError::IsoNoDataTrack);
+    }
+
+    let time = lba
+        .checked_add(CdTime::SECTOR_0_START.to_sector_number())
+        .filter(|&sector_number| sector_number < CdTime::MAX_SECTORS)
+        .map(CdTime::from_sector_number)
+        .filter(|&time| time >= track.start_time && time < track.end_time)
+        .ok_or(CdRomError::IsoSectorOutOfRange { lba })?;
+    let relative_time = time - track.start_time;
+
+    let mut sector = [0; 2352];
+    disc.read_sector(1, relative_time, &mut sector)?;
+    Ok(sector)
+}
+
+/// Read the 2048 bytes of user data from a Mode 1 or Mode 2 Form 1 sector in data track 1.
+///
+/// # Errors
+///
+/// Will return an error if track 1 is not a data track, if `lba` is past the end of track 1, or if
+/// reading from the disc fails.
+pub fn read_user_data(
+    disc: &mut CdRom,
+    lba: u32,
+    out: &mut [u8; USER_DATA_LEN],
+) -> CdRomResult<()> {
+    let sector = read_track_1_sector(disc, lba)?;
+
+    // Byte 15 is the mode byte; Mode 2 sectors have a subheader before the user data
+    let data_start = match sector[SECTOR_HEADER_LEN - 1] {
+        2 => SECTOR_HEADER_LEN + MODE_2_SUBHEADER_LEN,
+        _ => SECTOR_HEADER_LEN,
+    };
+    out.copy_from_slice(&sector[data_start..data_start + USER_DATA_LEN]);
+
+    Ok(())
+}
+
+/// Read a Mode 2 sector from data track 1, including the subheader.
+///
+/// # Errors
+///
+/// Will return an error if track 1 is not a data track, if `lba` is past the end of track 1, or if
+/// reading from the disc fails.
+pub fn read_mode_2_sector(
+    disc: &mut CdRom,
+    lba: u32,
+    out: &mut [u8; MODE_2_SECTOR_LEN],
+) -> CdRomResult<()> {
+    let sector = read_track_1_sector(disc, lba)?;
+    out.copy_from_slice(&sector[SECTOR_HEADER_LEN..]);
+
+    Ok(())
+}
+
+fn parse_directory(data: &[u8]) -> Vec<DirectoryEntry> {
+    let mut entries = Vec::new();
+
+    for sector in data.chunks(USER_DATA_LEN) {
+        // Records never cross sector boundaries; a zero length means the rest of the sector is
+        // padding
+        let mut offset = 0;
+        while offset + RECORD_MIN_LEN < sector.len() {
+            let record_len = sector[offset] as usize;
+            if record_len < RECORD_MIN_LEN || offset + record_len > sector.len() {
+                break;
+            }
+
+            let record = &sector[offset..offset + record_len];
+            offset += record_len;
+
+            // $00 and $01 are the current and parent directory entries
+            let name_len = record[32] as usize;
+            if name_len == 1 && matches!(record.get(RECORD_MIN_LEN), None | Some(0 | 1)) {
+                continue;
+            }
+
+            entries.push(DirectoryEntry::parse(record));
+        }
+    }
+
+    entries
+}
+
+// Multi-byte ISO 9660 fields are stored in both little-endian and big-endian order; only the
+// little-endian copy is read
+fn read_u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().unwrap())
+}
+
+fn strip_version(name: &[u8]) -> &[u8] {
+    let name = name.iter().position(|&b| b == b';').map_or(name, |i| &name[..i]);
+    name.strip_suffix(b".").unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &[u8], lba: u32, size: u32, flags: u8, xa_attributes: Option<u16>) -> Vec<u8> {
+        let padded_name_len = name.len() + (1 - name.len() % 2);
+        let mut record = vec![0; RECORD_MIN_LEN + padded_name_len];
+        record[2..6].copy_from_slice(&lba.to_le_bytes());
+        record[6..10].copy_from_slice(&lba.to_be_bytes());
+        record[10..14].copy_from_slice(&size.to_le_bytes());
+        record[14..18].copy_from_slice(&size.to_be_bytes());
+        record[25] = flags;
+        record[32] = name.len() as u8;
+        record[33..33 + name.len()].copy_from_slice(name);
+
+        if let Some(attributes) = xa_attributes {
+            let mut system_use = [0; 14];
+            system_use[4..6].copy_from_slice(&attributes.to_be_bytes());
+            system_use[6..8].copy_from_slice(b"XA");
+            record.extend_from_slice(&system_use);
+        }
+
+        record[0] = record.len() as u8;
+        record
+    }
+
+    #[test]
+    fn directory_records() {
+        let mut sector = Vec::new();
+        sector.extend(record(&[0], 22, 2048, RECORD_FLAG_DIRECTORY, None));
+        sector.extend(record(&[1], 22, 2048, RECORD_FLAG_DIRECTORY, None));
+        sector.extend(record(b"SYSTEM.CNF;1", 23, 68, 0, Some(0x0D55)));
+        sector.extend(record(b"MOVIE", 30, 2048, RECORD_FLAG_DIRECTORY, Some(0x8D55)));
+        sector.extend(record(b"INTRO.STR;1", 40, 4096, 0, Some(0x3D55)));
+        sector.resize(USER_DATA_LEN, 0);
+
+        let entries = parse_directory(&sector);
+        let names: Vec<_> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["SYSTEM.CNF", "MOVIE", "INTRO.STR"]);
+
+        assert_eq!((entries[0].lba, entries[0].size, entries[0].is_directory), (23, 68, false));
+        assert!(entries[0].xa_attributes.unwrap().is_form_1());
+        assert!(!entries[0].xa_attributes.unwrap().is_form_2());
+
+        assert!(entries[1].is_directory);
+
+        let str_attributes = entries[2].xa_attributes.unwrap();
+        assert!(str_attributes.is_form_2());
+        assert!(str_attributes.is_interleaved());
+        assert_eq!(entries[2].sector_count(), 2);
+    }
+
+    #[test]
+    fn truncated_records() {
+        // Record that ends before its 1-byte name
+        let mut sector = vec![0; USER_DATA_LEN];
+        sector[0] = RECORD_MIN_LEN as u8;
+        sector[32] = 1;
+
+        assert!(parse_directory(&sector).is_empty());
+    }
+
+    #[test]
+    fn sector_lba_overflow() {
+        let mut sector = record(b"BAD.DAT;1", u32::MAX - 1, 3 * 2048, 0, None);
+        sector.resize(USER_DATA_LEN, 0);
+        let entry = &parse_directory(&sector)[0];
+
+        assert_eq!(entry.sector_lba(1).unwrap(), u32::MAX);
+        assert!(matches!(entry.sector_lba(2), Err(CdRomError::IsoEntryOutOfRange { .. })));
+    }
+
+    #[test]
+    fn version_suffix() {
+        assert_eq!(strip_version(b"SLUS_000.01;1"), b"SLUS_000.01");
+        assert_eq!(strip_version(b"README.;1"), b"README");
+        assert_eq!(strip_version(b"DATA"), b"DATA");
+    }
+}
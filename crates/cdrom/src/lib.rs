@@ -1,6 +1,11 @@
 pub mod cdtime;
 pub mod cue;
+pub mod discinfo;
+pub mod ecc;
+pub mod iso9660;
 pub mod reader;
+pub mod redump;
+pub mod subchannel;
 
 use std::io;
 use thiserror::Error;
@@ -23,6 +28,8 @@ pub enum CdRomError {
     CueInvalidIndexLine(String),
     #[error("Invalid/unsupported PREGAP line in CUE file: {0}")]
     CueInvalidPregapLine(String),
+    #[error("Invalid CATALOG/ISRC/FLAGS line in CUE file: {0}")]
+    CueInvalidMetadataLine(String),
     #[error("Unable to get file metadata for file '{path}': {source}")]
     FsMetadata {
         path: String,
@@ -41,6 +48,8 @@ pub enum CdRomError {
         #[source]
         source: io::Error,
     },
+    #[error("Unable to determine file name of BIN file '{0}'")]
+    BinFileName(String),
     #[error("CHD-related error: {0}")]
     ChdError(#[from] chd::Error),
     #[error("Error opening CHD file '{path}': {source}")]
@@ -59,6 +68,64 @@ pub enum CdRomError {
         "CD-ROM error detection check failed for track {track_number} sector {sector_number}; expected={expected:08X}, actual={actual:08X}"
     )]
     DiscReadInvalidChecksum { track_number: u8, sector_number: u32, expected: u32, actual: u32 },
+    #[error("Track 1 is not a data track")]
+    IsoNoDataTrack,
+    #[error("Disc does not have an ISO 9660 primary volume descriptor")]
+    IsoMissingVolumeDescriptor,
+    #[error("ISO 9660 sector {lba} is outside of track 1")]
+    IsoSectorOutOfRange { lba: u32 },
+    #[error("ISO 9660 entry '{name}' extends past the last addressable sector")]
+    IsoEntryOutOfRange { name: String },
+    #[error("ISO image is too short to contain an ISO 9660 filesystem ({len} bytes)")]
+    IsoImageTooShort { len: u64 },
+    #[error("Error opening ISO image '{path}': {source}")]
+    IsoImageOpen {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Error opening PBP file '{path}': {source}")]
+    PbpOpen {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Invalid or unsupported PBP file: {0}")]
+    PbpInvalid(String),
+    #[error("PBP file is encrypted; only unencrypted PS1 EBOOTs are supported")]
+    PbpEncrypted,
+    #[error("PBP file has {disc_count} disc(s), cannot open disc index {disc_index}")]
+    PbpDiscIndexOutOfRange { disc_index: usize, disc_count: usize },
+    #[error("Error opening CCD file '{path}': {source}")]
+    CcdOpen {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Error parsing CCD file: {0}")]
+    CcdParse(String),
+    #[error("Error opening subchannel file '{path}': {source}")]
+    SubchannelOpen {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Error opening subchannel patch file '{path}': {source}")]
+    SubchannelPatchOpen {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Invalid subchannel patch file: {0}")]
+    SubchannelPatchInvalid(String),
+    #[error("Error reading DAT file '{path}': {source}")]
+    DatOpen {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Error parsing DAT file: {0}")]
+    DatParse(#[from] roxmltree::Error),
 }
 
 pub type CdRomResult<T> = Result<T, CdRomError>;
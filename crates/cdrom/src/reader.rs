@@ -1,15 +1,24 @@
 //! Code for reading CD-ROM files
 
+mod audio;
+mod ccd;
 mod chd;
 mod cuebin;
+mod ecm;
+mod iso;
+mod pbp;
 mod seekvec;
 
 use crate::cdtime::CdTime;
 use crate::cue::{CueSheet, TrackMode, TrackType};
+use crate::reader::ccd::CloneCdFiles;
 use crate::reader::chd::ChdFile;
-use crate::reader::cuebin::CdBinFiles;
+use crate::reader::cuebin::{BinFile, CdBinFiles};
+use crate::reader::iso::IsoFile;
+use crate::reader::pbp::PbpFile;
 use crate::reader::seekvec::SeekableVec;
-use crate::{CdRomError, CdRomResult};
+use crate::subchannel::{SubchannelPatches, SubchannelQ};
+use crate::{CdRomError, CdRomResult, ecc};
 use bincode::de::{BorrowDecoder, Decoder};
 use bincode::enc::Encoder;
 use bincode::error::{DecodeError, EncodeError};
@@ -24,6 +33,9 @@ use std::path::Path;
 
 const SECTOR_HEADER_LEN: u64 = 16;
 
+const SYNC_PATTERN: [u8; 12] =
+    [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+
 const CD_ROM_CRC: Crc<u32> = Crc::<u32>::new(&crc::CRC_32_CD_ROM_EDC);
 
 const MODE_1_DIGEST_RANGE: Range<usize> = 0..2064;
@@ -37,18 +49,33 @@ const MODE_2_FORM_1_CHECKSUM_LOCATION: Range<usize> = 2072..2076;
 const MODE_2_FORM_2_DIGEST_RANGE: Range<usize> = 16..2348;
 const MODE_2_FORM_2_CHECKSUM_LOCATION: Range<usize> = 2348..2352;
 
-type CdBinFsFiles = CdBinFiles<File>;
+type CdBinFsFiles = CdBinFiles<BinFile<File>>;
 type CdBinMemoryFiles = CdBinFiles<SeekableVec>;
 
 type ChdFsFile = ChdFile<BufReader<File>>;
 type ChdMemoryFile = ChdFile<SeekableVec>;
 
+type IsoFsFile = IsoFile<File>;
+type IsoMemoryFile = IsoFile<SeekableVec>;
+
+type PbpFsFile = PbpFile<File>;
+type PbpMemoryFile = PbpFile<SeekableVec>;
+
+type CloneCdFsFiles = CloneCdFiles<File>;
+type CloneCdMemoryFiles = CloneCdFiles<SeekableVec>;
+
 #[derive(Debug)]
 enum CdRomReader {
     CueBin(CdBinFsFiles),
     CueBinMemory(CdBinMemoryFiles),
     ChdFs(ChdFsFile),
     ChdMemory(ChdMemoryFile),
+    IsoFs(IsoFsFile),
+    IsoMemory(IsoMemoryFile),
+    PbpFs(PbpFsFile),
+    PbpMemory(PbpMemoryFile),
+    CloneCdFs(CloneCdFsFiles),
+    CloneCdMemory(CloneCdMemoryFiles),
 }
 
 impl Default for CdRomReader {
@@ -77,6 +104,41 @@ impl CdRomReader {
             Self::ChdMemory(chd_file) => {
                 chd_file.read_sector(track_number, relative_sector_number, out)
             }
+            Self::IsoFs(iso_file) => {
+                iso_file.read_sector(track_number, relative_sector_number, out)
+            }
+            Self::IsoMemory(iso_file) => {
+                iso_file.read_sector(track_number, relative_sector_number, out)
+            }
+            Self::PbpFs(pbp_file) => {
+                pbp_file.read_sector(track_number, relative_sector_number, out)
+            }
+            Self::PbpMemory(pbp_file) => {
+                pbp_file.read_sector(track_number, relative_sector_number, out)
+            }
+            Self::CloneCdFs(ccd_files) => {
+                ccd_files.read_sector(track_number, relative_sector_number, out)
+            }
+            Self::CloneCdMemory(ccd_files) => {
+                ccd_files.read_sector(track_number, relative_sector_number, out)
+            }
+        }
+    }
+
+    fn read_subchannel_q(
+        &mut self,
+        track_number: u8,
+        relative_sector_number: u32,
+    ) -> CdRomResult<Option<SubchannelQ>> {
+        match self {
+            Self::CloneCdFs(ccd_files) => {
+                ccd_files.read_subchannel_q(track_number, relative_sector_number)
+            }
+            Self::CloneCdMemory(ccd_files) => {
+                ccd_files.read_subchannel_q(track_number, relative_sector_number)
+            }
+            // Other formats do not store subchannel data
+            _ => Ok(None),
         }
     }
 }
@@ -105,20 +167,33 @@ pub enum CdRomFileFormat {
     CueBin,
     // CHD files
     Chd,
+    // ISO files containing only 2048-byte user data sectors
+    Iso,
+    // ECM-compressed BIN file without a CUE file
+    Ecm,
+    // PSP EBOOT.PBP files, which may contain multiple discs
+    Pbp,
+    // CloneCD CCD file + IMG file + optional SUB file
+    CloneCd,
 }
 
 impl CdRomFileFormat {
     pub fn from_file_path<P: AsRef<Path>>(path: P) -> Option<Self> {
-        match path.as_ref().extension().and_then(OsStr::to_str) {
-            Some("cue") => Some(Self::CueBin),
-            Some("chd") => Some(Self::Chd),
+        let extension = path.as_ref().extension().and_then(OsStr::to_str)?;
+        match extension.to_ascii_lowercase().as_str() {
+            "cue" => Some(Self::CueBin),
+            "chd" => Some(Self::Chd),
+            "iso" => Some(Self::Iso),
+            "ecm" => Some(Self::Ecm),
+            "pbp" => Some(Self::Pbp),
+            "ccd" => Some(Self::CloneCd),
             _ => None,
         }
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Mode2Form {
+pub(crate) enum Mode2Form {
     // 2048-byte sector with ECC bytes
     One,
     // 2324-byte sector with no ECC bytes, only EDC
@@ -126,7 +201,7 @@ enum Mode2Form {
 }
 
 impl Mode2Form {
-    fn parse(sector_buffer: &[u8]) -> Self {
+    pub(crate) fn parse(sector_buffer: &[u8]) -> Self {
         // Submode bit 5 specifies Form 1 vs. Form 2
         if sector_buffer[MODE_2_SUBMODE_LOCATION] & (1 << 5) != 0 { Self::Two } else { Self::One }
     }
@@ -136,9 +211,14 @@ impl Mode2Form {
 pub struct CdRom {
     cue_sheet: CueSheet,
     reader: CdRomReader,
+    subchannel_patches: SubchannelPatches,
 }
 
 impl CdRom {
+    fn new(cue_sheet: CueSheet, reader: CdRomReader) -> Self {
+        Self { cue_sheet, reader, subchannel_patches: SubchannelPatches::default() }
+    }
+
     /// Open a CD-ROM reader that will read from the filesystem as needed.
     ///
     /// # Errors
@@ -146,16 +226,78 @@ impl CdRom {
     /// Will propagate any I/O errors, and will return an error if the CD-ROM metadata appears
     /// invalid.
     pub fn open<P: AsRef<Path>>(path: P, format: CdRomFileFormat) -> CdRomResult<Self> {
-        match format {
+        let path = path.as_ref();
+
+        let mut disc = match format {
             CdRomFileFormat::CueBin => Self::open_cue_bin(path),
             CdRomFileFormat::Chd => Self::open_chd(path),
-        }
+            CdRomFileFormat::Iso => Self::open_iso(path),
+            CdRomFileFormat::Ecm => Self::open_ecm(path),
+            CdRomFileFormat::Pbp => Self::open_pbp_file(path, 0),
+            CdRomFileFormat::CloneCd => Self::open_clone_cd(path),
+        }?;
+        disc.load_sibling_subchannel_patches(path);
+
+        Ok(disc)
     }
 
     fn open_cue_bin<P: AsRef<Path>>(cue_path: P) -> CdRomResult<Self> {
-        let (bin_files, cue_sheet) = CdBinFiles::create(cue_path, |path| File::open(path))?;
+        let (bin_files, cue_sheet) = CdBinFiles::create(cue_path, BinFile::open)?;
+
+        Ok(Self::new(cue_sheet, CdRomReader::CueBin(bin_files)))
+    }
+
+    fn open_ecm<P: AsRef<Path>>(ecm_path: P) -> CdRomResult<Self> {
+        let (bin_files, cue_sheet) = CdBinFiles::create_single_track(ecm_path, BinFile::open)?;
+
+        Ok(Self::new(cue_sheet, CdRomReader::CueBin(bin_files)))
+    }
+
+    fn open_clone_cd<P: AsRef<Path>>(ccd_path: P) -> CdRomResult<Self> {
+        let (ccd_files, cue_sheet) = CloneCdFiles::create(ccd_path, |path| File::open(path))?;
+
+        Ok(Self::new(cue_sheet, CdRomReader::CloneCdFs(ccd_files)))
+    }
+
+    /// Open one disc from a PBP file. `disc_index` is 0-based.
+    ///
+    /// # Errors
+    ///
+    /// Will propagate any I/O errors, and will return an error if the PBP file does not contain an
+    /// unencrypted PS1 disc image at the given index.
+    pub fn open_pbp<P: AsRef<Path>>(pbp_path: P, disc_index: usize) -> CdRomResult<Self> {
+        let pbp_path = pbp_path.as_ref();
+
+        let mut disc = Self::open_pbp_file(pbp_path, disc_index)?;
+        disc.load_sibling_subchannel_patches(pbp_path);
 
-        Ok(Self { cue_sheet, reader: CdRomReader::CueBin(bin_files) })
+        Ok(disc)
+    }
+
+    fn open_pbp_file(pbp_path: &Path, disc_index: usize) -> CdRomResult<Self> {
+        let file = File::open(pbp_path).map_err(|source| CdRomError::PbpOpen {
+            path: pbp_path.display().to_string(),
+            source,
+        })?;
+        let (pbp_file, cue_sheet) = PbpFile::open(file, disc_index)?;
+
+        Ok(Self::new(cue_sheet, CdRomReader::PbpFs(pbp_file)))
+    }
+
+    /// Number of discs contained in a PBP file.
+    ///
+    /// # Errors
+    ///
+    /// Will propagate any I/O errors, and will return an error if the file is not a PBP file
+    /// containing a PS1 disc image.
+    pub fn pbp_disc_count<P: AsRef<Path>>(pbp_path: P) -> CdRomResult<usize> {
+        let pbp_path = pbp_path.as_ref();
+
+        let mut file = File::open(pbp_path).map_err(|source| CdRomError::PbpOpen {
+            path: pbp_path.display().to_string(),
+            source,
+        })?;
+        PbpFile::disc_count(&mut file)
     }
 
     fn open_chd<P: AsRef<Path>>(chd_path: P) -> CdRomResult<Self> {
@@ -167,7 +309,19 @@ impl CdRom {
         })?;
         let (chd_file, cue_sheet) = ChdFile::open(BufReader::new(file))?;
 
-        Ok(Self { cue_sheet, reader: CdRomReader::ChdFs(chd_file) })
+        Ok(Self::new(cue_sheet, CdRomReader::ChdFs(chd_file)))
+    }
+
+    fn open_iso<P: AsRef<Path>>(iso_path: P) -> CdRomResult<Self> {
+        let iso_path = iso_path.as_ref();
+
+        let file = File::open(iso_path).map_err(|source| CdRomError::IsoImageOpen {
+            path: iso_path.display().to_string(),
+            source,
+        })?;
+        let (iso_file, cue_sheet) = IsoFile::open(file)?;
+
+        Ok(Self::new(cue_sheet, CdRomReader::IsoFs(iso_file)))
     }
 
     /// Open a CD-ROM reader that will load the entire disc image into memory.
@@ -179,7 +333,7 @@ impl CdRom {
     pub fn open_in_memory<P: AsRef<Path>>(path: P, format: CdRomFileFormat) -> CdRomResult<Self> {
         let path = path.as_ref();
 
-        match format {
+        let mut disc = match format {
             CdRomFileFormat::CueBin => Self::open_cue_bin_in_memory(path),
             CdRomFileFormat::Chd => {
                 let chd_bytes = fs::read(path).map_err(|source| CdRomError::ChdOpen {
@@ -188,7 +342,38 @@ impl CdRom {
                 })?;
                 Self::open_chd_in_memory(chd_bytes)
             }
-        }
+            CdRomFileFormat::Iso => {
+                let iso_bytes = fs::read(path).map_err(|source| CdRomError::IsoImageOpen {
+                    path: path.display().to_string(),
+                    source,
+                })?;
+                Self::open_iso_in_memory(iso_bytes)
+            }
+            CdRomFileFormat::Ecm => {
+                let (bin_files, cue_sheet) =
+                    CdBinFiles::create_single_track(path, cuebin::read_bin_file)?;
+
+                Ok(Self::new(cue_sheet, CdRomReader::CueBinMemory(bin_files)))
+            }
+            CdRomFileFormat::Pbp => {
+                let pbp_bytes = fs::read(path).map_err(|source| CdRomError::PbpOpen {
+                    path: path.display().to_string(),
+                    source,
+                })?;
+                let (pbp_file, cue_sheet) = PbpFile::open(SeekableVec::new(pbp_bytes), 0)?;
+
+                Ok(Self::new(cue_sheet, CdRomReader::PbpMemory(pbp_file)))
+            }
+            CdRomFileFormat::CloneCd => {
+                let (ccd_files, cue_sheet) =
+                    CloneCdFiles::create(path, |path| fs::read(path).map(SeekableVec::new))?;
+
+                Ok(Self::new(cue_sheet, CdRomReader::CloneCdMemory(ccd_files)))
+            }
+        }?;
+        disc.load_sibling_subchannel_patches(path);
+
+        Ok(disc)
     }
 
     /// Open a CD-ROM reader that will read from CUE/BIN files that will be read into memory.
@@ -198,12 +383,20 @@ impl CdRom {
     /// Will return any error encountered while reading from disk, or if the CUE file appears to be
     /// invalid.
     pub fn open_cue_bin_in_memory<P: AsRef<Path>>(cue_path: P) -> CdRomResult<Self> {
-        let (bin_files, cue_sheet) = CdBinFiles::create(cue_path, |path| {
-            let bin_bytes = fs::read(path)?;
-            Ok(SeekableVec::new(bin_bytes))
-        })?;
+        let (bin_files, cue_sheet) = CdBinFiles::create(cue_path, cuebin::read_bin_file)?;
+
+        Ok(Self::new(cue_sheet, CdRomReader::CueBinMemory(bin_files)))
+    }
+
+    /// Open a CD-ROM reader that will read from an ISO image that has been read into memory.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the image is too short to contain an ISO 9660 filesystem.
+    pub fn open_iso_in_memory(iso_bytes: Vec<u8>) -> CdRomResult<Self> {
+        let (iso_file, cue_sheet) = IsoFile::open(SeekableVec::new(iso_bytes))?;
 
-        Ok(Self { reader: CdRomReader::CueBinMemory(bin_files), cue_sheet })
+        Ok(Self::new(cue_sheet, CdRomReader::IsoMemory(iso_file)))
     }
 
     /// Open a CD-ROM reader that will read from a CHD file that has been read into memory.
@@ -215,7 +408,42 @@ impl CdRom {
         let seekable_vec = SeekableVec::new(chd_bytes);
         let (chd_file, cue_sheet) = ChdFile::open(seekable_vec)?;
 
-        Ok(Self { cue_sheet, reader: CdRomReader::ChdMemory(chd_file) })
+        Ok(Self::new(cue_sheet, CdRomReader::ChdMemory(chd_file)))
+    }
+
+    /// Load subchannel Q patches (e.g. LibCrypt data) from an SBI or LSD file. These replace any
+    /// previously loaded patches.
+    ///
+    /// # Errors
+    ///
+    /// Will propagate any I/O errors, and will return an error if the patch file is invalid.
+    pub fn load_subchannel_patches<P: AsRef<Path>>(&mut self, patch_path: P) -> CdRomResult<()> {
+        let patch_path = patch_path.as_ref();
+        self.subchannel_patches = SubchannelPatches::load(patch_path)?;
+
+        log::info!(
+            "Loaded {} subchannel Q patches from '{}'",
+            self.subchannel_patches.len(),
+            patch_path.display()
+        );
+
+        Ok(())
+    }
+
+    // SBI/LSD files are conventionally named after the disc image, e.g. "Game.cue" + "Game.sbi".
+    // A patch file that fails to load should not prevent the disc from opening
+    fn load_sibling_subchannel_patches(&mut self, disc_path: &Path) {
+        let Some(patch_path) = ["sbi", "SBI", "lsd", "LSD"]
+            .into_iter()
+            .map(|extension| disc_path.with_extension(extension))
+            .find(|path| path.is_file())
+        else {
+            return;
+        };
+
+        if let Err(err) = self.load_subchannel_patches(&patch_path) {
+            log::warn!("Ignoring subchannel patch file '{}': {err}", patch_path.display());
+        }
     }
 
     #[must_use]
@@ -259,12 +487,75 @@ impl CdRom {
         let relative_sector_number = (relative_time - track.pregap_len).to_sector_number();
         self.reader.read_sector(track_number, relative_sector_number, out)?;
 
-        validate_edc(track.mode, track_number, relative_sector_number, out)?;
+        if let Err(err) = validate_edc(track.mode, track_number, relative_sector_number, out) {
+            // Try to recover the sector using its P/Q parity. Correct a copy so that the sector is
+            // left as-is if it cannot be corrected, e.g. because the image has stripped parity
+            let mut corrected = [0; crate::BYTES_PER_SECTOR as usize];
+            corrected.copy_from_slice(&out[..crate::BYTES_PER_SECTOR as usize]);
+            if !ecc::correct(track.mode, &mut corrected)
+                || validate_edc(track.mode, track_number, relative_sector_number, &corrected)
+                    .is_err()
+            {
+                return Err(err);
+            }
 
-        // TODO check P/Q ECC?
+            log::warn!(
+                "Corrected track {track_number} sector {relative_sector_number} using ECC after EDC check failed"
+            );
+            out[..crate::BYTES_PER_SECTOR as usize].copy_from_slice(&corrected);
+        }
 
         Ok(())
     }
+
+    /// Read the subchannel Q data for the given absolute disc time.
+    ///
+    /// Loaded subchannel patches take priority, followed by subchannel data stored in the image.
+    /// If neither is available, Q data is generated from the disc's TOC.
+    ///
+    /// # Errors
+    ///
+    /// This method will propagate any I/O error encountered while reading from disk.
+    pub fn read_subchannel_q(&mut self, time: CdTime) -> CdRomResult<SubchannelQ> {
+        if let Some(q) = self.subchannel_patches.get(time) {
+            return Ok(q);
+        }
+
+        if let Some(track) = self.cue_sheet.find_track_by_time(time) {
+            let relative_time = time - track.start_time;
+            if relative_time >= track.pregap_len
+                && relative_time < track.end_time - track.postgap_len - track.start_time
+            {
+                let track_number = track.number;
+                let relative_sector_number = (relative_time - track.pregap_len).to_sector_number();
+                if let Some(q) =
+                    self.reader.read_subchannel_q(track_number, relative_sector_number)?
+                {
+                    return Ok(q);
+                }
+            }
+        }
+
+        Ok(SubchannelQ::from_position(&self.cue_sheet, time))
+    }
+
+    /// Number of sectors of the given track that are stored in the image, i.e. excluding any
+    /// pregap or postgap that is not present in the image file.
+    pub(crate) fn stored_sector_count(&self, track_number: u8) -> u32 {
+        let track = self.cue_sheet.track(track_number);
+        (track.end_time - track.postgap_len - track.start_time - track.pregap_len)
+            .to_sector_number()
+    }
+
+    /// Read a stored sector exactly as it appears in the image, without validating EDC.
+    pub(crate) fn read_stored_sector(
+        &mut self,
+        track_number: u8,
+        sector_number: u32,
+        out: &mut [u8],
+    ) -> CdRomResult<()> {
+        self.reader.read_sector(track_number, sector_number, out)
+    }
 }
 
 fn validate_edc(
@@ -273,20 +564,8 @@ fn validate_edc(
     relative_sector_number: u32,
     sector: &[u8],
 ) -> CdRomResult<()> {
-    let (digest_range, edc_location) = match mode {
-        TrackMode::Mode1 => (MODE_1_DIGEST_RANGE, MODE_1_CHECKSUM_LOCATION),
-        TrackMode::Mode2 => match Mode2Form::parse(sector) {
-            Mode2Form::One => (MODE_2_FORM_1_DIGEST_RANGE, MODE_2_FORM_1_CHECKSUM_LOCATION),
-            Mode2Form::Two => {
-                // In Form 2, an EDC of 0 indicates no EDC
-                if sector[MODE_2_FORM_2_CHECKSUM_LOCATION] == [0, 0, 0, 0] {
-                    return Ok(());
-                }
-
-                (MODE_2_FORM_2_DIGEST_RANGE, MODE_2_FORM_2_CHECKSUM_LOCATION)
-            }
-        },
-        TrackMode::Audio => return Ok(()),
+    let Some((digest_range, edc_location)) = edc_ranges(mode, sector) else {
+        return Ok(());
     };
 
     let checksum = CD_ROM_CRC.checksum(&sector[digest_range]);
@@ -306,6 +585,51 @@ fn validate_edc(
     Ok(())
 }
 
+/// Recompute a data sector's EDC from its contents and overwrite the stored EDC, e.g. to accept a
+/// sector from a patched image that did not update the EDC. Does nothing for audio sectors and for
+/// Mode 2 Form 2 sectors that do not have an EDC.
+///
+/// # Panics
+///
+/// This function will panic if `sector`'s length is less than 2352.
+pub fn regenerate_edc(mode: TrackMode, sector: &mut [u8]) {
+    let Some((digest_range, edc_location)) = edc_ranges(mode, sector) else {
+        return;
+    };
+
+    let checksum = CD_ROM_CRC.checksum(&sector[digest_range]);
+    sector[edc_location].copy_from_slice(&checksum.to_le_bytes());
+}
+
+/// Recompute a data sector's EDC and P/Q parity from its contents, e.g. to fix a sector from an image
+/// with stripped parity or a patched image that did not update the EDC/ECC. Does nothing for audio
+/// sectors.
+///
+/// # Panics
+///
+/// This function will panic if `sector`'s length is less than 2352.
+pub fn regenerate_edc_ecc(mode: TrackMode, sector: &mut [u8]) {
+    // The parity covers the EDC, so the EDC must be updated first
+    regenerate_edc(mode, sector);
+    ecc::regenerate(mode, sector);
+}
+
+// Returns the range covered by the EDC and the location of the EDC, or None if the sector has no EDC
+fn edc_ranges(mode: TrackMode, sector: &[u8]) -> Option<(Range<usize>, Range<usize>)> {
+    match mode {
+        TrackMode::Mode1 => Some((MODE_1_DIGEST_RANGE, MODE_1_CHECKSUM_LOCATION)),
+        TrackMode::Mode2 => match Mode2Form::parse(sector) {
+            Mode2Form::One => Some((MODE_2_FORM_1_DIGEST_RANGE, MODE_2_FORM_1_CHECKSUM_LOCATION)),
+            Mode2Form::Two => {
+                // In Form 2, an EDC of 0 indicates no EDC
+                (sector[MODE_2_FORM_2_CHECKSUM_LOCATION] != [0, 0, 0, 0])
+                    .then_some((MODE_2_FORM_2_DIGEST_RANGE, MODE_2_FORM_2_CHECKSUM_LOCATION))
+            }
+        },
+        TrackMode::Audio => None,
+    }
+}
+
 fn write_fake_data_pregap(time: CdTime, out: &mut [u8]) {
     // Make up a header; 12 sync bytes, then minutes, then seconds, then frames, then mode (always 1)
     let bcd_minutes = time_component_to_bcd(time.minutes);
@@ -332,8 +656,44 @@ fn write_fake_data_pregap(time: CdTime, out: &mut [u8]) {
     out[SECTOR_HEADER_LEN as usize..crate::BYTES_PER_SECTOR as usize].fill(0);
 }
 
-fn time_component_to_bcd(component: u8) -> u8 {
+// Write the sync pattern and header for a data sector at the given absolute time
+fn write_sector_header(time: CdTime, mode_byte: u8, out: &mut [u8]) {
+    out[..SYNC_PATTERN.len()].copy_from_slice(&SYNC_PATTERN);
+    out[SYNC_PATTERN.len()..SECTOR_HEADER_LEN as usize].copy_from_slice(&[
+        time_component_to_bcd(time.minutes),
+        time_component_to_bcd(time.seconds),
+        time_component_to_bcd(time.frames),
+        mode_byte,
+    ]);
+}
+
+pub(crate) fn time_component_to_bcd(component: u8) -> u8 {
     let msb = component / 10;
     let lsb = component % 10;
     (msb << 4) | lsb
 }
+
+pub(crate) fn bcd_to_binary(value: u8) -> u8 {
+    10 * (value >> 4) + (value & 0x0F)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_sibling_patch_file_is_ignored() {
+        let dir = std::env::temp_dir().join(format!("cdrom-sibling-sbi-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let iso_path = dir.join("Game.iso");
+        fs::write(&iso_path, vec![0; 32 * 2048]).unwrap();
+        fs::write(dir.join("Game.sbi"), b"not an SBI file").unwrap();
+
+        let disc = CdRom::open(&iso_path, CdRomFileFormat::Iso);
+        let disc_in_memory = CdRom::open_in_memory(&iso_path, CdRomFileFormat::Iso);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(disc.unwrap().subchannel_patches.is_empty());
+        assert!(disc_in_memory.unwrap().subchannel_patches.is_empty());
+    }
+}
@@ -0,0 +1,409 @@
+//! Code for reading CD-DA tracks from WAVE, FLAC, and Ogg Vorbis audio files
+//!
+//! Audio is decoded on demand and exposed as a stream of 16-bit little-endian stereo samples, which
+//! is the same layout as an audio track in a BIN file. Only the packets around the current read
+//! position are decoded, so seeking within a track does not require decoding the whole file.
+
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::{cmp, io, iter};
+use symphonia::core::audio::{Channels, SampleBuffer};
+use symphonia::core::codecs::{CODEC_TYPE_NULL, Decoder, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::{MediaSourceStream, MediaSourceStreamOptions};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+const CD_SAMPLE_RATE: u32 = 44_100;
+
+// 2 channels * 2 bytes per sample
+const BYTES_PER_FRAME: u64 = 4;
+
+const AUDIO_EXTENSIONS: [&str; 4] = ["wav", "flac", "ogg", "oga"];
+
+#[must_use]
+pub fn is_audio_path(path: &Path) -> bool {
+    path.extension().and_then(OsStr::to_str).is_some_and(|extension| {
+        AUDIO_EXTENSIONS.iter().any(|audio_ext| extension.eq_ignore_ascii_case(audio_ext))
+    })
+}
+
+pub struct AudioFile {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    mono: bool,
+    decoded_len: u64,
+    // Decoded length padded to a whole number of sectors; reads past the end of the decoded audio
+    // return silence
+    len: u64,
+    // Interleaved little-endian stereo samples from the most recently decoded packet
+    buffer: Vec<u8>,
+    buffer_start: u64,
+    position: u64,
+    // Whether the next decoded packet directly follows the current buffer contents
+    sequential: bool,
+}
+
+impl AudioFile {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(OsStr::to_str) {
+            hint.with_extension(extension);
+        }
+
+        let mss = MediaSourceStream::new(Box::new(file), MediaSourceStreamOptions::default());
+        let format = symphonia::default::get_probe()
+            .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
+            .map_err(to_io_error)?
+            .format;
+
+        let track = format
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(|| invalid_data("audio file does not contain an audio track"))?;
+        let track_id = track.id;
+        let codec_params = track.codec_params.clone();
+
+        let sample_rate = codec_params.sample_rate.unwrap_or(CD_SAMPLE_RATE);
+        if sample_rate != CD_SAMPLE_RATE {
+            return Err(invalid_data(&format!(
+                "audio file has sample rate {sample_rate} Hz, expected {CD_SAMPLE_RATE} Hz"
+            )));
+        }
+
+        let channel_count = codec_params.channels.map_or(2, Channels::count);
+        if !(1..=2).contains(&channel_count) {
+            return Err(invalid_data(&format!(
+                "audio file has {channel_count} channels, expected mono or stereo"
+            )));
+        }
+
+        let decoder = symphonia::default::get_codecs()
+            .make(&codec_params, &DecoderOptions::default())
+            .map_err(to_io_error)?;
+
+        // All supported formats report the length for seekable files, so this should only fail for
+        // truncated or otherwise malformed files
+        let len_frames = codec_params
+            .n_frames
+            .ok_or_else(|| invalid_data("audio file does not specify its length"))?;
+        let decoded_len = len_frames * BYTES_PER_FRAME;
+        let len = decoded_len.next_multiple_of(crate::BYTES_PER_SECTOR);
+
+        log::debug!(
+            "Opened audio file '{}' with {len_frames} frames ({channel_count} channels)",
+            path.display()
+        );
+
+        Ok(Self {
+            format,
+            decoder,
+            track_id,
+            mono: channel_count == 1,
+            decoded_len,
+            len,
+            buffer: Vec::new(),
+            buffer_start: 0,
+            position: 0,
+            sequential: true,
+        })
+    }
+
+    fn buffer_end(&self) -> u64 {
+        self.buffer_start + self.buffer.len() as u64
+    }
+
+    // Decode packets until the buffer contains the current position
+    fn fill_buffer(&mut self) -> io::Result<()> {
+        if self.position >= self.decoded_len {
+            self.fill_silence();
+            return Ok(());
+        }
+
+        if !self.sequential || self.position < self.buffer_start {
+            self.seek_decoder()?;
+        }
+
+        while self.position >= self.buffer_end() {
+            if !self.decode_next_packet()? {
+                self.fill_silence();
+                return Ok(());
+            }
+        }
+
+        if self.position < self.buffer_start {
+            // The decoder resumed after the requested position; treat the gap as silence
+            let gap = (self.buffer_start - self.position) as usize;
+            self.buffer.splice(0..0, iter::repeat_n(0, gap));
+            self.buffer_start = self.position;
+        }
+
+        Ok(())
+    }
+
+    fn fill_silence(&mut self) {
+        let len = cmp::min(crate::BYTES_PER_SECTOR, self.len - self.position);
+        self.buffer_start = self.position;
+        self.buffer.clear();
+        self.buffer.resize(len as usize, 0);
+        self.sequential = false;
+    }
+
+    fn seek_decoder(&mut self) -> io::Result<()> {
+        let frame = self.position / BYTES_PER_FRAME;
+        let seeked_to = self
+            .format
+            .seek(SeekMode::Accurate, SeekTo::TimeStamp { ts: frame, track_id: self.track_id })
+            .map_err(to_io_error)?;
+        self.decoder.reset();
+
+        self.buffer.clear();
+        self.buffer_start = seeked_to.actual_ts * BYTES_PER_FRAME;
+        self.sequential = true;
+
+        Ok(())
+    }
+
+    // Returns false at end of stream
+    fn decode_next_packet(&mut self) -> io::Result<bool> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Ok(false);
+                }
+                Err(err) => return Err(to_io_error(err)),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(SymphoniaError::DecodeError(err)) => {
+                    log::warn!("Skipping corrupt audio packet at frame {}: {err}", packet.ts());
+                    continue;
+                }
+                Err(err) => return Err(to_io_error(err)),
+            };
+
+            let mut samples = SampleBuffer::<i16>::new(decoded.capacity() as u64, *decoded.spec());
+            samples.copy_interleaved_ref(decoded);
+
+            self.buffer_start = packet.ts() * BYTES_PER_FRAME;
+            self.buffer.clear();
+            if self.mono {
+                for &sample in samples.samples() {
+                    let bytes = sample.to_le_bytes();
+                    self.buffer.extend_from_slice(&[bytes[0], bytes[1], bytes[0], bytes[1]]);
+                }
+            } else {
+                for &sample in samples.samples() {
+                    self.buffer.extend_from_slice(&sample.to_le_bytes());
+                }
+            }
+
+            return Ok(true);
+        }
+    }
+}
+
+impl Read for AudioFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+
+        if !(self.buffer_start..self.buffer_end()).contains(&self.position) {
+            self.fill_buffer()?;
+        }
+
+        let buffer_offset = (self.position - self.buffer_start) as usize;
+        let len = cmp::min(buf.len() as u64, self.len - self.position) as usize;
+        let len = cmp::min(len, self.buffer.len() - buffer_offset);
+        buf[..len].copy_from_slice(&self.buffer[buffer_offset..buffer_offset + len]);
+        self.position += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl Seek for AudioFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        let Some(position) = position else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position"));
+        };
+
+        // Seeking the decoder is deferred until the next read, and is skipped if the new position
+        // is already buffered or directly follows the buffer
+        if position != self.position {
+            self.sequential = (self.buffer_start..=self.buffer_end()).contains(&position);
+            self.position = position;
+        }
+
+        Ok(position)
+    }
+}
+
+impl std::fmt::Debug for AudioFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AudioFile")
+            .field("track_id", &self.track_id)
+            .field("mono", &self.mono)
+            .field("len", &self.len)
+            .field("position", &self.position)
+            .finish_non_exhaustive()
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn to_io_error(err: SymphoniaError) -> io::Error {
+    match err {
+        SymphoniaError::IoError(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    // Long enough to span several WAV packets
+    const LEN_FRAMES: usize = 10_000;
+
+    fn write_wav(name: &str, channels: u16, samples: &[i16]) -> PathBuf {
+        let data_len = 2 * samples.len() as u32;
+        let block_align = 2 * channels;
+
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16_u32.to_le_bytes());
+        wav.extend_from_slice(&1_u16.to_le_bytes());
+        wav.extend_from_slice(&channels.to_le_bytes());
+        wav.extend_from_slice(&CD_SAMPLE_RATE.to_le_bytes());
+        wav.extend_from_slice(&(CD_SAMPLE_RATE * u32::from(block_align)).to_le_bytes());
+        wav.extend_from_slice(&block_align.to_le_bytes());
+        wav.extend_from_slice(&16_u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        for sample in samples {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        let path = std::env::temp_dir().join(format!("cdrom-{name}-{}.wav", std::process::id()));
+        fs::write(&path, wav).unwrap();
+        path
+    }
+
+    fn open_wav(name: &str, channels: u16, samples: &[i16]) -> AudioFile {
+        let path = write_wav(name, channels, samples);
+        let file = AudioFile::open(&path);
+        fs::remove_file(&path).unwrap();
+        file.unwrap()
+    }
+
+    fn stereo_samples() -> Vec<i16> {
+        (0..LEN_FRAMES as i16).flat_map(|i| [i, -i]).collect()
+    }
+
+    fn to_bytes(samples: &[i16]) -> Vec<u8> {
+        samples.iter().flat_map(|sample| sample.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn stereo_layout_and_padding() {
+        let samples = stereo_samples();
+        let mut file = open_wav("stereo", 2, &samples);
+
+        let decoded_len = LEN_FRAMES as u64 * BYTES_PER_FRAME;
+        assert_eq!(file.decoded_len, decoded_len);
+        assert_eq!(file.len, decoded_len.next_multiple_of(crate::BYTES_PER_SECTOR));
+
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents.len() as u64, file.len);
+
+        let (audio, padding) = contents.split_at(decoded_len as usize);
+        assert_eq!(audio, to_bytes(&samples));
+        assert!(!padding.is_empty());
+        assert!(padding.iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn mono_is_duplicated_to_both_channels() {
+        let samples: Vec<i16> = (0..LEN_FRAMES as i16).map(|i| i.wrapping_mul(7)).collect();
+        let mut file = open_wav("mono", 1, &samples);
+        assert!(file.mono);
+
+        let mut contents = vec![0; LEN_FRAMES * BYTES_PER_FRAME as usize];
+        file.read_exact(&mut contents).unwrap();
+
+        let expected: Vec<i16> = samples.iter().flat_map(|&sample| [sample, sample]).collect();
+        assert_eq!(contents, to_bytes(&expected));
+    }
+
+    #[test]
+    fn reads_across_packet_boundaries() {
+        let samples = stereo_samples();
+        let expected = to_bytes(&samples);
+        let mut file = open_wav("chunks", 2, &samples);
+
+        // Odd-sized reads so that chunks straddle packet boundaries and split samples
+        let mut contents = Vec::new();
+        let mut chunk = [0; 999];
+        while contents.len() < expected.len() {
+            let len = file.read(&mut chunk).unwrap();
+            assert_ne!(len, 0);
+            contents.extend_from_slice(&chunk[..len]);
+        }
+
+        assert_eq!(contents[..expected.len()], expected);
+    }
+
+    #[test]
+    fn seeks() {
+        let samples = stereo_samples();
+        let expected = to_bytes(&samples);
+        let mut file = open_wav("seek", 2, &samples);
+
+        let mut read_at = |position: u64| {
+            assert_eq!(file.seek(SeekFrom::Start(position)).unwrap(), position);
+            let mut buf = [0; 64];
+            file.read_exact(&mut buf).unwrap();
+            buf
+        };
+
+        for position in [30_000, 4, 20_002, 0, 39_936] {
+            let start = position as usize;
+            assert_eq!(read_at(position), expected[start..start + 64], "position {position}");
+        }
+
+        // Past the end of the decoded audio but within the padded length
+        let padding_start = expected.len() as u64;
+        assert_eq!(read_at(padding_start), [0; 64]);
+        assert_eq!(read_at(8), expected[8..72]);
+
+        assert_eq!(file.seek(SeekFrom::End(0)).unwrap(), file.len);
+        assert_eq!(file.read(&mut [0; 16]).unwrap(), 0);
+        assert!(file.seek(SeekFrom::Current(-(file.len as i64) - 1)).is_err());
+    }
+}
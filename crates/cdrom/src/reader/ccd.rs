@@ -0,0 +1,262 @@
+//! Code for loading and reading CloneCD images (CCD/IMG/SUB)
+//!
+//! The CCD file is an INI-style description of the disc's TOC, the IMG file contains raw 2352-byte
+//! sectors starting from sector 0 (00:02:00), and the SUB file contains 96 bytes of subchannel data
+//! per sector. Subchannel data is stored deinterleaved, with 12 bytes for each of the P-W channels.
+
+use crate::cdtime::CdTime;
+use crate::cue::{CueSheet, TrackMode};
+use crate::reader::cuebin::{CdBinFiles, ParsedFile, ParsedTrack, SectorLayout};
+use crate::subchannel::{SUBCHANNEL_Q_LEN, SubchannelQ};
+use crate::{CdRomError, CdRomResult};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::{fs, iter};
+
+const SUBCHANNEL_BYTES_PER_SECTOR: u64 = 96;
+const SUBCHANNEL_Q_OFFSET: u64 = 12;
+
+// Control field bit that indicates a data track
+const CONTROL_DATA_TRACK: i64 = 0x04;
+
+#[derive(Debug)]
+pub struct CloneCdFiles<F: Read + Seek> {
+    img_files: CdBinFiles<F>,
+    sub_file: Option<F>,
+}
+
+impl<F: Read + Seek> CloneCdFiles<F> {
+    pub fn create<OpenFn, P: AsRef<Path>>(
+        ccd_path: P,
+        open_fn: OpenFn,
+    ) -> CdRomResult<(Self, CueSheet)>
+    where
+        OpenFn: for<'a> Fn(&'a Path) -> io::Result<F>,
+    {
+        let ccd_path = ccd_path.as_ref();
+
+        let ccd = fs::read_to_string(ccd_path).map_err(|source| CdRomError::CcdOpen {
+            path: ccd_path.display().to_string(),
+            source,
+        })?;
+        let tracks = parse_ccd(&ccd)?;
+
+        let img_path = sibling_path(ccd_path, "img");
+        let (Some(parent_dir), Some(img_file_name)) =
+            (ccd_path.parent(), img_path.file_name().and_then(OsStr::to_str))
+        else {
+            return Err(CdRomError::CueParentDir(ccd_path.display().to_string()));
+        };
+
+        let parsed_files = vec![ParsedFile { file_name: img_file_name.into(), tracks }];
+        let (img_files, cue_sheet) =
+            CdBinFiles::from_parsed_files(parsed_files, parent_dir, &open_fn)?;
+
+        let sub_path = sibling_path(ccd_path, "sub");
+        let sub_file = if sub_path.exists() {
+            let sub_file = open_fn(&sub_path).map_err(|source| CdRomError::SubchannelOpen {
+                path: sub_path.display().to_string(),
+                source,
+            })?;
+            Some(sub_file)
+        } else {
+            log::warn!(
+                "No subchannel file found at '{}'; subchannel Q data will be generated",
+                sub_path.display()
+            );
+            None
+        };
+
+        Ok((Self { img_files, sub_file }, cue_sheet))
+    }
+
+    pub fn read_sector(
+        &mut self,
+        track_number: u8,
+        relative_sector_number: u32,
+        out: &mut [u8],
+    ) -> CdRomResult<()> {
+        self.img_files.read_sector(track_number, relative_sector_number, out)
+    }
+
+    /// Read the sector's Q subchannel data from the SUB file. Returns `None` if the image has no
+    /// SUB file.
+    pub fn read_subchannel_q(
+        &mut self,
+        track_number: u8,
+        relative_sector_number: u32,
+    ) -> CdRomResult<Option<SubchannelQ>> {
+        let Some(sub_file) = &mut self.sub_file else { return Ok(None) };
+
+        let sector_number = self.img_files.file_sector_number(track_number, relative_sector_number);
+        let q_addr = u64::from(sector_number) * SUBCHANNEL_BYTES_PER_SECTOR + SUBCHANNEL_Q_OFFSET;
+
+        let mut q = [0; SUBCHANNEL_Q_LEN];
+        sub_file
+            .seek(SeekFrom::Start(q_addr))
+            .and_then(|_| sub_file.read_exact(&mut q))
+            .map_err(CdRomError::DiscReadIo)?;
+
+        Ok(Some(SubchannelQ::new(q)))
+    }
+}
+
+// CloneCD names the IMG and SUB files after the CCD file, and the extension case varies
+fn sibling_path(ccd_path: &Path, extension: &str) -> PathBuf {
+    let lowercase = ccd_path.with_extension(extension);
+    let uppercase = ccd_path.with_extension(extension.to_ascii_uppercase());
+    if !lowercase.exists() && uppercase.exists() { uppercase } else { lowercase }
+}
+
+fn parse_int(value: &str) -> Option<i64> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn lba_to_time(lba: i64) -> Option<CdTime> {
+    u32::try_from(lba).ok().filter(|&lba| lba < CdTime::MAX_SECTORS).map(CdTime::from_sector_number)
+}
+
+fn parse_ccd(ccd: &str) -> CdRomResult<Vec<ParsedTrack>> {
+    // Section names and keys are case-insensitive
+    let mut sections: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut current_section = None;
+    for line in ccd.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            let name = name.trim().to_ascii_lowercase();
+            sections.entry(name.clone()).or_default();
+            current_section = Some(name);
+        } else if let (Some((key, value)), Some(section)) = (line.split_once('='), &current_section)
+        {
+            sections
+                .get_mut(section)
+                .unwrap()
+                .insert(key.trim().to_ascii_lowercase(), value.trim().into());
+        }
+    }
+
+    let get_int = |section: &HashMap<String, String>, key: &str| {
+        section.get(key).and_then(|value| parse_int(value))
+    };
+
+    // TOC entries for points 1-99 are the tracks; other points are disc metadata
+    let mut tracks = Vec::new();
+    for (name, entry) in &sections {
+        if !name.starts_with("entry ") {
+            continue;
+        }
+
+        let Some(point) = get_int(entry, "point").filter(|point| (1..=99).contains(point)) else {
+            continue;
+        };
+        let track_number = point as u8;
+
+        let invalid_entry =
+            || CdRomError::CcdParse(format!("Invalid TOC entry for track {track_number}"));
+        let control = get_int(entry, "control").ok_or_else(invalid_entry)?;
+        let plba = get_int(entry, "plba").ok_or_else(invalid_entry)?;
+
+        // Track sections, if present, specify the data mode and the index positions
+        let track_section = sections.get(&format!("track {track_number}"));
+        let track_int = |key| track_section.and_then(|section| get_int(section, key));
+
+        let mode = if control & CONTROL_DATA_TRACK == 0 {
+            TrackMode::Audio
+        } else if track_int("mode") == Some(1) {
+            TrackMode::Mode1
+        } else {
+            TrackMode::Mode2
+        };
+
+        let track_start =
+            lba_to_time(track_int("index 1").unwrap_or(plba)).ok_or_else(invalid_entry)?;
+        let pause_start = track_int("index 0")
+            .and_then(lba_to_time)
+            .filter(|&pause_start| pause_start < track_start);
+
+        tracks.push(ParsedTrack {
+            number: track_number,
+            mode,
+            sector_layout: SectorLayout::Raw,
+            pregap_len: None,
+            pause_start,
+            track_start,
+        });
+    }
+
+    tracks.sort_by_key(|track| track.number);
+
+    if tracks.is_empty() || !iter::zip(&tracks, 1..).all(|(track, number)| track.number == number) {
+        let track_numbers: Vec<_> = tracks.iter().map(|track| track.number).collect();
+        return Err(CdRomError::CcdParse(format!("Invalid track list: {track_numbers:?}")));
+    }
+
+    Ok(tracks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CCD: &str = "\
+[CloneCD]
+Version=3
+
+[Disc]
+TocEntries=5
+Sessions=1
+
+[Entry 0]
+Session=1
+Point=0xa0
+Control=0x04
+PLBA=-150
+
+[Entry 1]
+Session=1
+Point=0xa2
+Control=0x04
+PLBA=20000
+
+[Entry 2]
+Session=1
+Point=0x01
+Control=0x04
+PLBA=0
+
+[Entry 3]
+Session=1
+Point=0x02
+Control=0x00
+PLBA=15150
+
+[TRACK 1]
+MODE=2
+INDEX 1=0
+
+[TRACK 2]
+MODE=0
+INDEX 0=15000
+INDEX 1=15150
+";
+
+    #[test]
+    fn parse() {
+        let tracks = parse_ccd(CCD).unwrap();
+        assert_eq!(tracks.len(), 2);
+
+        assert_eq!(tracks[0].number, 1);
+        assert_eq!(tracks[0].mode, TrackMode::Mode2);
+        assert_eq!(tracks[0].pause_start, None);
+        assert_eq!(tracks[0].track_start, CdTime::ZERO);
+
+        assert_eq!(tracks[1].number, 2);
+        assert_eq!(tracks[1].mode, TrackMode::Audio);
+        assert_eq!(tracks[1].pause_start, Some(CdTime::from_sector_number(15000)));
+        assert_eq!(tracks[1].track_start, CdTime::from_sector_number(15150));
+    }
+}
@@ -2,19 +2,68 @@
 
 use crate::cdtime::CdTime;
 use crate::cue::{CueSheet, Track, TrackMode, TrackType};
+use crate::reader::audio::{self, AudioFile};
+use crate::reader::ecm::{self, EcmFile};
+use crate::reader::seekvec::SeekableVec;
 use crate::{CdRomError, CdRomResult, cue};
 use bincode::{Decode, Encode};
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs::File;
 use std::io::{BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
 use std::sync::OnceLock;
 use std::{fs, io, mem};
 
+const SECTOR_HEADER_LEN: usize = 16;
+
+/// How each sector of a track is stored in its file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum SectorLayout {
+    /// Full 2352-byte sectors (`MODE1/2352`, `MODE2/2352`, `AUDIO`)
+    Raw,
+    /// Only the 2048 bytes of user data (`MODE1/2048`); the sync pattern, header, and EDC/ECC are
+    /// synthesized on read
+    Mode1UserData,
+    /// Everything after the 16-byte header (`MODE2/2336`); the sync pattern and header are
+    /// synthesized on read
+    Mode2NoHeader,
+}
+
+impl SectorLayout {
+    fn from_cue_track_mode(mode: &str) -> Self {
+        match mode {
+            "MODE1/2048" => Self::Mode1UserData,
+            "MODE2/2336" => Self::Mode2NoHeader,
+            _ => Self::Raw,
+        }
+    }
+
+    fn stored_len(self) -> u64 {
+        match self {
+            Self::Raw => crate::BYTES_PER_SECTOR,
+            Self::Mode1UserData => 2048,
+            Self::Mode2NoHeader => crate::BYTES_PER_SECTOR - SECTOR_HEADER_LEN as u64,
+        }
+    }
+
+    // Offset of the stored bytes within a full 2352-byte sector
+    fn stored_offset(self) -> usize {
+        match self {
+            Self::Raw => 0,
+            Self::Mode1UserData | Self::Mode2NoHeader => SECTOR_HEADER_LEN,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Encode, Decode)]
 pub struct TrackMetadata {
     pub file_name: String,
     pub time_in_file: CdTime,
+    pub sector_layout: SectorLayout,
+    /// Absolute disc time of the track's first sector in the file
+    pub file_start_time: CdTime,
 }
 
 #[derive(Debug)]
@@ -49,15 +98,32 @@ impl<F: Read + Seek> CdBinFiles<F> {
     {
         let cue_path = cue_path.as_ref();
 
-        let (cue_sheet, track_metadata) = parse_cue(cue_path)?;
-
-        let file_names: HashSet<_> =
-            track_metadata.iter().map(|metadata| metadata.file_name.clone()).collect();
+        let cue_file = fs::read_to_string(cue_path).map_err(|source| CdRomError::CueOpen {
+            path: cue_path.display().to_string(),
+            source,
+        })?;
+        let parsed_files = CueParser::new().parse(&cue_file)?;
 
         let parent_dir = cue_path
             .parent()
             .ok_or_else(|| CdRomError::CueParentDir(cue_path.display().to_string()))?;
 
+        Self::from_parsed_files(parsed_files, parent_dir, bin_open_fn)
+    }
+
+    /// Open the BIN files for tracks that were parsed from some other format's TOC, e.g. a
+    /// CloneCD control file. File names are relative to `parent_dir`.
+    pub(super) fn from_parsed_files<OpenFn>(
+        parsed_files: Vec<ParsedFile>,
+        parent_dir: &Path,
+        bin_open_fn: OpenFn,
+    ) -> CdRomResult<(Self, CueSheet)>
+    where
+        OpenFn: for<'a> Fn(&'a Path) -> io::Result<F>,
+    {
+        let file_names: HashSet<_> =
+            parsed_files.iter().map(|parsed_file| parsed_file.file_name.clone()).collect();
+
         let mut files = HashMap::with_capacity(file_names.len());
         for file_name in file_names {
             let file_path = parent_dir.join(Path::new(&file_name));
@@ -68,6 +134,73 @@ impl<F: Read + Seek> CdBinFiles<F> {
             files.insert(file_name, CdRomFile::new(file));
         }
 
+        Self::from_open_files(parsed_files, files)
+    }
+
+    /// Open a single BIN file without a CUE file, treating the entire file as one data track. The
+    /// track mode is determined from the first sector's header.
+    pub fn create_single_track<OpenFn, P: AsRef<Path>>(
+        bin_path: P,
+        bin_open_fn: OpenFn,
+    ) -> CdRomResult<(Self, CueSheet)>
+    where
+        OpenFn: for<'a> Fn(&'a Path) -> io::Result<F>,
+    {
+        let bin_path = bin_path.as_ref();
+
+        let Some(file_name) = bin_path.file_name().and_then(OsStr::to_str) else {
+            return Err(CdRomError::BinFileName(bin_path.display().to_string()));
+        };
+
+        let mut header = [0; SECTOR_HEADER_LEN];
+        let file = bin_open_fn(bin_path)
+            .and_then(|file| {
+                let mut file = CdRomFile::new(file);
+                file.file.read_exact(&mut header)?;
+                file.position = header.len() as u64;
+                Ok(file)
+            })
+            .map_err(|source| CdRomError::BinOpen {
+                path: bin_path.display().to_string(),
+                source,
+            })?;
+        let mode =
+            if header[SECTOR_HEADER_LEN - 1] == 1 { TrackMode::Mode1 } else { TrackMode::Mode2 };
+
+        let parsed_files = vec![ParsedFile {
+            file_name: file_name.into(),
+            tracks: vec![ParsedTrack {
+                number: 1,
+                mode,
+                sector_layout: SectorLayout::Raw,
+                pregap_len: None,
+                pause_start: None,
+                track_start: CdTime::ZERO,
+            }],
+        }];
+
+        Self::from_open_files(parsed_files, HashMap::from([(file_name.into(), file)]))
+    }
+
+    fn from_open_files(
+        parsed_files: Vec<ParsedFile>,
+        mut files: HashMap<String, CdRomFile<F>>,
+    ) -> CdRomResult<(Self, CueSheet)> {
+        // Use the length of the opened file rather than the file on disk, which differs for
+        // compressed files
+        let mut file_lens = HashMap::with_capacity(files.len());
+        for (file_name, file) in &mut files {
+            let file_len = file
+                .file
+                .seek(SeekFrom::End(0))
+                .map_err(|source| CdRomError::FsMetadata { path: file_name.clone(), source })?;
+            file.position = file_len;
+
+            file_lens.insert(file_name.clone(), file_len);
+        }
+
+        let (cue_sheet, track_metadata) = to_cue_sheet(parsed_files, &file_lens);
+
         let bin_files = Self { files, track_metadata };
         Ok((bin_files, cue_sheet))
     }
@@ -78,42 +211,127 @@ impl<F: Read + Seek> CdBinFiles<F> {
         relative_sector_number: u32,
         out: &mut [u8],
     ) -> CdRomResult<()> {
+        let sector_number = self.file_sector_number(track_number, relative_sector_number);
+
         let metadata = &self.track_metadata[(track_number - 1) as usize];
         let CdRomFile { file: track_file, position } = self
             .files
             .get_mut(&metadata.file_name)
             .expect("Track file was not opened on load; this is a bug");
 
-        let sector_number = metadata.time_in_file.to_sector_number() + relative_sector_number;
-        let sector_addr = u64::from(sector_number) * crate::BYTES_PER_SECTOR;
+        let layout = metadata.sector_layout;
+        let sector_addr = u64::from(sector_number) * layout.stored_len();
 
         // Only seek if the file descriptor is not already at the desired position
         if *position != sector_addr {
             track_file.seek(SeekFrom::Start(sector_addr)).map_err(CdRomError::DiscReadIo)?;
         }
 
-        track_file
-            .read_exact(&mut out[..crate::BYTES_PER_SECTOR as usize])
-            .map_err(CdRomError::DiscReadIo)?;
-        *position = sector_addr + crate::BYTES_PER_SECTOR;
+        let stored_range =
+            layout.stored_offset()..layout.stored_offset() + layout.stored_len() as usize;
+        track_file.read_exact(&mut out[stored_range]).map_err(CdRomError::DiscReadIo)?;
+        *position = sector_addr + layout.stored_len();
+
+        let time = metadata.file_start_time + CdTime::from_sector_number(relative_sector_number);
+        match layout {
+            SectorLayout::Raw => {}
+            SectorLayout::Mode1UserData => {
+                super::write_sector_header(time, 1, out);
+
+                // Mode 1 has 8 zero bytes between the EDC and the P parity
+                out[2068..2076].fill(0);
+                super::regenerate_edc_ecc(TrackMode::Mode1, out);
+            }
+            SectorLayout::Mode2NoHeader => {
+                super::write_sector_header(time, 2, out);
+            }
+        }
 
         Ok(())
     }
+
+    /// Sector number within the track's file of the given track-relative sector
+    pub(super) fn file_sector_number(&self, track_number: u8, relative_sector_number: u32) -> u32 {
+        let metadata = &self.track_metadata[(track_number - 1) as usize];
+        metadata.time_in_file.to_sector_number() + relative_sector_number
+    }
+}
+
+/// A BIN file, which may be ECM-compressed, or an audio file that is decoded on demand
+#[derive(Debug)]
+pub enum BinFile<F: Read + Seek> {
+    Raw(F),
+    Ecm(EcmFile<F>),
+    Audio(AudioFile),
+}
+
+impl BinFile<File> {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if audio::is_audio_path(path) {
+            return Ok(Self::Audio(AudioFile::open(path)?));
+        }
+
+        let file = File::open(path)?;
+        if ecm::is_ecm_path(path) {
+            Ok(Self::Ecm(EcmFile::open(file)?))
+        } else {
+            Ok(Self::Raw(file))
+        }
+    }
+}
+
+impl<F: Read + Seek> Read for BinFile<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Raw(file) => file.read(buf),
+            Self::Ecm(file) => file.read(buf),
+            Self::Audio(file) => file.read(buf),
+        }
+    }
+}
+
+impl<F: Read + Seek> Seek for BinFile<F> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::Raw(file) => file.seek(pos),
+            Self::Ecm(file) => file.seek(pos),
+            Self::Audio(file) => file.seek(pos),
+        }
+    }
+}
+
+/// Read a BIN file into memory, decoding it if it is ECM-compressed or an audio file
+pub fn read_bin_file(path: &Path) -> io::Result<SeekableVec> {
+    if audio::is_audio_path(path) {
+        let mut decoded = Vec::new();
+        AudioFile::open(path)?.read_to_end(&mut decoded)?;
+        return Ok(SeekableVec::new(decoded));
+    }
+
+    let bytes = fs::read(path)?;
+    if !ecm::is_ecm_path(path) {
+        return Ok(SeekableVec::new(bytes));
+    }
+
+    let mut decoded = Vec::new();
+    EcmFile::open(SeekableVec::new(bytes))?.read_to_end(&mut decoded)?;
+    Ok(SeekableVec::new(decoded))
 }
 
 #[derive(Debug, Clone)]
-struct ParsedTrack {
-    number: u8,
-    mode: TrackMode,
-    pregap_len: Option<CdTime>,
-    pause_start: Option<CdTime>,
-    track_start: CdTime,
+pub(super) struct ParsedTrack {
+    pub(super) number: u8,
+    pub(super) mode: TrackMode,
+    pub(super) sector_layout: SectorLayout,
+    pub(super) pregap_len: Option<CdTime>,
+    pub(super) pause_start: Option<CdTime>,
+    pub(super) track_start: CdTime,
 }
 
 #[derive(Debug, Clone)]
-struct ParsedFile {
-    file_name: String,
-    tracks: Vec<ParsedTrack>,
+pub(super) struct ParsedFile {
+    pub(super) file_name: String,
+    pub(super) tracks: Vec<ParsedTrack>,
 }
 
 #[derive(Debug, Clone)]
@@ -121,7 +339,7 @@ struct CueParser {
     files: Vec<ParsedFile>,
     tracks: Vec<ParsedTrack>,
     current_file: Option<String>,
-    current_track: Option<(u8, TrackMode)>,
+    current_track: Option<(u8, TrackMode, SectorLayout)>,
     last_track_number: Option<u8>,
     pregap_len: Option<CdTime>,
     pause_start: Option<CdTime>,
@@ -143,15 +361,20 @@ impl CueParser {
     }
 
     fn parse(mut self, file: &str) -> CdRomResult<Vec<ParsedFile>> {
-        for line in file.lines() {
-            if line.starts_with("FILE ") {
-                self.parse_file_line(line)?;
-            } else if line.starts_with("  TRACK ") {
-                self.parse_track_line(line)?;
-            } else if line.starts_with("    INDEX ") {
-                self.parse_index_line(line)?;
-            } else if line.starts_with("    PREGAP ") {
-                self.parse_pregap_line(line)?;
+        for line in file.trim_start_matches('\u{feff}').lines() {
+            let line = line.trim();
+            let keyword = line.split_ascii_whitespace().next().unwrap_or("");
+            match keyword {
+                "FILE" => self.parse_file_line(line)?,
+                "TRACK" => self.parse_track_line(line)?,
+                "INDEX" => self.parse_index_line(line)?,
+                "PREGAP" => self.parse_pregap_line(line)?,
+                "CATALOG" | "ISRC" | "FLAGS" => parse_metadata_line(keyword, line)?,
+                // Comments and CD-TEXT fields do not affect the disc contents
+                "" | "REM" | "TITLE" | "PERFORMER" | "SONGWRITER" | "CDTEXTFILE" => {}
+                _ => {
+                    log::warn!("Ignoring unsupported line in CUE file: {line}");
+                }
             }
         }
 
@@ -169,10 +392,19 @@ impl CueParser {
 
         self.push_file()?;
 
-        let re = RE.get_or_init(|| Regex::new(r#"FILE "(.*)" BINARY"#).unwrap());
+        // File names are usually quoted, but some older tools only quote names that contain spaces
+        let re = RE.get_or_init(|| Regex::new(r#"^FILE\s+(?:"(.*)"|(\S+))\s+(\S+)$"#).unwrap());
         let captures =
             re.captures(line).ok_or_else(|| CdRomError::CueInvalidFileLine(line.into()))?;
-        let file_name = captures.get(1).unwrap();
+        let file_name = captures.get(1).or_else(|| captures.get(2)).unwrap();
+
+        // WAVE is used for all audio files regardless of codec; these are decoded based on the file
+        // extension
+        let file_type = captures.get(3).unwrap().as_str();
+        if !matches!(file_type, "BINARY" | "WAVE") {
+            return Err(CdRomError::CueInvalidFileLine(line.into()));
+        }
+
         self.current_file = Some(file_name.as_str().into());
 
         Ok(())
@@ -183,7 +415,7 @@ impl CueParser {
 
         self.push_track()?;
 
-        let re = RE.get_or_init(|| Regex::new(r"TRACK ([^ ]*) ([^ ]*)").unwrap());
+        let re = RE.get_or_init(|| Regex::new(r"^TRACK\s+(\S+)\s+(\S+)").unwrap());
         let captures =
             re.captures(line).ok_or_else(|| CdRomError::CueInvalidTrackLine(line.into()))?;
         let track_number = captures
@@ -192,14 +424,13 @@ impl CueParser {
             .as_str()
             .parse::<u8>()
             .map_err(|_| CdRomError::CueInvalidTrackLine(line.into()))?;
-        let mode = captures
-            .get(2)
-            .unwrap()
-            .as_str()
+        let mode_str = captures.get(2).unwrap().as_str();
+        let mode = mode_str
             .parse::<TrackMode>()
             .map_err(|_| CdRomError::CueInvalidTrackLine(line.into()))?;
+        let sector_layout = SectorLayout::from_cue_track_mode(mode_str);
 
-        self.current_track = Some((track_number, mode));
+        self.current_track = Some((track_number, mode, sector_layout));
 
         Ok(())
     }
@@ -207,7 +438,7 @@ impl CueParser {
     fn parse_index_line(&mut self, line: &str) -> CdRomResult<()> {
         static RE: OnceLock<Regex> = OnceLock::new();
 
-        let re = RE.get_or_init(|| Regex::new(r"INDEX ([^ ]*) ([^ ]*)").unwrap());
+        let re = RE.get_or_init(|| Regex::new(r"^INDEX\s+(\S+)\s+(\S+)").unwrap());
         let captures =
             re.captures(line).ok_or_else(|| CdRomError::CueInvalidIndexLine(line.into()))?;
         let index_number = captures.get(1).unwrap();
@@ -236,7 +467,7 @@ impl CueParser {
     fn parse_pregap_line(&mut self, line: &str) -> CdRomResult<()> {
         static RE: OnceLock<Regex> = OnceLock::new();
 
-        let re = RE.get_or_init(|| Regex::new(r"PREGAP ([^ ]*)").unwrap());
+        let re = RE.get_or_init(|| Regex::new(r"^PREGAP\s+(\S+)").unwrap());
         let captures =
             re.captures(line).ok_or_else(|| CdRomError::CueInvalidPregapLine(line.into()))?;
         let pregap_len = captures
@@ -271,7 +502,7 @@ impl CueParser {
     }
 
     fn push_track(&mut self) -> CdRomResult<()> {
-        let Some((track_number, track_mode)) = self.current_track.take() else {
+        let Some((track_number, track_mode, sector_layout)) = self.current_track.take() else {
             return Ok(());
         };
 
@@ -302,6 +533,7 @@ impl CueParser {
         self.tracks.push(ParsedTrack {
             number: track_number,
             mode: track_mode,
+            sector_layout,
             pregap_len: self.pregap_len.take(),
             pause_start: self.pause_start.take(),
             track_start,
@@ -311,37 +543,54 @@ impl CueParser {
     }
 }
 
-fn parse_cue<P: AsRef<Path>>(cue_path: P) -> CdRomResult<(CueSheet, Vec<TrackMetadata>)> {
-    let cue_path = cue_path.as_ref();
+// CATALOG, ISRC, and FLAGS lines are validated but otherwise only logged; the emulated drive does
+// not report catalog numbers or ISRCs, and the flags only affect audio playback on real CD players
+fn parse_metadata_line(keyword: &str, line: &str) -> CdRomResult<()> {
+    let invalid = || CdRomError::CueInvalidMetadataLine(line.into());
+
+    let mut values = line.split_ascii_whitespace().skip(1).peekable();
+    if values.peek().is_none() {
+        return Err(invalid());
+    }
+
+    match keyword {
+        "CATALOG" => {
+            // 13-digit UPC/EAN
+            let catalog = values.next().unwrap();
+            if catalog.len() != 13 || !catalog.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+        }
+        "ISRC" => {
+            // 12 alphanumeric characters
+            let isrc = values.next().unwrap();
+            if isrc.len() != 12 || !isrc.bytes().all(|b| b.is_ascii_alphanumeric()) {
+                return Err(invalid());
+            }
+        }
+        "FLAGS" => {
+            if !values.all(|flag| matches!(flag, "DCP" | "4CH" | "PRE" | "SCMS")) {
+                return Err(invalid());
+            }
+        }
+        _ => unreachable!("not a metadata keyword: {keyword}"),
+    }
 
-    let cue_file = fs::read_to_string(cue_path)
-        .map_err(|source| CdRomError::CueOpen { path: cue_path.display().to_string(), source })?;
-    let parsed_files = CueParser::new().parse(&cue_file)?;
+    log::debug!("Parsed CUE metadata: {line}");
 
-    to_cue_sheet(parsed_files, cue_path)
+    Ok(())
 }
 
 fn to_cue_sheet(
     parsed_files: Vec<ParsedFile>,
-    cue_path: &Path,
-) -> CdRomResult<(CueSheet, Vec<TrackMetadata>)> {
-    let cue_parent_dir = cue_path
-        .parent()
-        .ok_or_else(|| CdRomError::CueParentDir(cue_path.display().to_string()))?;
-
+    file_lens: &HashMap<String, u64>,
+) -> (CueSheet, Vec<TrackMetadata>) {
     let mut absolute_start_time = CdTime::ZERO;
     let mut tracks = Vec::new();
     let mut track_metadata = Vec::new();
 
     for ParsedFile { file_name, tracks: parsed_tracks } in parsed_files {
-        let bin_path = cue_parent_dir.join(&file_name);
-
-        let file_metadata = fs::metadata(&bin_path).map_err(|source| CdRomError::FsMetadata {
-            path: bin_path.display().to_string(),
-            source,
-        })?;
-        let file_len_bytes = file_metadata.len();
-        let file_len_sectors = (file_len_bytes / crate::BYTES_PER_SECTOR) as u32;
+        let file_len_bytes = file_lens[&file_name];
 
         for i in 0..parsed_tracks.len() {
             let track = &parsed_tracks[i];
@@ -360,7 +609,8 @@ fn to_cue_sheet(
 
             let is_last_track_in_file = i == parsed_tracks.len() - 1;
             let data_end_time = if is_last_track_in_file {
-                CdTime::from_sector_number(file_len_sectors)
+                let file_len_sectors = file_len_bytes / track.sector_layout.stored_len();
+                CdTime::from_sector_number(file_len_sectors as u32)
             } else {
                 let next_track = &parsed_tracks[i + 1];
                 next_track.pause_start.unwrap_or(next_track.track_start)
@@ -383,6 +633,8 @@ fn to_cue_sheet(
             track_metadata.push(TrackMetadata {
                 file_name: file_name.clone(),
                 time_in_file: track.pause_start.unwrap_or(track.track_start),
+                sector_layout: track.sector_layout,
+                file_start_time: absolute_start_time + pregap_len,
             });
 
             absolute_start_time += padded_track_len;
@@ -398,5 +650,59 @@ fn to_cue_sheet(
         "Tracks in parsed CUE sheet are not continuous; this is a bug"
     );
 
-    Ok((CueSheet::new(tracks), track_metadata))
+    (CueSheet::new(tracks), track_metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cue() {
+        let cue = "\u{feff}REM GENRE Game\r\n\
+                   CATALOG 0000000000000\r\n\
+                   FILE \"Game (Track 1).bin\" BINARY\r\n\
+                   \tTRACK 01 MODE1/2048\r\n\
+                   \t\tINDEX 01 00:00:00\r\n\
+                   FILE track02.flac WAVE\r\n\
+                   \tTRACK 02 AUDIO\r\n\
+                   \t\tFLAGS DCP PRE\r\n\
+                   \t\tISRC USABC1234567\r\n\
+                   \t\tINDEX 00 00:00:00\r\n\
+                   \t\tINDEX 01 00:02:00\r\n\
+                   \tTRACK 03 MODE2/2336\r\n\
+                   \t\tINDEX 01 01:00:00\r\n";
+
+        let files = CueParser::new().parse(cue).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].file_name, "Game (Track 1).bin");
+        assert_eq!(files[0].tracks[0].mode, TrackMode::Mode1);
+        assert_eq!(files[0].tracks[0].sector_layout, SectorLayout::Mode1UserData);
+
+        assert_eq!(files[1].file_name, "track02.flac");
+        let [audio_track, data_track] = &files[1].tracks[..] else { panic!("expected 2 tracks") };
+        assert_eq!(audio_track.mode, TrackMode::Audio);
+        assert_eq!(audio_track.sector_layout, SectorLayout::Raw);
+        assert_eq!(audio_track.pause_start, Some(CdTime::ZERO));
+        assert_eq!(audio_track.track_start, CdTime::new(0, 2, 0));
+        assert_eq!(data_track.mode, TrackMode::Mode2);
+        assert_eq!(data_track.sector_layout, SectorLayout::Mode2NoHeader);
+
+        let invalid = ["CATALOG 123", "ISRC US-ABC-12-34567", "FLAGS DCP LOUD"];
+        for line in invalid {
+            let cue = format!(
+                "{line}\nFILE \"a.bin\" BINARY\n  TRACK 01 MODE2/2352\n    INDEX 01 00:00:00\n"
+            );
+            assert!(CueParser::new().parse(&cue).is_err(), "{line}");
+        }
+
+        let cue = "FILE \"a.bin\" MOTOROLA\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n";
+        assert!(CueParser::new().parse(cue).is_err());
+    }
+
+    #[test]
+    fn single_track_requires_file_name() {
+        let result = CdBinFiles::create_single_track("/", |path| File::open(path));
+        assert!(matches!(result, Err(CdRomError::BinFileName(path)) if path == "/"));
+    }
 }
@@ -0,0 +1,358 @@
+//! Code for reading ECM (Error Code Modeler) files, which store a CD image with the sync pattern,
+//! EDC, and ECC removed from every sector that they can be regenerated for
+//!
+//! An ECM file is a sequence of records, each of which is either a run of literal bytes or a run of
+//! Mode 1, Mode 2 Form 1, or Mode 2 Form 2 sectors with the redundant fields stripped. Mode 2
+//! sectors are stored without their sync pattern and header, which are kept as literal bytes.
+//!
+//! The records are indexed when the file is opened so that any part of the decoded image can be
+//! read without decoding everything before it.
+
+use crate::cue::TrackMode;
+use crate::ecc;
+use crate::reader::{
+    CD_ROM_CRC, MODE_1_CHECKSUM_LOCATION, MODE_1_DIGEST_RANGE, MODE_2_FORM_1_CHECKSUM_LOCATION,
+    MODE_2_FORM_1_DIGEST_RANGE, MODE_2_FORM_2_CHECKSUM_LOCATION, MODE_2_FORM_2_DIGEST_RANGE,
+};
+use std::cmp;
+use std::ffi::OsStr;
+use std::fmt::{Debug, Formatter};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"ECM\0";
+
+// Returned by the type/count encoding to mark the end of the records
+const END_OF_RECORDS: u64 = 0xFFFF_FFFF;
+
+const MODE_1_ZERO_LOCATION: Range<usize> = 0x814..0x81C;
+const MODE_2_SUBHEADER_LOCATION: usize = 0x10;
+const MODE_2_SUBHEADER_COPY_LOCATION: usize = 0x14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecordType {
+    Literal,
+    Mode1,
+    Mode2Form1,
+    Mode2Form2,
+}
+
+impl RecordType {
+    fn from_bits(bits: u8) -> Self {
+        match bits & 3 {
+            0 => Self::Literal,
+            1 => Self::Mode1,
+            2 => Self::Mode2Form1,
+            3 => Self::Mode2Form2,
+            _ => unreachable!("value & 3 is always <= 3"),
+        }
+    }
+
+    // Length of one unit (byte or sector) in the decoded image
+    fn decoded_len(self) -> u64 {
+        match self {
+            Self::Literal => 1,
+            Self::Mode1 => crate::BYTES_PER_SECTOR,
+            // Mode 2 sectors are decoded without their sync pattern and header
+            Self::Mode2Form1 | Self::Mode2Form2 => crate::BYTES_PER_SECTOR - 16,
+        }
+    }
+
+    // Length of one unit (byte or sector) in the ECM file
+    fn encoded_len(self) -> u64 {
+        match self {
+            Self::Literal => 1,
+            // 3-byte address + 2048 bytes of data
+            Self::Mode1 => 0x803,
+            // 4-byte subheader + 2048 bytes of data
+            Self::Mode2Form1 => 0x804,
+            // 4-byte subheader + 2324 bytes of data
+            Self::Mode2Form2 => 0x918,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Record {
+    record_type: RecordType,
+    count: u64,
+    decoded_offset: u64,
+    encoded_offset: u64,
+}
+
+impl Record {
+    fn decoded_len(&self) -> u64 {
+        self.count * self.record_type.decoded_len()
+    }
+}
+
+// The underlying ECM file, tracked separately from the decoded sector buffer so that sectors can
+// be decoded in place
+struct EncodedFile<F: Read + Seek> {
+    file: BufReader<F>,
+    position: u64,
+}
+
+impl<F: Read + Seek> EncodedFile<F> {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        if offset != self.position {
+            // Records are usually read in order with small gaps for record headers, so seek
+            // relative to keep the read buffer when possible
+            self.file.seek_relative(offset as i64 - self.position as i64)?;
+        }
+
+        self.file.read_exact(buf)?;
+        self.position = offset + buf.len() as u64;
+
+        Ok(())
+    }
+}
+
+/// A decoded view of an ECM file, which can be read and seeked like the original image
+pub struct EcmFile<F: Read + Seek> {
+    encoded: EncodedFile<F>,
+    records: Vec<Record>,
+    len: u64,
+    position: u64,
+    sector: Box<[u8; crate::BYTES_PER_SECTOR as usize]>,
+}
+
+impl<F: Read + Seek> EcmFile<F> {
+    /// Index the records in an ECM file.
+    ///
+    /// # Errors
+    ///
+    /// Will propagate any I/O errors, and will return an error of kind `InvalidData` if the file
+    /// is not a valid ECM file.
+    pub fn open(file: F) -> io::Result<Self> {
+        let mut file = BufReader::new(file);
+
+        let mut magic = [0; MAGIC.len()];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "missing ECM file header"));
+        }
+
+        let mut encoded_offset = MAGIC.len() as u64;
+        let mut decoded_offset = 0;
+        let mut records = Vec::new();
+        loop {
+            let (record_type, count, header_len) = read_record_header(&mut file)?;
+            encoded_offset += header_len;
+
+            if count == END_OF_RECORDS {
+                break;
+            }
+
+            let record = Record { record_type, count: count + 1, decoded_offset, encoded_offset };
+            records.push(record);
+
+            // Skip over the record contents without decoding them
+            let encoded_len = record.count * record_type.encoded_len();
+            file.seek_relative(encoded_len as i64)?;
+            encoded_offset += encoded_len;
+            decoded_offset += record.decoded_len();
+        }
+
+        log::debug!("Indexed {} ECM records; decoded length is {decoded_offset}", records.len());
+
+        Ok(Self {
+            encoded: EncodedFile { file, position: encoded_offset },
+            records,
+            len: decoded_offset,
+            position: 0,
+            sector: Box::new([0; crate::BYTES_PER_SECTOR as usize]),
+        })
+    }
+
+    // Returns the decoded sector, which for Mode 2 sectors does not include the sync pattern and
+    // header
+    fn decode_sector(&mut self, record_type: RecordType, encoded_offset: u64) -> io::Result<&[u8]> {
+        let sector = &mut *self.sector;
+        sector.fill(0);
+        sector[1..11].fill(0xFF);
+
+        match record_type {
+            RecordType::Mode1 => {
+                sector[15] = 1;
+                self.encoded.read_at(encoded_offset, &mut sector[12..15])?;
+                self.encoded.read_at(encoded_offset + 3, &mut sector[16..0x810])?;
+
+                let edc = CD_ROM_CRC.checksum(&sector[MODE_1_DIGEST_RANGE]);
+                sector[MODE_1_CHECKSUM_LOCATION].copy_from_slice(&edc.to_le_bytes());
+                sector[MODE_1_ZERO_LOCATION].fill(0);
+                ecc::generate(TrackMode::Mode1, sector);
+            }
+            RecordType::Mode2Form1 | RecordType::Mode2Form2 => {
+                sector[15] = 2;
+                let encoded_len = record_type.encoded_len() as usize;
+                self.encoded.read_at(
+                    encoded_offset,
+                    &mut sector[MODE_2_SUBHEADER_COPY_LOCATION
+                        ..MODE_2_SUBHEADER_COPY_LOCATION + encoded_len],
+                )?;
+                sector.copy_within(
+                    MODE_2_SUBHEADER_COPY_LOCATION..MODE_2_SUBHEADER_COPY_LOCATION + 4,
+                    MODE_2_SUBHEADER_LOCATION,
+                );
+
+                if record_type == RecordType::Mode2Form1 {
+                    let edc = CD_ROM_CRC.checksum(&sector[MODE_2_FORM_1_DIGEST_RANGE]);
+                    sector[MODE_2_FORM_1_CHECKSUM_LOCATION].copy_from_slice(&edc.to_le_bytes());
+                    ecc::generate(TrackMode::Mode2, sector);
+                } else {
+                    let edc = CD_ROM_CRC.checksum(&sector[MODE_2_FORM_2_DIGEST_RANGE]);
+                    sector[MODE_2_FORM_2_CHECKSUM_LOCATION].copy_from_slice(&edc.to_le_bytes());
+                }
+            }
+            RecordType::Literal => unreachable!("literal records do not contain sectors"),
+        }
+
+        let decoded_start = (crate::BYTES_PER_SECTOR - record_type.decoded_len()) as usize;
+        Ok(&self.sector[decoded_start..])
+    }
+}
+
+impl<F: Read + Seek> Read for EcmFile<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.position >= self.len {
+            return Ok(0);
+        }
+
+        let record_idx =
+            self.records.partition_point(|record| record.decoded_offset <= self.position) - 1;
+        let record = self.records[record_idx];
+        let offset_in_record = self.position - record.decoded_offset;
+
+        let bytes_read = match record.record_type {
+            RecordType::Literal => {
+                let len = cmp::min(buf.len() as u64, record.count - offset_in_record) as usize;
+                self.encoded.read_at(record.encoded_offset + offset_in_record, &mut buf[..len])?;
+                len
+            }
+            record_type => {
+                let sector_idx = offset_in_record / record_type.decoded_len();
+                let offset_in_sector = (offset_in_record % record_type.decoded_len()) as usize;
+
+                let encoded_offset = record.encoded_offset + sector_idx * record_type.encoded_len();
+                let sector = self.decode_sector(record_type, encoded_offset)?;
+
+                let len = cmp::min(buf.len(), sector.len() - offset_in_sector);
+                buf[..len].copy_from_slice(&sector[offset_in_sector..offset_in_sector + len]);
+                len
+            }
+        };
+
+        self.position += bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+impl<F: Read + Seek> Seek for EcmFile<F> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+
+        let Some(position) = position else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            ));
+        };
+
+        self.position = position;
+        Ok(position)
+    }
+}
+
+impl<F: Read + Seek> Debug for EcmFile<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EcmFile {{ records: {}, len: {} }}", self.records.len(), self.len)
+    }
+}
+
+/// Whether the file at the given path should be decoded as an ECM file, based on its extension
+/// (e.g. `.bin.ecm`)
+#[must_use]
+pub fn is_ecm_path(path: &Path) -> bool {
+    path.extension().and_then(OsStr::to_str).is_some_and(|ext| ext.eq_ignore_ascii_case("ecm"))
+}
+
+// Returns the record type, the count (minus 1), and the number of bytes read
+fn read_record_header<R: Read>(file: &mut R) -> io::Result<(RecordType, u64, u64)> {
+    let mut read_byte = || -> io::Result<u8> {
+        let mut byte = [0];
+        file.read_exact(&mut byte)?;
+        Ok(byte[0])
+    };
+
+    let mut byte = read_byte()?;
+    let mut header_len = 1;
+
+    let record_type = RecordType::from_bits(byte);
+    let mut count = u64::from((byte >> 2) & 0x1F);
+    let mut bits = 5;
+    while byte & 0x80 != 0 {
+        if bits > 32 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid ECM record header"));
+        }
+
+        byte = read_byte()?;
+        header_len += 1;
+        count |= u64::from(byte & 0x7F) << bits;
+        bits += 7;
+    }
+
+    if count > END_OF_RECORDS {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid ECM record count"));
+    }
+
+    Ok((record_type, count, header_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::seekvec::SeekableVec;
+
+    #[test]
+    fn decode_literal_and_sectors() {
+        // Literal record with 16 bytes: a Mode 2 sync pattern and header
+        let mut ecm = MAGIC.to_vec();
+        ecm.push(15 << 2);
+        ecm.extend([0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]);
+        ecm.extend([0x00, 0x02, 0x16, 0x02]);
+
+        // 2 Mode 2 Form 1 sectors, with a count that needs 2 bytes to encode
+        ecm.push(0x80 | (1 << 2) | 2);
+        ecm.push(0);
+        for i in 0..2 {
+            ecm.extend([0x00, 0x00, 0x08, 0x00]);
+            ecm.extend((0..2048).map(|j| (i + j) as u8));
+        }
+
+        ecm.extend([0xFC, 0xFF, 0xFF, 0xFF, 0x3F]);
+
+        let mut file = EcmFile::open(SeekableVec::new(ecm)).unwrap();
+        assert_eq!(file.seek(SeekFrom::End(0)).unwrap(), 16 + 2 * 2336);
+
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut sector = vec![0; crate::BYTES_PER_SECTOR as usize];
+        file.read_exact(&mut sector).unwrap();
+
+        assert_eq!(sector[15], 2);
+        assert_eq!(&sector[16..24], &[0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x08, 0x00]);
+        assert_eq!(sector[24 + 100], 100);
+        assert!(ecc::is_valid(TrackMode::Mode2, &sector));
+
+        // Second sector, read without reading the first
+        let mut data = [0; 4];
+        file.seek(SeekFrom::Start(16 + 2336 + 8 + 100)).unwrap();
+        file.read_exact(&mut data).unwrap();
+        assert_eq!(data, [101, 102, 103, 104]);
+    }
+}
@@ -0,0 +1,188 @@
+//! Code for loading and reading plain ISO images, which contain only the 2048 bytes of user data
+//! from each sector of a single data track
+//!
+//! The sync pattern, header, subheader, and EDC/ECC of each sector are synthesized on read so that
+//! raw sector reads behave the same as with a full 2352-byte image.
+
+use crate::cdtime::CdTime;
+use crate::cue::{CueSheet, Track, TrackMode, TrackType};
+use crate::{CdRomError, CdRomResult, cue};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::mem;
+
+const USER_DATA_LEN: u64 = 2048;
+
+// CD-XA discs (e.g. all PS1 discs) identify themselves with this string in the primary volume
+// descriptor. ISOs without it were mastered as Mode 1 discs
+const PRIMARY_VOLUME_DESCRIPTOR_LBA: u64 = 16;
+const CD_XA_SIGNATURE_OFFSET: u64 = 1024;
+const CD_XA_SIGNATURE: &[u8; 8] = b"CD-XA001";
+
+// File 0, channel 0, Form 1 data sector
+const MODE_2_FORM_1_SUBHEADER: [u8; 4] = [0x00, 0x00, 0x08, 0x00];
+
+#[derive(Debug)]
+pub struct IsoFile<F: Read + Seek> {
+    file: BufReader<F>,
+    position: u64,
+    mode: TrackMode,
+}
+
+impl<F: Read + Seek> IsoFile<F> {
+    pub fn open(file: F) -> CdRomResult<(Self, CueSheet)> {
+        let mut file = BufReader::new(file);
+
+        let file_len = file.seek(SeekFrom::End(0)).map_err(CdRomError::DiscReadIo)?;
+        let sector_count = (file_len / USER_DATA_LEN) as u32;
+        if sector_count <= PRIMARY_VOLUME_DESCRIPTOR_LBA as u32 {
+            return Err(CdRomError::IsoImageTooShort { len: file_len });
+        }
+
+        let mut signature = [0; CD_XA_SIGNATURE.len()];
+        file.seek(SeekFrom::Start(
+            PRIMARY_VOLUME_DESCRIPTOR_LBA * USER_DATA_LEN + CD_XA_SIGNATURE_OFFSET,
+        ))
+        .and_then(|_| file.read_exact(&mut signature))
+        .map_err(CdRomError::DiscReadIo)?;
+
+        let mode = if &signature == CD_XA_SIGNATURE { TrackMode::Mode2 } else { TrackMode::Mode1 };
+        log::debug!("ISO image has {sector_count} sectors, using {mode:?} sectors");
+
+        // Data tracks always have a 2-second pregap and postgap
+        let pregap_len = CdTime::new(0, 2, 0);
+        let postgap_len = TrackType::Data.default_postgap_len();
+        let mut tracks = vec![Track {
+            number: 1,
+            mode,
+            track_type: TrackType::Data,
+            start_time: CdTime::ZERO,
+            end_time: pregap_len + CdTime::from_sector_number(sector_count) + postgap_len,
+            pregap_len,
+            pause_len: CdTime::ZERO,
+            postgap_len,
+        }];
+        cue::finalize_track_list(&mut tracks);
+
+        // Force a seek on the first read
+        let position = u64::MAX;
+
+        Ok((Self { file, position, mode }, CueSheet::new(tracks)))
+    }
+
+    pub fn read_sector(
+        &mut self,
+        track_number: u8,
+        relative_sector_number: u32,
+        out: &mut [u8],
+    ) -> CdRomResult<()> {
+        debug_assert_eq!(track_number, 1, "ISO images only have 1 track");
+
+        // The file position is unknown if the seek or read fails, so clear the cached position
+        // until the read succeeds
+        let sector_addr = u64::from(relative_sector_number) * USER_DATA_LEN;
+        if mem::replace(&mut self.position, u64::MAX) != sector_addr {
+            self.file.seek(SeekFrom::Start(sector_addr)).map_err(CdRomError::DiscReadIo)?;
+        }
+
+        let user_data_offset = match self.mode {
+            TrackMode::Mode2 => 24,
+            TrackMode::Mode1 | TrackMode::Audio => 16,
+        };
+        self.file
+            .read_exact(&mut out[user_data_offset..user_data_offset + USER_DATA_LEN as usize])
+            .map_err(CdRomError::DiscReadIo)?;
+        self.position = sector_addr + USER_DATA_LEN;
+
+        // Sector addresses in the header are absolute, and track 1 starts after the 2-second pregap
+        let absolute_time =
+            CdTime::from_sector_number(relative_sector_number) + CdTime::new(0, 2, 0);
+        let mode_byte = match self.mode {
+            TrackMode::Mode2 => 2,
+            TrackMode::Mode1 | TrackMode::Audio => 1,
+        };
+        super::write_sector_header(absolute_time, mode_byte, out);
+
+        if self.mode == TrackMode::Mode2 {
+            // The subheader is stored twice
+            out[16..20].copy_from_slice(&MODE_2_FORM_1_SUBHEADER);
+            out[20..24].copy_from_slice(&MODE_2_FORM_1_SUBHEADER);
+        } else {
+            // Mode 1 has 8 zero bytes between the EDC and the P parity
+            out[2068..2076].fill(0);
+        }
+
+        super::regenerate_edc_ecc(self.mode, out);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecc;
+    use crate::reader::{CD_ROM_CRC, SYNC_PATTERN};
+    use std::io::Cursor;
+
+    const SECTOR_COUNT: u64 = 80;
+
+    fn user_data(lba: u64) -> Vec<u8> {
+        (0..USER_DATA_LEN).map(|i| (i * 7 + lba) as u8).collect()
+    }
+
+    fn open_iso(cd_xa: bool) -> IsoFile<Cursor<Vec<u8>>> {
+        let mut image: Vec<u8> = (0..SECTOR_COUNT).flat_map(user_data).collect();
+        if cd_xa {
+            let offset =
+                (PRIMARY_VOLUME_DESCRIPTOR_LBA * USER_DATA_LEN + CD_XA_SIGNATURE_OFFSET) as usize;
+            image[offset..offset + CD_XA_SIGNATURE.len()].copy_from_slice(CD_XA_SIGNATURE);
+        }
+
+        IsoFile::open(Cursor::new(image)).unwrap().0
+    }
+
+    fn read_sector(iso: &mut IsoFile<Cursor<Vec<u8>>>, relative_sector_number: u32) -> Vec<u8> {
+        let mut sector = vec![0xCC; crate::BYTES_PER_SECTOR as usize];
+        iso.read_sector(1, relative_sector_number, &mut sector).unwrap();
+        sector
+    }
+
+    #[test]
+    fn synthesized_mode_2_sector() {
+        let mut iso = open_iso(true);
+        assert_eq!(iso.mode, TrackMode::Mode2);
+
+        // 00:02:05 after the 2-second pregap
+        let sector = read_sector(&mut iso, 5);
+        assert_eq!(sector[..12], SYNC_PATTERN);
+        assert_eq!(sector[12..16], [0x00, 0x02, 0x05, 0x02]);
+        assert_eq!(sector[16..20], MODE_2_FORM_1_SUBHEADER);
+        assert_eq!(sector[20..24], MODE_2_FORM_1_SUBHEADER);
+        assert_eq!(sector[24..2072], user_data(5));
+
+        let edc = CD_ROM_CRC.checksum(&sector[16..2072]);
+        assert_eq!(sector[2072..2076], edc.to_le_bytes());
+        assert!(ecc::is_valid(TrackMode::Mode2, &sector));
+
+        let mut corrupted = sector.clone();
+        corrupted[100] ^= 1;
+        assert!(!ecc::is_valid(TrackMode::Mode2, &corrupted));
+    }
+
+    #[test]
+    fn synthesized_mode_1_sector() {
+        let mut iso = open_iso(false);
+        assert_eq!(iso.mode, TrackMode::Mode1);
+
+        // 76 sectors + the pregap = 00:03:01
+        let sector = read_sector(&mut iso, 76);
+        assert_eq!(sector[..12], SYNC_PATTERN);
+        assert_eq!(sector[12..16], [0x00, 0x03, 0x01, 0x01]);
+        assert_eq!(sector[16..2064], user_data(76));
+
+        let edc = CD_ROM_CRC.checksum(&sector[..2064]);
+        assert_eq!(sector[2064..2068], edc.to_le_bytes());
+        assert_eq!(sector[2068..2076], [0; 8]);
+        assert!(ecc::is_valid(TrackMode::Mode1, &sector));
+    }
+}
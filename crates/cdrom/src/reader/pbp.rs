@@ -0,0 +1,450 @@
+//! Code for loading and reading PS1 disc images in PSP EBOOT.PBP format
+//!
+//! The disc image is stored in the PBP's DATA.PSAR section, either as a single PSISOIMG section or
+//! as a PSTITLEIMG section that contains one PSISOIMG section per disc. Each PSISOIMG section
+//! contains a lead-in style TOC and a table of raw deflate-compressed blocks of 16 sectors each.
+//! Blocks are decompressed on demand.
+
+use crate::cdtime::CdTime;
+use crate::cue::{CueSheet, Track, TrackMode, TrackType};
+use crate::reader::bcd_to_binary;
+use crate::{CdRomError, CdRomResult, cue};
+use flate2::read::DeflateDecoder;
+use std::fmt::{Debug, Formatter};
+use std::io::{self, Read, Seek, SeekFrom};
+
+const PBP_MAGIC: &[u8; 4] = b"\0PBP";
+// The DATA.PSAR offset is the last of the 8 section offsets following the magic and version
+const PSAR_OFFSET_LOCATION: u64 = 0x24;
+
+const MULTI_DISC_MAGIC: &[u8; 16] = b"PSTITLEIMG000000";
+const MULTI_DISC_OFFSETS_LOCATION: u64 = 0x200;
+const MAX_DISCS: usize = 5;
+
+const DISC_MAGIC: &[u8; 12] = b"PSISOIMG0000";
+// Images from the PlayStation Store are encrypted, which is not supported
+const ENCRYPTED_MAGIC_LOCATION: u64 = 0x400;
+const ENCRYPTED_MAGIC: &[u8; 4] = b"\0PGD";
+
+const TOC_LOCATION: u64 = 0x800;
+const TOC_ENTRY_LEN: usize = 10;
+const TOC_MAX_ENTRIES: usize = 102;
+
+const BLOCK_TABLE_LOCATION: u64 = 0x3C00;
+const BLOCK_TABLE_ENTRY_LEN: usize = 32;
+const BLOCK_DATA_LOCATION: u64 = 0x10_0000;
+const MAX_BLOCKS: u64 = (BLOCK_DATA_LOCATION - BLOCK_TABLE_LOCATION) / BLOCK_TABLE_ENTRY_LEN as u64;
+
+const SECTORS_PER_BLOCK: u32 = 16;
+const BLOCK_LEN: usize = SECTORS_PER_BLOCK as usize * crate::BYTES_PER_SECTOR as usize;
+
+// TOC points with special meanings; other points are BCD track numbers
+const TOC_POINT_LEAD_OUT: u8 = 0xA2;
+
+#[derive(Debug, Clone, Copy)]
+struct BlockEntry {
+    offset: u32,
+    len: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TocTrack {
+    number: u8,
+    mode: TrackMode,
+    start_time: CdTime,
+}
+
+pub struct PbpFile<F: Read + Seek> {
+    file: F,
+    data_offset: u64,
+    blocks: Vec<BlockEntry>,
+    track_start_sectors: Vec<u32>,
+    compressed_buffer: Vec<u8>,
+    block_buffer: Vec<u8>,
+    current_block: u32,
+}
+
+impl<F: Read + Seek> PbpFile<F> {
+    /// Number of discs in the PBP file.
+    pub fn disc_count(file: &mut F) -> CdRomResult<usize> {
+        read_disc_offsets(file).map(|offsets| offsets.len())
+    }
+
+    pub fn open(mut file: F, disc_index: usize) -> CdRomResult<(Self, CueSheet)> {
+        let disc_offsets = read_disc_offsets(&mut file)?;
+        let Some(&disc_offset) = disc_offsets.get(disc_index) else {
+            return Err(CdRomError::PbpDiscIndexOutOfRange {
+                disc_index,
+                disc_count: disc_offsets.len(),
+            });
+        };
+
+        let mut magic = [0; DISC_MAGIC.len()];
+        read_at(&mut file, disc_offset, &mut magic)?;
+        if &magic != DISC_MAGIC {
+            return Err(CdRomError::PbpInvalid(format!(
+                "missing PSISOIMG header for disc {}",
+                disc_index + 1
+            )));
+        }
+
+        let mut encrypted_magic = [0; ENCRYPTED_MAGIC.len()];
+        read_at(&mut file, disc_offset + ENCRYPTED_MAGIC_LOCATION, &mut encrypted_magic)?;
+        if &encrypted_magic == ENCRYPTED_MAGIC {
+            return Err(CdRomError::PbpEncrypted);
+        }
+
+        let mut toc = [0; TOC_MAX_ENTRIES * TOC_ENTRY_LEN];
+        read_at(&mut file, disc_offset + TOC_LOCATION, &mut toc)?;
+        let (toc_tracks, lead_out_time) = parse_toc(&toc)?;
+
+        // The image starts at sector 0 (00:02:00) and continues through the start of the lead-out
+        let image_sectors = lead_out_time
+            .to_sector_number()
+            .saturating_sub(CdTime::SECTOR_0_START.to_sector_number());
+        let block_count = image_sectors.div_ceil(SECTORS_PER_BLOCK);
+        if u64::from(block_count) > MAX_BLOCKS {
+            return Err(CdRomError::PbpInvalid(format!(
+                "disc {} has too many sectors ({image_sectors})",
+                disc_index + 1
+            )));
+        }
+
+        let mut block_table = vec![0; block_count as usize * BLOCK_TABLE_ENTRY_LEN];
+        read_at(&mut file, disc_offset + BLOCK_TABLE_LOCATION, &mut block_table)?;
+        let blocks: Vec<_> = block_table
+            .chunks_exact(BLOCK_TABLE_ENTRY_LEN)
+            .map(|entry| BlockEntry {
+                offset: u32::from_le_bytes(entry[0..4].try_into().unwrap()),
+                len: u16::from_le_bytes(entry[4..6].try_into().unwrap()),
+            })
+            .collect();
+
+        let (tracks, track_start_sectors) =
+            build_track_list(&toc_tracks, CdTime::from_sector_number(image_sectors));
+
+        log::debug!(
+            "PBP disc {} of {} has {image_sectors} sectors in {block_count} blocks",
+            disc_index + 1,
+            disc_offsets.len()
+        );
+
+        let pbp_file = Self {
+            file,
+            data_offset: disc_offset + BLOCK_DATA_LOCATION,
+            blocks,
+            track_start_sectors,
+            compressed_buffer: Vec::with_capacity(BLOCK_LEN),
+            block_buffer: Vec::with_capacity(BLOCK_LEN),
+            current_block: u32::MAX,
+        };
+
+        Ok((pbp_file, CueSheet::new(tracks)))
+    }
+
+    pub fn read_sector(
+        &mut self,
+        track_number: u8,
+        relative_sector_number: u32,
+        out: &mut [u8],
+    ) -> CdRomResult<()> {
+        let sector_number =
+            self.track_start_sectors[(track_number - 1) as usize] + relative_sector_number;
+
+        let block_number = sector_number / SECTORS_PER_BLOCK;
+        if block_number != self.current_block {
+            self.load_block(block_number).map_err(CdRomError::DiscReadIo)?;
+            self.current_block = block_number;
+        }
+
+        let block_offset =
+            (sector_number % SECTORS_PER_BLOCK) as usize * crate::BYTES_PER_SECTOR as usize;
+        out[..crate::BYTES_PER_SECTOR as usize].copy_from_slice(
+            &self.block_buffer[block_offset..block_offset + crate::BYTES_PER_SECTOR as usize],
+        );
+
+        Ok(())
+    }
+
+    fn load_block(&mut self, block_number: u32) -> io::Result<()> {
+        let Some(&BlockEntry { offset, len }) = self.blocks.get(block_number as usize) else {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("PBP block {block_number} is past the end of the disc"),
+            ));
+        };
+
+        self.compressed_buffer.resize(len.into(), 0);
+        self.file.seek(SeekFrom::Start(self.data_offset + u64::from(offset)))?;
+        self.file.read_exact(&mut self.compressed_buffer)?;
+
+        self.block_buffer.clear();
+        if usize::from(len) == BLOCK_LEN {
+            // Blocks that do not compress are stored as-is
+            self.block_buffer.extend_from_slice(&self.compressed_buffer);
+        } else {
+            DeflateDecoder::new(self.compressed_buffer.as_slice())
+                .take(BLOCK_LEN as u64)
+                .read_to_end(&mut self.block_buffer)?;
+
+            // The last block may be partial
+            self.block_buffer.resize(BLOCK_LEN, 0);
+        }
+
+        Ok(())
+    }
+}
+
+impl<F: Read + Seek> Debug for PbpFile<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PbpFile {{ data_offset: {}, current_block: {} }}",
+            self.data_offset, self.current_block
+        )
+    }
+}
+
+fn read_at<R: Read + Seek>(file: &mut R, offset: u64, buf: &mut [u8]) -> CdRomResult<()> {
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read_exact(buf))
+        .map_err(CdRomError::DiscReadIo)
+}
+
+// Returns the absolute file offset of each disc's PSISOIMG section
+fn read_disc_offsets<R: Read + Seek>(file: &mut R) -> CdRomResult<Vec<u64>> {
+    let mut magic = [0; PBP_MAGIC.len()];
+    read_at(file, 0, &mut magic)?;
+    if &magic != PBP_MAGIC {
+        return Err(CdRomError::PbpInvalid("missing PBP header".into()));
+    }
+
+    let mut psar_offset = [0; 4];
+    read_at(file, PSAR_OFFSET_LOCATION, &mut psar_offset)?;
+    let psar_offset = u64::from(u32::from_le_bytes(psar_offset));
+
+    let mut psar_magic = [0; MULTI_DISC_MAGIC.len()];
+    read_at(file, psar_offset, &mut psar_magic)?;
+
+    if &psar_magic == MULTI_DISC_MAGIC {
+        let mut disc_offsets = [0; 4 * MAX_DISCS];
+        read_at(file, psar_offset + MULTI_DISC_OFFSETS_LOCATION, &mut disc_offsets)?;
+
+        let disc_offsets: Vec<_> = disc_offsets
+            .chunks_exact(4)
+            .map(|offset| u32::from_le_bytes(offset.try_into().unwrap()))
+            .take_while(|&offset| offset != 0)
+            .map(|offset| psar_offset + u64::from(offset))
+            .collect();
+        if disc_offsets.is_empty() {
+            return Err(CdRomError::PbpInvalid("multi-disc PBP contains no discs".into()));
+        }
+
+        Ok(disc_offsets)
+    } else if psar_magic.starts_with(DISC_MAGIC) {
+        Ok(vec![psar_offset])
+    } else {
+        Err(CdRomError::PbpInvalid("DATA.PSAR does not contain a PS1 disc image".into()))
+    }
+}
+
+// Returns the tracks in order and the start time of the lead-out. TOC entries use the same format as
+// lead-in Q subchannel data: control/ADR, track number, point, MSF, zero, PMSF
+fn parse_toc(toc: &[u8]) -> CdRomResult<(Vec<TocTrack>, CdTime)> {
+    let mut tracks = Vec::new();
+    let mut lead_out_time = None;
+
+    for entry in toc.chunks_exact(TOC_ENTRY_LEN) {
+        let point = entry[2];
+        let time = CdTime::new_checked(
+            bcd_to_binary(entry[7]),
+            bcd_to_binary(entry[8]),
+            bcd_to_binary(entry[9]),
+        );
+
+        match point {
+            TOC_POINT_LEAD_OUT => lead_out_time = time,
+            0x01..=0x99 => {
+                let Some(start_time) = time else {
+                    return Err(CdRomError::PbpInvalid(format!(
+                        "invalid start time for track {point:02X}"
+                    )));
+                };
+
+                // Control bit 2 indicates a data track
+                let mode = if entry[0] & 0x40 != 0 { TrackMode::Mode2 } else { TrackMode::Audio };
+                tracks.push(TocTrack { number: bcd_to_binary(point), mode, start_time });
+            }
+            _ => {}
+        }
+    }
+
+    tracks.sort_by_key(|track| track.number);
+
+    let track_numbers_valid =
+        tracks.iter().enumerate().all(|(i, track)| usize::from(track.number) == i + 1);
+    if tracks.is_empty() || !track_numbers_valid {
+        let track_numbers: Vec<_> = tracks.iter().map(|track| track.number).collect();
+        return Err(CdRomError::PbpInvalid(format!("invalid track list {track_numbers:?}")));
+    }
+
+    let lead_out_time =
+        lead_out_time.ok_or_else(|| CdRomError::PbpInvalid("TOC has no lead-out".into()))?;
+
+    Ok((tracks, lead_out_time))
+}
+
+// Returns the tracks and the image sector where each track's stored data starts
+fn build_track_list(toc_tracks: &[TocTrack], image_end_time: CdTime) -> (Vec<Track>, Vec<u32>) {
+    // The TOC only lists index 01 of each track. Assume that audio tracks after the first track
+    // have a standard 2-second pause (index 00), which is stored in the image
+    let pause_lens: Vec<_> = toc_tracks
+        .iter()
+        .enumerate()
+        .map(|(i, track)| match track.mode.to_type() {
+            TrackType::Audio if i != 0 => CdTime::new(0, 2, 0),
+            _ => CdTime::ZERO,
+        })
+        .collect();
+    let data_start_times: Vec<_> = toc_tracks
+        .iter()
+        .zip(&pause_lens)
+        .map(|(track, &pause_len)| {
+            track.start_time.saturating_sub(CdTime::SECTOR_0_START).saturating_sub(pause_len)
+        })
+        .collect();
+
+    let mut tracks = Vec::with_capacity(toc_tracks.len());
+    let mut absolute_start_time = CdTime::ZERO;
+    for (i, toc_track) in toc_tracks.iter().enumerate() {
+        let track_type = toc_track.mode.to_type();
+        let pregap_len = match track_type {
+            // Data tracks always have a 2-second pregap
+            TrackType::Data => CdTime::new(0, 2, 0),
+            TrackType::Audio => CdTime::ZERO,
+        };
+        let postgap_len = track_type.default_postgap_len();
+
+        let data_end_time = data_start_times.get(i + 1).copied().unwrap_or(image_end_time);
+        let data_len = data_end_time.saturating_sub(data_start_times[i]);
+
+        let padded_track_len = pregap_len + data_len + postgap_len;
+        tracks.push(Track {
+            number: toc_track.number,
+            mode: toc_track.mode,
+            track_type,
+            start_time: absolute_start_time,
+            end_time: absolute_start_time + padded_track_len,
+            pregap_len,
+            pause_len: pause_lens[i],
+            postgap_len,
+        });
+
+        absolute_start_time += padded_track_len;
+    }
+
+    cue::finalize_track_list(&mut tracks);
+
+    assert!(cue::tracks_are_continuous(&tracks), "PBP tracks are not continuous; this is a bug");
+
+    let track_start_sectors = data_start_times.iter().map(|time| time.to_sector_number()).collect();
+    (tracks, track_start_sectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::seekvec::SeekableVec;
+    use flate2::Compression;
+    use flate2::write::DeflateEncoder;
+    use std::io::Write;
+
+    const PSAR_OFFSET: usize = 0x100;
+
+    fn time_to_bcd(time: CdTime) -> [u8; 3] {
+        [time.minutes, time.seconds, time.frames].map(crate::reader::time_component_to_bcd)
+    }
+
+    // Builds a PSISOIMG section with a single data track where every byte of sector N is N
+    fn disc_image(sector_count: u32) -> Vec<u8> {
+        let mut disc = vec![0; BLOCK_DATA_LOCATION as usize];
+        disc[..DISC_MAGIC.len()].copy_from_slice(DISC_MAGIC);
+
+        let toc = TOC_LOCATION as usize;
+        disc[toc..toc + 3].copy_from_slice(&[0x41, 0x00, 0x01]);
+        disc[toc + 7..toc + 10].copy_from_slice(&time_to_bcd(CdTime::SECTOR_0_START));
+        disc[toc + 10..toc + 13].copy_from_slice(&[0x41, 0x00, TOC_POINT_LEAD_OUT]);
+        disc[toc + 17..toc + 20].copy_from_slice(&time_to_bcd(
+            CdTime::SECTOR_0_START + CdTime::from_sector_number(sector_count),
+        ));
+
+        let mut block_data = Vec::new();
+        for block_number in 0..sector_count.div_ceil(SECTORS_PER_BLOCK) {
+            let sectors = (block_number * SECTORS_PER_BLOCK
+                ..((block_number + 1) * SECTORS_PER_BLOCK).min(sector_count))
+                .flat_map(|sector| [sector as u8; crate::BYTES_PER_SECTOR as usize]);
+
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&sectors.collect::<Vec<_>>()).unwrap();
+            let compressed = encoder.finish().unwrap();
+
+            let entry =
+                BLOCK_TABLE_LOCATION as usize + block_number as usize * BLOCK_TABLE_ENTRY_LEN;
+            disc[entry..entry + 4].copy_from_slice(&(block_data.len() as u32).to_le_bytes());
+            disc[entry + 4..entry + 6].copy_from_slice(&(compressed.len() as u16).to_le_bytes());
+            block_data.extend(compressed);
+        }
+
+        disc.extend(block_data);
+        disc
+    }
+
+    fn pbp(discs: &[Vec<u8>]) -> Vec<u8> {
+        let mut pbp = vec![0; PSAR_OFFSET];
+        pbp[..4].copy_from_slice(PBP_MAGIC);
+        pbp[PSAR_OFFSET_LOCATION as usize..PSAR_OFFSET_LOCATION as usize + 4]
+            .copy_from_slice(&(PSAR_OFFSET as u32).to_le_bytes());
+
+        if let [disc] = discs {
+            pbp.extend(disc);
+            return pbp;
+        }
+
+        let mut psar = vec![0; 0x8000];
+        psar[..MULTI_DISC_MAGIC.len()].copy_from_slice(MULTI_DISC_MAGIC);
+        for (i, disc) in discs.iter().enumerate() {
+            let offset = MULTI_DISC_OFFSETS_LOCATION as usize + 4 * i;
+            let disc_offset = psar.len() as u32;
+            psar[offset..offset + 4].copy_from_slice(&disc_offset.to_le_bytes());
+            psar.extend(disc);
+        }
+
+        pbp.extend(psar);
+        pbp
+    }
+
+    #[test]
+    fn read_multi_disc() {
+        let pbp = pbp(&[disc_image(20), disc_image(40)]);
+        assert_eq!(PbpFile::disc_count(&mut SeekableVec::new(pbp.clone())).unwrap(), 2);
+
+        let (mut pbp_file, cue_sheet) = PbpFile::open(SeekableVec::new(pbp.clone()), 1).unwrap();
+        let track = cue_sheet.track(1);
+        assert_eq!(track.mode, TrackMode::Mode2);
+        assert_eq!(
+            track.end_time - track.start_time,
+            CdTime::new(0, 2, 0) + CdTime::from_sector_number(40) + track.postgap_len
+        );
+
+        let mut sector = vec![0; crate::BYTES_PER_SECTOR as usize];
+        for sector_number in [39, 0, 17] {
+            pbp_file.read_sector(1, sector_number, &mut sector).unwrap();
+            assert!(sector.iter().all(|&byte| byte == sector_number as u8));
+        }
+
+        assert!(matches!(
+            PbpFile::open(SeekableVec::new(pbp), 2),
+            Err(CdRomError::PbpDiscIndexOutOfRange { disc_index: 2, disc_count: 2 })
+        ));
+    }
+}
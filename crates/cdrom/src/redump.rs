@@ -0,0 +1,366 @@
+//! Verification of disc images against a Redump DAT file
+//!
+//! Redump DATs list the size, CRC32, MD5, and SHA-1 of each track file in a known-good dump. Tracks
+//! are hashed exactly as they are stored in the image, which matches Redump's per-track BIN files
+//! regardless of whether the image is a single BIN, one BIN per track, or a CHD. Unlike the sector
+//! EDC check, this also catches bad dumps where the damaged sectors have valid EDC (e.g. audio
+//! tracks or incorrectly patched data sectors).
+
+use crate::reader::CdRom;
+use crate::{CdRomError, CdRomResult};
+use crc::Crc;
+use md5::{Digest, Md5};
+use sha1::Sha1;
+use std::fmt::{Display, Formatter, Write};
+use std::fs;
+use std::path::Path;
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
+/// Hashes of one track, as stored in the disc image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackHashes {
+    pub track_number: u8,
+    pub size: u64,
+    pub crc32: u32,
+    pub md5: [u8; 16],
+    pub sha1: [u8; 20],
+}
+
+impl TrackHashes {
+    #[cfg(test)]
+    fn compute(track_number: u8, data: &[u8]) -> Self {
+        let mut hasher = TrackHasher::new();
+        hasher.update(data);
+        hasher.finish(track_number)
+    }
+}
+
+struct TrackHasher {
+    size: u64,
+    crc32: crc::Digest<'static, u32>,
+    md5: Md5,
+    sha1: Sha1,
+}
+
+impl TrackHasher {
+    fn new() -> Self {
+        Self { size: 0, crc32: CRC32.digest(), md5: Md5::new(), sha1: Sha1::new() }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.size += data.len() as u64;
+        self.crc32.update(data);
+        self.md5.update(data);
+        self.sha1.update(data);
+    }
+
+    fn finish(self, track_number: u8) -> TrackHashes {
+        TrackHashes {
+            track_number,
+            size: self.size,
+            crc32: self.crc32.finalize(),
+            md5: self.md5.finalize().into(),
+            sha1: self.sha1.finalize().into(),
+        }
+    }
+}
+
+impl Display for TrackHashes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Track {:02}: size={} crc32={:08x} md5={} sha1={}",
+            self.track_number,
+            self.size,
+            self.crc32,
+            to_hex(&self.md5),
+            to_hex(&self.sha1)
+        )
+    }
+}
+
+/// Hash every track on the disc. This reads the entire disc image.
+///
+/// # Errors
+///
+/// This function will propagate any I/O error encountered while reading from disk.
+pub fn hash_tracks(disc: &mut CdRom) -> CdRomResult<Vec<TrackHashes>> {
+    let track_count = disc.cue().last_track().number;
+
+    let mut sector = vec![0; crate::BYTES_PER_SECTOR as usize];
+    let mut hashes = Vec::with_capacity(track_count.into());
+    for track_number in 1..=track_count {
+        let sector_count = disc.stored_sector_count(track_number);
+
+        let mut hasher = TrackHasher::new();
+        for sector_number in 0..sector_count {
+            disc.read_stored_sector(track_number, sector_number, &mut sector)?;
+            hasher.update(&sector);
+        }
+        let track_hashes = hasher.finish(track_number);
+
+        log::debug!("{track_hashes}");
+        hashes.push(track_hashes);
+    }
+
+    Ok(hashes)
+}
+
+/// A track file listed in a DAT file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatRom {
+    pub name: String,
+    pub size: u64,
+    pub crc32: Option<u32>,
+    pub md5: Option<[u8; 16]>,
+    pub sha1: Option<[u8; 20]>,
+}
+
+impl DatRom {
+    /// Compare using the strongest hash that the DAT lists
+    fn matches(&self, hashes: &TrackHashes) -> bool {
+        if self.size != hashes.size {
+            return false;
+        }
+
+        match (self.sha1, self.md5, self.crc32) {
+            (Some(sha1), ..) => sha1 == hashes.sha1,
+            (None, Some(md5), _) => md5 == hashes.md5,
+            (None, None, Some(crc32)) => crc32 == hashes.crc32,
+            (None, None, None) => false,
+        }
+    }
+}
+
+/// A disc listed in a DAT file. Only track files are included; the CUE file is not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatGame {
+    pub name: String,
+    pub tracks: Vec<DatRom>,
+}
+
+impl DatGame {
+    fn matching_track_count(&self, hashes: &[TrackHashes]) -> usize {
+        self.tracks.iter().zip(hashes).filter(|(rom, hashes)| rom.matches(hashes)).count()
+    }
+
+    fn sizes_match(&self, hashes: &[TrackHashes]) -> bool {
+        self.tracks.len() == hashes.len()
+            && self.tracks.iter().zip(hashes).all(|(rom, hashes)| rom.size == hashes.size)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// Every track matches the DAT entry
+    Verified { title: String },
+    /// The disc was identified, but some tracks do not match
+    BadDump { title: String, bad_tracks: Vec<u8>, expected_track_count: usize },
+    /// No DAT entry matches the disc
+    Unknown,
+}
+
+impl Display for Verification {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Verified { title } => write!(f, "Verified: {title}"),
+            Self::BadDump { title, bad_tracks, expected_track_count } => {
+                write!(f, "Bad dump of {title}: ")?;
+                if !bad_tracks.is_empty() {
+                    let bad_tracks: Vec<_> =
+                        bad_tracks.iter().map(|track| format!("{track:02}")).collect();
+                    write!(f, "track(s) {} do not match", bad_tracks.join(", "))?;
+                } else {
+                    write!(f, "expected {expected_track_count} tracks")?;
+                }
+                Ok(())
+            }
+            Self::Unknown => write!(f, "Disc does not match any entry in the DAT file"),
+        }
+    }
+}
+
+/// A parsed Redump DAT file (Logiqx XML format)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RedumpDat {
+    games: Vec<DatGame>,
+}
+
+impl RedumpDat {
+    /// Read and parse a DAT file.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the file cannot be read or is not valid XML.
+    pub fn open<P: AsRef<Path>>(path: P) -> CdRomResult<Self> {
+        let path = path.as_ref();
+
+        let xml = fs::read_to_string(path)
+            .map_err(|source| CdRomError::DatOpen { path: path.display().to_string(), source })?;
+        Self::parse(&xml)
+    }
+
+    /// Parse the contents of a DAT file. Malformed `rom` entries are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the text is not valid XML.
+    pub fn parse(xml: &str) -> CdRomResult<Self> {
+        let document = roxmltree::Document::parse(xml)?;
+
+        let games = document
+            .descendants()
+            .filter(|node| node.has_tag_name("game") || node.has_tag_name("machine"))
+            .filter_map(|game| {
+                let name = game.attribute("name")?.into();
+                let tracks = game
+                    .children()
+                    .filter(|node| node.has_tag_name("rom"))
+                    .filter_map(|rom| {
+                        let name = rom.attribute("name")?;
+                        if name.to_ascii_lowercase().ends_with(".cue") {
+                            return None;
+                        }
+
+                        Some(DatRom {
+                            name: name.into(),
+                            size: rom.attribute("size")?.parse().ok()?,
+                            crc32: rom
+                                .attribute("crc")
+                                .and_then(|crc| u32::from_str_radix(crc, 16).ok()),
+                            md5: rom.attribute("md5").and_then(from_hex),
+                            sha1: rom.attribute("sha1").and_then(from_hex),
+                        })
+                    })
+                    .collect();
+                Some(DatGame { name, tracks })
+            })
+            .collect();
+
+        Ok(Self { games })
+    }
+
+    #[must_use]
+    pub fn games(&self) -> &[DatGame] {
+        &self.games
+    }
+
+    /// Match a disc's track hashes against the DAT. The disc is identified by the entry with the
+    /// most matching tracks, or failing that by an entry with identical track sizes.
+    #[must_use]
+    pub fn verify(&self, hashes: &[TrackHashes]) -> Verification {
+        let best_match = self
+            .games
+            .iter()
+            .map(|game| (game, game.matching_track_count(hashes)))
+            .filter(|&(_, count)| count != 0)
+            .max_by_key(|&(_, count)| count)
+            .map(|(game, _)| game);
+
+        let game = match best_match {
+            Some(game) => game,
+            None => {
+                // A single-track disc with a damaged data track matches no hashes at all
+                let mut same_size = self.games.iter().filter(|game| game.sizes_match(hashes));
+                match (same_size.next(), same_size.next()) {
+                    (Some(game), None) => game,
+                    _ => return Verification::Unknown,
+                }
+            }
+        };
+
+        let bad_tracks: Vec<_> = hashes
+            .iter()
+            .zip(&game.tracks)
+            .filter(|(hashes, rom)| !rom.matches(hashes))
+            .map(|(hashes, _)| hashes.track_number)
+            .collect();
+
+        if bad_tracks.is_empty() && game.tracks.len() == hashes.len() {
+            Verification::Verified { title: game.name.clone() }
+        } else {
+            Verification::BadDump {
+                title: game.name.clone(),
+                bad_tracks,
+                expected_track_count: game.tracks.len(),
+            }
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(2 * bytes.len()), |mut s, byte| {
+        let _ = write!(s, "{byte:02x}");
+        s
+    })
+}
+
+fn from_hex<const N: usize>(s: &str) -> Option<[u8; N]> {
+    if s.len() != 2 * N {
+        return None;
+    }
+
+    let mut bytes = [0; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(s.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACK_1: &[u8] = b"data track";
+    const TRACK_2: &[u8] = b"audio track";
+
+    fn dat() -> RedumpDat {
+        let [track_1, track_2] = [TRACK_1, TRACK_2].map(|data| {
+            let hashes = TrackHashes::compute(0, data);
+            format!(
+                r#"size="{}" crc="{:08x}" md5="{}" sha1="{}""#,
+                hashes.size,
+                hashes.crc32,
+                to_hex(&hashes.md5),
+                to_hex(&hashes.sha1)
+            )
+        });
+
+        RedumpDat::parse(&format!(
+            r#"<?xml version="1.0"?>
+            <datafile>
+                <header><name>Sony - PlayStation</name></header>
+                <game name="Test Game (USA)">
+                    <rom name="Test Game (USA).cue" size="100" crc="00000000"/>
+                    <rom name="Test Game (USA) (Track 1).bin" {track_1}/>
+                    <rom name="Test Game (USA) (Track 2).bin" {track_2}/>
+                </game>
+            </datafile>"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn verify_tracks() {
+        let dat = dat();
+        assert_eq!(dat.games()[0].tracks.len(), 2);
+
+        let good = TrackHashes::compute(1, TRACK_1);
+        let bad = TrackHashes::compute(2, b"audio trick");
+
+        assert_eq!(
+            dat.verify(&[good.clone(), TrackHashes::compute(2, TRACK_2)]),
+            Verification::Verified { title: "Test Game (USA)".into() }
+        );
+        assert_eq!(
+            dat.verify(&[good, bad.clone()]),
+            Verification::BadDump {
+                title: "Test Game (USA)".into(),
+                bad_tracks: vec![2],
+                expected_track_count: 2
+            }
+        );
+        assert_eq!(dat.verify(&[bad]), Verification::Unknown);
+    }
+}
@@ -0,0 +1,298 @@
+//! Subchannel Q data, which encodes the current position on the disc
+//!
+//! Each sector has 12 bytes of Q data: control/ADR, track number, index, relative MSF, a zero byte,
+//! absolute MSF, and a CRC-16 of the first 10 bytes. Track number, index, and times are BCD.
+//!
+//! Most disc image formats do not store subchannel data, so Q data is generated from the TOC for
+//! those. LibCrypt-protected discs have intentionally corrupted Q data in specific sectors, which
+//! can be restored from SBI or LSD patch files.
+
+use crate::cdtime::CdTime;
+use crate::cue::{CueSheet, TrackType};
+use crate::reader::{bcd_to_binary, time_component_to_bcd};
+use crate::{CdRomError, CdRomResult};
+use bincode::{Decode, Encode};
+use crc::Crc;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+
+// Polynomial 0x1021 with the result inverted, stored big-endian
+const SUBCHANNEL_Q_CRC: Crc<u16> = Crc::<u16>::new(&crc::CRC_16_GSM);
+
+pub const SUBCHANNEL_Q_LEN: usize = 12;
+
+// Q data excluding the CRC
+const SUBCHANNEL_Q_DATA_LEN: usize = 10;
+
+// Control field bit that indicates a data track
+const CONTROL_DATA_TRACK: u8 = 0x04;
+
+const LEAD_OUT_TRACK_NUMBER: u8 = 0xAA;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct SubchannelQ([u8; SUBCHANNEL_Q_LEN]);
+
+impl SubchannelQ {
+    /// ADR value for Q data that contains the current position. Other ADR values are used for the
+    /// disc's catalog number and the tracks' ISRCs.
+    pub const ADR_POSITION: u8 = 1;
+
+    #[must_use]
+    pub fn new(bytes: [u8; SUBCHANNEL_Q_LEN]) -> Self {
+        Self(bytes)
+    }
+
+    /// Generate position Q data for the given absolute time based on the disc's TOC.
+    #[must_use]
+    pub fn from_position(cue_sheet: &CueSheet, absolute_time: CdTime) -> Self {
+        let (track, track_number, index, relative_time) =
+            match cue_sheet.find_track_by_time(absolute_time) {
+                Some(track) => {
+                    let effective_start_time = track.effective_start_time();
+                    if absolute_time < effective_start_time {
+                        // Relative time counts down to index 1 during the pregap
+                        let relative_time = effective_start_time - absolute_time;
+                        (track, time_component_to_bcd(track.number), 0x00, relative_time)
+                    } else {
+                        let relative_time = absolute_time - effective_start_time;
+                        (track, time_component_to_bcd(track.number), 0x01, relative_time)
+                    }
+                }
+                None => {
+                    let last_track = cue_sheet.last_track();
+                    let relative_time = absolute_time - last_track.end_time;
+                    (last_track, LEAD_OUT_TRACK_NUMBER, 0x01, relative_time)
+                }
+            };
+
+        let control = match track.track_type {
+            TrackType::Data => CONTROL_DATA_TRACK,
+            TrackType::Audio => 0x00,
+        };
+
+        Self::with_crc([
+            (control << 4) | Self::ADR_POSITION,
+            track_number,
+            index,
+            time_component_to_bcd(relative_time.minutes),
+            time_component_to_bcd(relative_time.seconds),
+            time_component_to_bcd(relative_time.frames),
+            0x00,
+            time_component_to_bcd(absolute_time.minutes),
+            time_component_to_bcd(absolute_time.seconds),
+            time_component_to_bcd(absolute_time.frames),
+        ])
+    }
+
+    fn with_crc(data: [u8; SUBCHANNEL_Q_DATA_LEN]) -> Self {
+        let mut bytes = [0; SUBCHANNEL_Q_LEN];
+        bytes[..SUBCHANNEL_Q_DATA_LEN].copy_from_slice(&data);
+        bytes[SUBCHANNEL_Q_DATA_LEN..]
+            .copy_from_slice(&SUBCHANNEL_Q_CRC.checksum(&data).to_be_bytes());
+        Self(bytes)
+    }
+
+    #[must_use]
+    pub fn bytes(&self) -> &[u8; SUBCHANNEL_Q_LEN] {
+        &self.0
+    }
+
+    #[must_use]
+    pub fn adr(&self) -> u8 {
+        self.0[0] & 0x0F
+    }
+
+    #[must_use]
+    pub fn crc_valid(&self) -> bool {
+        SUBCHANNEL_Q_CRC.checksum(&self.0[..SUBCHANNEL_Q_DATA_LEN])
+            == u16::from_be_bytes([self.0[10], self.0[11]])
+    }
+}
+
+/// Replacement Q data for individual sectors, keyed by absolute time.
+///
+/// SBI files store only the first 10 bytes of each replaced sector's Q data, so the CRC is
+/// generated to be invalid, matching the discs that these files are dumped from. LSD files store
+/// the full 12 bytes, including the original CRC.
+#[derive(Debug, Clone, Default, Encode, Decode)]
+pub struct SubchannelPatches(BTreeMap<CdTime, SubchannelQ>);
+
+const SBI_MAGIC: &[u8; 4] = b"SBI\0";
+
+// Entry types in SBI files; only type 1 (full Q data) is used in practice
+const SBI_TYPE_Q_DATA: u8 = 1;
+const SBI_TYPE_RELATIVE_TIME: u8 = 2;
+const SBI_TYPE_ABSOLUTE_TIME: u8 = 3;
+
+// 3-byte BCD time + full Q data
+const LSD_ENTRY_LEN: usize = 3 + SUBCHANNEL_Q_LEN;
+
+impl SubchannelPatches {
+    /// Load patches from an SBI or LSD file, determined by the file extension.
+    ///
+    /// # Errors
+    ///
+    /// Will propagate any I/O errors, and will return an error if the file is not a valid SBI or
+    /// LSD file.
+    pub fn load<P: AsRef<Path>>(path: P) -> CdRomResult<Self> {
+        let path = path.as_ref();
+
+        let bytes = fs::read(path).map_err(|source| CdRomError::SubchannelPatchOpen {
+            path: path.display().to_string(),
+            source,
+        })?;
+
+        let extension = path.extension().and_then(OsStr::to_str).unwrap_or("");
+        match extension.to_ascii_lowercase().as_str() {
+            "sbi" => Self::parse_sbi(&bytes),
+            "lsd" => Self::parse_lsd(&bytes),
+            _ => Err(CdRomError::SubchannelPatchInvalid(format!(
+                "Unrecognized patch file extension: '{extension}'"
+            ))),
+        }
+    }
+
+    fn parse_sbi(bytes: &[u8]) -> CdRomResult<Self> {
+        let Some(mut remaining) = bytes.strip_prefix(SBI_MAGIC) else {
+            return Err(CdRomError::SubchannelPatchInvalid("Missing SBI header".into()));
+        };
+
+        let mut patches = BTreeMap::new();
+        while !remaining.is_empty() {
+            let truncated = || CdRomError::SubchannelPatchInvalid("Truncated SBI entry".into());
+
+            let (header, rest) = remaining.split_first_chunk::<4>().ok_or_else(truncated)?;
+            let time = parse_bcd_time(&header[..3])?;
+
+            let data_len = match header[3] {
+                SBI_TYPE_Q_DATA => SUBCHANNEL_Q_DATA_LEN,
+                SBI_TYPE_RELATIVE_TIME | SBI_TYPE_ABSOLUTE_TIME => 3,
+                entry_type => {
+                    return Err(CdRomError::SubchannelPatchInvalid(format!(
+                        "Invalid SBI entry type {entry_type}"
+                    )));
+                }
+            };
+            if rest.len() < data_len {
+                return Err(truncated());
+            }
+            let (data, rest) = rest.split_at(data_len);
+            remaining = rest;
+
+            if header[3] != SBI_TYPE_Q_DATA {
+                log::warn!("Ignoring partial SBI entry at {time}, type {}", header[3]);
+                continue;
+            }
+
+            let mut q = SubchannelQ::with_crc(data.try_into().unwrap());
+            q.0[SUBCHANNEL_Q_DATA_LEN] ^= 0xFF;
+            q.0[SUBCHANNEL_Q_DATA_LEN + 1] ^= 0xFF;
+            patches.insert(time, q);
+        }
+
+        Ok(Self(patches))
+    }
+
+    fn parse_lsd(bytes: &[u8]) -> CdRomResult<Self> {
+        if !bytes.len().is_multiple_of(LSD_ENTRY_LEN) {
+            return Err(CdRomError::SubchannelPatchInvalid(format!(
+                "LSD file length {} is not a multiple of {LSD_ENTRY_LEN}",
+                bytes.len()
+            )));
+        }
+
+        let patches = bytes
+            .chunks_exact(LSD_ENTRY_LEN)
+            .map(|entry| {
+                let time = parse_bcd_time(&entry[..3])?;
+                let q = SubchannelQ(entry[3..].try_into().unwrap());
+                Ok((time, q))
+            })
+            .collect::<CdRomResult<_>>()?;
+
+        Ok(Self(patches))
+    }
+
+    #[must_use]
+    pub fn get(&self, time: CdTime) -> Option<SubchannelQ> {
+        self.0.get(&time).copied()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+fn parse_bcd_time(bcd: &[u8]) -> CdRomResult<CdTime> {
+    CdTime::new_checked(bcd_to_binary(bcd[0]), bcd_to_binary(bcd[1]), bcd_to_binary(bcd[2]))
+        .ok_or_else(|| {
+            CdRomError::SubchannelPatchInvalid(format!("Invalid BCD time: {:02X?}", &bcd[..3]))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cue::{Track, TrackMode};
+
+    #[test]
+    fn crc() {
+        let mut q = SubchannelQ::new([
+            0x41, 0x01, 0x01, 0x00, 0x00, 0x16, 0x00, 0x00, 0x02, 0x16, 0x93, 0x1A,
+        ]);
+        assert_eq!(q.adr(), SubchannelQ::ADR_POSITION);
+        assert!(q.crc_valid());
+
+        q.0[5] = 0x17;
+        assert!(!q.crc_valid());
+    }
+
+    #[test]
+    fn from_position() {
+        let cue_sheet = CueSheet::new(vec![Track {
+            number: 1,
+            mode: TrackMode::Mode2,
+            track_type: TrackType::Data,
+            start_time: CdTime::ZERO,
+            end_time: CdTime::new(1, 0, 0),
+            pregap_len: CdTime::new(0, 2, 0),
+            pause_len: CdTime::ZERO,
+            postgap_len: CdTime::ZERO,
+        }]);
+
+        let q = SubchannelQ::from_position(&cue_sheet, CdTime::new(0, 2, 0));
+        assert_eq!(
+            q.bytes(),
+            &[0x41, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x28, 0x32]
+        );
+
+        let q = SubchannelQ::from_position(&cue_sheet, CdTime::new(0, 1, 74));
+        assert_eq!(&q.bytes()[..10], &[0x41, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x01, 0x74]);
+        assert!(q.crc_valid());
+    }
+
+    #[test]
+    fn sbi() {
+        let mut sbi = SBI_MAGIC.to_vec();
+        sbi.extend([0x03, 0x08, 0x05, SBI_TYPE_Q_DATA]);
+        sbi.extend([0x41, 0x01, 0x01, 0x07, 0x06, 0x05, 0x00, 0x23, 0x08, 0x05]);
+
+        let patches = SubchannelPatches::parse_sbi(&sbi).unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches.get(CdTime::new(3, 8, 4)), None);
+
+        let q = patches.get(CdTime::new(3, 8, 5)).unwrap();
+        assert_eq!(&q.bytes()[..10], &sbi[8..]);
+        assert!(!q.crc_valid());
+
+        assert!(SubchannelPatches::parse_sbi(&sbi[..sbi.len() - 1]).is_err());
+    }
+}
@@ -2,18 +2,22 @@ mod input;
 
 use crate::app::input::{ConfigurableInput, ControllerNumber, InputSet};
 use crate::config::input::SingleInput;
+use crate::config::overrides::{ControllerTypes, GameConfig, MemoryCardModes};
 use crate::config::{
-    AppConfig, AspectRatio, FilterMode, FiltersConfig, MemoryCardMode, Rasterizer, VSyncMode,
-    WgpuBackend,
+    AppConfig, AspectRatio, FilterMode, FiltersConfig, GraphicsConfig, MemoryCardMode, Rasterizer,
+    VSyncMode, WgpuBackend,
 };
 use crate::emustate::EmulatorState;
+use crate::playlist::Playlist;
 use crate::{OpenFileType, UserEvent, config};
+use cdrom::reader::{CdRom, CdRomFileFormat};
 use egui::{
     Align, Button, CentralPanel, Color32, ComboBox, Context, Grid, Key, KeyboardShortcut, Layout,
     Modifiers, Response, TextEdit, TopBottomPanel, Ui, Vec2, Widget, Window,
 };
 use egui_extras::{Column, TableBuilder};
-use ps1_core::api::AdpcmInterpolation;
+use ps1_core::api::{AdpcmInterpolation, BiosMode, ConsoleRegion, EdcMismatchPolicy};
+use ps1_core::compat::CompatibilityEntry;
 use ps1_core::input::ControllerType;
 use regex::Regex;
 use std::collections::HashSet;
@@ -65,9 +69,11 @@ struct AppState {
     paths_window_open: bool,
     memcards_window_open: bool,
     debug_window_open: bool,
+    game_window_open: bool,
     audio_sync_threshold: NumericText,
     audio_device_queue_size: NumericText,
     internal_audio_buffer_size: NumericText,
+    gdb_server_port: NumericText,
     selected_controller: ControllerNumber,
     selected_input_set: InputSet,
     waiting_for_input: Option<(ControllerNumber, InputSet, ConfigurableInput)>,
@@ -76,6 +82,8 @@ struct AppState {
     change_disc_list: Rc<[ChangeDiscEntry]>,
     last_opened_disc_path: Option<PathBuf>,
     last_serialized_config: AppConfig,
+    game_config: Option<GameConfig>,
+    last_serialized_game_config: Option<GameConfig>,
     filter_by_title: String,
     filter_by_title_lower: String,
     last_filter_by_title: String,
@@ -95,9 +103,11 @@ impl AppState {
             paths_window_open: false,
             memcards_window_open: false,
             debug_window_open: false,
+            game_window_open: false,
             audio_sync_threshold: NumericText::new(config.audio.sync_threshold),
             audio_device_queue_size: NumericText::new(config.audio.device_queue_size),
             internal_audio_buffer_size: NumericText::new(config.audio.internal_buffer_size),
+            gdb_server_port: NumericText::new(config.debug.gdb_server_port),
             selected_controller: ControllerNumber::One,
             selected_input_set: InputSet::One,
             waiting_for_input: None,
@@ -106,6 +116,8 @@ impl AppState {
             change_disc_list: Rc::default(),
             last_opened_disc_path: None,
             last_serialized_config: config.clone(),
+            game_config: None,
+            last_serialized_game_config: None,
             filter_by_title: String::new(),
             filter_by_title_lower: String::new(),
             last_filter_by_title: String::new(),
@@ -140,16 +152,45 @@ impl App {
         Self { config_path, config, state }
     }
 
+    /// Called after `emu_state` has handled the event, so a newly opened game is already running
     #[must_use]
-    pub fn handle_event(&mut self, event: &UserEvent) -> AppEventResponse {
+    pub fn handle_event(
+        &mut self,
+        event: &UserEvent,
+        emu_state: &EmulatorState,
+    ) -> AppEventResponse {
         match event {
             UserEvent::FileOpened(OpenFileType::BiosPath, Some(path)) => {
                 self.config.paths.bios = Some(path.clone());
             }
-            UserEvent::FileOpened(OpenFileType::Open | OpenFileType::DiscChange, Some(path)) => {
+            UserEvent::FileOpened(OpenFileType::Open, Some(path)) => {
+                self.state.last_opened_disc_path = Some(path.clone());
+                self.refresh_change_disc_list(path);
+
+                self.state.game_config = emu_state.game_config().cloned();
+                self.state.last_serialized_game_config.clone_from(&self.state.game_config);
+
+                // Surface known issues for this game as soon as it is opened
+                if self
+                    .state
+                    .game_config
+                    .as_ref()
+                    .is_some_and(|config| config.compatibility.is_some())
+                {
+                    self.state.game_window_open = true;
+                }
+            }
+            // Keep the current list when switching between discs in it, e.g. from a playlist
+            UserEvent::FileOpened(OpenFileType::DiscChange, Some(path))
+                if !self.state.change_disc_list.iter().any(|entry| entry.path == *path) =>
+            {
                 self.state.last_opened_disc_path = Some(path.clone());
                 self.refresh_change_disc_list(path);
             }
+            UserEvent::RunBios => {
+                self.state.game_config = None;
+                self.state.last_serialized_game_config = None;
+            }
             UserEvent::FileOpened(OpenFileType::SearchDir, Some(path)) => {
                 self.config.paths.search.push(path.clone());
             }
@@ -218,6 +259,21 @@ impl App {
             self.render_debug_window(ctx);
         }
 
+        if self.state.game_window_open {
+            self.render_game_window(ctx);
+        }
+
+        if self.state.game_config != self.state.last_serialized_game_config {
+            if let Some(game_config) = &self.state.game_config {
+                if let Err(err) = game_config.save() {
+                    log::error!("Error serializing game config for '{}': {err}", game_config.key);
+                }
+            }
+            self.state.last_serialized_game_config.clone_from(&self.state.game_config);
+
+            proxy.send_event(UserEvent::GameConfigChanged).unwrap();
+        }
+
         if self.config != self.state.last_serialized_config {
             if let Err(err) = self.serialize_config() {
                 log::error!(
@@ -255,6 +311,42 @@ impl App {
         static DISC_REGEX: LazyLock<Regex> =
             LazyLock::new(|| Regex::new(r" \(Disc ([1-9])\)").unwrap());
 
+        if Playlist::is_playlist_path(path) {
+            let playlist = Playlist::load(path).map_err(|err| log::error!("{err}")).ok();
+            self.state.change_disc_list = playlist
+                .iter()
+                .flat_map(Playlist::discs)
+                .enumerate()
+                .map(|(disc_index, disc_path)| ChangeDiscEntry {
+                    label: format!("Disc {}", disc_index + 1),
+                    path: disc_path.clone(),
+                    pbp_disc_index: None,
+                })
+                .collect();
+            return;
+        }
+
+        if CdRomFileFormat::from_file_path(path) == Some(CdRomFileFormat::Pbp) {
+            // Multi-disc PBP files contain all of the game's discs
+            let disc_count = CdRom::pbp_disc_count(path).unwrap_or_else(|err| {
+                log::error!("Unable to read disc count from '{}': {err}", path.display());
+                0
+            });
+            if disc_count <= 1 {
+                self.state.change_disc_list = Rc::default();
+                return;
+            }
+
+            self.state.change_disc_list = (0..disc_count)
+                .map(|disc_index| ChangeDiscEntry {
+                    label: format!("Disc {}", disc_index + 1),
+                    path: path.into(),
+                    pbp_disc_index: Some(disc_index),
+                })
+                .collect();
+            return;
+        }
+
         let Some(path_str) = path.to_str() else {
             self.state.change_disc_list = Rc::default();
             return;
@@ -274,7 +366,8 @@ impl App {
                 if metadata_path_str == new_path {
                     change_disc_list.push(ChangeDiscEntry {
                         label: format!("Disc {disc_number}"),
-                        file: metadata.clone(),
+                        path: metadata.full_path.clone(),
+                        pbp_disc_index: None,
                     });
                 }
             }
@@ -366,6 +459,13 @@ impl App {
                         self.state.debug_window_open = true;
                         ui.close_menu();
                     }
+
+                    ui.separator();
+
+                    if ui.button("Current Game").clicked() {
+                        self.state.game_window_open = true;
+                        ui.close_menu();
+                    }
                 });
 
                 ui.menu_button("Emulation", |ui| {
@@ -386,6 +486,22 @@ impl App {
                             self.render_change_disc_submenu(proxy, ui);
                         });
 
+                        let playlist_position = emu_state.playlist_position();
+                        let has_next_disc = playlist_position
+                            .is_some_and(|(disc_index, disc_count)| disc_index + 1 < disc_count);
+                        if ui.add_enabled(has_next_disc, Button::new("Next Disc")).clicked() {
+                            proxy.send_event(UserEvent::NextDisc).unwrap();
+                            ui.close_menu();
+                        }
+
+                        let has_previous_disc =
+                            playlist_position.is_some_and(|(disc_index, _)| disc_index > 0);
+                        if ui.add_enabled(has_previous_disc, Button::new("Previous Disc")).clicked()
+                        {
+                            proxy.send_event(UserEvent::PreviousDisc).unwrap();
+                            ui.close_menu();
+                        }
+
                         if ui.button("Remove Disc").clicked() {
                             proxy.send_event(UserEvent::RemoveDisc).unwrap();
                             ui.close_menu();
@@ -399,12 +515,12 @@ impl App {
     fn render_change_disc_submenu(&mut self, proxy: &EventLoopProxy<UserEvent>, ui: &mut Ui) {
         for change_disc_entry in &*self.state.change_disc_list {
             if ui.button(&change_disc_entry.label).clicked() {
-                proxy
-                    .send_event(UserEvent::FileOpened(
-                        OpenFileType::DiscChange,
-                        Some(change_disc_entry.file.full_path.clone()),
-                    ))
-                    .unwrap();
+                let path = change_disc_entry.path.clone();
+                let event = match change_disc_entry.pbp_disc_index {
+                    Some(disc_index) => UserEvent::ChangePbpDisc { path, disc_index },
+                    None => UserEvent::FileOpened(OpenFileType::DiscChange, Some(path)),
+                };
+                proxy.send_event(event).unwrap();
                 ui.close_menu();
             }
         }
@@ -803,6 +919,66 @@ impl App {
                     ui.label("BIOS path");
                 });
 
+                let mut hle_bios = self.config.paths.bios_mode == BiosMode::Hle;
+                if ui
+                    .checkbox(&mut hle_bios, "Use HLE BIOS")
+                    .on_hover_text(
+                        "Emulate the BIOS kernel instead of running a BIOS ROM; the BIOS path is not \
+                         required, but the BIOS shell is not available",
+                    )
+                    .changed()
+                {
+                    self.config.paths.bios_mode = if hle_bios { BiosMode::Hle } else { BiosMode::Rom };
+                }
+
+                ui.checkbox(&mut self.config.paths.fast_boot, "Fast boot").on_hover_text(
+                    "Skip the BIOS boot animation when launching a disc; takes effect on the next \
+                     launch",
+                );
+
+                ui.group(|ui| {
+                    ui.label("Console region")
+                        .on_hover_text("Region-locked consoles refuse to boot discs from other regions");
+
+                    ui.horizontal(|ui| {
+                        for (region, label) in [
+                            (ConsoleRegion::RegionFree, "Region-free"),
+                            (ConsoleRegion::NtscU, "NTSC-U"),
+                            (ConsoleRegion::NtscJ, "NTSC-J"),
+                            (ConsoleRegion::Pal, "PAL"),
+                        ] {
+                            ui.radio_value(&mut self.config.paths.console_region, region, label);
+                        }
+                    });
+                });
+
+                ui.group(|ui| {
+                    ui.label("Disc sectors with bad EDC").on_hover_text(
+                        "Damaged rips and patched translations often contain data sectors that \
+                         fail the sector error detection check",
+                    );
+
+                    ui.horizontal(|ui| {
+                        for (policy, label, hover_text) in [
+                            (EdcMismatchPolicy::Ignore, "Ignore", "Use the sector as-is"),
+                            (
+                                EdcMismatchPolicy::Repair,
+                                "Repair",
+                                "Recompute the sector's EDC and ECC before using it",
+                            ),
+                            (
+                                EdcMismatchPolicy::ReadError,
+                                "Read error",
+                                "Report a read error to the game, as a real drive would",
+                            ),
+                            (EdcMismatchPolicy::Fail, "Stop emulation", "Stop with an error"),
+                        ] {
+                            ui.radio_value(&mut self.config.paths.edc_mismatch_policy, policy, label)
+                                .on_hover_text(hover_text);
+                        }
+                    });
+                });
+
                 ui.group(|ui| {
                     ui.heading("Search paths");
 
@@ -863,17 +1039,153 @@ impl App {
             .resizable(false)
             .show(ctx, |ui| {
                 ui.checkbox(&mut self.config.debug.tty_enabled, "TTY output enabled")
-                    .on_hover_text("Print TTY output to stdout");
+                    .on_hover_text("Print BIOS putchar() and DTL-H debug UART output to stdout");
+
+                ui.checkbox(
+                    &mut self.config.debug.kernel_call_trace_enabled,
+                    "BIOS kernel call tracing enabled",
+                )
+                .on_hover_text("Print BIOS kernel calls and their return values to stdout");
 
                 ui.checkbox(&mut self.config.debug.vram_display, "VRAM display").on_hover_text(
                     "Display the entire contents of VRAM instead of only the current frame buffer",
                 );
+
+                ui.add_space(10.0);
+
+                ui.checkbox(&mut self.config.debug.gdb_server_enabled, "GDB server enabled")
+                    .on_hover_text("Listen for GDB remote debugging connections on localhost");
+
+                ui.horizontal(|ui| {
+                    self.state.gdb_server_port.add_ui(
+                        ui,
+                        &mut self.config.debug.gdb_server_port,
+                        |value: u16| value != 0,
+                    );
+
+                    ui.label("GDB server port");
+                });
+
+                if self.state.gdb_server_port.invalid {
+                    ui.colored_label(Color32::RED, "GDB server port must be between 1 and 65535");
+                }
+            });
+    }
+
+    fn render_game_window(&mut self, ctx: &Context) {
+        Window::new("Current Game Settings")
+            .open(&mut self.state.game_window_open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let Some(game_config) = &mut self.state.game_config else {
+                    ui.label("Open a game to edit its settings");
+                    return;
+                };
+
+                if let Some(compatibility) = &game_config.compatibility {
+                    render_compatibility_notice(ui, compatibility);
+                    ui.add_space(10.0);
+                }
+
+                ui.label(format!("Settings overrides for {}", game_config.key));
+
+                ui.add_space(10.0);
+
+                let overrides = &mut game_config.overrides;
+                let compatibility = game_config.compatibility.as_ref();
+
+                // Start from the settings the game is currently running with, including any
+                // compatibility workarounds
+                override_checkbox(
+                    ui,
+                    "Override graphics settings",
+                    &mut overrides.graphics,
+                    || {
+                        let mut graphics = self.config.graphics.clone();
+                        if let Some(compatibility) = compatibility {
+                            graphics.apply_compatibility(compatibility);
+                        }
+                        graphics
+                    },
+                );
+                if let Some(graphics) = &mut overrides.graphics {
+                    ui.indent("graphics_overrides", |ui| render_graphics_overrides(ui, graphics));
+                }
+
+                override_checkbox(ui, "Override audio settings", &mut overrides.audio, || {
+                    self.config.audio.clone()
+                });
+                if let Some(audio) = &mut overrides.audio {
+                    ui.indent("audio_overrides", |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("SPU ADPCM interpolation");
+                            ui.radio_value(
+                                &mut audio.adpcm_interpolation,
+                                AdpcmInterpolation::Gaussian,
+                                "Gaussian",
+                            );
+                            ui.radio_value(
+                                &mut audio.adpcm_interpolation,
+                                AdpcmInterpolation::Hermite,
+                                "Cubic Hermite",
+                            );
+                        });
+                    });
+                }
+
+                override_checkbox(
+                    ui,
+                    "Override controller types",
+                    &mut overrides.controllers,
+                    || ControllerTypes {
+                        p1_device: self.config.input.p1_device,
+                        p2_device: self.config.input.p2_device,
+                    },
+                );
+                if let Some(controllers) = &mut overrides.controllers {
+                    ui.indent("controller_overrides", |ui| {
+                        for (label, device) in [
+                            ("Controller 1", &mut controllers.p1_device),
+                            ("Controller 2", &mut controllers.p2_device),
+                        ] {
+                            ui.horizontal(|ui| {
+                                ui.label(label);
+                                ui.radio_value(device, ControllerType::None, "None");
+                                ui.radio_value(device, ControllerType::Digital, "Digital");
+                                ui.radio_value(device, ControllerType::DualShock, "DualShock");
+                            });
+                        }
+                    });
+                }
+
+                override_checkbox(
+                    ui,
+                    "Override memory card modes",
+                    &mut overrides.memory_cards,
+                    || MemoryCardModes {
+                        slot_1_mode: self.config.memory_cards.slot_1_mode,
+                        slot_2_mode: self.config.memory_cards.slot_2_mode,
+                    },
+                );
+                if let Some(memory_cards) = &mut overrides.memory_cards {
+                    ui.indent("memory_card_overrides", |ui| {
+                        ui.add(MemoryCardModeWidget::new(
+                            "Memory card slot 1 mode",
+                            &mut memory_cards.slot_1_mode,
+                        ));
+                        ui.add(MemoryCardModeWidget::new(
+                            "Memory card slot 2 mode",
+                            &mut memory_cards.slot_2_mode,
+                        ));
+                    });
+                }
             });
     }
 
     fn render_central_panel(&mut self, ctx: &Context, proxy: &EventLoopProxy<UserEvent>) {
         CentralPanel::default().show(ctx, |ui| {
-            let bios_path_configured = self.config.paths.bios.is_some();
+            let bios_path_configured =
+                self.config.paths.bios.is_some() || self.config.paths.bios_mode == BiosMode::Hle;
             let search_paths_configured = !self.config.paths.search.is_empty();
 
             if !bios_path_configured || !search_paths_configured {
@@ -915,6 +1227,11 @@ impl App {
                 ui.checkbox(&mut self.config.filters.exe, "EXE");
                 ui.checkbox(&mut self.config.filters.cue, "CUE");
                 ui.checkbox(&mut self.config.filters.chd, "CHD");
+                ui.checkbox(&mut self.config.filters.iso, "ISO");
+                ui.checkbox(&mut self.config.filters.ecm, "ECM");
+                ui.checkbox(&mut self.config.filters.pbp, "PBP");
+                ui.checkbox(&mut self.config.filters.ccd, "CCD");
+                ui.checkbox(&mut self.config.filters.m3u, "M3U");
             });
 
             ui.add_space(15.0);
@@ -1026,6 +1343,69 @@ impl<'a> Widget for MemoryCardModeWidget<'a> {
     }
 }
 
+fn render_compatibility_notice(ui: &mut Ui, compatibility: &CompatibilityEntry) {
+    ui.colored_label(Color32::YELLOW, "This game has known compatibility issues");
+
+    for flag in &compatibility.flags {
+        ui.label(format!("• {}", flag.description()));
+    }
+
+    if let Some(notes) = &compatibility.notes {
+        ui.label(format!("• {notes}"));
+    }
+}
+
+/// Checkbox that enables an override section, initializing it from the global config
+fn override_checkbox<T>(
+    ui: &mut Ui,
+    label: &str,
+    value: &mut Option<T>,
+    global_value: impl FnOnce() -> T,
+) {
+    let mut enabled = value.is_some();
+    if ui.checkbox(&mut enabled, label).changed() {
+        *value = enabled.then(global_value);
+    }
+}
+
+fn render_graphics_overrides(ui: &mut Ui, graphics: &mut GraphicsConfig) {
+    ui.horizontal(|ui| {
+        ui.label("Rasterizer");
+        ui.radio_value(&mut graphics.rasterizer, Rasterizer::Software, "Software");
+        ui.radio_value(&mut graphics.rasterizer, Rasterizer::Hardware, "Hardware (wgpu)");
+    });
+
+    ui.add_enabled_ui(graphics.rasterizer == Rasterizer::Hardware, |ui| {
+        let format_scale = |scale| match scale {
+            1 => "1x (Native)".into(),
+            _ => format!("{scale}x"),
+        };
+
+        ComboBox::from_label("Resolution scale")
+            .selected_text(format_scale(graphics.hardware_resolution_scale))
+            .show_ui(ui, |ui| {
+                for scale in 1..=16 {
+                    ui.selectable_value(
+                        &mut graphics.hardware_resolution_scale,
+                        scale,
+                        format_scale(scale),
+                    );
+                }
+            });
+
+        ui.checkbox(&mut graphics.hardware_high_color, "24bpp high color rendering");
+
+        ui.checkbox(&mut graphics.pgxp_enabled, "PGXP enabled");
+        ui.add_enabled_ui(graphics.pgxp_enabled, |ui| {
+            ui.checkbox(&mut graphics.pgxp_precise_culling, "PGXP high-precision culling");
+            ui.checkbox(
+                &mut graphics.pgxp_perspective_texture_mapping,
+                "PGXP perspective-correct texture mapping",
+            );
+        });
+    });
+}
+
 fn read_config<P: AsRef<Path>>(path: P) -> anyhow::Result<AppConfig> {
     let path = path.as_ref();
 
@@ -1040,6 +1420,11 @@ enum FileExtension {
     Exe,
     Cue,
     Chd,
+    Iso,
+    Ecm,
+    Pbp,
+    Ccd,
+    M3u,
 }
 
 impl FileExtension {
@@ -1048,6 +1433,11 @@ impl FileExtension {
             Self::Exe => "EXE",
             Self::Cue => "CUE",
             Self::Chd => "CHD",
+            Self::Iso => "ISO",
+            Self::Ecm => "ECM",
+            Self::Pbp => "PBP",
+            Self::Ccd => "CCD",
+            Self::M3u => "M3U",
         }
     }
 }
@@ -1080,7 +1470,12 @@ fn filter_file_list(
         let name_match = metadata.file_name_no_ext.to_lowercase().contains(filter_by_title_lower);
         let extension_match = (metadata.extension == FileExtension::Exe && file_filters.exe)
             || (metadata.extension == FileExtension::Cue && file_filters.cue)
-            || (metadata.extension == FileExtension::Chd && file_filters.chd);
+            || (metadata.extension == FileExtension::Chd && file_filters.chd)
+            || (metadata.extension == FileExtension::Iso && file_filters.iso)
+            || (metadata.extension == FileExtension::Ecm && file_filters.ecm)
+            || (metadata.extension == FileExtension::Pbp && file_filters.pbp)
+            || (metadata.extension == FileExtension::Ccd && file_filters.ccd)
+            || (metadata.extension == FileExtension::M3u && file_filters.m3u);
 
         name_match && extension_match
     });
@@ -1107,16 +1502,26 @@ fn do_file_search_inner(
 
         let entry_path = dir_entry.path();
         let path_no_ext = entry_path.with_extension("");
-        let Some(file_name_no_ext) = path_no_ext.file_name().and_then(OsStr::to_str) else {
+        let Some(mut file_name_no_ext) = path_no_ext.file_name().and_then(OsStr::to_str) else {
             continue;
         };
 
+        // PBP files are conventionally all named EBOOT.PBP, with one directory per game
+        if file_name_no_ext.eq_ignore_ascii_case("eboot") {
+            if let Some(dir_name) = dir.file_name().and_then(OsStr::to_str) {
+                file_name_no_ext = dir_name;
+            }
+        }
+
         if file_type.is_dir() && recursive {
             do_file_search_inner(&entry_path, true, visited_dirs, out);
         } else if file_type.is_file() {
             let Some(extension) = entry_path.extension().and_then(OsStr::to_str) else { continue };
             let ext_lower = extension.to_lowercase();
-            if matches!(ext_lower.as_str(), "exe" | "cue" | "chd") {
+            if matches!(
+                ext_lower.as_str(),
+                "exe" | "cue" | "chd" | "iso" | "ecm" | "pbp" | "ccd" | "m3u"
+            ) {
                 // TODO check that EXE is a PS1 executable
                 out.push(FileMetadata {
                     file_name_no_ext: file_name_no_ext.into(),
@@ -1124,6 +1529,11 @@ fn do_file_search_inner(
                         "exe" => FileExtension::Exe,
                         "cue" => FileExtension::Cue,
                         "chd" => FileExtension::Chd,
+                        "iso" => FileExtension::Iso,
+                        "ecm" => FileExtension::Ecm,
+                        "pbp" => FileExtension::Pbp,
+                        "ccd" => FileExtension::Ccd,
+                        "m3u" => FileExtension::M3u,
                         _ => unreachable!("nested match expressions"),
                     },
                     full_path: entry_path,
@@ -1136,5 +1546,6 @@ fn do_file_search_inner(
 #[derive(Debug, Clone)]
 struct ChangeDiscEntry {
     label: String,
-    file: FileMetadata,
+    path: PathBuf,
+    pbp_disc_index: Option<usize>,
 }
@@ -1,17 +1,21 @@
 pub mod input;
+pub mod overrides;
 
 use crate::config::input::ControllerConfig;
 use anyhow::anyhow;
+use cdrom::discinfo::DiscInfo;
 use cfg_if::cfg_if;
 use ps1_core::RasterizerType;
 use ps1_core::api::{
-    AdpcmInterpolation, DisplayConfig, MemoryCardSlot, MemoryCardsEnabled, PgxpConfig,
-    Ps1EmulatorConfig,
+    AdpcmInterpolation, BiosMode, ConsoleRegion, DisplayConfig, EdcMismatchPolicy, MemoryCardSlot,
+    MemoryCardsEnabled, PgxpConfig, Ps1EmulatorConfig,
 };
+use ps1_core::compat::CompatibilityEntry;
 use ps1_core::input::ControllerType;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::ffi::OsStr;
+use std::fs;
 use std::num::NonZeroU32;
 use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
@@ -160,6 +164,20 @@ impl Default for GraphicsConfig {
 }
 
 impl GraphicsConfig {
+    /// Apply a game's compatibility settings, which currently only affect PGXP
+    pub fn apply_compatibility(&mut self, compatibility: &CompatibilityEntry) {
+        let mut pgxp = PgxpConfig {
+            enabled: self.pgxp_enabled,
+            precise_nclip: self.pgxp_precise_culling,
+            perspective_texture_mapping: self.pgxp_perspective_texture_mapping,
+        };
+        compatibility.apply_pgxp(&mut pgxp);
+
+        self.pgxp_enabled = pgxp.enabled;
+        self.pgxp_precise_culling = pgxp.precise_nclip;
+        self.pgxp_perspective_texture_mapping = pgxp.perspective_texture_mapping;
+    }
+
     #[must_use]
     pub fn rasterizer_type(&self) -> RasterizerType {
         let use_avx2_software = self.avx2_software_rasterizer && supports_avx2();
@@ -216,6 +234,14 @@ impl Default for AudioConfig {
 pub struct PathsConfig {
     pub bios: Option<PathBuf>,
     #[serde(default)]
+    pub bios_mode: BiosMode,
+    #[serde(default)]
+    pub fast_boot: bool,
+    #[serde(default)]
+    pub console_region: ConsoleRegion,
+    #[serde(default)]
+    pub edc_mismatch_policy: EdcMismatchPolicy,
+    #[serde(default)]
     pub search: Vec<PathBuf>,
     #[serde(default = "true_fn")]
     pub search_recursively: bool,
@@ -261,18 +287,38 @@ impl MemoryCardConfig {
         MemoryCardsEnabled { slot_1: self.slot_1_enabled, slot_2: self.slot_2_enabled }
     }
 
-    pub(crate) fn slot_1_path<P: AsRef<Path>>(&self, disc_path: Option<P>) -> PathBuf {
-        slot_path(self.slot_1_mode, disc_path, MemoryCardSlot::One)
+    pub(crate) fn slot_1_path(
+        &self,
+        disc_path: Option<&Path>,
+        disc_info: Option<&DiscInfo>,
+    ) -> PathBuf {
+        slot_path(self.slot_1_mode, disc_path, disc_info, MemoryCardSlot::One)
     }
 
-    pub(crate) fn slot_2_path<P: AsRef<Path>>(&self, disc_path: Option<P>) -> PathBuf {
-        slot_path(self.slot_2_mode, disc_path, MemoryCardSlot::Two)
+    pub(crate) fn slot_2_path(
+        &self,
+        disc_path: Option<&Path>,
+        disc_info: Option<&DiscInfo>,
+    ) -> PathBuf {
+        slot_path(self.slot_2_mode, disc_path, disc_info, MemoryCardSlot::Two)
+    }
+
+    /// Carry over per-game memory cards from before cards were keyed by the game database. This
+    /// should be called once when a game is launched, before the memory cards are loaded.
+    pub(crate) fn migrate_per_game_cards(
+        &self,
+        disc_path: Option<&Path>,
+        disc_info: Option<&DiscInfo>,
+    ) {
+        migrate_per_game_card(self.slot_1_mode, disc_path, disc_info, MemoryCardSlot::One);
+        migrate_per_game_card(self.slot_2_mode, disc_path, disc_info, MemoryCardSlot::Two);
     }
 }
 
-fn slot_path<P: AsRef<Path>>(
+fn slot_path(
     mode: MemoryCardMode,
-    disc_path: Option<P>,
+    disc_path: Option<&Path>,
+    disc_info: Option<&DiscInfo>,
     slot: MemoryCardSlot,
 ) -> PathBuf {
     let shared_file_name = match slot {
@@ -282,23 +328,23 @@ fn slot_path<P: AsRef<Path>>(
 
     match (mode, disc_path) {
         (MemoryCardMode::PerGame, Some(disc_path)) => {
-            let disc_path = disc_path.as_ref();
-            let file_name_no_ext = match file_name_without_disc_or_revision(disc_path) {
-                Ok(value) => value,
+            // Prefer keying off the game database so that renaming an image does not orphan its
+            // saves, falling back to the file name for games that are not in the database
+            if let Some(disc_key) = disc_info.and_then(game_database_key) {
+                return per_game_path(&disc_key, slot);
+            }
+
+            match file_name_without_disc_or_revision(disc_path) {
+                Ok(value) => per_game_path(&value, slot),
                 Err(err) => {
                     log::error!(
                         "Unable to remove extension/Disc/Rev from file path '{}', using shared memory card for slot {}: {err}",
                         disc_path.display(),
                         slot as u8
                     );
-                    return shared_path(shared_file_name);
+                    shared_path(shared_file_name)
                 }
-            };
-
-            let memory_card_file_name_no_ext = format!("{file_name_no_ext}_{}", slot as u8);
-            let memory_card_file_name =
-                Path::new(&memory_card_file_name_no_ext).with_extension("mcd");
-            Path::new(MEMORY_CARDS_DIRECTORY).join(memory_card_file_name)
+            }
         }
         (MemoryCardMode::PerGame, None) | (MemoryCardMode::Shared, _) => {
             shared_path(shared_file_name)
@@ -306,18 +352,75 @@ fn slot_path<P: AsRef<Path>>(
     }
 }
 
-fn file_name_without_disc_or_revision(path: &Path) -> anyhow::Result<String> {
-    static DISC_REV_REGEX: OnceLock<Regex> = OnceLock::new();
+// Per-game cards used to always be keyed by file name. If this game now has a game database key
+// and only the old card exists, copy the old card to the new key. The old card is left in place
+fn migrate_per_game_card(
+    mode: MemoryCardMode,
+    disc_path: Option<&Path>,
+    disc_info: Option<&DiscInfo>,
+    slot: MemoryCardSlot,
+) {
+    let (MemoryCardMode::PerGame, Some(disc_path), Some(disc_key)) =
+        (mode, disc_path, disc_info.and_then(game_database_key))
+    else {
+        return;
+    };
+    let Ok(file_name_key) = file_name_without_disc_or_revision(disc_path) else { return };
+
+    let old_path = per_game_path(&file_name_key, slot);
+    let new_path = per_game_path(&disc_key, slot);
+    if old_path == new_path || new_path.exists() || !old_path.exists() {
+        return;
+    }
+
+    match fs::copy(&old_path, &new_path) {
+        Ok(_) => {
+            log::info!("Copied memory card '{}' to '{}'", old_path.display(), new_path.display());
+        }
+        Err(err) => log::error!(
+            "Unable to copy memory card '{}' to '{}': {err}",
+            old_path.display(),
+            new_path.display()
+        ),
+    }
+}
+
+fn per_game_path(key: &str, slot: MemoryCardSlot) -> PathBuf {
+    let memory_card_file_name_no_ext = format!("{key}_{}", slot as u8);
+    let memory_card_file_name = Path::new(&memory_card_file_name_no_ext).with_extension("mcd");
+    Path::new(MEMORY_CARDS_DIRECTORY).join(memory_card_file_name)
+}
 
+/// Per-game memory cards are shared between all discs of a multi-disc game, so this uses the
+/// game database title without the disc number. The region is included so that different regional
+/// releases of a game do not share a card.
+fn game_database_key(disc_info: &DiscInfo) -> Option<String> {
+    let title = strip_disc_and_revision(disc_info.title.as_deref()?);
+    let key = match disc_info.region {
+        Some(region) => format!("{title} [{region}]"),
+        None => title,
+    };
+
+    // Titles can contain characters that are not valid in file names, e.g. "Crash Bandicoot: Warped"
+    Some(key.replace(['<', '>', ':', '"', '/', '\\', '|', '?', '*'], "_"))
+}
+
+fn file_name_without_disc_or_revision(path: &Path) -> anyhow::Result<String> {
     let path_no_ext = path.with_extension("");
     let file_name_no_ext = path_no_ext.file_name().and_then(OsStr::to_str).ok_or_else(|| {
         anyhow!("Unable to determine file extension for path: {}", path.display())
     })?;
 
+    Ok(strip_disc_and_revision(file_name_no_ext))
+}
+
+fn strip_disc_and_revision(name: &str) -> String {
+    static DISC_REV_REGEX: OnceLock<Regex> = OnceLock::new();
+
     let disc_rev_regex =
         DISC_REV_REGEX.get_or_init(|| Regex::new(r"( \(Disc [1-9]\))?( \(Rev [1-9]\))?$").unwrap());
 
-    Ok(disc_rev_regex.replace(file_name_no_ext, "").into())
+    disc_rev_regex.replace(name, "").into()
 }
 
 fn shared_path(shared_file_name: &str) -> PathBuf {
@@ -332,6 +435,16 @@ pub struct FiltersConfig {
     pub cue: bool,
     #[serde(default = "true_fn")]
     pub chd: bool,
+    #[serde(default = "true_fn")]
+    pub iso: bool,
+    #[serde(default = "true_fn")]
+    pub ecm: bool,
+    #[serde(default = "true_fn")]
+    pub pbp: bool,
+    #[serde(default = "true_fn")]
+    pub ccd: bool,
+    #[serde(default = "true_fn")]
+    pub m3u: bool,
 }
 
 impl Default for FiltersConfig {
@@ -345,7 +458,17 @@ pub struct DebugConfig {
     #[serde(default)]
     pub tty_enabled: bool,
     #[serde(default)]
+    pub kernel_call_trace_enabled: bool,
+    #[serde(default)]
     pub vram_display: bool,
+    #[serde(default)]
+    pub gdb_server_enabled: bool,
+    #[serde(default = "default_gdb_server_port")]
+    pub gdb_server_port: u16,
+}
+
+fn default_gdb_server_port() -> u16 {
+    3333
 }
 
 impl Default for DebugConfig {
@@ -451,6 +574,11 @@ impl AppConfig {
             adpcm_interpolation: self.audio.adpcm_interpolation,
             internal_audio_buffer_size: self.audio.internal_buffer_size,
             tty_enabled: self.debug.tty_enabled,
+            kernel_call_trace_enabled: self.debug.kernel_call_trace_enabled,
+            ram_seed: None,
+            bios_mode: self.paths.bios_mode,
+            console_region: self.paths.console_region,
+            edc_mismatch_policy: self.paths.edc_mismatch_policy,
         }
     }
 }
@@ -458,9 +586,42 @@ impl AppConfig {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use cdrom::discinfo::DiscRegion;
 
     #[test]
     fn gui_config_default_does_not_panic() {
         let _ = AppConfig::default();
     }
+
+    #[test]
+    fn memory_card_keys() {
+        let disc_info = |title: Option<&str>, region| DiscInfo {
+            boot_file: None,
+            serial: Some("SLUS-00000".into()),
+            region,
+            title: title.map(String::from),
+        };
+
+        assert_eq!(
+            game_database_key(&disc_info(
+                Some("Crash Bandicoot: Warped (Disc 1)"),
+                Some(DiscRegion::NtscU)
+            ))
+            .as_deref(),
+            Some("Crash Bandicoot_ Warped [NTSC-U]")
+        );
+        assert_eq!(
+            game_database_key(&disc_info(Some("Game (Disc 2)"), Some(DiscRegion::NtscJ)))
+                .as_deref(),
+            Some("Game [NTSC-J]")
+        );
+        assert_eq!(game_database_key(&disc_info(None, Some(DiscRegion::Pal))), None);
+
+        // Games that are not in the database share a card between discs by file name
+        let disc_1 = file_name_without_disc_or_revision(Path::new("roms/Game (Disc 1).cue"));
+        let disc_2 =
+            file_name_without_disc_or_revision(Path::new("roms/Game (Disc 2) (Rev 1).chd"));
+        assert_eq!(disc_1.unwrap(), "Game");
+        assert_eq!(disc_2.unwrap(), "Game");
+    }
 }
@@ -0,0 +1,251 @@
+//! Per-game settings overrides, stored as one TOML file per game and applied on top of the global
+//! config when a game is running
+
+use crate::config::{AppConfig, AudioConfig, GraphicsConfig, MemoryCardMode};
+use crate::playlist::Playlist;
+use cdrom::discinfo::DiscInfo;
+use cdrom::iso9660::{self, MODE_2_SECTOR_LEN};
+use cdrom::reader::{CdRom, CdRomFileFormat};
+use crc::Crc;
+use ps1_core::compat::{CompatibilityDatabase, CompatibilityEntry};
+use ps1_core::input::ControllerType;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::{fs, io};
+
+pub const GAME_CONFIGS_DIRECTORY: &str = "game-configs";
+
+/// Local additions to the built-in compatibility database, in the same format
+pub const LOCAL_COMPATIBILITY_DATABASE: &str = "coffeepsx-compatibility.tsv";
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ControllerTypes {
+    pub p1_device: ControllerType,
+    pub p2_device: ControllerType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryCardModes {
+    pub slot_1_mode: MemoryCardMode,
+    pub slot_2_mode: MemoryCardMode,
+}
+
+/// Each section replaces the corresponding global section when set
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameConfigOverrides {
+    #[serde(default)]
+    pub graphics: Option<GraphicsConfig>,
+    #[serde(default)]
+    pub audio: Option<AudioConfig>,
+    #[serde(default)]
+    pub controllers: Option<ControllerTypes>,
+    #[serde(default)]
+    pub memory_cards: Option<MemoryCardModes>,
+}
+
+impl GameConfigOverrides {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    #[must_use]
+    pub fn apply(&self, config: &AppConfig) -> AppConfig {
+        let mut config = config.clone();
+
+        if let Some(graphics) = &self.graphics {
+            config.graphics.clone_from(graphics);
+        }
+
+        if let Some(audio) = &self.audio {
+            config.audio.clone_from(audio);
+        }
+
+        if let Some(controllers) = self.controllers {
+            config.input.p1_device = controllers.p1_device;
+            config.input.p2_device = controllers.p2_device;
+        }
+
+        if let Some(memory_cards) = self.memory_cards {
+            config.memory_cards.slot_1_mode = memory_cards.slot_1_mode;
+            config.memory_cards.slot_2_mode = memory_cards.slot_2_mode;
+        }
+
+        config
+    }
+}
+
+/// The overrides for a specific game, along with the key that identifies the game's override file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameConfig {
+    pub key: String,
+    pub overrides: GameConfigOverrides,
+    /// Settings from the compatibility database. These are applied before the overrides so that
+    /// the user can still turn a workaround back off for a game.
+    pub compatibility: Option<CompatibilityEntry>,
+}
+
+impl GameConfig {
+    /// Load the overrides for the game at the given path, identified by its already-read disc
+    /// info. Returns `None` if the game could not be identified.
+    #[must_use]
+    pub fn load(file_path: &Path, disc_info: Option<&DiscInfo>) -> Option<Self> {
+        let serial = disc_info.and_then(|info| info.serial.clone());
+        let compatibility = serial.as_deref().and_then(|serial| {
+            let entry = compatibility_database().get(serial).cloned();
+            if let Some(entry) = &entry {
+                log::info!("Applying compatibility settings for {serial}: {entry:?}");
+            }
+            entry
+        });
+
+        let key = match serial {
+            Some(serial) => serial,
+            None => hash_key(file_path)?,
+        };
+        let overrides = read_overrides(&key);
+
+        Some(Self { key, overrides, compatibility })
+    }
+
+    /// Re-read the overrides from this game's override file, e.g. after the GUI edits them
+    pub fn reload(&mut self) {
+        self.overrides = read_overrides(&self.key);
+    }
+
+    /// Write the overrides to this game's override file, or delete the file if nothing is
+    /// overridden.
+    ///
+    /// # Errors
+    ///
+    /// Propagates any errors encountered while serializing or writing the file.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = game_config_path(&self.key);
+
+        if self.overrides.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let config_str = toml::to_string_pretty(&self.overrides)?;
+        fs::write(&path, config_str)?;
+
+        log::debug!("Serialized game config to '{}'", path.display());
+
+        Ok(())
+    }
+}
+
+fn read_overrides(key: &str) -> GameConfigOverrides {
+    let path = game_config_path(key);
+
+    let overrides = match fs::read_to_string(&path) {
+        Ok(config_str) => toml::from_str(&config_str).unwrap_or_else(|err| {
+            log::error!("Unable to parse game config '{}': {err}", path.display());
+            GameConfigOverrides::default()
+        }),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => GameConfigOverrides::default(),
+        Err(err) => {
+            log::error!("Unable to read game config '{}': {err}", path.display());
+            GameConfigOverrides::default()
+        }
+    };
+
+    if !overrides.is_empty() {
+        log::info!("Loaded game config overrides from '{}'", path.display());
+    }
+
+    overrides
+}
+
+fn compatibility_database() -> &'static CompatibilityDatabase {
+    static DATABASE: OnceLock<CompatibilityDatabase> = OnceLock::new();
+
+    DATABASE.get_or_init(|| {
+        let mut database = CompatibilityDatabase::builtin();
+
+        match fs::read_to_string(LOCAL_COMPATIBILITY_DATABASE) {
+            Ok(text) => match CompatibilityDatabase::parse(&text) {
+                Ok(local) => database.extend(local),
+                Err(err) => {
+                    log::error!("Error parsing '{LOCAL_COMPATIBILITY_DATABASE}': {err}");
+                }
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => log::error!("Unable to read '{LOCAL_COMPATIBILITY_DATABASE}': {err}"),
+        }
+
+        database
+    })
+}
+
+/// Discs are identified by serial. Discs without a recognizable serial are identified by a CRC32 of
+/// the sectors following the system area, and EXEs by a CRC32 of the file.
+fn hash_key(file_path: &Path) -> Option<String> {
+    if Playlist::is_playlist_path(file_path) {
+        let playlist = Playlist::load(file_path).map_err(|err| log::error!("{err}")).ok()?;
+        return hash_key(&playlist.discs()[0]);
+    }
+
+    let hash = match CdRomFileFormat::from_file_path(file_path) {
+        Some(format) => hash_disc(file_path, format),
+        None => hash_file(file_path),
+    };
+
+    match hash {
+        Ok(crc) => Some(format!("{crc:08X}")),
+        Err(err) => {
+            log::error!("Unable to hash file '{}': {err}", file_path.display());
+            None
+        }
+    }
+}
+
+// Hashing the image file would only hash the CUE text for CUE/BIN images, and could mean reading
+// several GB. The volume descriptors and root directory in these sectors are unique enough.
+fn hash_disc(path: &Path, format: CdRomFileFormat) -> anyhow::Result<u32> {
+    const HASHED_SECTORS: Range<u32> = 16..32;
+
+    let mut disc = CdRom::open(path, format)?;
+    let mut digest = CRC32.digest();
+
+    let mut sector = [0; MODE_2_SECTOR_LEN];
+    for lba in HASHED_SECTORS {
+        iso9660::read_mode_2_sector(&mut disc, lba, &mut sector)?;
+        digest.update(&sector);
+    }
+
+    Ok(digest.finalize())
+}
+
+fn hash_file(path: &Path) -> anyhow::Result<u32> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut digest = CRC32.digest();
+
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        digest.update(&buffer[..bytes_read]);
+    }
+
+    Ok(digest.finalize())
+}
+
+fn game_config_path(key: &str) -> PathBuf {
+    Path::new(GAME_CONFIGS_DIRECTORY).join(key).with_extension("toml")
+}
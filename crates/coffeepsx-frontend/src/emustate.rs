@@ -1,8 +1,11 @@
-use crate::config::{AppConfig, InputConfig, Rasterizer, VSyncMode, VideoConfig};
-use crate::emuthread::{EmulationThreadHandle, EmulatorThreadCommand};
+use crate::config::overrides::GameConfig;
+use crate::config::{AppConfig, GraphicsConfig, InputConfig, Rasterizer, VSyncMode, VideoConfig};
+use crate::emuthread::{self, EmulationThreadHandle, EmulatorThreadCommand};
 use crate::input::InputMapper;
+use crate::playlist::Playlist;
 use crate::{OpenFileType, UserEvent};
 use anyhow::anyhow;
+use cdrom::discinfo::DiscInfo;
 use sdl2::controller::GameController;
 use sdl2::event::Event as SdlEvent;
 use sdl2::{EventPump, GameControllerSubsystem, Sdl};
@@ -33,11 +36,12 @@ struct EmulatorWindow {
 impl EmulatorWindow {
     fn new(
         file_path: Option<&Path>,
+        disc_info: Option<&DiscInfo>,
         event_loop: &ActiveEventLoop,
         config: &AppConfig,
     ) -> anyhow::Result<Self> {
         let window_title = match file_path {
-            Some(file_path) => determine_window_title(file_path),
+            Some(file_path) => determine_window_title(file_path, disc_info),
             None => "(BIOS)".into(),
         };
         let window_size = LogicalSize::new(config.video.window_width, config.video.window_height);
@@ -171,16 +175,93 @@ impl EmulatorWindow {
     }
 }
 
-fn determine_window_title(path: &Path) -> String {
-    path.with_extension("")
+fn determine_window_title(path: &Path, disc_info: Option<&DiscInfo>) -> String {
+    let file_name = path
+        .with_extension("")
         .file_name()
         .and_then(OsStr::to_str)
-        .map_or_else(|| "PS1".into(), String::from)
+        .map_or_else(|| "PS1".into(), String::from);
+
+    match disc_info {
+        Some(DiscInfo { title: Some(title), serial: Some(serial), .. }) => {
+            format!("{title} [{serial}]")
+        }
+        Some(DiscInfo { serial: Some(serial), .. }) => format!("{file_name} [{serial}]"),
+        _ => file_name,
+    }
 }
 
 struct RunningState {
     window: EmulatorWindow,
     emu_thread: EmulationThreadHandle,
+    game_config: Option<GameConfig>,
+    playlist: Option<PlaylistState>,
+}
+
+#[derive(Debug)]
+struct PlaylistState {
+    playlist: Playlist,
+    disc_index: usize,
+}
+
+impl RunningState {
+    fn change_playlist_disc(&mut self, next: bool) {
+        let Some(PlaylistState { playlist, disc_index }) = &mut self.playlist else {
+            log::warn!("Cannot change to next/previous disc; no playlist is loaded");
+            return;
+        };
+
+        let new_index = if next {
+            *disc_index + 1
+        } else {
+            let Some(new_index) = disc_index.checked_sub(1) else {
+                log::info!("Already at first disc of playlist");
+                return;
+            };
+            new_index
+        };
+        let Some(disc_path) = playlist.discs().get(new_index) else {
+            log::info!("Already at last disc of playlist");
+            return;
+        };
+
+        log::info!(
+            "Changing to disc {} of {}: '{}'",
+            new_index + 1,
+            playlist.discs().len(),
+            disc_path.display()
+        );
+        *disc_index = new_index;
+
+        // The window title is updated when the emulator thread reports the disc change
+        self.emu_thread.send_command(EmulatorThreadCommand::ChangeDisc {
+            disc_path: disc_path.clone(),
+            disc_index: 0,
+        });
+    }
+}
+
+fn effective_config(game_config: Option<&GameConfig>, app_config: &AppConfig) -> AppConfig {
+    let Some(game_config) = game_config else { return app_config.clone() };
+
+    let mut config = app_config.clone();
+    if let Some(compatibility) = &game_config.compatibility {
+        config.graphics.apply_compatibility(compatibility);
+    }
+    game_config.overrides.apply(&config)
+}
+
+/// Graphics hotkeys change the running game's graphics overrides if it has any, since the
+/// overrides would otherwise hide changes to the global graphics config. Changes to the overrides
+/// last until the game config is reloaded.
+fn active_graphics_config<'a>(
+    game_config: &'a mut Option<GameConfig>,
+    app_config: &'a mut AppConfig,
+) -> &'a mut GraphicsConfig {
+    match game_config.as_mut().and_then(|game_config| game_config.overrides.graphics.as_mut()) {
+        Some(graphics) => graphics,
+        None => &mut app_config.graphics,
+    }
 }
 
 struct Controllers {
@@ -242,7 +323,7 @@ impl EmulatorState {
         Ok(Self { running: None, sdl_ctx, sdl_event_pump, controllers, input_mapper })
     }
 
-    #[allow(clippy::missing_errors_doc)]
+    #[allow(clippy::missing_errors_doc, clippy::missing_panics_doc)]
     pub fn handle_event(
         &mut self,
         event: &Event<UserEvent>,
@@ -252,10 +333,10 @@ impl EmulatorState {
     ) -> anyhow::Result<()> {
         match event {
             Event::UserEvent(UserEvent::FileOpened(OpenFileType::Open, Some(file_path))) => {
-                return self.start_emulator(Some(file_path), elwt, app_config);
+                return self.start_emulator(Some(file_path), elwt, proxy, app_config);
             }
             Event::UserEvent(UserEvent::RunBios) => {
-                return self.start_emulator(None, elwt, app_config);
+                return self.start_emulator(None, elwt, proxy, app_config);
             }
             Event::UserEvent(UserEvent::AppConfigChanged) => {
                 self.input_mapper = InputMapper::new(&app_config.input);
@@ -266,12 +347,37 @@ impl EmulatorState {
             _ => {}
         }
 
-        let Some(RunningState { window, emu_thread }) = &mut self.running else { return Ok(()) };
+        let Some(running) = &mut self.running else {
+            return Ok(());
+        };
+
+        match event {
+            Event::UserEvent(UserEvent::NextDisc) => {
+                running.change_playlist_disc(true);
+                return Ok(());
+            }
+            Event::UserEvent(UserEvent::PreviousDisc) => {
+                running.change_playlist_disc(false);
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let RunningState { window, emu_thread, game_config, playlist } = running;
 
         match event {
             Event::UserEvent(UserEvent::AppConfigChanged) => {
-                window.update_config(&app_config.video);
-                emu_thread.handle_config_change(app_config)?;
+                let config = effective_config(game_config.as_ref(), app_config);
+                window.update_config(&config.video);
+                emu_thread.handle_config_change(&config)?;
+            }
+            Event::UserEvent(UserEvent::GameConfigChanged) => {
+                if let Some(game_config) = game_config {
+                    game_config.reload();
+                }
+
+                let config = effective_config(game_config.as_ref(), app_config);
+                emu_thread.handle_config_change(&config)?;
             }
             &Event::UserEvent(UserEvent::ControllerButton { player, button, pressed }) => {
                 log::debug!("Player {player:?} digital input: {button:?} pressed={pressed}");
@@ -291,10 +397,25 @@ impl EmulatorState {
             }
             Event::UserEvent(UserEvent::FileOpened(OpenFileType::DiscChange, Some(disc_path))) => {
                 log::info!("Changing disc to '{}'", disc_path.display());
+                if let Some(PlaylistState { playlist, disc_index }) = playlist {
+                    if let Some(position) = playlist.position(disc_path) {
+                        *disc_index = position;
+                    }
+                }
                 emu_thread.send_command(EmulatorThreadCommand::ChangeDisc {
                     disc_path: disc_path.clone(),
+                    disc_index: 0,
+                });
+            }
+            Event::UserEvent(UserEvent::ChangePbpDisc { path, disc_index }) => {
+                log::info!("Changing disc to disc {} of '{}'", disc_index + 1, path.display());
+                emu_thread.send_command(EmulatorThreadCommand::ChangeDisc {
+                    disc_path: path.clone(),
+                    disc_index: *disc_index,
                 });
-                window.window.set_title(&determine_window_title(disc_path));
+            }
+            Event::UserEvent(UserEvent::DiscChanged { path, disc_info }) => {
+                window.window.set_title(&determine_window_title(path, disc_info.as_ref()));
             }
             Event::UserEvent(UserEvent::RemoveDisc) => {
                 log::info!("Removing disc");
@@ -356,41 +477,43 @@ impl EmulatorState {
                             Some(Hotkey::ToggleVramDisplay) => {
                                 app_config.debug.vram_display = !app_config.debug.vram_display;
                                 emu_thread.send_command(EmulatorThreadCommand::UpdateConfig(
-                                    app_config.clone().into(),
+                                    effective_config(game_config.as_ref(), app_config).into(),
                                 ));
                             }
                             Some(Hotkey::EnableHardwareRasterizer) => {
-                                app_config.graphics.rasterizer = Rasterizer::Hardware;
+                                let graphics = active_graphics_config(game_config, app_config);
+                                graphics.rasterizer = Rasterizer::Hardware;
+                                let scale = graphics.hardware_resolution_scale;
                                 emu_thread.send_command(EmulatorThreadCommand::UpdateConfig(
-                                    app_config.clone().into(),
+                                    effective_config(game_config.as_ref(), app_config).into(),
                                 ));
                                 log::info!(
-                                    "Using hardware rasterizer with resolution scale {}",
-                                    app_config.graphics.hardware_resolution_scale
+                                    "Using hardware rasterizer with resolution scale {scale}"
                                 );
                             }
                             Some(Hotkey::EnableSoftwareRasterizer) => {
-                                app_config.graphics.rasterizer = Rasterizer::Software;
+                                active_graphics_config(game_config, app_config).rasterizer =
+                                    Rasterizer::Software;
                                 emu_thread.send_command(EmulatorThreadCommand::UpdateConfig(
-                                    app_config.clone().into(),
+                                    effective_config(game_config.as_ref(), app_config).into(),
                                 ));
                                 log::info!("Using software rasterizer");
                             }
                             Some(Hotkey::DecreaseResolutionScale) => {
-                                let scale =
-                                    cmp::max(1, app_config.graphics.hardware_resolution_scale - 1);
-                                app_config.graphics.hardware_resolution_scale = scale;
+                                let graphics = active_graphics_config(game_config, app_config);
+                                let scale = cmp::max(1, graphics.hardware_resolution_scale - 1);
+                                graphics.hardware_resolution_scale = scale;
                                 emu_thread.send_command(EmulatorThreadCommand::UpdateConfig(
-                                    app_config.clone().into(),
+                                    effective_config(game_config.as_ref(), app_config).into(),
                                 ));
                                 log::info!("Set resolution scale to {scale}");
                             }
                             Some(Hotkey::IncreaseResolutionScale) => {
-                                let scale =
-                                    cmp::min(16, app_config.graphics.hardware_resolution_scale + 1);
-                                app_config.graphics.hardware_resolution_scale = scale;
+                                let graphics = active_graphics_config(game_config, app_config);
+                                let scale = cmp::min(16, graphics.hardware_resolution_scale + 1);
+                                graphics.hardware_resolution_scale = scale;
                                 emu_thread.send_command(EmulatorThreadCommand::UpdateConfig(
-                                    app_config.clone().into(),
+                                    effective_config(game_config.as_ref(), app_config).into(),
                                 ));
                                 log::info!("Set resolution scale to {scale}");
                             }
@@ -406,6 +529,12 @@ impl EmulatorState {
                             Some(Hotkey::StepFrame) => {
                                 emu_thread.send_command(EmulatorThreadCommand::StepFrame);
                             }
+                            Some(Hotkey::NextDisc) => {
+                                proxy.send_event(UserEvent::NextDisc).unwrap();
+                            }
+                            Some(Hotkey::PreviousDisc) => {
+                                proxy.send_event(UserEvent::PreviousDisc).unwrap();
+                            }
                             Some(Hotkey::FastForward) => {
                                 let enabled = state == ElementState::Pressed;
                                 emu_thread
@@ -467,24 +596,45 @@ impl EmulatorState {
         &mut self,
         file_path: Option<&Path>,
         elwt: &ActiveEventLoop,
+        proxy: &EventLoopProxy<UserEvent>,
         app_config: &AppConfig,
     ) -> anyhow::Result<()> {
-        if let Some(RunningState { emu_thread, .. }) = &self.running {
+        // Don't leave the previous game's state behind if the new game fails to start
+        let previous = self.running.take();
+        if let Some(RunningState { emu_thread, .. }) = &previous {
             emu_thread.send_command(EmulatorThreadCommand::Stop);
         }
 
-        let window = EmulatorWindow::new(file_path, elwt, app_config)?;
+        let playlist = match file_path {
+            Some(file_path) if Playlist::is_playlist_path(file_path) => {
+                Some(PlaylistState { playlist: Playlist::load(file_path)?, disc_index: 0 })
+            }
+            _ => None,
+        };
+
+        // Identify the disc once; the game config, window title, and memory cards are all keyed
+        // off of it
+        let disc_info = file_path.and_then(emuthread::read_disc_info);
+
+        let game_config =
+            file_path.and_then(|file_path| GameConfig::load(file_path, disc_info.as_ref()));
+        let config = effective_config(game_config.as_ref(), app_config);
+        config.memory_cards.migrate_per_game_cards(file_path, disc_info.as_ref());
+
+        let window = EmulatorWindow::new(file_path, disc_info.as_ref(), elwt, &config)?;
 
         let emu_thread = EmulationThreadHandle::spawn(
             &self.sdl_ctx,
             file_path,
-            app_config,
+            disc_info,
+            &config,
             &window.surface_config,
             Arc::clone(&window.device),
             Arc::clone(&window.queue),
+            proxy.clone(),
         )?;
 
-        self.running = Some(RunningState { window, emu_thread });
+        self.running = Some(RunningState { window, emu_thread, game_config, playlist });
 
         Ok(())
     }
@@ -492,6 +642,17 @@ impl EmulatorState {
     pub fn is_emulator_running(&self) -> bool {
         self.running.is_some()
     }
+
+    /// Per-game config for the running game, if it could be identified
+    pub fn game_config(&self) -> Option<&GameConfig> {
+        self.running.as_ref()?.game_config.as_ref()
+    }
+
+    /// Current disc index and disc count if the running game was opened from a playlist
+    pub fn playlist_position(&self) -> Option<(usize, usize)> {
+        let PlaylistState { playlist, disc_index } = self.running.as_ref()?.playlist.as_ref()?;
+        Some((*disc_index, playlist.discs().len()))
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -508,6 +669,8 @@ enum Hotkey {
     Pause,
     StepFrame,
     FastForward,
+    NextDisc,
+    PreviousDisc,
 }
 
 fn check_hotkey(key: PhysicalKey, state: ElementState) -> Option<Hotkey> {
@@ -528,6 +691,8 @@ fn check_hotkey(key: PhysicalKey, state: ElementState) -> Option<Hotkey> {
         KeyCode::KeyP if pressed => Some(Hotkey::Pause),
         KeyCode::KeyN if pressed => Some(Hotkey::StepFrame),
         KeyCode::Tab => Some(Hotkey::FastForward),
+        KeyCode::F7 if pressed => Some(Hotkey::PreviousDisc),
+        KeyCode::F8 if pressed => Some(Hotkey::NextDisc),
         _ => None,
     }
 }
@@ -1,17 +1,20 @@
 mod audio;
 mod renderer;
 
-use crate::Never;
-use crate::config::{AppConfig, GraphicsConfig, MemoryCardConfig};
+use crate::config::{AppConfig, DebugConfig, GraphicsConfig, MemoryCardConfig};
 use crate::emuthread::audio::{AudioQueue, QueueAudioCallback, QueueAudioOutput};
 use crate::emuthread::renderer::{SurfaceRenderer, SwapChainRenderer};
+use crate::playlist::Playlist;
+use crate::{Never, UserEvent};
 use anyhow::{Context, anyhow};
+use cdrom::discinfo::DiscInfo;
 use cdrom::reader::{CdRom, CdRomFileFormat};
 use cfg_if::cfg_if;
 use ps1_core::api::{
-    LoadedMemoryCards, MemoryCardSlot, Ps1Emulator, Ps1EmulatorBuilder, Ps1EmulatorState,
+    BiosMode, LoadedMemoryCards, MemoryCardSlot, Ps1Emulator, Ps1EmulatorBuilder, Ps1EmulatorState,
     SaveWriter, TickEffect, TickError,
 };
+use ps1_core::gdb::GdbServer;
 use ps1_core::input::{AnalogJoypadState, DigitalJoypadState, Ps1Inputs};
 use sdl2::audio::AudioDevice;
 use sdl2::{AudioSubsystem, Sdl};
@@ -26,6 +29,7 @@ use std::sync::{Arc, Mutex, mpsc};
 use std::time::Duration;
 use std::{fs, io, thread};
 use winit::dpi::PhysicalSize;
+use winit::event_loop::EventLoopProxy;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Player {
@@ -68,7 +72,7 @@ pub enum EmulatorThreadCommand {
     Stop,
     DigitalInput { player: Player, button: Ps1Button, pressed: bool },
     AnalogInput { player: Player, input: Ps1AnalogInput, value: i16 },
-    ChangeDisc { disc_path: PathBuf },
+    ChangeDisc { disc_path: PathBuf, disc_index: usize },
     RemoveDisc,
     UpdateConfig(Box<AppConfig>),
     SaveState,
@@ -145,44 +149,53 @@ pub struct EmulationThreadHandle {
 }
 
 impl EmulationThreadHandle {
-    #[allow(clippy::missing_errors_doc)]
+    #[allow(clippy::missing_errors_doc, clippy::too_many_arguments)]
     pub fn spawn(
         sdl_ctx: &Sdl,
         file_path: Option<&Path>,
+        disc_info: Option<DiscInfo>,
         config: &AppConfig,
         surface_config: &wgpu::SurfaceConfiguration,
         device: Arc<wgpu::Device>,
         queue: Arc<wgpu::Queue>,
+        proxy: EventLoopProxy<UserEvent>,
     ) -> anyhow::Result<Self> {
-        let Some(bios_path) = &config.paths.bios else {
-            return Err(anyhow!("BIOS path is required to run emulator"));
+        let bios = match config.paths.bios_mode {
+            BiosMode::Rom => {
+                let Some(bios_path) = &config.paths.bios else {
+                    return Err(anyhow!("BIOS path is required to run emulator"));
+                };
+
+                fs::read(bios_path).with_context(|| {
+                    format!("Failed to read BIOS from '{}'", bios_path.display())
+                })?
+            }
+            // The emulator generates its own BIOS ROM in HLE mode
+            BiosMode::Hle => Vec::new(),
         };
 
-        let bios = fs::read(bios_path)
-            .with_context(|| format!("Failed to read BIOS from '{}'", bios_path.display()))?;
-
         let emulator_config = config.to_emulator_config();
 
-        let save_writer = FsSaveWriter::new(file_path, &config.memory_cards)?;
+        let save_writer = FsSaveWriter::new(file_path, disc_info.as_ref(), &config.memory_cards)?;
         let memory_cards = load_memory_cards(&save_writer);
 
-        let builder = Ps1EmulatorBuilder::new(bios, Arc::clone(&device), Arc::clone(&queue))
+        let builder = Ps1EmulatorBuilder::new(bios)
+            .with_wgpu(Arc::clone(&device), Arc::clone(&queue))
             .with_config(emulator_config)
             .with_memory_cards_enabled(config.memory_cards.cards_enabled())
-            .with_memory_cards(memory_cards);
+            .with_memory_cards(memory_cards)
+            .with_fast_boot(config.paths.fast_boot);
+
+        // Discs from a playlist start with the first disc, but memory cards and save states are
+        // keyed off the playlist so that every disc shares them
+        let playlist_path = file_path.filter(|path| Playlist::is_playlist_path(path));
+        let disc_path = match playlist_path {
+            Some(playlist_path) => Some(Playlist::load(playlist_path)?.discs()[0].clone()),
+            None => file_path.map(PathBuf::from),
+        };
 
-        let emulator = match file_path {
+        let emulator = match disc_path.as_deref() {
             Some(file_path) => match file_path.extension().and_then(OsStr::to_str) {
-                Some(extension @ ("cue" | "chd")) => {
-                    let format = match extension {
-                        "cue" => CdRomFileFormat::CueBin,
-                        "chd" => CdRomFileFormat::Chd,
-                        _ => unreachable!("nested match expressions"),
-                    };
-
-                    let disc = CdRom::open(file_path, format)?;
-                    builder.with_disc(disc).build()?
-                }
                 Some("exe") => {
                     let exe = fs::read(file_path).with_context(|| {
                         format!("Failed to read EXE from path {}", file_path.display())
@@ -194,7 +207,12 @@ impl EmulationThreadHandle {
                     emulator
                 }
                 Some(extension) => {
-                    return Err(anyhow!("Unsupported file extension {extension}"));
+                    let Some(format) = CdRomFileFormat::from_file_path(file_path) else {
+                        return Err(anyhow!("Unsupported file extension {extension}"));
+                    };
+
+                    let disc = CdRom::open(file_path, format)?;
+                    builder.with_disc(disc).build()?
                 }
                 None => {
                     return Err(anyhow!(
@@ -233,17 +251,24 @@ impl EmulationThreadHandle {
 
         log::info!("Launching emulator with config:\n{config:#?}");
 
-        spawn_emu_thread(&config.memory_cards, EmulatorRunner {
+        let mut runner = EmulatorRunner {
             emulator,
             renderer: swap_chain_renderer,
             audio_output,
             audio_sync_threshold: config.audio.sync_threshold,
             save_writer,
             inputs,
-            disc_path: file_path.map(PathBuf::from),
+            disc_path,
+            playlist_path: playlist_path.map(PathBuf::from),
+            disc_info,
             save_state_path,
             command_receiver,
-        });
+            proxy,
+            gdb_server: None,
+        };
+        update_gdb_server(&config.debug, &mut runner);
+
+        spawn_emu_thread(&config.memory_cards, runner);
 
         let surface_renderer = SurfaceRenderer::new(
             &config.video,
@@ -324,19 +349,52 @@ struct EmulatorRunner {
     save_writer: FsSaveWriter,
     inputs: Ps1Inputs,
     disc_path: Option<PathBuf>,
+    playlist_path: Option<PathBuf>,
+    // Disc info for the memory card key path; for playlists this is always the first disc
+    disc_info: Option<DiscInfo>,
     save_state_path: PathBuf,
     command_receiver: Receiver<EmulatorThreadCommand>,
+    proxy: EventLoopProxy<UserEvent>,
+    gdb_server: Option<GdbServer>,
 }
 
 impl EmulatorRunner {
+    // Memory cards for playlists are keyed off the playlist rather than the current disc
+    fn memory_card_key_path(&self) -> Option<&Path> {
+        self.playlist_path.as_deref().or(self.disc_path.as_deref())
+    }
+
+    fn gdb_halted(&self) -> bool {
+        self.gdb_server.as_ref().is_some_and(GdbServer::is_halted)
+    }
+
     fn process_next_frame(&mut self) -> Result<(), TickError<Never, Never, io::Error>> {
-        while self.emulator.tick(
-            self.inputs,
-            &mut self.renderer,
-            &mut self.audio_output,
-            &mut self.save_writer,
-        )? != TickEffect::FrameRendered
-        {}
+        // If GDB halts the CPU mid-frame, the rest of the frame runs after GDB resumes execution
+        while !self.gdb_halted() {
+            match self.emulator.tick(
+                self.inputs,
+                &mut self.renderer,
+                &mut self.audio_output,
+                &mut self.save_writer,
+            )? {
+                TickEffect::None => {}
+                TickEffect::FrameRendered => break,
+                TickEffect::DebugStop(reason) => {
+                    if let Some(gdb_server) = &mut self.gdb_server {
+                        gdb_server.notify_stop(&mut self.emulator, reason);
+                    }
+                }
+            }
+        }
+
+        // Separate log targets so that these can be filtered independently, e.g. RUST_LOG=tty=info
+        for line in self.emulator.drain_tty_output() {
+            log::info!(target: "tty", "{}", line.text);
+        }
+
+        for event in self.emulator.drain_kernel_calls() {
+            log::info!(target: "bios", "{event}");
+        }
 
         Ok(())
     }
@@ -353,6 +411,10 @@ fn spawn_emu_thread(memory_card_config: &MemoryCardConfig, mut runner: EmulatorR
         let mut memory_card_config = memory_card_config;
 
         loop {
+            if let Some(gdb_server) = &mut runner.gdb_server {
+                gdb_server.poll(&mut runner.emulator);
+            }
+
             if (!paused || step_frame)
                 && (fast_forward
                     || (runner.audio_output.samples_len() as u32) < runner.audio_sync_threshold)
@@ -385,15 +447,31 @@ fn spawn_emu_thread(memory_card_config: &MemoryCardConfig, mut runner: EmulatorR
                     EmulatorThreadCommand::AnalogInput { player, input, value } => {
                         update_analog_inputs(&mut runner.inputs, player, input, value);
                     }
-                    EmulatorThreadCommand::ChangeDisc { disc_path } => {
-                        try_change_disc(&mut runner.emulator, &disc_path);
+                    EmulatorThreadCommand::ChangeDisc { disc_path, disc_index } => {
+                        let disc_info =
+                            try_change_disc(&mut runner.emulator, &disc_path, disc_index);
+
+                        // Let the UI thread update the window title without re-reading the disc
+                        if let Err(err) = runner.proxy.send_event(UserEvent::DiscChanged {
+                            path: disc_path.clone(),
+                            disc_info: disc_info.clone(),
+                        }) {
+                            log::error!("Failed to send disc change event: {err}");
+                        }
+
                         runner.disc_path = Some(disc_path);
+                        if runner.playlist_path.is_none() {
+                            runner.disc_info = disc_info;
+                        }
 
                         update_memcard_config(&memory_card_config, &mut runner);
                     }
                     EmulatorThreadCommand::RemoveDisc => {
                         runner.emulator.change_disc(None);
                         runner.disc_path = None;
+                        if runner.playlist_path.is_none() {
+                            runner.disc_info = None;
+                        }
 
                         update_memcard_config(&memory_card_config, &mut runner);
                     }
@@ -401,6 +479,7 @@ fn spawn_emu_thread(memory_card_config: &MemoryCardConfig, mut runner: EmulatorR
                         runner.emulator.update_config(config.to_emulator_config());
                         runner.audio_sync_threshold = config.audio.sync_threshold;
                         update_input_config(&config, &mut runner.inputs);
+                        update_gdb_server(&config.debug, &mut runner);
 
                         if memory_card_config != config.memory_cards {
                             update_memcard_config(&config.memory_cards, &mut runner);
@@ -471,8 +550,40 @@ fn update_input_config(config: &AppConfig, inputs: &mut Ps1Inputs) {
     inputs.p2.analog = AnalogJoypadState::default();
 }
 
+fn update_gdb_server(config: &DebugConfig, runner: &mut EmulatorRunner) {
+    let current_port = runner
+        .gdb_server
+        .as_ref()
+        .and_then(|gdb_server| gdb_server.local_addr().ok())
+        .map(|addr| addr.port());
+    if config.gdb_server_enabled && current_port == Some(config.gdb_server_port) {
+        return;
+    }
+
+    if runner.gdb_server.take().is_some() {
+        // Don't leave behind breakpoints from a GDB session that was still attached
+        runner.emulator.clear_breakpoints();
+    }
+
+    if !config.gdb_server_enabled {
+        return;
+    }
+
+    runner.gdb_server = match GdbServer::bind(config.gdb_server_port) {
+        Ok(gdb_server) => Some(gdb_server),
+        Err(err) => {
+            log::error!("Error starting GDB server on port {}: {err}", config.gdb_server_port);
+            None
+        }
+    };
+}
+
 fn update_memcard_config(config: &MemoryCardConfig, runner: &mut EmulatorRunner) {
-    if let Err(err) = runner.save_writer.update_config(runner.disc_path.as_ref(), config) {
+    let key_path = runner.memory_card_key_path().map(PathBuf::from);
+    let disc_info = runner.disc_info.clone();
+    if let Err(err) =
+        runner.save_writer.update_config(key_path.as_deref(), disc_info.as_ref(), config)
+    {
         log::error!("Error updating memory card config: {err}");
         return;
     }
@@ -537,30 +648,63 @@ fn update_analog_inputs(inputs: &mut Ps1Inputs, player: Player, input: Ps1Analog
     }
 }
 
-fn try_change_disc(emulator: &mut Ps1Emulator, disc_path: &Path) {
+/// `disc_index` selects the disc within multi-disc PBP files and is ignored for other formats.
+///
+/// Returns the info of the new disc, or `None` if it could not be opened.
+fn try_change_disc(
+    emulator: &mut Ps1Emulator,
+    disc_path: &Path,
+    disc_index: usize,
+) -> Option<DiscInfo> {
     let Some(extension) = disc_path.extension().and_then(OsStr::to_str) else {
         log::error!("Unable to determine file extension of disc path '{}'", disc_path.display());
-        return;
+        return None;
     };
 
-    let format = match extension.to_ascii_lowercase().as_str() {
-        "chd" => CdRomFileFormat::Chd,
-        "cue" => CdRomFileFormat::CueBin,
-        _ => {
-            log::error!("Unsupported disc file extension '{extension}'");
-            return;
-        }
+    let Some(format) = CdRomFileFormat::from_file_path(disc_path) else {
+        log::error!("Unsupported disc file extension '{extension}'");
+        return None;
     };
 
-    let disc = match CdRom::open(disc_path, format) {
+    let disc = match format {
+        CdRomFileFormat::Pbp => CdRom::open_pbp(disc_path, disc_index),
+        _ => CdRom::open(disc_path, format),
+    };
+    let mut disc = match disc {
         Ok(disc) => disc,
         Err(err) => {
             log::error!("Error opening disc at '{}': {err}", disc_path.display());
-            return;
+            return None;
         }
     };
 
+    let disc_info = DiscInfo::read(&mut disc);
     emulator.change_disc(Some(disc));
+
+    Some(disc_info)
+}
+
+/// Identify the game in a disc image. Returns `None` if the path is not a disc image or the disc
+/// could not be opened.
+///
+/// Playlists are identified by their first disc.
+pub(crate) fn read_disc_info(disc_path: &Path) -> Option<DiscInfo> {
+    if Playlist::is_playlist_path(disc_path) {
+        let playlist = Playlist::load(disc_path).map_err(|err| log::error!("{err}")).ok()?;
+        return read_disc_info(&playlist.discs()[0]);
+    }
+
+    let format = CdRomFileFormat::from_file_path(disc_path)?;
+
+    let mut disc = match CdRom::open(disc_path, format) {
+        Ok(disc) => disc,
+        Err(err) => {
+            log::error!("Error opening disc at '{}': {err}", disc_path.display());
+            return None;
+        }
+    };
+
+    Some(DiscInfo::read(&mut disc))
 }
 
 macro_rules! bincode_config {
@@ -612,9 +756,13 @@ struct FsSaveWriter {
 }
 
 impl FsSaveWriter {
-    fn new(disc_path: Option<&Path>, config: &MemoryCardConfig) -> anyhow::Result<Self> {
-        let card_1_path = config.slot_1_path(disc_path);
-        let card_2_path = config.slot_2_path(disc_path);
+    fn new(
+        disc_path: Option<&Path>,
+        disc_info: Option<&DiscInfo>,
+        config: &MemoryCardConfig,
+    ) -> anyhow::Result<Self> {
+        let card_1_path = config.slot_1_path(disc_path, disc_info);
+        let card_2_path = config.slot_2_path(disc_path, disc_info);
 
         ensure_parent_dir_exists(&card_1_path)?;
         ensure_parent_dir_exists(&card_2_path)?;
@@ -625,13 +773,14 @@ impl FsSaveWriter {
         Ok(Self { card_1_path, card_2_path })
     }
 
-    fn update_config<P: AsRef<Path> + Copy>(
+    fn update_config(
         &mut self,
-        disc_path: Option<P>,
+        disc_path: Option<&Path>,
+        disc_info: Option<&DiscInfo>,
         config: &MemoryCardConfig,
     ) -> anyhow::Result<()> {
-        self.card_1_path = config.slot_1_path(disc_path);
-        self.card_2_path = config.slot_2_path(disc_path);
+        self.card_1_path = config.slot_1_path(disc_path, disc_info);
+        self.card_2_path = config.slot_2_path(disc_path, disc_info);
 
         ensure_parent_dir_exists(&self.card_1_path)?;
         ensure_parent_dir_exists(&self.card_2_path)?;
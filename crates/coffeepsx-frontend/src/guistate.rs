@@ -151,7 +151,7 @@ impl GuiState {
         proxy: &EventLoopProxy<UserEvent>,
     ) {
         if let Event::UserEvent(user_event) = event {
-            let response = self.app.handle_event(user_event);
+            let response = self.app.handle_event(user_event, emu_state);
             if response.repaint {
                 self.repaint(emu_state, proxy);
             }
@@ -316,8 +316,8 @@ fn async_open_file_dialog(
     proxy: &EventLoopProxy<UserEvent>,
 ) {
     let (name, extensions): (_, &[_]) = match file_type {
-        OpenFileType::Open => ("PS1", &["cue", "chd", "exe"]),
-        OpenFileType::DiscChange => ("PS1", &["cue", "chd"]),
+        OpenFileType::Open => ("PS1", &["cue", "chd", "iso", "ecm", "pbp", "ccd", "m3u", "exe"]),
+        OpenFileType::DiscChange => ("PS1", &["cue", "chd", "iso", "ecm", "pbp", "ccd"]),
         OpenFileType::BiosPath => ("BIOS", &["bin", "BIN"]),
         OpenFileType::SearchDir => {
             let proxy = proxy.clone();
@@ -4,8 +4,10 @@ pub mod emustate;
 pub mod emuthread;
 pub mod guistate;
 pub mod input;
+pub mod playlist;
 
 use crate::emuthread::{Player, Ps1AnalogInput, Ps1Button};
+use cdrom::discinfo::DiscInfo;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -18,18 +20,48 @@ pub enum OpenFileType {
 
 #[derive(Debug)]
 pub enum UserEvent {
-    OpenFileDialog { file_type: OpenFileType, initial_dir: Option<PathBuf> },
+    OpenFileDialog {
+        file_type: OpenFileType,
+        initial_dir: Option<PathBuf>,
+    },
     FileOpened(OpenFileType, Option<PathBuf>),
     RunBios,
     AppConfigChanged,
+    GameConfigChanged,
     Close,
-    ControllerButton { player: Player, button: Ps1Button, pressed: bool },
-    ControllerAnalog { player: Player, input: Ps1AnalogInput, value: i16 },
+    ControllerButton {
+        player: Player,
+        button: Ps1Button,
+        pressed: bool,
+    },
+    ControllerAnalog {
+        player: Player,
+        input: Ps1AnalogInput,
+        value: i16,
+    },
+    ChangePbpDisc {
+        path: PathBuf,
+        disc_index: usize,
+    },
+    /// Sent by the emulator thread after it opens a new disc
+    DiscChanged {
+        path: PathBuf,
+        disc_info: Option<DiscInfo>,
+    },
+    NextDisc,
+    PreviousDisc,
     RemoveDisc,
     Reset,
     PowerOff,
-    SdlButtonPress { which: u32, button: sdl2::controller::Button },
-    SdlAxisMotion { which: u32, axis: sdl2::controller::Axis, value: i16 },
+    SdlButtonPress {
+        which: u32,
+        button: sdl2::controller::Button,
+    },
+    SdlAxisMotion {
+        which: u32,
+        axis: sdl2::controller::Axis,
+        value: i16,
+    },
 }
 
 // Enum with no variants cannot be instantiated
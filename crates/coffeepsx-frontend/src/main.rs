@@ -1,3 +1,6 @@
+use anyhow::anyhow;
+use cdrom::reader::{CdRom, CdRomFileFormat};
+use cdrom::redump::{self, RedumpDat, Verification};
 use clap::Parser;
 use coffeepsx_frontend::app::App;
 use coffeepsx_frontend::config::AppConfig;
@@ -5,7 +8,7 @@ use coffeepsx_frontend::emustate::EmulatorState;
 use coffeepsx_frontend::guistate::GuiState;
 use coffeepsx_frontend::{OpenFileType, UserEvent};
 use env_logger::Env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
@@ -22,6 +25,14 @@ struct Args {
     /// File path to use when running in headless mode. Will run the BIOS if not set
     #[arg(long, short = 'f')]
     headless_file: Option<PathBuf>,
+
+    /// Verify a disc image against a Redump DAT file and exit without opening any windows
+    #[arg(long, requires = "redump_dat")]
+    verify: Option<PathBuf>,
+
+    /// Path to the Redump DAT file to use with --verify
+    #[arg(long)]
+    redump_dat: Option<PathBuf>,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -32,6 +43,10 @@ fn main() -> anyhow::Result<()> {
 
     let args = Args::parse();
 
+    if let (Some(disc_path), Some(dat_path)) = (&args.verify, &args.redump_dat) {
+        return verify_disc(disc_path, dat_path);
+    }
+
     let event_loop = EventLoop::with_user_event().build()?;
     event_loop.set_control_flow(ControlFlow::Poll);
 
@@ -126,6 +141,29 @@ fn run_headless(
     Ok(())
 }
 
+fn verify_disc(disc_path: &Path, dat_path: &Path) -> anyhow::Result<()> {
+    let Some(format) = CdRomFileFormat::from_file_path(disc_path) else {
+        return Err(anyhow!("Unsupported disc image format: '{}'", disc_path.display()));
+    };
+
+    let dat = RedumpDat::open(dat_path)?;
+    let mut disc = CdRom::open(disc_path, format)?;
+
+    log::info!("Hashing '{}'", disc_path.display());
+    let hashes = redump::hash_tracks(&mut disc)?;
+    for track_hashes in &hashes {
+        println!("{track_hashes}");
+    }
+
+    let verification = dat.verify(&hashes);
+    println!("{verification}");
+
+    match verification {
+        Verification::Verified { .. } => Ok(()),
+        _ => Err(anyhow!("Disc image '{}' is not a verified dump", disc_path.display())),
+    }
+}
+
 fn throttle_if_necessary(event: &Event<UserEvent>, elwt: &ActiveEventLoop) {
     // Wait for 1ms every time the event queue is exhausted to prevent pegging a CPU core at
     // 100% while the app is running
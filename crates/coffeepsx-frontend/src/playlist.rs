@@ -0,0 +1,85 @@
+//! M3U playlists, which list the disc images of a multi-disc game in order
+
+use anyhow::anyhow;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct Playlist {
+    discs: Vec<PathBuf>,
+}
+
+impl Playlist {
+    #[must_use]
+    pub fn is_playlist_path(path: &Path) -> bool {
+        path.extension().and_then(OsStr::to_str).is_some_and(|ext| ext.eq_ignore_ascii_case("m3u"))
+    }
+
+    /// Read a playlist file. Relative disc paths are resolved relative to the playlist's
+    /// directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or if it does not list any discs.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| anyhow!("Error reading playlist '{}': {err}", path.display()))?;
+        let playlist = Self::parse(path, &contents)?;
+
+        log::info!("Loaded playlist '{}' with {} discs", path.display(), playlist.discs.len());
+
+        Ok(playlist)
+    }
+
+    fn parse(path: &Path, contents: &str) -> anyhow::Result<Self> {
+        let parent_dir = path.parent().unwrap_or(Path::new(""));
+        let discs: Vec<_> = contents
+            .trim_start_matches('\u{feff}')
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| parent_dir.join(line))
+            .collect();
+
+        if discs.is_empty() {
+            return Err(anyhow!("Playlist '{}' does not list any discs", path.display()));
+        }
+
+        Ok(Self { discs })
+    }
+
+    #[must_use]
+    pub fn discs(&self) -> &[PathBuf] {
+        &self.discs
+    }
+
+    /// Index of the given disc path within the playlist, if present
+    #[must_use]
+    pub fn position(&self, disc_path: &Path) -> Option<usize> {
+        self.discs.iter().position(|path| path == disc_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let path = Path::new("games/Final Fantasy VII.m3u");
+        let contents = "\u{feff}#EXTM3U\r\nFinal Fantasy VII (Disc 1).cue\r\n\r\n  Final Fantasy VII (Disc 2).chd  \r\n";
+
+        let playlist = Playlist::parse(path, contents).unwrap();
+        assert_eq!(
+            playlist.discs(),
+            &[
+                PathBuf::from("games/Final Fantasy VII (Disc 1).cue"),
+                PathBuf::from("games/Final Fantasy VII (Disc 2).chd"),
+            ]
+        );
+        assert_eq!(playlist.position(Path::new("games/Final Fantasy VII (Disc 2).chd")), Some(1));
+
+        assert!(Playlist::parse(path, "#EXTM3U\n").is_err());
+    }
+}
@@ -1,11 +1,15 @@
 //! PS1 public interface and main loop
 
+use crate::biostrace::KernelCallTracer;
 use crate::bus::Bus;
 use crate::cd::{CdController, CdControllerState};
 use crate::cpu::R3000;
+use crate::debug::{BreakpointId, CpuRegister, Debugger, StopReason, WatchKind, WatchpointId};
 use crate::dma::{DmaContext, DmaController};
+use crate::fastboot;
 use crate::gpu::Gpu;
 use crate::gpu::GpuState;
+use crate::hle::{self, HleBios};
 use crate::input::Ps1Inputs;
 use crate::interrupts::{InterruptRegisters, InterruptType};
 use crate::mdec::MacroblockDecoder;
@@ -14,18 +18,25 @@ use crate::scheduler::{Scheduler, SchedulerEvent, SchedulerEventType};
 use crate::sio::{SerialPort0, SerialPort1};
 use crate::spu::Spu;
 use crate::timers::Timers;
+use crate::tty::{Tty, TtyState};
 use bincode::{Decode, Encode};
 use cdrom::CdRomError;
+use cdrom::discinfo::DiscRegion;
 use cdrom::reader::CdRom;
 use proc_macros::SaveState;
 use std::fmt::{Display, Formatter};
+use std::mem;
 use std::num::NonZeroU32;
+use std::ops::RangeInclusive;
+#[cfg(feature = "wgpu")]
 use std::sync::Arc;
 use thiserror::Error;
 
-pub use crate::gpu::DisplayConfig;
+pub use crate::biostrace::{KernelCallEvent, KernelFunction, KernelTable};
+pub use crate::gpu::{DisplayConfig, GraphicsBackend};
 pub use crate::pgxp::PgxpConfig;
 use crate::sio::memcard::MemoryCard;
+pub use crate::tty::{TtyLine, TtySource};
 
 pub const DEFAULT_AUDIO_BUFFER_SIZE: u32 = 64;
 
@@ -51,6 +62,7 @@ impl ColorDepthBits {
     }
 }
 
+#[cfg(feature = "wgpu")]
 pub trait Renderer {
     type Err;
 
@@ -65,6 +77,33 @@ pub trait Renderer {
     ) -> Result<(), Self::Err>;
 }
 
+/// A displayed frame in CPU-side memory.
+#[derive(Debug, Clone, Copy)]
+pub struct CpuFrame<'a> {
+    /// Pixels in row-major order with no padding between rows, 4 bytes per pixel (RGBA8)
+    pub rgba: &'a [u8],
+    pub width: u32,
+    pub height: u32,
+    pub pixel_aspect_ratio: f64,
+}
+
+impl CpuFrame<'_> {
+    /// Convert the frame to packed RGB24, e.g. for writing to an image file.
+    #[must_use]
+    pub fn to_rgb24(&self) -> Vec<u8> {
+        self.rgba.chunks_exact(4).flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect()
+    }
+}
+
+pub trait CpuRenderer {
+    type Err;
+
+    /// # Errors
+    ///
+    /// Should propagate any error encountered while rendering the frame.
+    fn render_frame(&mut self, frame: CpuFrame<'_>) -> Result<(), Self::Err>;
+}
+
 pub trait AudioOutput {
     type Err;
 
@@ -96,6 +135,8 @@ pub enum Ps1Error {
     IncorrectBiosSize { bios_len: usize },
     #[error("EXE format is invalid")]
     InvalidExeFormat,
+    #[error("BIOS did not finish initializing the kernel")]
+    BiosBootTimeout,
 }
 
 pub type Ps1Result<T> = Result<T, Ps1Error>;
@@ -120,13 +161,85 @@ pub enum AdpcmInterpolation {
     Hermite,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BiosMode {
+    /// Execute the BIOS ROM passed to the emulator
+    #[default]
+    Rom,
+    /// Emulate the BIOS kernel at a high level, ignoring the BIOS ROM passed to the emulator.
+    /// Compatible with most software, but does not include the BIOS shell (memory card manager
+    /// and CD player) or boot animation
+    Hle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConsoleRegion {
+    /// Accept discs from any region, as a modded console would. Unlicensed discs are reported as
+    /// SCEA
+    #[default]
+    RegionFree,
+    /// Only accept discs licensed for North America
+    NtscU,
+    /// Only accept discs licensed for Japan
+    NtscJ,
+    /// Only accept discs licensed for Europe
+    Pal,
+}
+
+impl ConsoleRegion {
+    /// Whether the drive accepts a disc with the given license region (`None` if unlicensed)
+    #[must_use]
+    pub fn accepts(self, disc_region: Option<DiscRegion>) -> bool {
+        match self {
+            Self::RegionFree => true,
+            Self::NtscU => disc_region == Some(DiscRegion::NtscU),
+            Self::NtscJ => disc_region == Some(DiscRegion::NtscJ),
+            Self::Pal => disc_region == Some(DiscRegion::Pal),
+        }
+    }
+}
+
+/// What to do when a data sector read from the disc image fails its EDC (error detection code)
+/// check. Slightly damaged rips and patched translations commonly have sectors with bad EDC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EdcMismatchPolicy {
+    /// Log a warning and use the sector as-is
+    Ignore,
+    /// Log a warning and recompute the sector's EDC and ECC from its contents before using it
+    Repair,
+    /// Report a read error to software via INT5, as a real drive does after failing to re-read
+    /// the sector
+    ReadError,
+    /// Stop emulation with an error
+    #[default]
+    Fail,
+}
+
 #[derive(Debug, Clone, Copy, Encode, Decode)]
 pub struct Ps1EmulatorConfig {
     pub display: DisplayConfig,
     pub pgxp: PgxpConfig,
     pub adpcm_interpolation: AdpcmInterpolation,
     pub internal_audio_buffer_size: NonZeroU32,
+    /// Capture TTY output from BIOS `putchar()` calls and the DTL-H debug UART; see
+    /// [`Ps1Emulator::drain_tty_output`]
     pub tty_enabled: bool,
+    /// Trace BIOS kernel calls and their return values; see
+    /// [`Ps1Emulator::drain_kernel_calls`]
+    pub kernel_call_trace_enabled: bool,
+    /// Seed for the garbage that main RAM and scratchpad contain at power-on. If not set, the
+    /// initial contents are random, which makes emulation non-deterministic across runs
+    pub ram_seed: Option<u64>,
+    /// Whether to execute a real BIOS or use the built-in HLE kernel. Only takes effect when the
+    /// emulator is created
+    pub bios_mode: BiosMode,
+    /// Which disc regions the CD-ROM drive accepts. Discs from other regions fail the `GetID`
+    /// license check, which prevents the BIOS from booting them
+    pub console_region: ConsoleRegion,
+    pub edc_mismatch_policy: EdcMismatchPolicy,
 }
 
 impl Default for Ps1EmulatorConfig {
@@ -137,6 +250,11 @@ impl Default for Ps1EmulatorConfig {
             adpcm_interpolation: AdpcmInterpolation::default(),
             internal_audio_buffer_size: NonZeroU32::new(DEFAULT_AUDIO_BUFFER_SIZE).unwrap(),
             tty_enabled: false,
+            kernel_call_trace_enabled: false,
+            ram_seed: None,
+            bios_mode: BiosMode::default(),
+            console_region: ConsoleRegion::default(),
+            edc_mismatch_policy: EdcMismatchPolicy::default(),
         }
     }
 }
@@ -162,10 +280,13 @@ pub struct LoadedMemoryCards {
 pub struct UnserializedFields {
     disc: Option<CdRom>,
     memory_cards: LoadedMemoryCards,
-    wgpu_device: Arc<wgpu::Device>,
-    wgpu_queue: Arc<wgpu::Queue>,
+    graphics_backend: GraphicsBackend,
     config: Ps1EmulatorConfig,
     memory_cards_enabled: MemoryCardsEnabled,
+    debugger: Debugger,
+    tty: Tty,
+    kernel_call_tracer: KernelCallTracer,
+    fast_boot: bool,
 }
 
 #[derive(SaveState)]
@@ -187,40 +308,53 @@ pub struct Ps1Emulator {
     timers: Timers,
     scheduler: Scheduler,
     last_render_cycles: u64,
+    hle_bios: Option<HleBios>,
     #[save_state(skip)]
     config: Ps1EmulatorConfig,
-    tty_buffer: String,
+    #[save_state(skip)]
+    debugger: Debugger,
+    #[save_state(to = TtyState)]
+    tty: Tty,
+    #[save_state(skip)]
+    kernel_call_tracer: KernelCallTracer,
+    #[save_state(skip)]
+    fast_boot: bool,
 }
 
 #[derive(Debug)]
 pub struct Ps1EmulatorBuilder {
     bios_rom: Vec<u8>,
-    wgpu_device: Arc<wgpu::Device>,
-    wgpu_queue: Arc<wgpu::Queue>,
+    graphics_backend: GraphicsBackend,
     config: Ps1EmulatorConfig,
     memory_cards_enabled: MemoryCardsEnabled,
     loaded_memory_cards: Option<LoadedMemoryCards>,
     disc: Option<CdRom>,
+    fast_boot: bool,
 }
 
 impl Ps1EmulatorBuilder {
+    /// Create a builder for an emulator with no graphics device. Frames can only be output through
+    /// a [`CpuRenderer`] unless a wgpu device is set using `with_wgpu()`.
     #[must_use]
-    pub fn new(
-        bios_rom: Vec<u8>,
-        wgpu_device: Arc<wgpu::Device>,
-        wgpu_queue: Arc<wgpu::Queue>,
-    ) -> Self {
+    pub fn new(bios_rom: Vec<u8>) -> Self {
         Self {
             bios_rom,
-            wgpu_device,
-            wgpu_queue,
+            graphics_backend: GraphicsBackend::default(),
             config: Ps1EmulatorConfig::default(),
             memory_cards_enabled: MemoryCardsEnabled::default(),
             loaded_memory_cards: None,
             disc: None,
+            fast_boot: false,
         }
     }
 
+    #[cfg(feature = "wgpu")]
+    #[must_use]
+    pub fn with_wgpu(mut self, device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>) -> Self {
+        self.graphics_backend = GraphicsBackend::Wgpu { device, queue };
+        self
+    }
+
     #[must_use]
     pub fn with_disc(mut self, disc: CdRom) -> Self {
         self.disc = Some(disc);
@@ -245,19 +379,36 @@ impl Ps1EmulatorBuilder {
         self
     }
 
+    /// Skip the BIOS boot animation when booting a disc, jumping straight into the disc's boot EXE
+    /// after the BIOS initializes the kernel. Has no effect without a disc, and has no effect in
+    /// HLE BIOS mode because the HLE BIOS never shows a boot animation.
+    ///
+    /// Resetting the emulator will also fast boot.
+    #[must_use]
+    pub fn with_fast_boot(mut self, fast_boot: bool) -> Self {
+        self.fast_boot = fast_boot;
+        self
+    }
+
     /// # Errors
     ///
     /// Will return an error if the BIOS ROM is invalid.
     pub fn build(self) -> Ps1Result<Ps1Emulator> {
-        Ps1Emulator::new(
+        let mut emulator = Ps1Emulator::new(
             self.bios_rom,
-            self.wgpu_device,
-            self.wgpu_queue,
+            self.graphics_backend,
             self.config,
             self.memory_cards_enabled,
             self.loaded_memory_cards.unwrap_or(LoadedMemoryCards { slot_1: None, slot_2: None }),
             self.disc,
-        )
+        )?;
+
+        if self.fast_boot {
+            emulator.fast_boot = true;
+            emulator.fast_boot_disc();
+        }
+
+        Ok(emulator)
     }
 }
 
@@ -265,6 +416,8 @@ impl Ps1EmulatorBuilder {
 pub enum TickEffect {
     None,
     FrameRendered,
+    /// Execution stopped because of a breakpoint, a watchpoint, or a completed step
+    DebugStop(StopReason),
 }
 
 // The SPU/CD-ROM clock rate is exactly 1/768 the CPU clock rate
@@ -286,6 +439,8 @@ macro_rules! new_bus {
             sio1: &mut $self.sio1,
             timers: &mut $self.timers,
             scheduler: &mut $self.scheduler,
+            debugger: &mut $self.debugger,
+            tty: &mut $self.tty,
         }
     };
 }
@@ -307,24 +462,32 @@ macro_rules! new_dma_ctx {
 impl Ps1Emulator {
     /// # Errors
     ///
-    /// Will return an error if the BIOS ROM is invalid.
+    /// Will return an error if the BIOS ROM is invalid. The BIOS ROM is ignored if the config
+    /// enables HLE BIOS mode.
     pub fn new(
         bios_rom: Vec<u8>,
-        wgpu_device: Arc<wgpu::Device>,
-        wgpu_queue: Arc<wgpu::Queue>,
+        graphics_backend: GraphicsBackend,
         config: Ps1EmulatorConfig,
         memory_cards_enabled: MemoryCardsEnabled,
         loaded_memory_cards: LoadedMemoryCards,
         disc: Option<CdRom>,
     ) -> Ps1Result<Self> {
-        let memory = Memory::new(bios_rom)?;
+        let (bios_rom, hle_bios) = match config.bios_mode {
+            BiosMode::Rom => (bios_rom, None),
+            BiosMode::Hle => (hle::generate_rom(), Some(HleBios::new())),
+        };
+        let memory = Memory::new(bios_rom, config.ram_seed)?;
 
         let mut emulator = Self {
             cpu: R3000::new(config.pgxp),
-            gpu: Gpu::new(wgpu_device, wgpu_queue, config.display, config.pgxp),
+            gpu: Gpu::new(graphics_backend, config.display, config.pgxp),
             spu: Spu::new(config.adpcm_interpolation),
             audio_buffer: Vec::with_capacity(1600),
-            cd_controller: CdController::new(disc),
+            cd_controller: CdController::new(
+                disc,
+                config.console_region,
+                config.edc_mismatch_policy,
+            ),
             mdec: MacroblockDecoder::new(),
             memory,
             memory_control: MemoryControl::new(),
@@ -335,8 +498,12 @@ impl Ps1Emulator {
             timers: Timers::new(),
             scheduler: Scheduler::new(),
             last_render_cycles: 0,
+            hle_bios,
             config,
-            tty_buffer: String::new(),
+            debugger: Debugger::default(),
+            tty: Tty::new(config.tty_enabled),
+            kernel_call_tracer: KernelCallTracer::new(config.kernel_call_trace_enabled),
+            fast_boot: false,
         };
         emulator.schedule_initial_events();
 
@@ -350,14 +517,21 @@ impl Ps1Emulator {
 
         *self = Ps1Emulator::new(
             bios_rom,
-            unserialized.wgpu_device,
-            unserialized.wgpu_queue,
+            unserialized.graphics_backend,
             self.config,
             unserialized.memory_cards_enabled,
             unserialized.memory_cards,
             unserialized.disc,
         )
         .expect("Emulator creation during reset should never fail");
+        self.debugger = unserialized.debugger;
+        self.tty = unserialized.tty;
+        self.kernel_call_tracer = unserialized.kernel_call_tracer;
+        self.fast_boot = unserialized.fast_boot;
+
+        if self.fast_boot {
+            self.fast_boot_disc();
+        }
     }
 
     fn schedule_initial_events(&mut self) {
@@ -373,16 +547,39 @@ impl Ps1Emulator {
 
     /// # Errors
     ///
-    /// Will return an error if the EXE does not appear to be a PS1 executable based on the header.
+    /// Will return an error if the EXE does not appear to be a PS1 executable based on the header,
+    /// or if the BIOS never jumps to the shell.
     pub fn run_until_exe_sideloaded(&mut self, exe: &[u8]) -> Ps1Result<()> {
         let mut bus = new_bus!(self);
-        while self.cpu.pc() != 0x80030000 {
-            let _ = self.cpu.execute_instruction(&mut bus);
+        if let Some(hle_bios) = &mut self.hle_bios {
+            hle_bios.boot_kernel(&mut self.cpu, &mut bus, false);
+        } else if !fastboot::run_bios_until_shell(&mut self.cpu, &mut bus) {
+            return Err(Ps1Error::BiosBootTimeout);
         }
 
         self.sideload_exe(exe)
     }
 
+    fn fast_boot_disc(&mut self) {
+        if self.hle_bios.is_some() || !self.cd_controller.has_disc() {
+            return;
+        }
+
+        // Sideloading the EXE would bypass the region lock; boot normally so that the BIOS runs
+        // into the drive's license check instead
+        if !self.cd_controller.disc_region_accepted() {
+            log::info!("Disc region rejected by console region; skipping fast boot");
+            return;
+        }
+
+        let mut bus = new_bus!(self);
+        let Some(exe) = fastboot::prepare_disc_boot(&mut self.cpu, &mut bus) else { return };
+
+        if let Err(err) = self.sideload_exe(&exe) {
+            log::error!("Failed to load boot EXE during fast boot: {err}");
+        }
+    }
+
     /// # Errors
     ///
     /// Will return an error if the EXE does not appear to be a PS1 executable based on the header.
@@ -420,9 +617,16 @@ impl Ps1Emulator {
         Ok(())
     }
 
+    /// Run the emulator until the next scheduler event, outputting frames as wgpu textures.
+    ///
     /// # Errors
     ///
     /// Will propagate any error encountered while rendering a frame.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the emulator was created without a wgpu device.
+    #[cfg(feature = "wgpu")]
     #[inline]
     #[allow(clippy::type_complexity)]
     pub fn tick<R: Renderer, A: AudioOutput, S: SaveWriter>(
@@ -432,6 +636,50 @@ impl Ps1Emulator {
         audio_output: &mut A,
         save_writer: &mut S,
     ) -> Result<TickEffect, TickError<R::Err, A::Err, S::Err>> {
+        self.tick_inner(
+            inputs,
+            &mut |gpu: &mut Gpu| {
+                let pixel_aspect_ratio = gpu.pixel_aspect_ratio();
+                let (frame, command_buffers) = gpu.generate_frame_texture();
+                renderer.render_frame(command_buffers, frame, pixel_aspect_ratio)
+            },
+            audio_output,
+            save_writer,
+        )
+    }
+
+    /// Run the emulator until the next scheduler event, outputting frames as CPU-side pixel
+    /// buffers.
+    ///
+    /// # Errors
+    ///
+    /// Will propagate any error encountered while rendering a frame.
+    #[inline]
+    #[allow(clippy::type_complexity)]
+    pub fn tick_with_cpu_renderer<R: CpuRenderer, A: AudioOutput, S: SaveWriter>(
+        &mut self,
+        inputs: Ps1Inputs,
+        renderer: &mut R,
+        audio_output: &mut A,
+        save_writer: &mut S,
+    ) -> Result<TickEffect, TickError<R::Err, A::Err, S::Err>> {
+        self.tick_inner(
+            inputs,
+            &mut |gpu: &mut Gpu| renderer.render_frame(gpu.generate_frame_buffer()),
+            audio_output,
+            save_writer,
+        )
+    }
+
+    #[inline]
+    #[allow(clippy::type_complexity)]
+    fn tick_inner<RErr, A: AudioOutput, S: SaveWriter>(
+        &mut self,
+        inputs: Ps1Inputs,
+        render_fn: &mut impl FnMut(&mut Gpu) -> Result<(), RErr>,
+        audio_output: &mut A,
+        save_writer: &mut S,
+    ) -> Result<TickEffect, TickError<RErr, A::Err, S::Err>> {
         self.sio0.set_inputs(inputs);
 
         if self.dma_controller.cpu_wait_cycles() != 0 {
@@ -443,16 +691,37 @@ impl Ps1Emulator {
 
         let mut bus = new_bus!(self);
         while !bus.scheduler.is_event_ready() {
-            let cycles = self.cpu.execute_instruction(&mut bus);
+            if bus.debugger.enabled() {
+                if let Some(reason) = bus.debugger.check_before_instruction(self.cpu.pc()) {
+                    return Ok(TickEffect::DebugStop(reason));
+                }
+            }
+
+            let cycles = match &mut self.hle_bios {
+                Some(hle_bios) if hle::is_hle_address(self.cpu.pc()) => {
+                    hle_bios.dispatch(&mut self.cpu, &mut bus)
+                }
+                _ => self.cpu.execute_instruction(&mut bus),
+            };
             bus.scheduler.increment_cpu_cycles(cycles.into());
 
-            if self.config.tty_enabled {
-                check_for_putchar_call(&self.cpu, &mut self.tty_buffer);
+            if bus.tty.enabled() {
+                check_for_putchar_call(&self.cpu, bus.tty);
+            }
+
+            if self.kernel_call_tracer.enabled() {
+                self.kernel_call_tracer.check(&self.cpu, bus.memory);
+            }
+
+            if bus.debugger.enabled() {
+                if let Some(reason) = bus.debugger.check_after_instruction(self.cpu.pc()) {
+                    return Ok(TickEffect::DebugStop(reason));
+                }
             }
         }
 
         let tick_effect = if self.scheduler.is_event_ready() {
-            self.process_scheduler_events(renderer, audio_output, save_writer)?
+            self.process_scheduler_events(render_fn, audio_output, save_writer)?
         } else {
             TickEffect::None
         };
@@ -461,7 +730,7 @@ impl Ps1Emulator {
             // Force a frame render
             // TODO handle this with the scheduler if the GPU stops generating VBlank IRQs due to
             // invalid Y1/Y2
-            self.render_frame(renderer, audio_output, save_writer)?;
+            self.render_frame(render_fn, audio_output, save_writer)?;
             return Ok(TickEffect::FrameRendered);
         }
 
@@ -469,19 +738,15 @@ impl Ps1Emulator {
     }
 
     #[allow(clippy::type_complexity)]
-    fn render_frame<R: Renderer, A: AudioOutput, S: SaveWriter>(
+    fn render_frame<RErr, A: AudioOutput, S: SaveWriter>(
         &mut self,
-        renderer: &mut R,
+        render_fn: &mut impl FnMut(&mut Gpu) -> Result<(), RErr>,
         audio_output: &mut A,
         save_writer: &mut S,
-    ) -> Result<(), TickError<R::Err, A::Err, S::Err>> {
+    ) -> Result<(), TickError<RErr, A::Err, S::Err>> {
         self.last_render_cycles = self.scheduler.cpu_cycle_counter();
 
-        let pixel_aspect_ratio = self.gpu.pixel_aspect_ratio();
-        let (frame, command_buffers) = self.gpu.generate_frame_texture();
-        renderer
-            .render_frame(command_buffers, frame, pixel_aspect_ratio)
-            .map_err(TickError::Render)?;
+        render_fn(&mut self.gpu).map_err(TickError::Render)?;
 
         self.drain_audio_samples(audio_output).map_err(TickError::Audio)?;
 
@@ -503,12 +768,12 @@ impl Ps1Emulator {
 
     #[inline]
     #[allow(clippy::type_complexity)]
-    fn process_scheduler_events<R: Renderer, A: AudioOutput, S: SaveWriter>(
+    fn process_scheduler_events<RErr, A: AudioOutput, S: SaveWriter>(
         &mut self,
-        renderer: &mut R,
+        render_fn: &mut impl FnMut(&mut Gpu) -> Result<(), RErr>,
         audio_output: &mut A,
         save_writer: &mut S,
-    ) -> Result<TickEffect, TickError<R::Err, A::Err, S::Err>> {
+    ) -> Result<TickEffect, TickError<RErr, A::Err, S::Err>> {
         let mut tick_effect = TickEffect::None;
 
         while let Some(event) = self.scheduler.pop_ready_event() {
@@ -525,7 +790,7 @@ impl Ps1Emulator {
                     self.sio0.catch_up(&mut self.scheduler, &mut self.interrupt_registers);
                     self.sio1.catch_up(&mut self.scheduler, &mut self.interrupt_registers);
 
-                    self.render_frame(renderer, audio_output, save_writer)?;
+                    self.render_frame(render_fn, audio_output, save_writer)?;
 
                     tick_effect = TickEffect::FrameRendered;
                 }
@@ -570,6 +835,105 @@ impl Ps1Emulator {
         Ok(tick_effect)
     }
 
+    /// Render the current display contents to a CPU-side buffer, regardless of which renderer is
+    /// used for [`Self::tick`] or [`Self::tick_with_cpu_renderer`]. Useful for screenshots.
+    ///
+    /// With the hardware rasterizer, this reads back the frame from the GPU and the returned frame
+    /// is at the configured resolution scale.
+    #[must_use]
+    pub fn capture_frame(&mut self) -> CpuFrame<'_> {
+        self.gpu.generate_frame_buffer()
+    }
+
+    /// Add an execution breakpoint. [`Self::tick`] will return [`TickEffect::DebugStop`] before
+    /// the CPU executes the instruction at `pc`.
+    ///
+    /// `pc` is compared against the virtual address, so a breakpoint on a KSEG0 address will not
+    /// trigger if the same code is executed through KSEG1.
+    pub fn add_breakpoint(&mut self, pc: u32) -> BreakpointId {
+        self.debugger.add_breakpoint(pc)
+    }
+
+    /// Returns `false` if no breakpoint exists with the given ID.
+    pub fn remove_breakpoint(&mut self, id: BreakpointId) -> bool {
+        self.debugger.remove_breakpoint(id)
+    }
+
+    /// Add a watchpoint on CPU loads and/or stores to the given address range, which can cover
+    /// main RAM, scratchpad, or I/O registers. Addresses are masked to physical addresses before
+    /// comparing. DMA transfers do not trigger watchpoints.
+    pub fn add_watchpoint(&mut self, range: RangeInclusive<u32>, kind: WatchKind) -> WatchpointId {
+        self.debugger.add_watchpoint(range, kind)
+    }
+
+    /// Returns `false` if no watchpoint exists with the given ID.
+    pub fn remove_watchpoint(&mut self, id: WatchpointId) -> bool {
+        self.debugger.remove_watchpoint(id)
+    }
+
+    /// Remove all breakpoints and watchpoints, and cancel any pending step.
+    pub fn clear_breakpoints(&mut self) {
+        self.debugger.clear();
+    }
+
+    /// Make the next call to [`Self::tick`] execute a single instruction and then return
+    /// [`TickEffect::DebugStop`].
+    pub fn request_step(&mut self) {
+        self.debugger.request_step();
+    }
+
+    /// Like [`Self::request_step`], except that if the current instruction is a call
+    /// (JAL/JALR/BLTZAL/BGEZAL), execution continues until the call returns to the instruction
+    /// following the delay slot.
+    ///
+    /// Stepping over a call may require multiple calls to `tick()`; each returns normally until
+    /// the step finishes.
+    pub fn request_step_over(&mut self) {
+        let pc = self.cpu.pc();
+        let opcode = self.memory.peek_u32(pc).unwrap_or(0);
+        self.debugger.request_step_over(pc, opcode);
+    }
+
+    /// Read a byte from memory without side effects. Returns `None` if the address does not map to
+    /// main RAM, scratchpad, or BIOS ROM; I/O registers cannot be read this way.
+    #[must_use]
+    pub fn peek_memory(&self, address: u32) -> Option<u8> {
+        self.memory.peek_u8(address)
+    }
+
+    /// Write a byte to main RAM or scratchpad, bypassing the CPU. The I-cache line containing the
+    /// address is invalidated so that modified code is not masked by stale cached opcodes.
+    ///
+    /// Returns `false` if the address does not map to main RAM or scratchpad.
+    pub fn poke_memory(&mut self, address: u32, value: u8) -> bool {
+        if !self.memory.poke_u8(address, value) {
+            return false;
+        }
+
+        self.cpu.invalidate_i_cache_line(address);
+        true
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if the register index is not in the range 0-31.
+    #[must_use]
+    pub fn read_cpu_register(&self, register: CpuRegister) -> u32 {
+        assert!(register.is_valid(), "Invalid CPU register: {register:?}");
+        self.cpu.read_debug_register(register)
+    }
+
+    /// Writes behave the same as the corresponding CPU instruction (e.g. MTC0 or CTC2), except
+    /// that writes to the read-only COP0 registers `BadVaddr` and `EPC` are allowed.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the register index is not in the range 0-31.
+    pub fn write_cpu_register(&mut self, register: CpuRegister, value: u32) {
+        assert!(register.is_valid(), "Invalid CPU register: {register:?}");
+        self.cpu.write_debug_register(register, value);
+    }
+
     pub fn change_disc(&mut self, disc: Option<CdRom>) {
         self.cd_controller.change_disc(disc);
     }
@@ -579,25 +943,50 @@ impl Ps1Emulator {
         self.dma_controller.update_pgxp_config(config.pgxp);
         self.gpu.update_config(config.display, config.pgxp);
         self.spu.update_adpcm_interpolation(config.adpcm_interpolation);
-        self.config = config;
+        self.tty.set_enabled(config.tty_enabled);
+        self.kernel_call_tracer.set_enabled(config.kernel_call_trace_enabled);
+        self.cd_controller.set_console_region(config.console_region);
+        self.cd_controller.set_edc_mismatch_policy(config.edc_mismatch_policy);
+
+        // Switching BIOS mode requires recreating the emulator
+        self.config = Ps1EmulatorConfig { bios_mode: self.config.bios_mode, ..config };
     }
 
     pub fn update_memory_cards(&mut self, enabled: MemoryCardsEnabled, loaded: LoadedMemoryCards) {
         self.sio0.update_memory_cards(enabled, loaded);
     }
 
+    /// Take all complete lines of TTY output captured since the last call. Output is only
+    /// captured if `tty_enabled` is set in the config.
+    ///
+    /// The emulator buffers a limited number of lines, so this should be called regularly (e.g.
+    /// once per frame) to avoid losing output.
+    pub fn drain_tty_output(&mut self) -> impl Iterator<Item = TtyLine> + '_ {
+        self.tty.drain_lines()
+    }
+
+    /// Take all kernel call and return events traced since the last call. Calls are only traced if
+    /// `kernel_call_trace_enabled` is set in the config.
+    ///
+    /// As with TTY output, the emulator buffers a limited number of events.
+    pub fn drain_kernel_calls(&mut self) -> impl Iterator<Item = KernelCallEvent> + '_ {
+        self.kernel_call_tracer.drain_events()
+    }
+
     #[must_use]
     pub fn take_unserialized_fields(&mut self) -> UnserializedFields {
-        let (wgpu_device, wgpu_queue) = self.gpu.get_wgpu_resources();
         let (memory_cards_enabled, memory_cards) = self.sio0.clone_unserialized_fields();
 
         UnserializedFields {
             disc: self.cd_controller.take_disc(),
             memory_cards,
-            wgpu_device,
-            wgpu_queue,
+            graphics_backend: self.gpu.graphics_backend(),
             config: self.config,
             memory_cards_enabled,
+            debugger: mem::take(&mut self.debugger),
+            tty: mem::take(&mut self.tty),
+            kernel_call_tracer: mem::take(&mut self.kernel_call_tracer),
+            fast_boot: self.fast_boot,
         }
     }
 
@@ -611,13 +1000,17 @@ impl Ps1Emulator {
             cpu: state.cpu,
             gpu: Gpu::from_state(
                 state.gpu,
-                unserialized.wgpu_device,
-                unserialized.wgpu_queue,
+                unserialized.graphics_backend,
                 unserialized.config.display,
             ),
             spu: state.spu,
             audio_buffer: state.audio_buffer,
-            cd_controller: CdController::from_state(state.cd_controller, unserialized.disc),
+            cd_controller: CdController::from_state(
+                state.cd_controller,
+                unserialized.disc,
+                unserialized.config.console_region,
+                unserialized.config.edc_mismatch_policy,
+            ),
             mdec: state.mdec,
             memory: state.memory,
             memory_control: state.memory_control,
@@ -628,16 +1021,35 @@ impl Ps1Emulator {
             timers: state.timers,
             scheduler: state.scheduler,
             last_render_cycles: state.last_render_cycles,
+            hle_bios: state.hle_bios,
             config: unserialized.config,
-            tty_buffer: state.tty_buffer,
+            debugger: unserialized.debugger,
+            tty: Tty::from_state(state.tty, unserialized.tty),
+            kernel_call_tracer: unserialized.kernel_call_tracer,
+            fast_boot: unserialized.fast_boot,
         };
 
+        // The BIOS mode is part of the save state rather than the config
+        emulator.config.bios_mode =
+            if emulator.hle_bios.is_some() { BiosMode::Hle } else { BiosMode::Rom };
         emulator.update_config(unserialized.config);
 
         emulator
     }
 }
 
+#[cfg(test)]
+impl Ps1Emulator {
+    /// Give a unit test direct access to the CPU and bus, e.g. to call HLE kernel functions
+    pub(crate) fn with_cpu_and_bus<R>(
+        &mut self,
+        f: impl FnOnce(&mut R3000, &mut Bus<'_>) -> R,
+    ) -> R {
+        let mut bus = new_bus!(self);
+        f(&mut self.cpu, &mut bus)
+    }
+}
+
 fn save_memory_card<S: SaveWriter>(
     slot: MemoryCardSlot,
     card: Option<&mut MemoryCard>,
@@ -653,28 +1065,23 @@ fn save_memory_card<S: SaveWriter>(
     save_writer.save_memory_card(slot, card.data())
 }
 
-fn check_for_putchar_call(cpu: &R3000, tty_buffer: &mut String) {
-    // BIOS function calls work by jumping to $A0 (A functions), $B0 (B functions), or
-    // $C0 (C functions) with the function number specified in R9.
-    //
+fn check_for_putchar_call(cpu: &R3000, tty: &mut Tty) {
     // A($3C) and B($3D) are both the putchar() function, which prints the ASCII character
     // in R4 to the TTY.
-    let pc = cpu.pc() & 0x1FFFFFFF;
-    let r9 = cpu.get_gpr(9);
-    if (pc == 0xA0 && r9 == 0x3C) || (pc == 0xB0 && r9 == 0x3D) {
-        let r4 = cpu.get_gpr(4);
-        let c = r4 as u8 as char;
-        if c == '\n' {
-            println!("TTY: {tty_buffer}");
-            tty_buffer.clear();
-        } else {
-            tty_buffer.push(c);
-        }
+    if matches!(
+        KernelFunction::at(cpu),
+        Some(
+            KernelFunction { table: KernelTable::A, number: 0x3C }
+                | KernelFunction { table: KernelTable::B, number: 0x3D }
+        )
+    ) {
+        tty.putchar(TtySource::Bios, cpu.get_gpr(4) as u8);
     }
 }
 
 struct NullOutput;
 
+#[cfg(feature = "wgpu")]
 impl Renderer for NullOutput {
     type Err = String;
 
@@ -688,6 +1095,14 @@ impl Renderer for NullOutput {
     }
 }
 
+impl CpuRenderer for NullOutput {
+    type Err = String;
+
+    fn render_frame(&mut self, _frame: CpuFrame<'_>) -> Result<(), Self::Err> {
+        Ok(())
+    }
+}
+
 impl AudioOutput for NullOutput {
     type Err = String;
 
@@ -707,3 +1122,30 @@ impl SaveWriter for NullOutput {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn console_region_accepts() {
+        let disc_regions =
+            [None, Some(DiscRegion::NtscU), Some(DiscRegion::NtscJ), Some(DiscRegion::Pal)];
+
+        assert!(disc_regions.iter().all(|&region| ConsoleRegion::RegionFree.accepts(region)));
+
+        for (console_region, accepted) in [
+            (ConsoleRegion::NtscU, DiscRegion::NtscU),
+            (ConsoleRegion::NtscJ, DiscRegion::NtscJ),
+            (ConsoleRegion::Pal, DiscRegion::Pal),
+        ] {
+            for disc_region in disc_regions {
+                assert_eq!(
+                    console_region.accepts(disc_region),
+                    disc_region == Some(accepted),
+                    "{console_region:?} console, {disc_region:?} disc"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,478 @@
+//! BIOS kernel call tracing
+//!
+//! Kernel functions are called by jumping to $A0, $B0, or $C0 with the function number in R9 and
+//! the return address in R31. The tracer watches for the CPU arriving at one of those addresses,
+//! decodes the call's arguments using a table of known function signatures, and then watches for
+//! the CPU arriving at the return address to capture the return value in R2.
+//!
+//! This works the same way with a real BIOS and with the HLE BIOS, since both go through the
+//! dispatchers in low RAM.
+
+use crate::cpu::R3000;
+use crate::memory::Memory;
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+
+// Events are dropped oldest-first if the frontend does not drain them
+const MAX_BUFFERED_EVENTS: usize = 10000;
+
+// Calls that never return (e.g. ReturnFromException or longjmp) would otherwise leave entries on
+// the pending stack forever
+const MAX_PENDING_CALLS: usize = 32;
+
+// Guard against garbage pointers to unterminated strings
+const MAX_STRING_LEN: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KernelTable {
+    A,
+    B,
+    C,
+}
+
+impl KernelTable {
+    fn from_pc(pc: u32) -> Option<Self> {
+        match pc & 0x1FFFFFFF {
+            0xA0 => Some(Self::A),
+            0xB0 => Some(Self::B),
+            0xC0 => Some(Self::C),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KernelFunction {
+    pub table: KernelTable,
+    pub number: u8,
+}
+
+impl KernelFunction {
+    /// Returns the kernel function that the CPU is about to call, if the CPU is at one of the
+    /// $A0/$B0/$C0 dispatchers
+    pub(crate) fn at(cpu: &R3000) -> Option<Self> {
+        let table = KernelTable::from_pc(cpu.pc())?;
+        let number = cpu.get_gpr(9);
+        (number <= 0xFF).then_some(Self { table, number: number as u8 })
+    }
+
+    /// Symbolic name of this function, or `None` if the function is unused or unknown
+    #[must_use]
+    pub fn name(self) -> Option<&'static str> {
+        signature(self).map(|(name, _)| name)
+    }
+}
+
+impl Display for KernelFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{name}"),
+            None => write!(f, "{:?}({:02X})", self.table, self.number),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KernelCallEvent {
+    Call {
+        function: KernelFunction,
+        /// Raw values of argument registers R4-R7
+        args: [u32; 4],
+        /// Human-readable call with decoded arguments, e.g. `open("cdrom:\\SLUS_000.01;1", 1)`
+        decoded: String,
+        return_address: u32,
+    },
+    Return {
+        function: KernelFunction,
+        /// Value of R2 when the function returned
+        value: u32,
+        return_address: u32,
+    },
+}
+
+impl Display for KernelCallEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Call { decoded, return_address, .. } => {
+                write!(f, "{decoded} from {return_address:08X}")
+            }
+            Self::Return { function, value, .. } => {
+                write!(f, "{function} returned {}", format_value(*value))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingCall {
+    function: KernelFunction,
+    return_address: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct KernelCallTracer {
+    enabled: bool,
+    pending: Vec<PendingCall>,
+    events: VecDeque<KernelCallEvent>,
+}
+
+impl KernelCallTracer {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, ..Self::default() }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.pending.clear();
+        }
+    }
+
+    /// Check whether the CPU just arrived at a kernel call dispatcher or at the return address of
+    /// a pending call. Should be called after every instruction.
+    pub fn check(&mut self, cpu: &R3000, memory: &Memory) {
+        let pc = cpu.pc();
+
+        if let Some(i) = self.pending.iter().rposition(|call| call.return_address == pc) {
+            // Anything above the matching entry returned some other way (e.g. longjmp)
+            let call = self.pending[i];
+            self.pending.truncate(i);
+            self.push_event(KernelCallEvent::Return {
+                function: call.function,
+                value: cpu.get_gpr(2),
+                return_address: call.return_address,
+            });
+        }
+
+        let Some(function) = KernelFunction::at(cpu) else { return };
+        let return_address = cpu.get_gpr(31);
+
+        // The HLE BIOS re-enters the dispatcher while WaitEvent is blocked; don't report the same
+        // call again
+        if self
+            .pending
+            .last()
+            .is_some_and(|call| call.function == function && call.return_address == return_address)
+        {
+            return;
+        }
+
+        if self.pending.len() == MAX_PENDING_CALLS {
+            self.pending.remove(0);
+        }
+        self.pending.push(PendingCall { function, return_address });
+
+        let args = [cpu.get_gpr(4), cpu.get_gpr(5), cpu.get_gpr(6), cpu.get_gpr(7)];
+        let decoded = decode_call(function, args, memory);
+        self.push_event(KernelCallEvent::Call { function, args, decoded, return_address });
+    }
+
+    fn push_event(&mut self, event: KernelCallEvent) {
+        log::debug!("Kernel call: {event}");
+
+        if self.events.len() == MAX_BUFFERED_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    pub fn drain_events(&mut self) -> impl Iterator<Item = KernelCallEvent> + '_ {
+        self.events.drain(..)
+    }
+}
+
+fn format_value(value: u32) -> String {
+    let signed = value as i32;
+    if (-0x10000..0x10000).contains(&signed) {
+        signed.to_string()
+    } else {
+        format!("0x{value:08X}")
+    }
+}
+
+fn decode_call(function: KernelFunction, args: [u32; 4], memory: &Memory) -> String {
+    let Some((name, arg_types)) = signature(function) else {
+        return format!("{function}({})", args.map(|arg| format!("0x{arg:X}")).join(", "));
+    };
+
+    let decoded_args: Vec<_> = arg_types
+        .chars()
+        .zip(args)
+        .map(|(arg_type, value)| match arg_type {
+            's' => read_string(memory, value),
+            'c' => match u8::try_from(value) {
+                Ok(c) if c.is_ascii_graphic() || c == b' ' => format!("'{}'", c as char),
+                _ => value.to_string(),
+            },
+            'i' => (value as i32).to_string(),
+            // Single digits are the same in decimal and hex
+            _ if value < 10 => value.to_string(),
+            _ => format!("0x{value:X}"),
+        })
+        .collect();
+
+    format!("{name}({})", decoded_args.join(", "))
+}
+
+fn read_string(memory: &Memory, address: u32) -> String {
+    if address == 0 {
+        return "NULL".into();
+    }
+
+    let mut bytes = Vec::new();
+    for i in 0..=MAX_STRING_LEN as u32 {
+        match memory.peek_u8(address.wrapping_add(i)) {
+            Some(0) => return format!("{:?}", String::from_utf8_lossy(&bytes)),
+            Some(b) if i < MAX_STRING_LEN as u32 => bytes.push(b),
+            Some(_) => return format!("{:?}...", String::from_utf8_lossy(&bytes)),
+            None => break,
+        }
+    }
+
+    format!("0x{address:08X}")
+}
+
+/// Name and argument types for a kernel function.
+///
+/// Argument types: `s` = pointer to a C string, `i` = signed integer, `c` = character,
+/// `x` = hex value or pointer. Functions with more than 4 arguments only have the register
+/// arguments listed.
+#[allow(clippy::match_same_arms)]
+fn signature(function: KernelFunction) -> Option<(&'static str, &'static str)> {
+    let signature = match (function.table, function.number) {
+        (KernelTable::A, 0x00) | (KernelTable::B, 0x32) => ("open", "sx"),
+        (KernelTable::A, 0x01) | (KernelTable::B, 0x33) => ("lseek", "iii"),
+        (KernelTable::A, 0x02) | (KernelTable::B, 0x34) => ("read", "ixi"),
+        (KernelTable::A, 0x03) | (KernelTable::B, 0x35) => ("write", "ixi"),
+        (KernelTable::A, 0x04) | (KernelTable::B, 0x36) => ("close", "i"),
+        (KernelTable::A, 0x05) | (KernelTable::B, 0x37) => ("ioctl", "ixx"),
+        (KernelTable::A, 0x06) | (KernelTable::B, 0x38) => ("exit", "i"),
+        (KernelTable::A, 0x07) | (KernelTable::B, 0x39) => ("isatty", "i"),
+        (KernelTable::A, 0x08) | (KernelTable::B, 0x3A) => ("getc", "i"),
+        (KernelTable::A, 0x09) | (KernelTable::B, 0x3B) => ("putc", "ci"),
+        (KernelTable::A, 0x0A) => ("todigit", "c"),
+        (KernelTable::A, 0x0B) => ("atof", "s"),
+        (KernelTable::A, 0x0C) => ("strtoul", "sxi"),
+        (KernelTable::A, 0x0D) => ("strtol", "sxi"),
+        (KernelTable::A, 0x0E) => ("abs", "i"),
+        (KernelTable::A, 0x0F) => ("labs", "i"),
+        (KernelTable::A, 0x10) => ("atoi", "s"),
+        (KernelTable::A, 0x11) => ("atol", "s"),
+        (KernelTable::A, 0x12) => ("atob", "sx"),
+        (KernelTable::A, 0x13) => ("setjmp", "x"),
+        (KernelTable::A, 0x14) => ("longjmp", "xx"),
+        (KernelTable::A, 0x15) => ("strcat", "xs"),
+        (KernelTable::A, 0x16) => ("strncat", "xsi"),
+        (KernelTable::A, 0x17) => ("strcmp", "ss"),
+        (KernelTable::A, 0x18) => ("strncmp", "ssi"),
+        (KernelTable::A, 0x19) => ("strcpy", "xs"),
+        (KernelTable::A, 0x1A) => ("strncpy", "xsi"),
+        (KernelTable::A, 0x1B) => ("strlen", "s"),
+        (KernelTable::A, 0x1C) => ("index", "sc"),
+        (KernelTable::A, 0x1D) => ("rindex", "sc"),
+        (KernelTable::A, 0x1E) => ("strchr", "sc"),
+        (KernelTable::A, 0x1F) => ("strrchr", "sc"),
+        (KernelTable::A, 0x20) => ("strpbrk", "ss"),
+        (KernelTable::A, 0x21) => ("strspn", "ss"),
+        (KernelTable::A, 0x22) => ("strcspn", "ss"),
+        (KernelTable::A, 0x23) => ("strtok", "ss"),
+        (KernelTable::A, 0x24) => ("strstr", "ss"),
+        (KernelTable::A, 0x25) => ("toupper", "c"),
+        (KernelTable::A, 0x26) => ("tolower", "c"),
+        (KernelTable::A, 0x27) => ("bcopy", "xxi"),
+        (KernelTable::A, 0x28) => ("bzero", "xi"),
+        (KernelTable::A, 0x29) => ("bcmp", "xxi"),
+        (KernelTable::A, 0x2A) => ("memcpy", "xxi"),
+        (KernelTable::A, 0x2B) => ("memset", "xxi"),
+        (KernelTable::A, 0x2C) => ("memmove", "xxi"),
+        (KernelTable::A, 0x2D) => ("memcmp", "xxi"),
+        (KernelTable::A, 0x2E) => ("memchr", "xxi"),
+        (KernelTable::A, 0x2F) => ("rand", ""),
+        (KernelTable::A, 0x30) => ("srand", "x"),
+        (KernelTable::A, 0x31) => ("qsort", "xiix"),
+        (KernelTable::A, 0x32) => ("strtod", "sx"),
+        (KernelTable::A, 0x33) => ("malloc", "i"),
+        (KernelTable::A, 0x34) => ("free", "x"),
+        (KernelTable::A, 0x35) => ("lsearch", "xxii"),
+        (KernelTable::A, 0x36) => ("bsearch", "xxii"),
+        (KernelTable::A, 0x37) => ("calloc", "ii"),
+        (KernelTable::A, 0x38) => ("realloc", "xi"),
+        (KernelTable::A, 0x39) => ("InitHeap", "xi"),
+        (KernelTable::A, 0x3A) => ("_exit", "i"),
+        (KernelTable::A, 0x3B) | (KernelTable::B, 0x3C) => ("getchar", ""),
+        (KernelTable::A, 0x3C) | (KernelTable::B, 0x3D) => ("putchar", "c"),
+        (KernelTable::A, 0x3D) | (KernelTable::B, 0x3E) => ("gets", "x"),
+        (KernelTable::A, 0x3E) | (KernelTable::B, 0x3F) => ("puts", "s"),
+        (KernelTable::A, 0x3F) => ("printf", "sxxx"),
+        (KernelTable::A, 0x40) => ("SystemErrorUnresolvedException", ""),
+        (KernelTable::A, 0x41) => ("LoadTest", "sx"),
+        (KernelTable::A, 0x42) => ("Load", "sx"),
+        (KernelTable::A, 0x43) => ("Exec", "xxx"),
+        (KernelTable::A, 0x44) => ("FlushCache", ""),
+        (KernelTable::A, 0x45) => ("init_a0_b0_c0_vectors", ""),
+        (KernelTable::A, 0x46) => ("GPU_dw", "iiii"),
+        (KernelTable::A, 0x47) => ("gpu_send_dma", "iiii"),
+        (KernelTable::A, 0x48) => ("SendGP1Command", "x"),
+        (KernelTable::A, 0x49) => ("GPU_cw", "x"),
+        (KernelTable::A, 0x4A) => ("GPU_cwp", "xi"),
+        (KernelTable::A, 0x4B) => ("send_gpu_linked_list", "x"),
+        (KernelTable::A, 0x4C) => ("gpu_abort_dma", ""),
+        (KernelTable::A, 0x4D) => ("GetGPUStatus", ""),
+        (KernelTable::A, 0x4E) => ("gpu_sync", ""),
+        (KernelTable::A, 0x4F | 0x50 | 0x53 | 0x9A | 0x9B)
+        | (KernelTable::B, 0x1A..=0x1F | 0x21..=0x31 | 0x52 | 0x5A)
+        | (KernelTable::C, 0x0B | 0x14) => ("SystemError", ""),
+        (KernelTable::A, 0x51) => ("LoadExec", "sxx"),
+        (KernelTable::A, 0x52) => ("GetSysSp", ""),
+        (KernelTable::A, 0x54 | 0x71) => ("CdInit", ""),
+        (KernelTable::A, 0x55 | 0x70) => ("_bu_init", ""),
+        (KernelTable::A, 0x56 | 0x72) => ("CdRemove", ""),
+        (KernelTable::A, 0x5B) => ("dev_tty_init", ""),
+        (KernelTable::A, 0x5C) => ("dev_tty_open", "xsi"),
+        (KernelTable::A, 0x5D) => ("dev_tty_in_out", "xi"),
+        (KernelTable::A, 0x5E) => ("dev_tty_ioctl", "xix"),
+        (KernelTable::A, 0x5F) => ("dev_cd_open", "xsi"),
+        (KernelTable::A, 0x60) => ("dev_cd_read", "xxi"),
+        (KernelTable::A, 0x61) => ("dev_cd_close", "x"),
+        (KernelTable::A, 0x62) => ("dev_cd_firstfile", "xsx"),
+        (KernelTable::A, 0x63) => ("dev_cd_nextfile", "xx"),
+        (KernelTable::A, 0x64) => ("dev_cd_chdir", "xs"),
+        (KernelTable::A, 0x65) => ("dev_card_open", "xsi"),
+        (KernelTable::A, 0x66) => ("dev_card_read", "xxi"),
+        (KernelTable::A, 0x67) => ("dev_card_write", "xxi"),
+        (KernelTable::A, 0x68) => ("dev_card_close", "x"),
+        (KernelTable::A, 0x69) => ("dev_card_firstfile", "xsx"),
+        (KernelTable::A, 0x6A) => ("dev_card_nextfile", "xx"),
+        (KernelTable::A, 0x6B) => ("dev_card_erase", "xs"),
+        (KernelTable::A, 0x6C) => ("dev_card_undelete", "xs"),
+        (KernelTable::A, 0x6D) => ("dev_card_format", "x"),
+        (KernelTable::A, 0x6E) => ("dev_card_rename", "xsxs"),
+        (KernelTable::A, 0x6F) => ("card_clear_error", "x"),
+        (KernelTable::A, 0x78) => ("CdAsyncSeekL", "x"),
+        (KernelTable::A, 0x7C) => ("CdAsyncGetStatus", "x"),
+        (KernelTable::A, 0x7E) => ("CdAsyncReadSector", "ixx"),
+        (KernelTable::A, 0x81) => ("CdAsyncSetMode", "x"),
+        (KernelTable::A, 0x90) => ("CdromIoIrqFunc1", ""),
+        (KernelTable::A, 0x91) => ("CdromDmaIrqFunc1", ""),
+        (KernelTable::A, 0x92) => ("CdromIoIrqFunc2", ""),
+        (KernelTable::A, 0x93) => ("CdromDmaIrqFunc2", ""),
+        (KernelTable::A, 0x94) => ("CdromGetInt5errCode", "xx"),
+        (KernelTable::A, 0x95) => ("CdInitSubFunc", ""),
+        (KernelTable::A, 0x96) => ("AddCDROMDevice", ""),
+        (KernelTable::A, 0x97) => ("AddMemCardDevice", ""),
+        (KernelTable::A, 0x98) => ("AddDuartTtyDevice", ""),
+        (KernelTable::A, 0x99) => ("AddDummyTtyDevice", ""),
+        (KernelTable::A, 0x9C) => ("SetConf", "iix"),
+        (KernelTable::A, 0x9D) => ("GetConf", "xxx"),
+        (KernelTable::A, 0x9E) => ("SetCdromIrqAutoAbort", "ii"),
+        (KernelTable::A, 0x9F) => ("SetMemSize", "i"),
+        (KernelTable::A, 0xA0) => ("WarmBoot", ""),
+        (KernelTable::A, 0xA1) => ("SystemErrorBootOrDiskFailure", "cx"),
+        (KernelTable::A, 0xA2) => ("EnqueueCdIntr", ""),
+        (KernelTable::A, 0xA3) => ("DequeueCdIntr", ""),
+        (KernelTable::A, 0xA4) => ("CdGetLbn", "s"),
+        (KernelTable::A, 0xA5) => ("CdReadSector", "iix"),
+        (KernelTable::A, 0xA6) => ("CdGetStatus", ""),
+        (KernelTable::A, 0xA7) => ("bufs_cb_0", ""),
+        (KernelTable::A, 0xA8) => ("bufs_cb_1", ""),
+        (KernelTable::A, 0xA9) => ("bufs_cb_2", ""),
+        (KernelTable::A, 0xAA) => ("bufs_cb_3", ""),
+        (KernelTable::A, 0xAB) => ("_card_info", "x"),
+        (KernelTable::A, 0xAC) => ("_card_load", "x"),
+        (KernelTable::A, 0xAD) => ("_card_auto", "i"),
+        (KernelTable::A, 0xAE) => ("bufs_cb_4", ""),
+        (KernelTable::A, 0xAF) => ("card_write_test", "x"),
+        (KernelTable::A, 0xB2) => ("ioabort_raw", "x"),
+        (KernelTable::A, 0xB4) => ("GetSystemInfo", "i"),
+        (KernelTable::B, 0x00) => ("alloc_kernel_memory", "i"),
+        (KernelTable::B, 0x01) => ("free_kernel_memory", "x"),
+        (KernelTable::B, 0x02) => ("SetRCnt", "ixx"),
+        (KernelTable::B, 0x03) => ("GetRCnt", "i"),
+        (KernelTable::B, 0x04) => ("StartRCnt", "i"),
+        (KernelTable::B, 0x05) => ("StopRCnt", "i"),
+        (KernelTable::B, 0x06) => ("ResetRCnt", "i"),
+        (KernelTable::B, 0x07) => ("DeliverEvent", "xx"),
+        (KernelTable::B, 0x08) => ("OpenEvent", "xxxx"),
+        (KernelTable::B, 0x09) => ("CloseEvent", "x"),
+        (KernelTable::B, 0x0A) => ("WaitEvent", "x"),
+        (KernelTable::B, 0x0B) => ("TestEvent", "x"),
+        (KernelTable::B, 0x0C) => ("EnableEvent", "x"),
+        (KernelTable::B, 0x0D) => ("DisableEvent", "x"),
+        (KernelTable::B, 0x0E) => ("OpenThread", "xxx"),
+        (KernelTable::B, 0x0F) => ("CloseThread", "x"),
+        (KernelTable::B, 0x10) => ("ChangeThread", "x"),
+        (KernelTable::B, 0x11) => ("jump_to_00000000h", ""),
+        (KernelTable::B, 0x12) => ("InitPad", "xixi"),
+        (KernelTable::B, 0x13) => ("StartPad", ""),
+        (KernelTable::B, 0x14) => ("StopPad", ""),
+        (KernelTable::B, 0x15) => ("OutdatedPadInitAndStart", "xxxx"),
+        (KernelTable::B, 0x16) => ("OutdatedPadGetButtons", ""),
+        (KernelTable::B, 0x17) => ("ReturnFromException", ""),
+        (KernelTable::B, 0x18) => ("SetDefaultExitFromException", ""),
+        (KernelTable::B, 0x19) => ("SetCustomExitFromException", "x"),
+        (KernelTable::B, 0x20) => ("UnDeliverEvent", "xx"),
+        (KernelTable::B, 0x40) => ("cd", "s"),
+        (KernelTable::B, 0x41) => ("format", "s"),
+        (KernelTable::B, 0x42) => ("firstfile", "sx"),
+        (KernelTable::B, 0x43) => ("nextfile", "x"),
+        (KernelTable::B, 0x44) => ("rename", "ss"),
+        (KernelTable::B, 0x45) => ("erase", "s"),
+        (KernelTable::B, 0x46) => ("undelete", "s"),
+        (KernelTable::B, 0x47) => ("AddDrv", "x"),
+        (KernelTable::B, 0x48) => ("DelDrv", "s"),
+        (KernelTable::B, 0x49) => ("PrintInstalledDevices", ""),
+        (KernelTable::B, 0x4A) => ("InitCard", "i"),
+        (KernelTable::B, 0x4B) => ("StartCard", ""),
+        (KernelTable::B, 0x4C) => ("StopCard", ""),
+        (KernelTable::B, 0x4D) => ("_card_info_subfunc", "x"),
+        (KernelTable::B, 0x4E) => ("write_card_sector", "xix"),
+        (KernelTable::B, 0x4F) => ("read_card_sector", "xix"),
+        (KernelTable::B, 0x50) => ("allow_new_card", ""),
+        (KernelTable::B, 0x51) => ("Krom2RawAdd", "x"),
+        (KernelTable::B, 0x53) => ("Krom2Offset", "x"),
+        (KernelTable::B, 0x54) => ("GetLastError", ""),
+        (KernelTable::B, 0x55) => ("GetLastFileError", "i"),
+        (KernelTable::B, 0x56) => ("GetC0Table", ""),
+        (KernelTable::B, 0x57) => ("GetB0Table", ""),
+        (KernelTable::B, 0x58) => ("get_bu_callback_port", ""),
+        (KernelTable::B, 0x59) => ("testdevice", "s"),
+        (KernelTable::B, 0x5B) => ("ChangeClearPad", "i"),
+        (KernelTable::B, 0x5C) => ("get_card_status", "i"),
+        (KernelTable::B, 0x5D) => ("wait_card_status", "i"),
+        (KernelTable::C, 0x00) => ("EnqueueTimerAndVblankIrqs", "i"),
+        (KernelTable::C, 0x01) => ("EnqueueSyscallHandler", "i"),
+        (KernelTable::C, 0x02) => ("SysEnqIntRP", "ix"),
+        (KernelTable::C, 0x03) => ("SysDeqIntRP", "ix"),
+        (KernelTable::C, 0x04) => ("get_free_EvCB_slot", ""),
+        (KernelTable::C, 0x05) => ("get_free_TCB_slot", ""),
+        (KernelTable::C, 0x06) => ("ExceptionHandler", ""),
+        (KernelTable::C, 0x07) => ("InstallExceptionHandlers", ""),
+        (KernelTable::C, 0x08) => ("SysInitMemory", "xi"),
+        (KernelTable::C, 0x09) => ("SysInitKernelVariables", ""),
+        (KernelTable::C, 0x0A) => ("ChangeClearRCnt", "ii"),
+        (KernelTable::C, 0x0C) => ("InitDefInt", "i"),
+        (KernelTable::C, 0x0D) => ("SetIrqAutoAck", "ii"),
+        (KernelTable::C, 0x0E) => ("dev_sio_init", ""),
+        (KernelTable::C, 0x0F) => ("dev_sio_open", "xsi"),
+        (KernelTable::C, 0x10) => ("dev_sio_in_out", "xi"),
+        (KernelTable::C, 0x11) => ("dev_sio_ioctl", "xix"),
+        (KernelTable::C, 0x12) => ("InstallDevices", "i"),
+        (KernelTable::C, 0x13) => ("FlushStdInOutPut", ""),
+        (KernelTable::C, 0x15) => ("tty_cdevinput", "xc"),
+        (KernelTable::C, 0x16) => ("tty_cdevscan", ""),
+        (KernelTable::C, 0x17) => ("tty_circgetc", "x"),
+        (KernelTable::C, 0x18) => ("tty_circputc", "cx"),
+        (KernelTable::C, 0x19) => ("ioabort", "ss"),
+        (KernelTable::C, 0x1A) => ("set_card_find_mode", "i"),
+        (KernelTable::C, 0x1B) => ("KernelRedirect", "i"),
+        (KernelTable::C, 0x1C) => ("AdjustA0Table", ""),
+        (KernelTable::C, 0x1D) => ("get_card_find_mode", ""),
+        _ => return None,
+    };
+
+    Some(signature)
+}
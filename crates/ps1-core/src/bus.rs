@@ -2,6 +2,7 @@
 
 use crate::cd::CdController;
 use crate::cpu::OpSize;
+use crate::debug::Debugger;
 use crate::dma::DmaController;
 use crate::gpu::Gpu;
 use crate::interrupts::InterruptRegisters;
@@ -12,6 +13,7 @@ use crate::scheduler::Scheduler;
 use crate::sio::{SerialPort0, SerialPort1};
 use crate::spu::Spu;
 use crate::timers::Timers;
+use crate::tty::{self, Tty};
 
 pub struct Bus<'a> {
     pub gpu: &'a mut Gpu,
@@ -26,6 +28,8 @@ pub struct Bus<'a> {
     pub sio1: &'a mut SerialPort1,
     pub timers: &'a mut Timers,
     pub scheduler: &'a mut Scheduler,
+    pub debugger: &'a mut Debugger,
+    pub tty: &'a mut Tty,
 }
 
 macro_rules! memory_map {
@@ -61,6 +65,7 @@ impl<'a> Bus<'a> {
             },
             scratchpad => self.memory.read_scratchpad_u8(address).into(),
             io_registers => self.read_io_register(address, OpSize::Byte),
+            expansion_2 => tty::read_expansion_2(address, OpSize::Byte),
             bios => self.memory.read_bios_u8(address).into(),
             _ => todo!("8-bit read {address:08X}")
         ])
@@ -75,6 +80,7 @@ impl<'a> Bus<'a> {
             },
             scratchpad => self.memory.read_scratchpad_u16(address).into(),
             io_registers => self.read_io_register(address, OpSize::HalfWord),
+            expansion_2 => tty::read_expansion_2(address, OpSize::HalfWord),
             bios => self.memory.read_bios_u16(address).into(),
             _ => todo!("16-bit read {address:08X}")
         ])
@@ -89,6 +95,7 @@ impl<'a> Bus<'a> {
             },
             scratchpad => self.memory.read_scratchpad_u32(address),
             io_registers => self.read_io_register(address, OpSize::Word),
+            expansion_2 => tty::read_expansion_2(address, OpSize::Word),
             bios => self.memory.read_bios_u32(address),
             _ => todo!("32-bit read {address:08X}")
         ])
@@ -108,7 +115,7 @@ impl<'a> Bus<'a> {
             expansion_1 => unimplemented_expansion_write("Expansion Device 1", address, value, OpSize::Byte),
             scratchpad => self.memory.write_scratchpad_u8(address, value as u8),
             io_registers => self.write_io_register(address, value, OpSize::Byte),
-            expansion_2 => unimplemented_expansion_write("Expansion Device 2", address, value, OpSize::Byte),
+            expansion_2 => self.tty.write_expansion_2(address, value, OpSize::Byte),
             _ => todo!("8-bit write {address:08X} {value:08X}")
         ]);
     }
@@ -119,7 +126,7 @@ impl<'a> Bus<'a> {
             expansion_1 => unimplemented_expansion_write("Expansion Device 1", address, value, OpSize::HalfWord),
             scratchpad => self.memory.write_scratchpad_u16(address, value as u16),
             io_registers => self.write_io_register(address, value, OpSize::HalfWord),
-            expansion_2 => unimplemented_expansion_write("Expansion Device 2", address, value, OpSize::HalfWord),
+            expansion_2 => self.tty.write_expansion_2(address, value, OpSize::HalfWord),
             _ => todo!("16-bit write {address:08X} {value:08X}")
         ]);
     }
@@ -130,7 +137,7 @@ impl<'a> Bus<'a> {
             expansion_1 => unimplemented_expansion_write("Expansion Device 1", address, value, OpSize::Word),
             scratchpad => self.memory.write_scratchpad_u32(address, value),
             io_registers => self.write_io_register(address, value, OpSize::Word),
-            expansion_2 => unimplemented_expansion_write("Expansion Device 2", address, value, OpSize::Word),
+            expansion_2 => self.tty.write_expansion_2(address, value, OpSize::Word),
             _ => todo!("32-bit write {address:08X} {value:08X}")
         ]);
     }
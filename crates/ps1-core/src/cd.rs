@@ -13,6 +13,7 @@ mod seek;
 mod status;
 mod xaadpcm;
 
+use crate::api::{ConsoleRegion, EdcMismatchPolicy};
 use crate::cd::audio::PlayState;
 use crate::cd::control::DriveMode;
 use crate::cd::fifo::{DataFifo, ParameterFifo};
@@ -21,9 +22,11 @@ use crate::cd::xaadpcm::XaAdpcmState;
 use crate::interrupts::{InterruptRegisters, InterruptType};
 use crate::num::U8Ext;
 use bincode::{Decode, Encode};
-use cdrom::CdRomResult;
 use cdrom::cdtime::CdTime;
+use cdrom::discinfo::DiscRegion;
 use cdrom::reader::CdRom;
+use cdrom::subchannel::{SUBCHANNEL_Q_LEN, SubchannelQ};
+use cdrom::{CdRomError, CdRomResult};
 #[allow(clippy::wildcard_imports)]
 use macros::*;
 use proc_macros::SaveState;
@@ -198,6 +201,16 @@ impl DriveState {
     }
 }
 
+// Roughly 1 second; the lid stays open long enough during a disc change for games that poll GetStat
+// to see it open
+const LID_OPEN_CYCLES: u32 = 44100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+enum LidState {
+    Closed,
+    Open { cycles_remaining: u32 },
+}
+
 const BYTES_PER_SECTOR: usize = 2352;
 
 type SectorBuffer = [u8; BYTES_PER_SECTOR];
@@ -207,6 +220,17 @@ pub struct CdController {
     index: u8,
     #[save_state(skip)]
     disc: Option<CdRom>,
+    #[save_state(skip)]
+    disc_region: Option<DiscRegion>,
+    // Disc that will be inserted when the lid closes during a disc change
+    #[save_state(skip)]
+    pending_disc: Option<CdRom>,
+    lid_state: LidState,
+    // Come from the emulator config rather than the save state
+    #[save_state(skip)]
+    console_region: ConsoleRegion,
+    #[save_state(skip)]
+    edc_mismatch_policy: EdcMismatchPolicy,
     interrupts: CdInterruptRegisters,
     parameter_fifo: ParameterFifo,
     response_fifo: ParameterFifo,
@@ -216,6 +240,7 @@ pub struct CdController {
     drive_state: DriveState,
     drive_mode: DriveMode,
     seek_location: Option<CdTime>,
+    last_subchannel_q: SubchannelQ,
     scex_read: bool,
     audio_muted: bool,
     shell_opened: bool,
@@ -226,13 +251,22 @@ pub struct CdController {
 }
 
 impl CdController {
-    pub fn new(disc: Option<CdRom>) -> Self {
+    pub fn new(
+        mut disc: Option<CdRom>,
+        console_region: ConsoleRegion,
+        edc_mismatch_policy: EdcMismatchPolicy,
+    ) -> Self {
         // Pretend the SCEx region code was always read if there's a disc in the drive
         let scex_read = disc.is_some();
 
         Self {
             index: 0,
+            disc_region: read_disc_region(disc.as_mut()),
             disc,
+            pending_disc: None,
+            lid_state: LidState::Closed,
+            console_region,
+            edc_mismatch_policy,
             interrupts: CdInterruptRegisters::new(),
             parameter_fifo: ParameterFifo::new(),
             response_fifo: ParameterFifo::new(),
@@ -242,6 +276,7 @@ impl CdController {
             drive_state: DriveState::default(),
             drive_mode: DriveMode::new(),
             seek_location: None,
+            last_subchannel_q: SubchannelQ::new([0; SUBCHANNEL_Q_LEN]),
             scex_read,
             audio_muted: false,
             shell_opened: false,
@@ -252,10 +287,26 @@ impl CdController {
         }
     }
 
-    pub fn from_state(state: CdControllerState, disc: Option<CdRom>) -> Self {
+    pub fn from_state(
+        state: CdControllerState,
+        disc: Option<CdRom>,
+        console_region: ConsoleRegion,
+        edc_mismatch_policy: EdcMismatchPolicy,
+    ) -> Self {
+        // If the state was saved mid disc change, the disc goes back in when the lid closes
+        let (mut disc, pending_disc) = match state.lid_state {
+            LidState::Closed => (disc, None),
+            LidState::Open { .. } => (None, disc),
+        };
+
         Self {
             index: state.index,
+            disc_region: read_disc_region(disc.as_mut()),
             disc,
+            pending_disc,
+            lid_state: state.lid_state,
+            console_region,
+            edc_mismatch_policy,
             interrupts: state.interrupts,
             parameter_fifo: state.parameter_fifo,
             response_fifo: state.response_fifo,
@@ -265,6 +316,7 @@ impl CdController {
             drive_state: state.drive_state,
             drive_mode: state.drive_mode,
             seek_location: state.seek_location,
+            last_subchannel_q: state.last_subchannel_q,
             scex_read: state.scex_read,
             audio_muted: state.audio_muted,
             shell_opened: state.shell_opened,
@@ -279,6 +331,7 @@ impl CdController {
     pub fn clock(&mut self, interrupt_registers: &mut InterruptRegisters) -> CdRomResult<()> {
         self.current_audio_sample = (0, 0);
 
+        self.advance_lid_state();
         self.advance_drive_state()?;
         self.advance_command_state();
 
@@ -294,6 +347,24 @@ impl CdController {
         Ok(())
     }
 
+    fn advance_lid_state(&mut self) {
+        self.lid_state = match self.lid_state {
+            LidState::Closed => LidState::Closed,
+            LidState::Open { cycles_remaining: 1 } => {
+                log::info!("Closing drive lid");
+
+                let mut disc = self.pending_disc.take();
+                self.disc_region = read_disc_region(disc.as_mut());
+                self.disc = disc;
+
+                LidState::Closed
+            }
+            LidState::Open { cycles_remaining } => {
+                LidState::Open { cycles_remaining: cycles_remaining - 1 }
+            }
+        };
+    }
+
     fn advance_drive_state(&mut self) -> CdRomResult<()> {
         self.drive_state = match self.drive_state {
             DriveState::Stopped => DriveState::Stopped,
@@ -680,7 +751,27 @@ impl CdController {
         log::debug!("  Applied CD-to-SPU volume changes: {}", value.bit(5));
     }
 
+    // The drive only latches position Q data with a valid CRC, so sectors with corrupted Q data
+    // (e.g. LibCrypt-protected sectors) leave the previous position in place
+    fn update_subchannel_q(&mut self, time: CdTime) {
+        let Some(disc) = &mut self.disc else { return };
+
+        match disc.read_subchannel_q(time) {
+            Ok(q) if q.adr() == SubchannelQ::ADR_POSITION && q.crc_valid() => {
+                self.last_subchannel_q = q;
+            }
+            Ok(q) => {
+                log::debug!("Not latching subchannel Q data at {time}: {:02X?}", q.bytes());
+            }
+            Err(err) => {
+                log::error!("Error reading subchannel Q data at {time}: {err}");
+            }
+        }
+    }
+
     fn read_sector_atime(&mut self, time: CdTime) -> CdRomResult<()> {
+        self.update_subchannel_q(time);
+
         let disc =
             self.disc.as_mut().expect("read_sector_atime() called with no disc in the drive");
 
@@ -690,13 +781,27 @@ impl CdController {
         };
 
         let track_number = track.number;
+        let track_mode = track.mode;
         let relative_time = time - track.start_time;
 
         log::debug!("Reading sector at atime {time}, track {track_number} time {relative_time}");
 
-        disc.read_sector(track_number, relative_time, self.sector_buffer.as_mut())?;
-
-        Ok(())
+        match disc.read_sector(track_number, relative_time, self.sector_buffer.as_mut()) {
+            Err(err @ CdRomError::DiscReadInvalidChecksum { .. }) => match self.edc_mismatch_policy
+            {
+                EdcMismatchPolicy::Ignore => {
+                    log::warn!("{err}; ignoring");
+                    Ok(())
+                }
+                EdcMismatchPolicy::Repair => {
+                    log::warn!("{err}; regenerating EDC and ECC");
+                    cdrom::reader::regenerate_edc_ecc(track_mode, self.sector_buffer.as_mut());
+                    Ok(())
+                }
+                EdcMismatchPolicy::ReadError | EdcMismatchPolicy::Fail => Err(err),
+            },
+            result => result,
+        }
     }
 
     pub fn current_audio_sample(&self) -> (i16, i16) {
@@ -707,17 +812,67 @@ impl CdController {
         self.cd_to_spu_volume
     }
 
+    pub fn has_disc(&self) -> bool {
+        self.disc.is_some()
+    }
+
+    /// Whether the disc passes the drive's `GetID` region check
+    pub fn disc_region_accepted(&self) -> bool {
+        self.console_region.accepts(self.disc_region)
+    }
+
+    /// Direct access to the disc, bypassing the drive (e.g. for reading files)
+    pub fn disc_mut(&mut self) -> Option<&mut CdRom> {
+        self.disc.as_mut()
+    }
+
     pub fn take_disc(&mut self) -> Option<CdRom> {
-        self.disc.take()
+        self.disc.take().or_else(|| self.pending_disc.take())
     }
 
+    /// Change discs by opening the lid, leaving it open for a short time, and then closing it with
+    /// the new disc inserted. `None` leaves the drive empty.
     pub fn change_disc(&mut self, disc: Option<CdRom>) {
-        self.disc = disc;
+        log::info!("Opening drive lid");
+
+        // Opening the lid stops the motor, which aborts any in-progress seek, read, or play
+        self.disc = None;
+        self.disc_region = None;
+        self.pending_disc = disc;
+        self.lid_state = LidState::Open { cycles_remaining: LID_OPEN_CYCLES };
         self.shell_opened = true;
         self.drive_state = DriveState::Stopped;
 
         self.int5(&[stat!(self), status::SHELL_OPENED]);
     }
+
+    pub fn set_console_region(&mut self, console_region: ConsoleRegion) {
+        self.console_region = console_region;
+    }
+
+    pub fn set_edc_mismatch_policy(&mut self, edc_mismatch_policy: EdcMismatchPolicy) {
+        self.edc_mismatch_policy = edc_mismatch_policy;
+    }
+}
+
+// The drive reads the region from the SCEx string in the lead-in, which disc images do not
+// contain; the license string in the system area always matches it
+fn read_disc_region(disc: Option<&mut CdRom>) -> Option<DiscRegion> {
+    let disc = disc?;
+
+    match DiscRegion::read_license(disc) {
+        Ok(region) => {
+            match region {
+                Some(region) => log::info!("Disc region is {region}"),
+                None => log::info!("Disc does not have a license string"),
+            }
+            region
+        }
+        Err(err) => {
+            log::warn!("Unable to read license sector from disc: {err}");
+            None
+        }
+    }
 }
 
 fn bcd_to_binary(value: u8) -> u8 {
@@ -1,12 +1,13 @@
 //! CD-ROM read commands
 
+use crate::api::EdcMismatchPolicy;
 #[allow(clippy::wildcard_imports)]
 use crate::cd::macros::*;
 use crate::cd::{CdController, CommandState, DriveState, SeekNextState, seek, status};
 use crate::num::U8Ext;
 use bincode::{Decode, Encode};
-use cdrom::CdRomResult;
 use cdrom::cdtime::CdTime;
+use cdrom::{CdRomError, CdRomResult};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
 pub struct ReadState {
@@ -89,7 +90,18 @@ impl CdController {
             return Ok(DriveState::Stopped);
         }
 
-        self.read_sector_atime(time)?;
+        match self.read_sector_atime(time) {
+            Ok(()) => {}
+            Err(err @ CdRomError::DiscReadInvalidChecksum { .. })
+                if self.edc_mismatch_policy == EdcMismatchPolicy::ReadError =>
+            {
+                // Real drives retry a sector several times before giving up and reporting an error
+                log::warn!("{err}; reporting read error");
+                self.int5(&[stat!(self, ERROR), status::READ_ERROR]);
+                return Ok(DriveState::Paused { time, int2_queued: false });
+            }
+            Err(err) => return Err(err),
+        }
 
         log::debug!(
             "  Data sector header: {:02X?} subheader: {:02X?}",
@@ -126,3 +138,119 @@ impl CdController {
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ConsoleRegion;
+    use cdrom::cue::TrackMode;
+    use cdrom::reader::{CdRom, CdRomFileFormat};
+    use std::fs;
+
+    const BAD_SECTOR: u32 = 16;
+
+    // Mode 2 Form 2 sectors have no ECC, so the drive cannot correct the bad EDC on its own
+    fn controller_with_bad_sector(policy: EdcMismatchPolicy) -> CdController {
+        let dir = std::env::temp_dir()
+            .join(format!("ps1-core-edc-{policy:?}-{}", std::process::id()).to_lowercase());
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut bin = vec![0; 32 * 2352];
+        let sector = &mut bin[BAD_SECTOR as usize * 2352..][..2352];
+        sector[18] = 1 << 5;
+        sector[22] = 1 << 5;
+        sector[100] = 0xAB;
+        sector[2348..].copy_from_slice(&[1, 2, 3, 4]);
+        fs::write(dir.join("disc.bin"), bin).unwrap();
+        fs::write(
+            dir.join("disc.cue"),
+            "FILE \"disc.bin\" BINARY\n  TRACK 01 MODE2/2352\n    INDEX 01 00:00:00\n",
+        )
+        .unwrap();
+
+        let disc = CdRom::open_in_memory(dir.join("disc.cue"), CdRomFileFormat::CueBin);
+        fs::remove_dir_all(&dir).unwrap();
+
+        CdController::new(Some(disc.unwrap()), ConsoleRegion::default(), policy)
+    }
+
+    fn bad_sector_time() -> CdTime {
+        // Track 1 data starts after the 2-second pregap
+        CdTime::new(0, 2, 0) + CdTime::from_sector_number(BAD_SECTOR)
+    }
+
+    fn response(controller: &mut CdController) -> Vec<u8> {
+        (0..controller.response_fifo.len()).map(|_| controller.response_fifo.pop()).collect()
+    }
+
+    fn has_valid_edc(sector: &[u8]) -> bool {
+        let mut regenerated = sector.to_vec();
+        cdrom::reader::regenerate_edc(TrackMode::Mode2, &mut regenerated);
+        regenerated == sector
+    }
+
+    #[test]
+    fn edc_mismatch_ignore() {
+        let mut controller = controller_with_bad_sector(EdcMismatchPolicy::Ignore);
+
+        let state = controller.read_data_sector(bad_sector_time()).unwrap();
+        assert!(
+            matches!(state, DriveState::Reading(ReadState { time, .. }) if time == bad_sector_time() + CdTime::new(0, 0, 1))
+        );
+        assert_eq!(controller.interrupts.flags & 7, 0);
+        assert_eq!(controller.sector_buffer[100], 0xAB);
+        assert!(!has_valid_edc(controller.sector_buffer.as_ref()));
+    }
+
+    #[test]
+    fn edc_mismatch_repair() {
+        let mut controller = controller_with_bad_sector(EdcMismatchPolicy::Repair);
+
+        let state = controller.read_data_sector(bad_sector_time()).unwrap();
+        assert!(matches!(state, DriveState::Reading(_)));
+        assert_eq!(controller.interrupts.flags & 7, 0);
+        assert_eq!(controller.sector_buffer[100], 0xAB);
+        assert!(has_valid_edc(controller.sector_buffer.as_ref()));
+    }
+
+    #[test]
+    fn edc_mismatch_read_error() {
+        let mut controller = controller_with_bad_sector(EdcMismatchPolicy::ReadError);
+
+        let state = controller.read_data_sector(bad_sector_time()).unwrap();
+        assert!(matches!(
+            state,
+            DriveState::Paused { time, int2_queued: false } if time == bad_sector_time()
+        ));
+        assert_eq!(controller.interrupts.flags & 7, 5);
+
+        let response = response(&mut controller);
+        assert_eq!(response.len(), 2);
+        // Stat error bit
+        assert_ne!(response[0] & 1, 0);
+        assert_eq!(response[1], status::READ_ERROR);
+    }
+
+    #[test]
+    fn edc_mismatch_fail() {
+        let mut controller = controller_with_bad_sector(EdcMismatchPolicy::Fail);
+
+        let result = controller.read_data_sector(bad_sector_time());
+        assert!(matches!(
+            result,
+            Err(CdRomError::DiscReadInvalidChecksum {
+                track_number: 1,
+                sector_number: BAD_SECTOR,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn valid_sector_is_read_with_any_policy() {
+        let mut controller = controller_with_bad_sector(EdcMismatchPolicy::Fail);
+
+        let time = bad_sector_time() + CdTime::new(0, 0, 1);
+        assert!(matches!(controller.read_data_sector(time), Ok(DriveState::Reading(_))));
+    }
+}
@@ -3,9 +3,9 @@
 use crate::cd;
 #[allow(clippy::wildcard_imports)]
 use crate::cd::macros::*;
-use crate::cd::{CdController, Command, CommandState, DriveState};
-use cdrom::cdtime::CdTime;
+use crate::cd::{CdController, Command, CommandState, DriveState, LidState};
 use cdrom::cue::TrackMode;
+use cdrom::discinfo::DiscRegion;
 use std::ops::BitOr;
 
 pub const INVALID_PARAMETER: u8 = 0x10;
@@ -13,6 +13,11 @@ pub const WRONG_NUM_PARAMETERS: u8 = 0x20;
 pub const INVALID_COMMAND: u8 = 0x40;
 pub const CANNOT_RESPOND_YET: u8 = 0x80;
 pub const SHELL_OPENED: u8 = 0x08;
+// Also reported for failed seeks
+pub const READ_ERROR: u8 = 0x04;
+
+// GetID flags byte
+const UNLICENSED: u8 = 0x80;
 
 // Roughly 18,944 CPU cycles
 pub const GET_ID_SECOND_CYCLES: u32 = 24;
@@ -25,6 +30,7 @@ pub struct ErrorFlags(u8);
 impl ErrorFlags {
     pub const NONE: Self = Self(0);
     pub const ERROR: Self = Self(1);
+    pub const ID_ERROR: Self = Self(1 << 3);
 }
 
 impl BitOr for ErrorFlags {
@@ -63,9 +69,11 @@ impl CdController {
     pub(super) fn execute_get_stat(&mut self) -> CommandState {
         self.int3(&[stat!(self)]);
 
-        // Executing GetStat clears the shell opened bit
-        // TODO does there need to be a delay before closing the shell on disc change?
-        self.shell_opened = false;
+        // Executing GetStat clears the shell opened bit, but the bit stays set while the lid is
+        // still open
+        if self.lid_state == LidState::Closed {
+            self.shell_opened = false;
+        }
 
         CommandState::Idle
     }
@@ -85,15 +93,30 @@ impl CdController {
 
     pub(super) fn get_id_second_response(&mut self) -> CommandState {
         match &self.disc {
-            // TODO don't hardcode region
             Some(disc) => {
-                let status = stat!(self);
                 let mode_byte = match disc.cue().track(1).mode {
                     TrackMode::Mode2 => 0x20,
                     TrackMode::Mode1 | TrackMode::Audio => 0x00,
                 };
 
-                self.int2(&[status, 0x00, mode_byte, 0x00, b'S', b'C', b'E', b'A']);
+                if !self.console_region.accepts(self.disc_region) {
+                    log::info!(
+                        "Disc region {:?} rejected by {:?} console",
+                        self.disc_region,
+                        self.console_region
+                    );
+
+                    // "Unlicensed" response; the BIOS refuses to boot the disc
+                    let status = stat!(self, ID_ERROR);
+                    self.int5(&[status, UNLICENSED, mode_byte, 0x00, 0x00, 0x00, 0x00, 0x00]);
+                    return CommandState::Idle;
+                }
+
+                // Region-free consoles report unlicensed discs as SCEA, as a modchip would
+                let region = self.disc_region.map_or(b'A', DiscRegion::scex_letter);
+
+                let status = stat!(self);
+                self.int2(&[status, 0x00, mode_byte, 0x00, b'S', b'C', b'E', region]);
             }
             None => {
                 // "No disc" response
@@ -191,35 +214,62 @@ impl CdController {
     // $11: GetLocP() -> INT3(track, index, mm, ss, sect, amm, ass, asect)
     // Returns position data from Subchannel Q
     pub(super) fn execute_get_loc_p(&mut self) -> CommandState {
-        let Some(disc) = &self.disc else {
+        if self.disc.is_none() {
             self.int5(&[stat!(self), CANNOT_RESPOND_YET]);
             return CommandState::Idle;
-        };
+        }
 
         // TODO better handle if this is executed while seeking
-        let absolute_time = self.drive_state.current_time();
-        let track = disc.cue().find_track_by_time(absolute_time);
-
-        let (track_number, index, relative_time) =
-            track.map_or((0xAA, 0x00, CdTime::ZERO), |track| {
-                let track_number = cd::binary_to_bcd(track.number);
-                let index = u8::from(absolute_time >= track.effective_start_time());
-                let relative_time = absolute_time.saturating_sub(track.effective_start_time());
-
-                (track_number, index, relative_time)
-            });
-
-        self.int3(&[
-            track_number,
-            index,
-            cd::binary_to_bcd(relative_time.minutes),
-            cd::binary_to_bcd(relative_time.seconds),
-            cd::binary_to_bcd(relative_time.frames),
-            cd::binary_to_bcd(absolute_time.minutes),
-            cd::binary_to_bcd(absolute_time.seconds),
-            cd::binary_to_bcd(absolute_time.frames),
-        ]);
+        self.update_subchannel_q(self.drive_state.current_time());
+
+        // Response is the position Q data excluding control/ADR, the zero byte, and the CRC
+        let q = *self.last_subchannel_q.bytes();
+        self.int3(&[q[1], q[2], q[3], q[4], q[5], q[7], q[8], q[9]]);
 
         CommandState::Idle
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{ConsoleRegion, EdcMismatchPolicy};
+    use cdrom::reader::CdRom;
+
+    fn controller_with_disc(
+        console_region: ConsoleRegion,
+        disc_region: DiscRegion,
+    ) -> CdController {
+        let disc = CdRom::open_iso_in_memory(vec![0; 32 * 2048]).unwrap();
+
+        let mut controller =
+            CdController::new(Some(disc), console_region, EdcMismatchPolicy::default());
+        controller.disc_region = Some(disc_region);
+        controller
+    }
+
+    fn response(controller: &mut CdController) -> Vec<u8> {
+        (0..controller.response_fifo.len()).map(|_| controller.response_fifo.pop()).collect()
+    }
+
+    #[test]
+    fn get_id_accepts_matching_region() {
+        let mut controller = controller_with_disc(ConsoleRegion::Pal, DiscRegion::Pal);
+        controller.get_id_second_response();
+
+        assert_eq!(controller.interrupts.flags & 7, 2);
+        assert_eq!(response(&mut controller)[1..], [0x00, 0x00, 0x00, b'S', b'C', b'E', b'E']);
+    }
+
+    #[test]
+    fn get_id_rejects_other_region() {
+        let mut controller = controller_with_disc(ConsoleRegion::NtscU, DiscRegion::NtscJ);
+        controller.get_id_second_response();
+
+        assert_eq!(controller.interrupts.flags & 7, 5);
+        let response = response(&mut controller);
+        assert_ne!(response[0] & ErrorFlags::ID_ERROR.0, 0);
+        assert_eq!(response[1], UNLICENSED);
+        assert_eq!(response[4..], [0; 4]);
+    }
+}
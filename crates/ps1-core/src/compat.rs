@@ -0,0 +1,207 @@
+//! Per-game compatibility settings, keyed by disc serial
+//!
+//! The built-in database is embedded in the binary. Frontends can layer a local database on top of
+//! it using [`CompatibilityDatabase::extend`] to add games or replace built-in entries.
+//!
+//! Only PGXP workarounds are supported so far. There are no DMA timing settings to adjust yet, so
+//! games that need DMA timing workarounds can only be documented with notes.
+
+use crate::api::{PgxpConfig, Ps1EmulatorConfig};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+
+const BUILTIN_DATABASE: &str = include_str!("compat/compatibility.tsv");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompatibilityFlag {
+    /// PGXP causes graphical problems
+    PgxpOff,
+    /// Requires PGXP CPU mode, which is not implemented, so PGXP is disabled instead
+    PgxpCpuMode,
+    /// PGXP high-precision culling causes graphical problems
+    PgxpNoPreciseCulling,
+    /// PGXP perspective-correct texture mapping causes graphical problems
+    PgxpNoPerspective,
+}
+
+impl CompatibilityFlag {
+    const ALL: [Self; 4] =
+        [Self::PgxpOff, Self::PgxpCpuMode, Self::PgxpNoPreciseCulling, Self::PgxpNoPerspective];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::PgxpOff => "pgxp-off",
+            Self::PgxpCpuMode => "pgxp-cpu-mode",
+            Self::PgxpNoPreciseCulling => "pgxp-no-precise-culling",
+            Self::PgxpNoPerspective => "pgxp-no-perspective",
+        }
+    }
+
+    /// User-facing description of the workaround
+    #[must_use]
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::PgxpOff => "PGXP is disabled because it causes graphical problems",
+            Self::PgxpCpuMode => {
+                "PGXP is disabled because this game requires PGXP CPU mode, which is not implemented"
+            }
+            Self::PgxpNoPreciseCulling => "PGXP high-precision culling is disabled",
+            Self::PgxpNoPerspective => "PGXP perspective-correct texture mapping is disabled",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|flag| flag.name() == name)
+    }
+}
+
+impl Display for CompatibilityFlag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompatibilityEntry {
+    pub flags: Vec<CompatibilityFlag>,
+    /// Known issues that the flags do not work around
+    pub notes: Option<String>,
+}
+
+impl CompatibilityEntry {
+    /// Apply this game's settings on top of a config
+    pub fn apply(&self, config: &mut Ps1EmulatorConfig) {
+        self.apply_pgxp(&mut config.pgxp);
+    }
+
+    /// Apply this game's PGXP settings on top of a PGXP config
+    pub fn apply_pgxp(&self, config: &mut PgxpConfig) {
+        for &flag in &self.flags {
+            match flag {
+                CompatibilityFlag::PgxpOff | CompatibilityFlag::PgxpCpuMode => {
+                    config.enabled = false;
+                }
+                CompatibilityFlag::PgxpNoPreciseCulling => config.precise_nclip = false,
+                CompatibilityFlag::PgxpNoPerspective => config.perspective_texture_mapping = false,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CompatibilityParseError {
+    #[error("Line {line}: expected a serial and flags separated by tabs")]
+    MissingFlags { line: usize },
+    #[error("Line {line}: invalid compatibility flag '{flag}'")]
+    InvalidFlag { line: usize, flag: String },
+}
+
+/// Compatibility settings keyed by serial, e.g. `SCUS-94228`.
+///
+/// The text format has one game per line: the serial, a comma-separated list of flags (or `-` for
+/// none), and optional notes, separated by tabs. Lines starting with `#` are comments.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompatibilityDatabase {
+    entries: HashMap<String, CompatibilityEntry>,
+}
+
+impl CompatibilityDatabase {
+    /// The database embedded in the binary
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the embedded database is invalid, which is checked by a unit test.
+    #[must_use]
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_DATABASE).expect("Built-in compatibility database should be valid")
+    }
+
+    /// # Errors
+    ///
+    /// Will return an error if any line has a missing or unrecognized flags field.
+    pub fn parse(text: &str) -> Result<Self, CompatibilityParseError> {
+        let mut entries = HashMap::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split('\t');
+            let serial = fields.next().unwrap_or_default().trim();
+            let flags_field = fields
+                .next()
+                .map(str::trim)
+                .ok_or(CompatibilityParseError::MissingFlags { line: line_number })?;
+            let notes = fields.next().map(str::trim).filter(|notes| !notes.is_empty());
+
+            let flags = match flags_field {
+                "-" | "" => Vec::new(),
+                _ => flags_field
+                    .split(',')
+                    .map(|name| {
+                        let name = name.trim();
+                        CompatibilityFlag::from_name(name).ok_or_else(|| {
+                            CompatibilityParseError::InvalidFlag {
+                                line: line_number,
+                                flag: name.into(),
+                            }
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
+            };
+
+            entries.insert(
+                serial.into(),
+                CompatibilityEntry { flags, notes: notes.map(String::from) },
+            );
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Add entries from another database, replacing any entries for the same serial
+    pub fn extend(&mut self, other: Self) {
+        self.entries.extend(other.entries);
+    }
+
+    #[must_use]
+    pub fn get(&self, serial: &str) -> Option<&CompatibilityEntry> {
+        self.entries.get(serial)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_database_is_valid() {
+        let database = CompatibilityDatabase::parse(BUILTIN_DATABASE).unwrap();
+        assert!(database.get("SCUS-94228").is_some());
+    }
+
+    #[test]
+    fn local_entries_replace_builtin() {
+        let mut database = CompatibilityDatabase::builtin();
+        database.extend(CompatibilityDatabase::parse("SCUS-94228\t-\n").unwrap());
+
+        let entry = database.get("SCUS-94228").unwrap();
+        assert!(entry.flags.is_empty());
+
+        let mut config = Ps1EmulatorConfig::default();
+        config.pgxp.enabled = true;
+        entry.apply(&mut config);
+        assert!(config.pgxp.enabled);
+    }
+
+    #[test]
+    fn invalid_flag() {
+        assert!(matches!(
+            CompatibilityDatabase::parse("SLUS-00001\tpgxp-of\n"),
+            Err(CompatibilityParseError::InvalidFlag { line: 1, .. })
+        ));
+    }
+}
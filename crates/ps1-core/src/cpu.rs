@@ -13,12 +13,15 @@ use crate::bus::Bus;
 use crate::cpu::cp0::ExceptionCode;
 use crate::cpu::gte::GeometryTransformationEngine;
 use crate::cpu::icache::InstructionCache;
+use crate::debug::{Cop0Register, CpuRegister, MemoryAccess};
 use crate::num::U32Ext;
 use crate::pgxp::{PgxpConfig, PgxpCpuRegisters};
 use bincode::{Decode, Encode};
 use cp0::SystemControlCoprocessor;
 use std::mem;
 
+pub use instructions::instruction_str;
+
 const RESET_VECTOR: u32 = 0xBFC0_0000;
 const EXCEPTION_VECTOR: u32 = 0x8000_0080;
 const BOOT_EXCEPTION_VECTOR: u32 = 0xBFC0_0180;
@@ -136,6 +139,14 @@ impl OpSize {
             Self::Word => value,
         }
     }
+
+    pub fn bytes(self) -> u32 {
+        match self {
+            Self::Byte => 1,
+            Self::HalfWord => 2,
+            Self::Word => 4,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Encode, Decode)]
@@ -152,7 +163,7 @@ pub struct R3000 {
 const BIU_CACHE_CONTROL_ADDR: u32 = 0xFFFE0130;
 
 macro_rules! impl_bus_write {
-    ($name:ident, $write_fn:ident, $memory_cycles_fn:ident) => {
+    ($name:ident, $write_fn:ident, $memory_cycles_fn:ident, $size:expr) => {
         fn $name(&mut self, bus: &mut Bus<'_>, address: u32, value: u32) {
             if self.cp0.status.isolate_cache {
                 // If cache is isolated, send writes directly to instruction cache
@@ -176,6 +187,7 @@ macro_rules! impl_bus_write {
             }
 
             validate_address(address);
+            bus.debugger.check_memory_access(address, $size, MemoryAccess::Write);
             bus.$write_fn(address & 0x1FFFFFFF, value);
         }
     };
@@ -217,6 +229,62 @@ impl R3000 {
         self.registers.write_gpr(register, value);
     }
 
+    pub fn read_debug_register(&self, register: CpuRegister) -> u32 {
+        match register {
+            CpuRegister::Gpr(register) => self.registers.gpr[register as usize],
+            CpuRegister::Hi => self.registers.hi,
+            CpuRegister::Lo => self.registers.lo,
+            CpuRegister::Pc => self.registers.pc,
+            CpuRegister::Cop0(register) => match register {
+                Cop0Register::BadVaddr => self.cp0.bad_v_addr,
+                Cop0Register::Status => self.cp0.read_register(12),
+                Cop0Register::Cause => self.cp0.read_register(13),
+                Cop0Register::Epc => self.cp0.epc,
+                Cop0Register::Prid => self.cp0.read_register(15),
+            },
+            CpuRegister::GteData(register) => self.gte.read_register(register.into()),
+            CpuRegister::GteControl(register) => self.gte.read_control_register(register.into()),
+        }
+    }
+
+    pub fn write_debug_register(&mut self, register: CpuRegister, value: u32) {
+        match register {
+            CpuRegister::Gpr(register) => self.registers.write_gpr(register.into(), value),
+            CpuRegister::Hi => self.registers.hi = value,
+            CpuRegister::Lo => self.registers.lo = value,
+            CpuRegister::Pc => self.set_pc(value),
+            CpuRegister::Cop0(register) => match register {
+                // BadVaddr and EPC are read-only to software, but allow the debugger to write them
+                Cop0Register::BadVaddr => self.cp0.bad_v_addr = value,
+                Cop0Register::Status => self.cp0.write_register(12, value),
+                Cop0Register::Cause => self.cp0.write_register(13, value),
+                Cop0Register::Epc => self.cp0.epc = value,
+                Cop0Register::Prid => {}
+            },
+            CpuRegister::GteData(register) => self.gte.write_register(register.into(), value),
+            CpuRegister::GteControl(register) => {
+                self.gte.write_control_register(register.into(), value);
+            }
+        }
+    }
+
+    /// Invalidate the I-cache line containing the given address, e.g. after a debugger writes to
+    /// memory that may contain cached code.
+    pub fn invalidate_i_cache_line(&mut self, address: u32) {
+        self.i_cache.invalidate_tag(address & 0x1FFFFFFF);
+    }
+
+    /// Invalidate the entire I-cache, e.g. after loading code into RAM outside of the CPU.
+    pub fn flush_i_cache(&mut self) {
+        *self.i_cache = InstructionCache::new();
+    }
+
+    /// Perform the equivalent of an RFE instruction, popping the Status register's
+    /// interrupt-enable/kernel-mode stack.
+    pub fn return_from_exception(&mut self) {
+        self.cp0.execute_operation(0x10);
+    }
+
     #[must_use]
     pub fn execute_instruction(&mut self, bus: &mut Bus<'_>) -> u32 {
         self.instruction_cycles = 1;
@@ -309,6 +377,7 @@ impl R3000 {
         self.instruction_cycles += memory_access_cycles_u8(address);
 
         validate_address(address);
+        bus.debugger.check_memory_access(address, OpSize::Byte, MemoryAccess::Read);
         bus.read_u8(address & 0x1FFFFFFF)
     }
 
@@ -316,6 +385,7 @@ impl R3000 {
         self.instruction_cycles += memory_access_cycles_u16(address);
 
         validate_address(address);
+        bus.debugger.check_memory_access(address, OpSize::HalfWord, MemoryAccess::Read);
         bus.read_u16(address & 0x1FFFFFFF)
     }
 
@@ -323,12 +393,13 @@ impl R3000 {
         self.instruction_cycles += memory_access_cycles_u32(address);
 
         validate_address(address);
+        bus.debugger.check_memory_access(address, OpSize::Word, MemoryAccess::Read);
         bus.read_u32(address & 0x1FFFFFFF)
     }
 
-    impl_bus_write!(bus_write_u8, write_u8, memory_access_cycles_u8);
-    impl_bus_write!(bus_write_u16, write_u16, memory_access_cycles_u16);
-    impl_bus_write!(bus_write_u32, write_u32, memory_access_cycles_u32);
+    impl_bus_write!(bus_write_u8, write_u8, memory_access_cycles_u8, OpSize::Byte);
+    impl_bus_write!(bus_write_u16, write_u16, memory_access_cycles_u16, OpSize::HalfWord);
+    impl_bus_write!(bus_write_u32, write_u32, memory_access_cycles_u32, OpSize::Word);
 
     fn handle_exception(&mut self, exception: Exception, pc: u32, in_delay_slot: bool) {
         self.cp0.handle_exception(exception, pc, in_delay_slot);
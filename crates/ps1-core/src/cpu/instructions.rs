@@ -1,5 +1,7 @@
 mod disassemble;
 
+pub use disassemble::instruction_str;
+
 use crate::bus::Bus;
 use crate::cpu::{CpuResult, Exception, R3000};
 use crate::num::U32Ext;
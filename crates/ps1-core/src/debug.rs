@@ -0,0 +1,420 @@
+//! CPU debugger support: execution breakpoints, memory watchpoints, and stepping
+//!
+//! Breakpoints and watchpoints are checked inside the main loop, and [`Ps1Emulator::tick`]
+//! returns [`TickEffect::DebugStop`] as soon as one triggers instead of running until the next
+//! scheduler event. Calling `tick()` again resumes execution.
+//!
+//! [`Ps1Emulator::tick`]: crate::api::Ps1Emulator::tick
+//! [`TickEffect::DebugStop`]: crate::api::TickEffect::DebugStop
+
+use crate::cpu::OpSize;
+use std::ops::RangeInclusive;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BreakpointId(u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WatchpointId(u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryAccess {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    fn matches(self, access: MemoryAccess) -> bool {
+        match self {
+            Self::Read => access == MemoryAccess::Read,
+            Self::Write => access == MemoryAccess::Write,
+            Self::ReadWrite => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The CPU is about to execute the instruction at a breakpoint; it has not executed yet
+    Breakpoint { id: BreakpointId, pc: u32 },
+    /// A CPU load or store accessed a watched address range. The instruction that performed the
+    /// access has finished executing. `address` is the physical address of the access
+    Watchpoint { id: WatchpointId, address: u32, access: MemoryAccess },
+    /// A step requested through `request_step()` or `request_step_over()` finished
+    Step,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cop0Register {
+    BadVaddr,
+    Status,
+    Cause,
+    Epc,
+    Prid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuRegister {
+    /// General-purpose register R0-R31
+    Gpr(u8),
+    Hi,
+    Lo,
+    Pc,
+    Cop0(Cop0Register),
+    /// GTE data register 0-31 (accessed via MFC2/MTC2)
+    GteData(u8),
+    /// GTE control register 0-31 (accessed via CFC2/CTC2)
+    GteControl(u8),
+}
+
+impl CpuRegister {
+    pub(crate) fn is_valid(self) -> bool {
+        match self {
+            Self::Gpr(register) | Self::GteData(register) | Self::GteControl(register) => {
+                register < 32
+            }
+            Self::Hi | Self::Lo | Self::Pc | Self::Cop0(_) => true,
+        }
+    }
+}
+
+// All watchpoint address comparisons are done on physical addresses, so KUSEG/KSEG0/KSEG1
+// accesses to the same location all match
+fn physical_address(address: u32) -> u32 {
+    address & 0x1FFFFFFF
+}
+
+#[derive(Debug, Clone)]
+struct Watchpoint {
+    id: WatchpointId,
+    range: RangeInclusive<u32>,
+    kind: WatchKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum StepMode {
+    #[default]
+    None,
+    Instruction,
+    Over {
+        return_address: u32,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Debugger {
+    breakpoints: Vec<(BreakpointId, u32)>,
+    watchpoints: Vec<Watchpoint>,
+    step: StepMode,
+    // Set on every stop so that resuming does not immediately stop again at a breakpoint on the
+    // current PC
+    resume_pc: Option<u32>,
+    watchpoint_hit: Option<StopReason>,
+    next_id: u32,
+    enabled: bool,
+}
+
+impl Debugger {
+    #[inline(always)]
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn update_enabled(&mut self) {
+        self.enabled = !self.breakpoints.is_empty()
+            || !self.watchpoints.is_empty()
+            || self.step != StepMode::None;
+
+        // Nothing is checked while disabled, so a leftover resume PC would suppress the first hit
+        // of a breakpoint that is added later
+        if !self.enabled {
+            self.resume_pc = None;
+        }
+    }
+
+    fn next_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        id
+    }
+
+    pub fn add_breakpoint(&mut self, pc: u32) -> BreakpointId {
+        let id = BreakpointId(self.next_id());
+        self.breakpoints.push((id, pc));
+        self.update_enabled();
+        id
+    }
+
+    pub fn remove_breakpoint(&mut self, id: BreakpointId) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints.retain(|&(breakpoint_id, _)| breakpoint_id != id);
+        self.update_enabled();
+        self.breakpoints.len() != len
+    }
+
+    pub fn add_watchpoint(&mut self, range: RangeInclusive<u32>, kind: WatchKind) -> WatchpointId {
+        let id = WatchpointId(self.next_id());
+        let range = physical_address(*range.start())..=physical_address(*range.end());
+        self.watchpoints.push(Watchpoint { id, range, kind });
+        self.update_enabled();
+        id
+    }
+
+    pub fn remove_watchpoint(&mut self, id: WatchpointId) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|watchpoint| watchpoint.id != id);
+        self.update_enabled();
+        self.watchpoints.len() != len
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+        self.step = StepMode::None;
+        self.resume_pc = None;
+        self.watchpoint_hit = None;
+        self.update_enabled();
+    }
+
+    pub fn request_step(&mut self) {
+        self.step = StepMode::Instruction;
+        self.update_enabled();
+    }
+
+    pub fn request_step_over(&mut self, pc: u32, opcode: u32) {
+        self.step = if is_call_opcode(opcode) {
+            // Run until the instruction after the call's delay slot
+            StepMode::Over { return_address: pc.wrapping_add(8) }
+        } else {
+            StepMode::Instruction
+        };
+        self.update_enabled();
+    }
+
+    // Any stop cancels an in-progress step, and resuming from the stop location should not
+    // immediately trigger a breakpoint at the same PC
+    fn stop(&mut self, reason: StopReason, pc: u32) -> StopReason {
+        self.step = StepMode::None;
+        self.resume_pc = Some(pc);
+        self.update_enabled();
+        reason
+    }
+
+    /// Called before the CPU executes the instruction at `pc`.
+    pub fn check_before_instruction(&mut self, pc: u32) -> Option<StopReason> {
+        if self.resume_pc.take() == Some(pc) {
+            return None;
+        }
+
+        if self.step == (StepMode::Over { return_address: pc }) {
+            return Some(self.stop(StopReason::Step, pc));
+        }
+
+        let &(id, _) = self.breakpoints.iter().find(|&&(_, address)| address == pc)?;
+        Some(self.stop(StopReason::Breakpoint { id, pc }, pc))
+    }
+
+    /// Called after the CPU finishes executing an instruction, with `pc` being the address of the
+    /// next instruction to execute.
+    pub fn check_after_instruction(&mut self, pc: u32) -> Option<StopReason> {
+        if let Some(hit) = self.watchpoint_hit.take() {
+            return Some(self.stop(hit, pc));
+        }
+
+        if self.step == StepMode::Instruction {
+            return Some(self.stop(StopReason::Step, pc));
+        }
+
+        None
+    }
+
+    #[inline(always)]
+    pub fn check_memory_access(&mut self, address: u32, size: OpSize, access: MemoryAccess) {
+        if self.watchpoints.is_empty() {
+            return;
+        }
+
+        self.check_watchpoints(address, size, access);
+    }
+
+    fn check_watchpoints(&mut self, address: u32, size: OpSize, access: MemoryAccess) {
+        if self.watchpoint_hit.is_some() {
+            // Only report the first access in an instruction
+            return;
+        }
+
+        let start = physical_address(address);
+        let end = start + size.bytes() - 1;
+        let Some(watchpoint) = self.watchpoints.iter().find(|watchpoint| {
+            watchpoint.kind.matches(access)
+                && start <= *watchpoint.range.end()
+                && end >= *watchpoint.range.start()
+        }) else {
+            return;
+        };
+
+        self.watchpoint_hit =
+            Some(StopReason::Watchpoint { id: watchpoint.id, address: start, access });
+    }
+}
+
+fn is_call_opcode(opcode: u32) -> bool {
+    match opcode >> 26 {
+        // JAL
+        0x03 => true,
+        // JALR
+        0x00 => opcode & 0x3F == 0x09,
+        // BLTZAL / BGEZAL
+        0x01 => (opcode >> 16) & 0x1E == 0x10,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // JAL 0x80020000
+    const JAL: u32 = 0x0C008000;
+    // ADDIU $v0, $zero, 1
+    const ADDIU: u32 = 0x24020001;
+
+    #[test]
+    fn breakpoint_hit_and_resume() {
+        let mut debugger = Debugger::default();
+        assert!(!debugger.enabled());
+
+        let id = debugger.add_breakpoint(0x80010000);
+        assert!(debugger.enabled());
+        assert_eq!(debugger.check_before_instruction(0x8000FFFC), None);
+        assert_eq!(
+            debugger.check_before_instruction(0x80010000),
+            Some(StopReason::Breakpoint { id, pc: 0x80010000 })
+        );
+
+        // Resuming executes the instruction at the breakpoint instead of stopping again
+        assert_eq!(debugger.check_before_instruction(0x80010000), None);
+        assert_eq!(debugger.check_after_instruction(0x80010004), None);
+        assert_eq!(debugger.check_before_instruction(0x80010004), None);
+
+        // The next time execution reaches the breakpoint, e.g. in a loop, it stops again
+        assert_eq!(
+            debugger.check_before_instruction(0x80010000),
+            Some(StopReason::Breakpoint { id, pc: 0x80010000 })
+        );
+
+        assert!(debugger.remove_breakpoint(id));
+        assert!(!debugger.remove_breakpoint(id));
+        assert!(!debugger.enabled());
+    }
+
+    #[test]
+    fn readded_breakpoint_is_not_suppressed() {
+        let mut debugger = Debugger::default();
+
+        let id = debugger.add_breakpoint(0x80010000);
+        assert!(debugger.check_before_instruction(0x80010000).is_some());
+        debugger.remove_breakpoint(id);
+
+        // The CPU runs without checks while the debugger is disabled
+        let id = debugger.add_breakpoint(0x80010000);
+        assert_eq!(
+            debugger.check_before_instruction(0x80010000),
+            Some(StopReason::Breakpoint { id, pc: 0x80010000 })
+        );
+
+        debugger.clear();
+        let id = debugger.add_breakpoint(0x80010000);
+        assert_eq!(
+            debugger.check_before_instruction(0x80010000),
+            Some(StopReason::Breakpoint { id, pc: 0x80010000 })
+        );
+    }
+
+    #[test]
+    fn step() {
+        let mut debugger = Debugger::default();
+
+        debugger.request_step();
+        assert!(debugger.enabled());
+        assert_eq!(debugger.check_before_instruction(0x80010000), None);
+        assert_eq!(debugger.check_after_instruction(0x80010004), Some(StopReason::Step));
+        assert!(!debugger.enabled());
+
+        // Stepping over a non-call instruction is a single step
+        debugger.request_step_over(0x80010004, ADDIU);
+        assert_eq!(debugger.check_after_instruction(0x80010008), Some(StopReason::Step));
+    }
+
+    #[test]
+    fn step_over_call() {
+        let mut debugger = Debugger::default();
+
+        debugger.request_step_over(0x80010000, JAL);
+        assert_eq!(debugger.check_before_instruction(0x80010000), None);
+        assert_eq!(debugger.check_after_instruction(0x80010004), None);
+        assert_eq!(debugger.check_before_instruction(0x80010004), None);
+        assert_eq!(debugger.check_after_instruction(0x80020000), None);
+        assert_eq!(debugger.check_before_instruction(0x80020000), None);
+        assert_eq!(debugger.check_before_instruction(0x80010008), Some(StopReason::Step));
+        assert!(!debugger.enabled());
+
+        // A breakpoint inside the called function cancels the step
+        let id = debugger.add_breakpoint(0x80020000);
+        debugger.request_step_over(0x80010000, JAL);
+        assert_eq!(
+            debugger.check_before_instruction(0x80020000),
+            Some(StopReason::Breakpoint { id, pc: 0x80020000 })
+        );
+        assert_eq!(debugger.check_before_instruction(0x80010008), None);
+    }
+
+    #[test]
+    fn watchpoints() {
+        let mut debugger = Debugger::default();
+        let id = debugger.add_watchpoint(0x80001000..=0x80001003, WatchKind::Write);
+
+        debugger.check_memory_access(0x80001000, OpSize::Word, MemoryAccess::Read);
+        debugger.check_memory_access(0x80001004, OpSize::Word, MemoryAccess::Write);
+        debugger.check_memory_access(0x80000FFC, OpSize::Word, MemoryAccess::Write);
+        assert_eq!(debugger.check_after_instruction(0x80010004), None);
+
+        // Accesses that partially overlap the range match, through any segment
+        debugger.check_memory_access(0xA0000FFE, OpSize::HalfWord, MemoryAccess::Write);
+        assert_eq!(debugger.check_after_instruction(0x80010004), None);
+        debugger.check_memory_access(0xA0000FFE, OpSize::Word, MemoryAccess::Write);
+        assert_eq!(
+            debugger.check_after_instruction(0x80010008),
+            Some(StopReason::Watchpoint { id, address: 0xFFE, access: MemoryAccess::Write })
+        );
+
+        // Only the first matching access in an instruction is reported
+        debugger.check_memory_access(0x00001003, OpSize::Byte, MemoryAccess::Write);
+        debugger.check_memory_access(0x00001000, OpSize::Byte, MemoryAccess::Write);
+        assert_eq!(
+            debugger.check_after_instruction(0x8001000C),
+            Some(StopReason::Watchpoint { id, address: 0x1003, access: MemoryAccess::Write })
+        );
+
+        let read_id = debugger.add_watchpoint(0x1F801070..=0x1F801070, WatchKind::ReadWrite);
+        debugger.check_memory_access(0xBF801070, OpSize::Byte, MemoryAccess::Read);
+        assert_eq!(
+            debugger.check_after_instruction(0x80010010),
+            Some(StopReason::Watchpoint {
+                id: read_id,
+                address: 0x1F801070,
+                access: MemoryAccess::Read
+            })
+        );
+
+        assert!(debugger.remove_watchpoint(id));
+        assert!(debugger.remove_watchpoint(read_id));
+        assert!(!debugger.enabled());
+    }
+}
@@ -0,0 +1,183 @@
+//! Fast boot: skip the BIOS shell's boot animation when launching a disc
+//!
+//! The BIOS is run until it jumps to the shell, at which point the kernel is fully initialized.
+//! Instead of running the shell, the emulator then does what the BIOS would do after the boot
+//! animation: read SYSTEM.CNF, reconfigure the kernel with `SetConf()`, and load the boot EXE.
+
+use crate::bus::Bus;
+use crate::cpu::R3000;
+use crate::hle::SystemConfig;
+use crate::memory::{MAIN_RAM_LEN, MAIN_RAM_MASK, Memory};
+use std::cmp;
+
+// The BIOS copies the shell to this address and jumps to it after initializing the kernel
+pub(crate) const SHELL_ENTRY: u32 = 0x8003_0000;
+
+// Kernel function A($9C), SetConf(events, threads, stack_top)
+const A_FUNCTIONS: u32 = 0xA0;
+const SET_CONF: u32 = 0x9C;
+
+// Guard against the BIOS never returning from a kernel call
+const MAX_CALL_INSTRUCTIONS: u32 = 10_000_000;
+
+// Guard against BIOSes that never jump to the shell. Retail BIOSes get there in a small fraction of
+// this many instructions
+const MAX_BOOT_INSTRUCTIONS: u32 = 200_000_000;
+
+const EXE_HEADER_LEN: usize = 0x800;
+
+/// Run the BIOS until it jumps to the shell. Returns `false` if it did not get there within
+/// [`MAX_BOOT_INSTRUCTIONS`].
+pub(crate) fn run_bios_until_shell(cpu: &mut R3000, bus: &mut Bus<'_>) -> bool {
+    for _ in 0..MAX_BOOT_INSTRUCTIONS {
+        if cpu.pc() == SHELL_ENTRY {
+            return true;
+        }
+        let _ = cpu.execute_instruction(bus);
+    }
+
+    cpu.pc() == SHELL_ENTRY
+}
+
+/// Run the BIOS until the kernel is initialized and then prepare to boot the disc. Returns the
+/// boot EXE, which should be sideloaded to start the game, or `None` if the disc could not be
+/// fast booted. If this returns `None`, the BIOS can continue running to boot normally.
+pub(crate) fn prepare_disc_boot(cpu: &mut R3000, bus: &mut Bus<'_>) -> Option<Vec<u8>> {
+    if !run_bios_until_shell(cpu, bus) {
+        log::error!("BIOS did not jump to the shell at {SHELL_ENTRY:08X}; booting normally");
+        return None;
+    }
+
+    let config = SystemConfig::read(bus.cd_controller);
+    let Some(exe) = config.read_boot_exe(bus.cd_controller) else {
+        log::warn!(
+            "Unable to read boot EXE '{}'; booting normally",
+            String::from_utf8_lossy(&config.boot)
+        );
+        return None;
+    };
+
+    if exe.len() < EXE_HEADER_LEN || &exe[..8] != b"PS-X EXE" {
+        log::warn!(
+            "Boot file '{}' is not a PS-X EXE; booting normally",
+            String::from_utf8_lossy(&config.boot)
+        );
+        return None;
+    }
+
+    log::info!(
+        "Fast booting '{}' with TCB={:X} EVENT={:X} STACK={:08X}",
+        String::from_utf8_lossy(&config.boot),
+        config.threads,
+        config.events,
+        config.stack
+    );
+
+    // The BIOS reconfigures the kernel using the SYSTEM.CNF settings before loading the EXE
+    if !call_kernel_function(
+        cpu,
+        bus,
+        A_FUNCTIONS,
+        SET_CONF,
+        [config.events, config.threads, config.stack],
+    ) {
+        log::error!("SetConf() did not return; booting normally");
+        return None;
+    }
+
+    // The EXE's header can override this stack pointer when it is sideloaded
+    cpu.set_gpr(29, config.stack);
+    cpu.set_gpr(30, config.stack);
+
+    // Exec() clears the BSS region before jumping to the EXE
+    clear_bss(bus.memory, &exe);
+
+    cpu.flush_i_cache();
+
+    Some(exe)
+}
+
+// The BSS address and size come straight from the EXE header, so a bad header must not be able to
+// write outside of main RAM
+fn clear_bss(memory: &mut Memory, exe: &[u8]) {
+    let bss_addr = u32::from_le_bytes(exe[0x028..0x02C].try_into().unwrap());
+    let bss_size = u32::from_le_bytes(exe[0x02C..0x030].try_into().unwrap());
+
+    let bss_size = cmp::min(bss_size, MAIN_RAM_LEN as u32);
+    for offset in 0..bss_size {
+        memory.write_main_ram_u8(bss_addr.wrapping_add(offset) & MAIN_RAM_MASK, 0);
+    }
+}
+
+/// Call a BIOS kernel function by executing it on the emulated CPU, returning once the function
+/// returns to the shell entry point. Returns `false` if the function did not return.
+fn call_kernel_function(
+    cpu: &mut R3000,
+    bus: &mut Bus<'_>,
+    vector: u32,
+    function: u32,
+    args: [u32; 3],
+) -> bool {
+    for (i, arg) in args.into_iter().enumerate() {
+        cpu.set_gpr(4 + i as u32, arg);
+    }
+    cpu.set_gpr(9, function);
+    cpu.set_gpr(31, SHELL_ENTRY);
+    cpu.set_pc(vector);
+
+    for _ in 0..MAX_CALL_INSTRUCTIONS {
+        let _ = cpu.execute_instruction(bus);
+        if cpu.pc() == SHELL_ENTRY {
+            return true;
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::BIOS_ROM_LEN;
+
+    fn exe_with_bss(bss_addr: u32, bss_size: u32) -> Vec<u8> {
+        let mut exe = vec![0; EXE_HEADER_LEN];
+        exe[..8].copy_from_slice(b"PS-X EXE");
+        exe[0x028..0x02C].copy_from_slice(&bss_addr.to_le_bytes());
+        exe[0x02C..0x030].copy_from_slice(&bss_size.to_le_bytes());
+        exe
+    }
+
+    fn filled_memory() -> Memory {
+        let mut memory = Memory::new(vec![0; BIOS_ROM_LEN], Some(0)).unwrap();
+        memory.copy_to_main_ram(&vec![0xFF; MAIN_RAM_LEN], 0);
+        memory
+    }
+
+    #[test]
+    fn clear_bss_in_range() {
+        let mut memory = filled_memory();
+        clear_bss(&mut memory, &exe_with_bss(0x8001_0000, 0x100));
+
+        assert_eq!(memory.read_main_ram_u8(0x0FFFF), 0xFF);
+        assert!((0x10000..0x10100).all(|addr| memory.read_main_ram_u8(addr) == 0));
+        assert_eq!(memory.read_main_ram_u8(0x10100), 0xFF);
+    }
+
+    #[test]
+    fn clear_bss_out_of_range() {
+        // Wraps around the end of main RAM
+        let mut memory = filled_memory();
+        clear_bss(&mut memory, &exe_with_bss(0x801F_FFF0, 0x20));
+
+        assert_eq!(memory.read_main_ram_u8(0x1F_FFEF), 0xFF);
+        assert!((0x1F_FFF0..0x20_0000).all(|addr| memory.read_main_ram_u8(addr) == 0));
+        assert!((0..0x10).all(|addr| memory.read_main_ram_u8(addr) == 0));
+        assert_eq!(memory.read_main_ram_u8(0x10), 0xFF);
+
+        // Size larger than main RAM is clamped instead of allocating or indexing out of bounds
+        let mut memory = filled_memory();
+        clear_bss(&mut memory, &exe_with_bss(0xFFFF_FFFF, u32::MAX));
+        assert!((0..MAIN_RAM_LEN as u32).all(|addr| memory.read_main_ram_u8(addr) == 0));
+    }
+}
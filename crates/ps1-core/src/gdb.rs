@@ -0,0 +1,930 @@
+//! GDB remote serial protocol (RSP) server
+//!
+//! Lets GDB attach to the emulated CPU over TCP, e.g.:
+//! ```text
+//! gdb-multiarch -ex 'set architecture mips:3000' -ex 'set endian little' \
+//!     -ex 'target remote localhost:3333' program.elf
+//! ```
+//!
+//! The server does not own the emulator or run it. The emulation loop should call
+//! [`GdbServer::poll`] regularly, only call `tick()` while [`GdbServer::is_halted`] returns `false`,
+//! and pass every [`TickEffect::DebugStop`] to [`GdbServer::notify_stop`].
+//!
+//! Breakpoints set from GDB (both software and hardware) are implemented using the emulator's
+//! execution breakpoints rather than by patching memory with BREAK instructions.
+//!
+//! [`TickEffect::DebugStop`]: crate::api::TickEffect::DebugStop
+
+use crate::api::Ps1Emulator;
+use crate::cpu;
+use crate::debug::{BreakpointId, Cop0Register, CpuRegister, StopReason, WatchKind, WatchpointId};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+use std::{io, thread};
+
+// GDB's default MIPS register layout: 32 GPRs, SR, LO, HI, BadVAddr, Cause, PC, 32 FPRs, FCSR, FIR
+const NUM_GDB_REGISTERS: u32 = 72;
+
+const PACKET_SIZE: usize = 0x4000;
+
+// POSIX signal numbers used in stop replies
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+// Escapes '#', '$', '}', and '*' in binary data; the next byte is XORed with 0x20
+const ESCAPE: u8 = b'}';
+
+/// The parts of the emulator that GDB can inspect and control. Packet handlers only go through
+/// this trait so that they can be tested without a full emulator.
+trait Target {
+    fn read_cpu_register(&self, register: CpuRegister) -> u32;
+
+    fn write_cpu_register(&mut self, register: CpuRegister, value: u32);
+
+    fn peek_memory(&self, address: u32) -> Option<u8>;
+
+    fn poke_memory(&mut self, address: u32, value: u8) -> bool;
+
+    fn add_breakpoint(&mut self, pc: u32) -> BreakpointId;
+
+    fn remove_breakpoint(&mut self, id: BreakpointId) -> bool;
+
+    fn add_watchpoint(&mut self, range: RangeInclusive<u32>, kind: WatchKind) -> WatchpointId;
+
+    fn remove_watchpoint(&mut self, id: WatchpointId) -> bool;
+
+    fn request_step(&mut self);
+
+    fn reset(&mut self);
+}
+
+impl Target for Ps1Emulator {
+    fn read_cpu_register(&self, register: CpuRegister) -> u32 {
+        Ps1Emulator::read_cpu_register(self, register)
+    }
+
+    fn write_cpu_register(&mut self, register: CpuRegister, value: u32) {
+        Ps1Emulator::write_cpu_register(self, register, value);
+    }
+
+    fn peek_memory(&self, address: u32) -> Option<u8> {
+        Ps1Emulator::peek_memory(self, address)
+    }
+
+    fn poke_memory(&mut self, address: u32, value: u8) -> bool {
+        Ps1Emulator::poke_memory(self, address, value)
+    }
+
+    fn add_breakpoint(&mut self, pc: u32) -> BreakpointId {
+        Ps1Emulator::add_breakpoint(self, pc)
+    }
+
+    fn remove_breakpoint(&mut self, id: BreakpointId) -> bool {
+        Ps1Emulator::remove_breakpoint(self, id)
+    }
+
+    fn add_watchpoint(&mut self, range: RangeInclusive<u32>, kind: WatchKind) -> WatchpointId {
+        Ps1Emulator::add_watchpoint(self, range, kind)
+    }
+
+    fn remove_watchpoint(&mut self, id: WatchpointId) -> bool {
+        Ps1Emulator::remove_watchpoint(self, id)
+    }
+
+    fn request_step(&mut self) {
+        Ps1Emulator::request_step(self);
+    }
+
+    fn reset(&mut self) {
+        Ps1Emulator::reset(self);
+    }
+}
+
+fn gdb_register(n: u32) -> Option<CpuRegister> {
+    let register = match n {
+        0..=31 => CpuRegister::Gpr(n as u8),
+        32 => CpuRegister::Cop0(Cop0Register::Status),
+        33 => CpuRegister::Lo,
+        34 => CpuRegister::Hi,
+        35 => CpuRegister::Cop0(Cop0Register::BadVaddr),
+        36 => CpuRegister::Cop0(Cop0Register::Cause),
+        37 => CpuRegister::Pc,
+        // The PS1 has no FPU
+        _ => return None,
+    };
+
+    Some(register)
+}
+
+fn parse_hex(s: &str) -> Option<u32> {
+    u32::from_str_radix(s, 16).ok()
+}
+
+fn parse_hex_bytes(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
+}
+
+// Register values are sent in target byte order, which is little-endian
+fn push_register_hex(out: &mut String, value: u32) {
+    for byte in value.to_le_bytes() {
+        write!(out, "{byte:02x}").unwrap();
+    }
+}
+
+fn parse_register_hex(s: &str) -> Option<u32> {
+    let bytes: [u8; 4] = parse_hex_bytes(s)?.try_into().ok()?;
+    Some(u32::from_le_bytes(bytes))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(2 * bytes.len()), |mut out, byte| {
+        write!(out, "{byte:02x}").unwrap();
+        out
+    })
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0_u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut bytes = data.iter().copied();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            ESCAPE => out.extend(bytes.next().map(|escaped| escaped ^ 0x20)),
+            _ => out.push(byte),
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct WatchpointKey {
+    address: u32,
+    len: u32,
+    kind: WatchKind,
+}
+
+#[derive(Debug)]
+struct Connection {
+    stream: TcpStream,
+    read_buffer: Vec<u8>,
+    halted: bool,
+    last_stop_reply: String,
+    breakpoints: HashMap<u32, BreakpointId>,
+    watchpoints: HashMap<WatchpointKey, WatchpointId>,
+}
+
+enum PacketResult {
+    Reply(String),
+    NoReply,
+    Disconnect,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            stream,
+            read_buffer: Vec::with_capacity(PACKET_SIZE),
+            // The target should be stopped when GDB attaches
+            halted: true,
+            last_stop_reply: format!("S{SIGTRAP:02x}"),
+            breakpoints: HashMap::new(),
+            watchpoints: HashMap::new(),
+        })
+    }
+
+    fn write_all(&mut self, mut bytes: &[u8]) -> io::Result<()> {
+        while !bytes.is_empty() {
+            match self.stream.write(bytes) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => bytes = &bytes[n..],
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn send_packet(&mut self, data: &str) -> io::Result<()> {
+        log::trace!("GDB <- {data}");
+
+        let packet = format!("${data}#{:02x}", checksum(data.as_bytes()));
+        self.write_all(packet.as_bytes())
+    }
+
+    fn stop(&mut self, reply: String) -> io::Result<()> {
+        self.halted = true;
+        self.send_packet(&reply)?;
+        self.last_stop_reply = reply;
+        Ok(())
+    }
+
+    // Returns false if the connection was closed
+    fn read_available(&mut self) -> io::Result<bool> {
+        let mut buf = [0; 4096];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Ok(false),
+                Ok(n) => self.read_buffer.extend_from_slice(&buf[..n]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(true),
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    // Returns the next complete packet in the read buffer, if any. Handles acks and interrupt
+    // requests that appear between packets
+    fn next_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            let Some(&first) = self.read_buffer.first() else { return Ok(None) };
+
+            match first {
+                b'$' => {
+                    let Some(end) = self.read_buffer.iter().position(|&b| b == b'#') else {
+                        return Ok(None);
+                    };
+                    if self.read_buffer.len() < end + 3 {
+                        return Ok(None);
+                    }
+
+                    let data = self.read_buffer[1..end].to_vec();
+                    let expected_checksum =
+                        std::str::from_utf8(&self.read_buffer[end + 1..end + 3])
+                            .ok()
+                            .and_then(|s| u8::from_str_radix(s, 16).ok());
+                    self.read_buffer.drain(..end + 3);
+
+                    if expected_checksum != Some(checksum(&data)) {
+                        log::warn!("GDB packet checksum mismatch, requesting retransmit");
+                        self.write_all(b"-")?;
+                        continue;
+                    }
+
+                    // The checksum covers the data as sent, before unescaping
+                    self.write_all(b"+")?;
+                    return Ok(Some(unescape(&data)));
+                }
+                0x03 => {
+                    // Ctrl-C from GDB
+                    self.read_buffer.remove(0);
+                    if !self.halted {
+                        self.stop(format!("S{SIGINT:02x}"))?;
+                    }
+                }
+                _ => {
+                    // Acks, or garbage between packets
+                    self.read_buffer.remove(0);
+                }
+            }
+        }
+    }
+
+    fn handle_packet(&mut self, packet: &[u8], target: &mut impl Target) -> PacketResult {
+        log::trace!("GDB -> {}", String::from_utf8_lossy(packet));
+
+        let Some((&command, args)) = packet.split_first() else { return PacketResult::NoReply };
+
+        // X is the only packet with binary data; everything else is ASCII
+        if command == b'X' {
+            return PacketResult::Reply(write_memory_binary(target, args));
+        }
+        let args = &*String::from_utf8_lossy(args);
+
+        let reply = match command {
+            b'?' => self.last_stop_reply.clone(),
+            b'g' => read_all_registers(target),
+            b'G' => write_all_registers(target, args),
+            b'p' => parse_hex(args).map_or_else(error_reply, |n| read_register(target, n)),
+            b'P' => write_register(target, args),
+            b'm' => read_memory(target, args),
+            b'M' => write_memory(target, args),
+            b'c' | b's' => {
+                if !args.is_empty() {
+                    let Some(pc) = parse_hex(args) else {
+                        return PacketResult::Reply(error_reply());
+                    };
+                    target.write_cpu_register(CpuRegister::Pc, pc);
+                }
+
+                if command == b's' {
+                    target.request_step();
+                }
+
+                // The stop reply is sent later by notify_stop() or by a Ctrl-C interrupt
+                self.halted = false;
+                return PacketResult::NoReply;
+            }
+            b'Z' | b'z' => self.handle_breakpoint_packet(command == b'Z', args, target),
+            b'D' => {
+                let _ = self.send_packet("OK");
+                return PacketResult::Disconnect;
+            }
+            b'k' => return PacketResult::Disconnect,
+            b'H' | b'T' => "OK".into(),
+            b'q' => handle_query(args, target),
+            _ => String::new(),
+        };
+
+        PacketResult::Reply(reply)
+    }
+
+    fn handle_breakpoint_packet(
+        &mut self,
+        insert: bool,
+        args: &str,
+        target: &mut impl Target,
+    ) -> String {
+        let mut fields = args.split(',');
+        let (Some(kind), Some(address), Some(len)) = (
+            fields.next().and_then(parse_hex),
+            fields.next().and_then(parse_hex),
+            fields.next().and_then(parse_hex),
+        ) else {
+            return error_reply();
+        };
+
+        let watch_kind = match kind {
+            // Software and hardware breakpoints are handled identically
+            0 | 1 => {
+                if insert {
+                    self.breakpoints
+                        .entry(address)
+                        .or_insert_with(|| target.add_breakpoint(address));
+                } else if let Some(id) = self.breakpoints.remove(&address) {
+                    target.remove_breakpoint(id);
+                }
+
+                return "OK".into();
+            }
+            2 => WatchKind::Write,
+            3 => WatchKind::Read,
+            4 => WatchKind::ReadWrite,
+            _ => return String::new(),
+        };
+
+        let key = WatchpointKey { address, len: len.max(1), kind: watch_kind };
+        if insert {
+            self.watchpoints.entry(key).or_insert_with(|| {
+                target.add_watchpoint(address..=address + (key.len - 1), watch_kind)
+            });
+        } else if let Some(id) = self.watchpoints.remove(&key) {
+            target.remove_watchpoint(id);
+        }
+
+        "OK".into()
+    }
+
+    fn stop_reply(&self, reason: StopReason) -> String {
+        match reason {
+            StopReason::Breakpoint { .. } | StopReason::Step => format!("S{SIGTRAP:02x}"),
+            StopReason::Watchpoint { id, address, .. } => {
+                let Some((key, _)) = self.watchpoints.iter().find(|&(_, &watch_id)| watch_id == id)
+                else {
+                    return format!("S{SIGTRAP:02x}");
+                };
+
+                // Report the address in the same address space that GDB used when setting the
+                // watchpoint; the emulator reports physical addresses
+                let offset = address.wrapping_sub(key.address & 0x1FFFFFFF);
+                let address = key.address.wrapping_add(offset);
+
+                let watch_type = match key.kind {
+                    WatchKind::Write => "watch",
+                    WatchKind::Read => "rwatch",
+                    WatchKind::ReadWrite => "awatch",
+                };
+                format!("T{SIGTRAP:02x}{watch_type}:{address:08x};")
+            }
+        }
+    }
+
+    fn remove_all_breakpoints(&mut self, target: &mut impl Target) {
+        for (_, id) in self.breakpoints.drain() {
+            target.remove_breakpoint(id);
+        }
+
+        for (_, id) in self.watchpoints.drain() {
+            target.remove_watchpoint(id);
+        }
+    }
+}
+
+fn error_reply() -> String {
+    "E01".into()
+}
+
+fn read_all_registers(target: &impl Target) -> String {
+    let mut out = String::with_capacity(8 * NUM_GDB_REGISTERS as usize);
+    for n in 0..NUM_GDB_REGISTERS {
+        let value = gdb_register(n).map_or(0, |register| target.read_cpu_register(register));
+        push_register_hex(&mut out, value);
+    }
+    out
+}
+
+fn write_all_registers(target: &mut impl Target, args: &str) -> String {
+    for n in 0..NUM_GDB_REGISTERS {
+        let start = 8 * n as usize;
+        let Some(value) = args.get(start..start + 8).and_then(parse_register_hex) else { break };
+
+        if let Some(register) = gdb_register(n) {
+            target.write_cpu_register(register, value);
+        }
+    }
+
+    "OK".into()
+}
+
+fn read_register(target: &impl Target, n: u32) -> String {
+    let mut out = String::with_capacity(8);
+    let value = gdb_register(n).map_or(0, |register| target.read_cpu_register(register));
+    push_register_hex(&mut out, value);
+    out
+}
+
+fn write_register(target: &mut impl Target, args: &str) -> String {
+    let Some((n, value)) = args.split_once('=') else { return error_reply() };
+    let (Some(n), Some(value)) = (parse_hex(n), parse_register_hex(value)) else {
+        return error_reply();
+    };
+
+    if let Some(register) = gdb_register(n) {
+        target.write_cpu_register(register, value);
+    }
+
+    "OK".into()
+}
+
+fn read_memory(target: &impl Target, args: &str) -> String {
+    let Some((address, len)) = args.split_once(',') else { return error_reply() };
+    let (Some(address), Some(len)) = (parse_hex(address), parse_hex(len)) else {
+        return error_reply();
+    };
+
+    let len = (len as usize).min(PACKET_SIZE / 2);
+    let bytes: Vec<u8> =
+        (0..len as u32).map_while(|i| target.peek_memory(address.wrapping_add(i))).collect();
+
+    // A partial read is allowed, but an empty reply is not
+    if bytes.is_empty() && len != 0 {
+        return error_reply();
+    }
+
+    to_hex(&bytes)
+}
+
+fn write_memory(target: &mut impl Target, args: &str) -> String {
+    let Some((location, data)) = args.split_once(':') else { return error_reply() };
+    let Some((address, len)) = location.split_once(',') else { return error_reply() };
+    let (Some(address), Some(len), Some(data)) =
+        (parse_hex(address), parse_hex(len), parse_hex_bytes(data))
+    else {
+        return error_reply();
+    };
+
+    poke_all(target, address, len, &data)
+}
+
+// Same as M, but the data is sent as escaped binary instead of hex
+fn write_memory_binary(target: &mut impl Target, args: &[u8]) -> String {
+    let Some(colon) = args.iter().position(|&byte| byte == b':') else { return error_reply() };
+    let Ok(location) = std::str::from_utf8(&args[..colon]) else { return error_reply() };
+    let Some((address, len)) = location.split_once(',') else { return error_reply() };
+    let (Some(address), Some(len)) = (parse_hex(address), parse_hex(len)) else {
+        return error_reply();
+    };
+
+    poke_all(target, address, len, &args[colon + 1..])
+}
+
+fn poke_all(target: &mut impl Target, address: u32, len: u32, data: &[u8]) -> String {
+    if data.len() != len as usize {
+        return error_reply();
+    }
+
+    for (i, &byte) in (0..).zip(data) {
+        if !target.poke_memory(address.wrapping_add(i), byte) {
+            return error_reply();
+        }
+    }
+
+    "OK".into()
+}
+
+fn handle_query(args: &str, target: &mut impl Target) -> String {
+    let (name, params) = args.split_once([':', ',']).unwrap_or((args, ""));
+
+    match name {
+        "Supported" => format!("PacketSize={PACKET_SIZE:x}"),
+        "Attached" => "1".into(),
+        "C" => "QC1".into(),
+        "fThreadInfo" => "m1".into(),
+        "sThreadInfo" => "l".into(),
+        "Rcmd" => {
+            let Some(command) = parse_hex_bytes(params) else { return error_reply() };
+            monitor_command(&String::from_utf8_lossy(&command), target)
+        }
+        _ => String::new(),
+    }
+}
+
+// Handles `monitor <command>` in GDB; the reply is hex-encoded console output
+fn monitor_command(command: &str, target: &mut impl Target) -> String {
+    let mut words = command.split_whitespace();
+    let output = match words.next() {
+        Some("reset") => {
+            target.reset();
+            "Emulator reset\n".into()
+        }
+        Some("disas") => {
+            let count = words.next().and_then(|count| count.parse().ok()).unwrap_or(8);
+            disassemble(target, count)
+        }
+        _ => "Supported commands:\n  reset\n  disas [count]\n".into(),
+    };
+
+    to_hex(output.as_bytes())
+}
+
+// Disassemble using the emulator's own disassembler, which uses raw register numbers and shows
+// exactly how the CPU will decode each opcode
+fn disassemble(target: &impl Target, count: u32) -> String {
+    let pc = target.read_cpu_register(CpuRegister::Pc);
+
+    let mut output = String::new();
+    for i in 0..count {
+        let address = pc.wrapping_add(4 * i);
+        let opcode = (0..4)
+            .map(|j| target.peek_memory(address.wrapping_add(j)))
+            .collect::<Option<Vec<_>>>()
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+        let Some(opcode) = opcode else { break };
+
+        let marker = if i == 0 { "=>" } else { "  " };
+        writeln!(output, "{marker} {address:08X}  {opcode:08X}  {}", cpu::instruction_str(opcode))
+            .unwrap();
+    }
+
+    output
+}
+
+pub struct GdbServer {
+    listener: TcpListener,
+    connection: Option<Connection>,
+}
+
+impl GdbServer {
+    /// Start listening for GDB connections on the given local port.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if unable to bind to the port.
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+
+        log::info!("GDB server listening on {}", listener.local_addr()?);
+
+        Ok(Self { listener, connection: None })
+    }
+
+    /// # Errors
+    ///
+    /// Will propagate any error from the underlying socket.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    #[must_use]
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// Whether GDB has the CPU halted. The emulator should not be ticked while this is `true`.
+    #[must_use]
+    pub fn is_halted(&self) -> bool {
+        self.connection.as_ref().is_some_and(|connection| connection.halted)
+    }
+
+    /// Accept a pending connection if there is one, then process any packets received from GDB.
+    /// Never blocks waiting for input.
+    pub fn poll(&mut self, emulator: &mut Ps1Emulator) {
+        if self.connection.is_none() {
+            match self.listener.accept() {
+                Ok((stream, addr)) => match Connection::new(stream) {
+                    Ok(connection) => {
+                        log::info!("GDB connected from {addr}");
+                        self.connection = Some(connection);
+                    }
+                    Err(err) => log::error!("Error initializing GDB connection: {err}"),
+                },
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(err) => log::error!("Error accepting GDB connection: {err}"),
+            }
+        }
+
+        if let Err(err) = self.process_packets(emulator) {
+            log::error!("GDB connection error: {err}");
+            self.disconnect(emulator);
+        }
+    }
+
+    fn process_packets(&mut self, emulator: &mut Ps1Emulator) -> io::Result<()> {
+        let Some(connection) = &mut self.connection else { return Ok(()) };
+
+        if !connection.read_available()? {
+            log::info!("GDB disconnected");
+            self.disconnect(emulator);
+            return Ok(());
+        }
+
+        while let Some(packet) = connection.next_packet()? {
+            match connection.handle_packet(&packet, emulator) {
+                PacketResult::Reply(reply) => connection.send_packet(&reply)?,
+                PacketResult::NoReply => {}
+                PacketResult::Disconnect => {
+                    log::info!("GDB detached");
+                    self.disconnect(emulator);
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Report that the emulator stopped, i.e. that `tick()` returned
+    /// [`TickEffect::DebugStop`](crate::api::TickEffect::DebugStop). Halts the emulator until
+    /// GDB resumes it.
+    pub fn notify_stop(&mut self, emulator: &mut Ps1Emulator, reason: StopReason) {
+        let Some(connection) = &mut self.connection else { return };
+
+        let reply = connection.stop_reply(reason);
+        if let Err(err) = connection.stop(reply) {
+            log::error!("GDB connection error: {err}");
+            self.disconnect(emulator);
+        }
+    }
+
+    fn disconnect(&mut self, emulator: &mut Ps1Emulator) {
+        if let Some(mut connection) = self.connection.take() {
+            connection.remove_all_breakpoints(emulator);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debug::Debugger;
+
+    #[derive(Default)]
+    struct FakeTarget {
+        registers: Vec<(CpuRegister, u32)>,
+        memory: HashMap<u32, u8>,
+        // Only used to allocate breakpoint and watchpoint IDs
+        debugger: Debugger,
+        breakpoints: HashMap<BreakpointId, u32>,
+        watchpoints: HashMap<WatchpointId, (RangeInclusive<u32>, WatchKind)>,
+    }
+
+    impl FakeTarget {
+        fn with_memory(range: RangeInclusive<u32>) -> Self {
+            Self { memory: range.map(|address| (address, 0)).collect(), ..Self::default() }
+        }
+    }
+
+    impl Target for FakeTarget {
+        fn read_cpu_register(&self, register: CpuRegister) -> u32 {
+            self.registers.iter().find(|&&(r, _)| r == register).map_or(0, |&(_, value)| value)
+        }
+
+        fn write_cpu_register(&mut self, register: CpuRegister, value: u32) {
+            self.registers.retain(|&(r, _)| r != register);
+            self.registers.push((register, value));
+        }
+
+        fn peek_memory(&self, address: u32) -> Option<u8> {
+            self.memory.get(&address).copied()
+        }
+
+        fn poke_memory(&mut self, address: u32, value: u8) -> bool {
+            self.memory.get_mut(&address).map(|byte| *byte = value).is_some()
+        }
+
+        fn add_breakpoint(&mut self, pc: u32) -> BreakpointId {
+            let id = self.debugger.add_breakpoint(pc);
+            self.breakpoints.insert(id, pc);
+            id
+        }
+
+        fn remove_breakpoint(&mut self, id: BreakpointId) -> bool {
+            self.breakpoints.remove(&id).is_some()
+        }
+
+        fn add_watchpoint(&mut self, range: RangeInclusive<u32>, kind: WatchKind) -> WatchpointId {
+            let id = self.debugger.add_watchpoint(range.clone(), kind);
+            self.watchpoints.insert(id, (range, kind));
+            id
+        }
+
+        fn remove_watchpoint(&mut self, id: WatchpointId) -> bool {
+            self.watchpoints.remove(&id).is_some()
+        }
+
+        fn request_step(&mut self) {}
+
+        fn reset(&mut self) {}
+    }
+
+    // Returns the server side of a loopback connection and the socket that GDB would use
+    fn connect() -> (Connection, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        (Connection::new(stream).unwrap(), client)
+    }
+
+    fn read_client(client: &mut TcpStream, len: usize) -> Vec<u8> {
+        let mut buf = vec![0; len];
+        client.read_exact(&mut buf).unwrap();
+        buf
+    }
+
+    fn frame(data: &[u8]) -> Vec<u8> {
+        let mut packet = vec![b'$'];
+        packet.extend_from_slice(data);
+        packet.extend_from_slice(format!("#{:02x}", checksum(data)).as_bytes());
+        packet
+    }
+
+    fn reply(connection: &mut Connection, packet: &str, target: &mut FakeTarget) -> String {
+        match connection.handle_packet(packet.as_bytes(), target) {
+            PacketResult::Reply(reply) => reply,
+            PacketResult::NoReply | PacketResult::Disconnect => panic!("no reply to '{packet}'"),
+        }
+    }
+
+    #[test]
+    fn packet_framing() {
+        let (mut connection, mut client) = connect();
+
+        assert_eq!(frame(b"g"), b"$g#67");
+
+        // Acks and garbage between packets are skipped
+        connection.read_buffer.extend_from_slice(b"+xyz");
+        connection.read_buffer.extend(frame(b"m80010000,4"));
+        assert_eq!(connection.next_packet().unwrap(), Some(b"m80010000,4".to_vec()));
+        assert_eq!(read_client(&mut client, 1), b"+");
+        assert_eq!(connection.next_packet().unwrap(), None);
+
+        // Incomplete packets stay buffered until the checksum arrives
+        connection.read_buffer.extend_from_slice(b"$g#6");
+        assert_eq!(connection.next_packet().unwrap(), None);
+        connection.read_buffer.push(b'7');
+        assert_eq!(connection.next_packet().unwrap(), Some(b"g".to_vec()));
+        assert_eq!(read_client(&mut client, 1), b"+");
+    }
+
+    #[test]
+    fn checksum_mismatch_requests_retransmit() {
+        let (mut connection, mut client) = connect();
+
+        connection.read_buffer.extend_from_slice(b"$g#00$g#zz");
+        connection.read_buffer.extend(frame(b"?"));
+        assert_eq!(connection.next_packet().unwrap(), Some(b"?".to_vec()));
+        assert_eq!(read_client(&mut client, 3), b"--+");
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(unescape(b"a}\x03b}]c}\x04}\x0a"), b"a#b}c$*");
+        assert_eq!(unescape(b"abc"), b"abc");
+        // A trailing escape character has nothing to escape
+        assert_eq!(unescape(b"ab}"), b"ab");
+
+        let (mut connection, mut client) = connect();
+
+        // The checksum is computed over the escaped data
+        connection.read_buffer.extend(frame(b"X100,3:}\x03}]\x00"));
+        let packet = connection.next_packet().unwrap().unwrap();
+        assert_eq!(packet, b"X100,3:#}\x00");
+        assert_eq!(read_client(&mut client, 1), b"+");
+
+        let mut target = FakeTarget::with_memory(0x100..=0x103);
+        target.memory.insert(0x102, 0xFF);
+        let PacketResult::Reply(reply) = connection.handle_packet(&packet, &mut target) else {
+            panic!("no reply to X packet");
+        };
+        assert_eq!(reply, "OK");
+        assert_eq!(target.memory[&0x100], b'#');
+        assert_eq!(target.memory[&0x101], b'}');
+        assert_eq!(target.memory[&0x102], 0);
+
+        // Zero-length X packets are used by GDB to probe for support
+        let PacketResult::Reply(reply) = connection.handle_packet(b"X100,0:", &mut target) else {
+            panic!("no reply to X packet");
+        };
+        assert_eq!(reply, "OK");
+    }
+
+    #[test]
+    fn read_write_registers() {
+        let (mut connection, _client) = connect();
+        let mut target = FakeTarget::default();
+        target.write_cpu_register(CpuRegister::Gpr(4), 0x12345678);
+        target.write_cpu_register(CpuRegister::Pc, 0x80010000);
+
+        let registers = reply(&mut connection, "g", &mut target);
+        assert_eq!(registers.len(), 8 * NUM_GDB_REGISTERS as usize);
+        assert_eq!(&registers[8 * 4..8 * 5], "78563412");
+        assert_eq!(&registers[8 * 37..8 * 38], "00000180");
+        // FPU registers do not exist and read as 0
+        assert_eq!(&registers[8 * 38..8 * 39], "00000000");
+
+        let mut registers = registers.into_bytes();
+        registers[8 * 5..8 * 6].copy_from_slice(b"efbeadde");
+        registers[8 * 33..8 * 34].copy_from_slice(b"01000000");
+        let registers = String::from_utf8(registers).unwrap();
+        assert_eq!(reply(&mut connection, &format!("G{registers}"), &mut target), "OK");
+        assert_eq!(target.read_cpu_register(CpuRegister::Gpr(4)), 0x12345678);
+        assert_eq!(target.read_cpu_register(CpuRegister::Gpr(5)), 0xDEADBEEF);
+        assert_eq!(target.read_cpu_register(CpuRegister::Lo), 1);
+        assert_eq!(target.read_cpu_register(CpuRegister::Pc), 0x80010000);
+    }
+
+    #[test]
+    fn read_write_memory() {
+        let (mut connection, _client) = connect();
+        let mut target = FakeTarget::with_memory(0x80000100..=0x80000103);
+
+        assert_eq!(reply(&mut connection, "M80000100,4:0102abcd", &mut target), "OK");
+        assert_eq!(reply(&mut connection, "m80000100,4", &mut target), "0102abcd");
+
+        // Partial reads stop at the first unmapped address
+        assert_eq!(reply(&mut connection, "m80000102,4", &mut target), "abcd");
+        assert_eq!(reply(&mut connection, "m80000200,4", &mut target), "E01");
+
+        // Length does not match the data
+        assert_eq!(reply(&mut connection, "M80000100,2:010203", &mut target), "E01");
+        assert_eq!(reply(&mut connection, "M80000103,2:ffff", &mut target), "E01");
+        assert_eq!(reply(&mut connection, "M80000100,1:zz", &mut target), "E01");
+        assert_eq!(reply(&mut connection, "m80000100", &mut target), "E01");
+    }
+
+    #[test]
+    fn breakpoints_and_watchpoints() {
+        let (mut connection, _client) = connect();
+        let mut target = FakeTarget::default();
+
+        assert_eq!(reply(&mut connection, "Z0,80010000,4", &mut target), "OK");
+        assert_eq!(reply(&mut connection, "Z1,80010000,4", &mut target), "OK");
+        assert_eq!(target.breakpoints.values().copied().collect::<Vec<_>>(), [0x80010000]);
+
+        assert_eq!(reply(&mut connection, "z0,80010000,4", &mut target), "OK");
+        assert!(target.breakpoints.is_empty());
+        assert_eq!(reply(&mut connection, "z0,80010000,4", &mut target), "OK");
+
+        assert_eq!(reply(&mut connection, "Z2,80001000,4", &mut target), "OK");
+        assert_eq!(reply(&mut connection, "Z4,80002000,0", &mut target), "OK");
+        let mut watchpoints: Vec<_> = target.watchpoints.values().cloned().collect();
+        watchpoints.sort_by_key(|(range, _)| *range.start());
+        assert_eq!(
+            watchpoints,
+            [
+                (0x80001000..=0x80001003, WatchKind::Write),
+                (0x80002000..=0x80002000, WatchKind::ReadWrite),
+            ]
+        );
+
+        assert_eq!(reply(&mut connection, "z2,80001000,4", &mut target), "OK");
+        assert_eq!(target.watchpoints.len(), 1);
+
+        connection.remove_all_breakpoints(&mut target);
+        assert!(target.watchpoints.is_empty());
+
+        // Unsupported breakpoint types get an empty reply
+        assert_eq!(reply(&mut connection, "Z5,80010000,4", &mut target), "");
+        assert_eq!(reply(&mut connection, "Z0,80010000", &mut target), "E01");
+    }
+}
@@ -20,11 +20,14 @@ use bincode::{Decode, Encode};
 use cfg_if::cfg_if;
 use proc_macros::SaveState;
 use std::ops::Add;
+#[cfg(feature = "wgpu")]
 use std::sync::Arc;
 
 use crate::gpu::rasterizer::Rasterizer;
 
+use crate::api::CpuFrame;
 use crate::boxedarray::BoxedArray;
+#[cfg(feature = "wgpu")]
 use crate::gpu::rasterizer::wgpuhardware::WgpuRasterizerConfig;
 use crate::interrupts::InterruptRegisters;
 use crate::pgxp::{PgxpConfig, PreciseVertex};
@@ -62,6 +65,7 @@ impl Default for DisplayConfig {
 }
 
 impl DisplayConfig {
+    #[cfg(feature = "wgpu")]
     pub(crate) fn to_wgpu_rasterizer_config(self) -> WgpuRasterizerConfig {
         WgpuRasterizerConfig {
             resolution_scale: self.hardware_resolution_scale,
@@ -72,12 +76,46 @@ impl DisplayConfig {
     }
 }
 
+/// Graphics device that the GPU can render through.
+///
+/// Without a wgpu device, the hardware rasterizer is not available and frames can only be output
+/// as CPU-side pixel buffers.
+#[derive(Debug, Clone, Default)]
+pub enum GraphicsBackend {
+    #[default]
+    Headless,
+    #[cfg(feature = "wgpu")]
+    Wgpu { device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue> },
+}
+
+impl GraphicsBackend {
+    fn supports_hardware_rasterizer(&self) -> bool {
+        match self {
+            Self::Headless => false,
+            #[cfg(feature = "wgpu")]
+            Self::Wgpu { .. } => true,
+        }
+    }
+}
+
+#[cfg(feature = "wgpu")]
 #[derive(Debug)]
 pub struct WgpuResources {
     pub device: Arc<wgpu::Device>,
     pub queue: Arc<wgpu::Queue>,
     pub queued_command_buffers: Vec<wgpu::CommandBuffer>,
-    pub display_config: DisplayConfig,
+}
+
+#[cfg(feature = "wgpu")]
+impl WgpuResources {
+    fn from_backend(backend: GraphicsBackend) -> Option<Self> {
+        match backend {
+            GraphicsBackend::Headless => None,
+            GraphicsBackend::Wgpu { device, queue } => {
+                Some(Self { device, queue, queued_command_buffers: Vec::with_capacity(64) })
+            }
+        }
+    }
 }
 
 #[derive(SaveState)]
@@ -86,14 +124,25 @@ pub struct Gpu {
     gp0: Gp0State,
     gpu_read_buffer: u32,
     #[save_state(skip)]
-    wgpu_resources: WgpuResources,
+    display_config: DisplayConfig,
+    #[cfg(feature = "wgpu")]
+    #[save_state(skip)]
+    wgpu_resources: Option<WgpuResources>,
     #[save_state(to = RasterizerState)]
     rasterizer: Rasterizer,
     pgxp_config: PgxpConfig,
 }
 
 #[must_use]
-fn check_rasterizer_type(rasterizer_type: RasterizerType) -> RasterizerType {
+fn check_rasterizer_type(
+    rasterizer_type: RasterizerType,
+    backend: &GraphicsBackend,
+) -> RasterizerType {
+    if rasterizer_type == RasterizerType::WgpuHardware && !backend.supports_hardware_rasterizer() {
+        log::error!("No wgpu device available; hardware rasterizer will not work, not using it");
+        return RasterizerType::default();
+    }
+
     if rasterizer_type != RasterizerType::SimdSoftware {
         return rasterizer_type;
     }
@@ -118,27 +167,22 @@ fn check_rasterizer_type(rasterizer_type: RasterizerType) -> RasterizerType {
 
 impl Gpu {
     pub fn new(
-        wgpu_device: Arc<wgpu::Device>,
-        wgpu_queue: Arc<wgpu::Queue>,
+        backend: GraphicsBackend,
         mut display_config: DisplayConfig,
         pgxp_config: PgxpConfig,
     ) -> Self {
-        display_config.rasterizer_type = check_rasterizer_type(display_config.rasterizer_type);
+        display_config.rasterizer_type =
+            check_rasterizer_type(display_config.rasterizer_type, &backend);
 
-        let rasterizer = Rasterizer::new(&wgpu_device, &wgpu_queue, display_config, pgxp_config);
-
-        let wgpu_resources = WgpuResources {
-            device: wgpu_device,
-            queue: wgpu_queue,
-            queued_command_buffers: Vec::with_capacity(64),
-            display_config,
-        };
+        let rasterizer = Rasterizer::new(&backend, display_config, pgxp_config);
 
         Self {
             registers: Registers::new(),
             gp0: Gp0State::new(),
             gpu_read_buffer: 0,
-            wgpu_resources,
+            display_config,
+            #[cfg(feature = "wgpu")]
+            wgpu_resources: WgpuResources::from_backend(backend),
             rasterizer,
             pgxp_config,
         }
@@ -183,18 +227,34 @@ impl Gpu {
         self.handle_gp1_write(value, timers, scheduler, interrupt_registers);
     }
 
+    #[cfg(feature = "wgpu")]
     pub fn generate_frame_texture(
         &mut self,
     ) -> (&wgpu::Texture, impl Iterator<Item = wgpu::CommandBuffer> + '_) {
-        let frame =
-            self.rasterizer.generate_frame_texture(&self.registers, &mut self.wgpu_resources);
-        let command_buffers = self.wgpu_resources.queued_command_buffers.drain(..);
+        let wgpu_resources = self.wgpu_resources.as_mut().expect(
+            "Frame textures can only be generated if the GPU was created with a wgpu device",
+        );
+
+        let frame = self.rasterizer.generate_frame_texture(
+            &self.registers,
+            self.display_config,
+            wgpu_resources,
+        );
+        let command_buffers = wgpu_resources.queued_command_buffers.drain(..);
 
         (frame, command_buffers)
     }
 
+    pub fn generate_frame_buffer(&mut self) -> CpuFrame<'_> {
+        let pixel_aspect_ratio = self.pixel_aspect_ratio();
+        let (frame_size, rgba) =
+            self.rasterizer.generate_frame_buffer(&self.registers, self.display_config);
+
+        CpuFrame { rgba, width: frame_size.width, height: frame_size.height, pixel_aspect_ratio }
+    }
+
     pub fn pixel_aspect_ratio(&self) -> f64 {
-        if self.wgpu_resources.display_config.dump_vram {
+        if self.display_config.dump_vram {
             return 1.0;
         }
 
@@ -213,59 +273,68 @@ impl Gpu {
     }
 
     pub fn update_config(&mut self, mut display_config: DisplayConfig, pgxp_config: PgxpConfig) {
-        display_config.rasterizer_type = check_rasterizer_type(display_config.rasterizer_type);
+        let backend = self.graphics_backend();
+        display_config.rasterizer_type =
+            check_rasterizer_type(display_config.rasterizer_type, &backend);
 
-        let prev_rasterizer_type = self.wgpu_resources.display_config.rasterizer_type;
-        let prev_wgpu_rasterizer_config =
-            self.wgpu_resources.display_config.to_wgpu_rasterizer_config();
-        let prev_pgxp_config = self.pgxp_config;
-        self.wgpu_resources.display_config = display_config;
-        self.pgxp_config = pgxp_config;
+        let recreate_rasterizer =
+            self.display_config.rasterizer_type != display_config.rasterizer_type;
 
-        if prev_rasterizer_type != display_config.rasterizer_type
+        #[cfg(feature = "wgpu")]
+        let recreate_rasterizer = recreate_rasterizer
             || (display_config.rasterizer_type == RasterizerType::WgpuHardware
-                && (prev_wgpu_rasterizer_config != display_config.to_wgpu_rasterizer_config()
-                    || prev_pgxp_config != pgxp_config))
-        {
+                && (self.display_config.to_wgpu_rasterizer_config()
+                    != display_config.to_wgpu_rasterizer_config()
+                    || self.pgxp_config != pgxp_config));
+
+        self.display_config = display_config;
+        self.pgxp_config = pgxp_config;
+
+        if recreate_rasterizer {
             let vram = self.rasterizer.clone_vram();
             self.rasterizer = Rasterizer::from_state(
                 RasterizerState { vram },
-                &self.wgpu_resources.device,
-                &self.wgpu_resources.queue,
+                &backend,
                 display_config,
                 pgxp_config,
             );
         }
     }
 
-    pub fn get_wgpu_resources(&self) -> (Arc<wgpu::Device>, Arc<wgpu::Queue>) {
-        (Arc::clone(&self.wgpu_resources.device), Arc::clone(&self.wgpu_resources.queue))
+    #[cfg_attr(not(feature = "wgpu"), allow(clippy::unused_self))]
+    pub fn graphics_backend(&self) -> GraphicsBackend {
+        cfg_if! {
+            if #[cfg(feature = "wgpu")] {
+                if let Some(wgpu_resources) = &self.wgpu_resources {
+                    return GraphicsBackend::Wgpu {
+                        device: Arc::clone(&wgpu_resources.device),
+                        queue: Arc::clone(&wgpu_resources.queue),
+                    };
+                }
+            }
+        }
+
+        GraphicsBackend::Headless
     }
 
     pub fn from_state(
         state: GpuState,
-        wgpu_device: Arc<wgpu::Device>,
-        wgpu_queue: Arc<wgpu::Queue>,
-        display_config: DisplayConfig,
+        backend: GraphicsBackend,
+        mut display_config: DisplayConfig,
     ) -> Self {
-        let rasterizer = Rasterizer::from_state(
-            state.rasterizer,
-            &wgpu_device,
-            &wgpu_queue,
-            display_config,
-            state.pgxp_config,
-        );
+        display_config.rasterizer_type =
+            check_rasterizer_type(display_config.rasterizer_type, &backend);
+
+        let rasterizer =
+            Rasterizer::from_state(state.rasterizer, &backend, display_config, state.pgxp_config);
 
         Self {
             registers: state.registers,
             gp0: state.gp0,
             gpu_read_buffer: state.gpu_read_buffer,
-            wgpu_resources: WgpuResources {
-                device: wgpu_device,
-                queue: wgpu_queue,
-                queued_command_buffers: Vec::with_capacity(64),
-                display_config,
-            },
+            display_config,
+            #[cfg(feature = "wgpu")]
+            wgpu_resources: WgpuResources::from_backend(backend),
             rasterizer,
             pgxp_config: state.pgxp_config,
         }
@@ -279,6 +348,7 @@ pub struct Vertex {
 }
 
 impl Vertex {
+    #[cfg_attr(not(feature = "wgpu"), allow(dead_code))]
     pub const fn new(x: i32, y: i32) -> Self {
         Self { x, y }
     }
@@ -1,24 +1,30 @@
 //! Rasterizer interface and dispatch code
 
 use crate::api::{DisplayConfig, PgxpConfig};
+#[cfg(feature = "wgpu")]
+use crate::gpu::WgpuResources;
 use crate::gpu::gp0::{DrawSettings, SemiTransparencyMode, TexturePage, TextureWindow};
 use crate::gpu::rasterizer::naive::NaiveSoftwareRasterizer;
 use crate::gpu::rasterizer::simd::SimdSoftwareRasterizer;
+#[cfg(feature = "wgpu")]
 use crate::gpu::rasterizer::wgpuhardware::WgpuRasterizer;
 use crate::gpu::registers::{Registers, VerticalResolution};
-use crate::gpu::{Color, Vertex, VideoMode, Vram, WgpuResources};
+use crate::gpu::{Color, GraphicsBackend, Vertex, VideoMode, Vram};
 use crate::pgxp::PreciseVertex;
 use bincode::{Decode, Encode};
 use std::cmp;
 use std::fmt::{Display, Formatter};
 use std::ops::{Deref, DerefMut};
+#[cfg(feature = "wgpu")]
 use std::sync::Arc;
+#[cfg(feature = "wgpu")]
 use wgpu::PipelineCompilationOptions;
 
 pub mod naive;
 #[cfg(target_arch = "x86_64")]
 pub mod simd;
 mod software;
+#[cfg(feature = "wgpu")]
 pub mod wgpuhardware;
 
 #[cfg(not(target_arch = "x86_64"))]
@@ -58,6 +64,8 @@ pub type RectangleTextureMapping = TextureMapping<1>;
 #[derive(Debug)]
 pub struct DrawTriangleArgs {
     pub vertices: [Vertex; 3],
+    // Only used by the hardware rasterizer
+    #[cfg_attr(not(feature = "wgpu"), allow(dead_code))]
     pub pgxp_vertices: Option<[PreciseVertex; 3]>,
     pub shading: TriangleShading,
     pub semi_transparent: bool,
@@ -121,12 +129,21 @@ pub trait RasterizerInterface {
 
     fn vram_to_vram_blit(&mut self, args: VramVramBlitArgs);
 
+    #[cfg(feature = "wgpu")]
     fn generate_frame_texture(
         &mut self,
         registers: &Registers,
+        display_config: DisplayConfig,
         wgpu_resources: &mut WgpuResources,
     ) -> &wgpu::Texture;
 
+    /// Render the current display frame to a CPU-side buffer with 4 bytes per pixel (RGBA8).
+    fn generate_frame_buffer(
+        &mut self,
+        registers: &Registers,
+        display_config: DisplayConfig,
+    ) -> (FrameSize, &[u8]);
+
     fn clone_vram(&mut self) -> Vram;
 
     fn clear_texture_cache(&mut self) {}
@@ -169,25 +186,27 @@ impl DerefMut for Rasterizer {
 }
 
 impl Rasterizer {
+    #[cfg_attr(not(feature = "wgpu"), allow(unused_variables))]
     pub fn new(
-        wgpu_device: &Arc<wgpu::Device>,
-        wgpu_queue: &Arc<wgpu::Queue>,
+        backend: &GraphicsBackend,
         display_config: DisplayConfig,
         pgxp_config: PgxpConfig,
     ) -> Self {
-        match display_config.rasterizer_type {
-            RasterizerType::NaiveSoftware => {
-                Self(Box::new(NaiveSoftwareRasterizer::new(wgpu_device)))
+        match (display_config.rasterizer_type, backend) {
+            (RasterizerType::NaiveSoftware, _) => Self(Box::new(NaiveSoftwareRasterizer::new())),
+            (RasterizerType::SimdSoftware, _) => Self(Box::new(SimdSoftwareRasterizer::new())),
+            #[cfg(feature = "wgpu")]
+            (RasterizerType::WgpuHardware, GraphicsBackend::Wgpu { device, queue }) => {
+                Self(Box::new(WgpuRasterizer::new(
+                    Arc::clone(device),
+                    Arc::clone(queue),
+                    display_config.to_wgpu_rasterizer_config(),
+                    pgxp_config,
+                )))
             }
-            RasterizerType::SimdSoftware => {
-                Self(Box::new(SimdSoftwareRasterizer::new(wgpu_device)))
+            (RasterizerType::WgpuHardware, GraphicsBackend::Headless) => {
+                unreachable!("hardware rasterizer requires a wgpu device; should have fallen back")
             }
-            RasterizerType::WgpuHardware => Self(Box::new(WgpuRasterizer::new(
-                Arc::clone(wgpu_device),
-                Arc::clone(wgpu_queue),
-                display_config.to_wgpu_rasterizer_config(),
-                pgxp_config,
-            ))),
         }
     }
 
@@ -196,30 +215,34 @@ impl Rasterizer {
         RasterizerState { vram }
     }
 
+    #[cfg_attr(not(feature = "wgpu"), allow(unused_variables))]
     pub fn from_state(
         state: RasterizerState,
-        wgpu_device: &Arc<wgpu::Device>,
-        wgpu_queue: &Arc<wgpu::Queue>,
+        backend: &GraphicsBackend,
         display_config: DisplayConfig,
         pgxp_config: PgxpConfig,
     ) -> Self {
-        match display_config.rasterizer_type {
-            RasterizerType::NaiveSoftware => {
-                Self(Box::new(NaiveSoftwareRasterizer::from_vram(wgpu_device, &state.vram)))
+        match (display_config.rasterizer_type, backend) {
+            (RasterizerType::NaiveSoftware, _) => {
+                Self(Box::new(NaiveSoftwareRasterizer::from_vram(&state.vram)))
             }
-            RasterizerType::SimdSoftware => {
-                Self(Box::new(SimdSoftwareRasterizer::from_vram(wgpu_device, &state.vram)))
+            (RasterizerType::SimdSoftware, _) => {
+                Self(Box::new(SimdSoftwareRasterizer::from_vram(&state.vram)))
             }
-            RasterizerType::WgpuHardware => {
+            #[cfg(feature = "wgpu")]
+            (RasterizerType::WgpuHardware, GraphicsBackend::Wgpu { device, queue }) => {
                 let rasterizer = WgpuRasterizer::new(
-                    Arc::clone(wgpu_device),
-                    Arc::clone(wgpu_queue),
+                    Arc::clone(device),
+                    Arc::clone(queue),
                     display_config.to_wgpu_rasterizer_config(),
                     pgxp_config,
                 );
                 rasterizer.copy_vram_from(&state.vram);
                 Self(Box::new(rasterizer))
             }
+            (RasterizerType::WgpuHardware, GraphicsBackend::Headless) => {
+                unreachable!("hardware rasterizer requires a wgpu device; should have fallen back")
+            }
         }
     }
 }
@@ -300,9 +323,9 @@ struct FrameCoords {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct FrameSize {
-    width: u32,
-    height: u32,
+pub struct FrameSize {
+    pub width: u32,
+    pub height: u32,
 }
 
 impl Display for FrameSize {
@@ -400,11 +423,13 @@ fn compute_frame_location(
     )
 }
 
+#[cfg(feature = "wgpu")]
 #[derive(Debug)]
 struct ClearPipeline {
     pipeline: wgpu::RenderPipeline,
 }
 
+#[cfg(feature = "wgpu")]
 impl ClearPipeline {
     fn new(device: &wgpu::Device, frame_format: wgpu::TextureFormat) -> Self {
         let clear_module =
@@ -2,20 +2,22 @@
 
 #![allow(clippy::many_single_char_names)]
 
+use crate::api::DisplayConfig;
+#[cfg(feature = "wgpu")]
+use crate::gpu::WgpuResources;
 use crate::gpu::gp0::{
     DrawSettings, SemiTransparencyMode, TextureColorDepthBits, TexturePage, TextureWindow,
 };
 use crate::gpu::rasterizer::software::SoftwareRenderer;
 use crate::gpu::rasterizer::{
-    CpuVramBlitArgs, DrawLineArgs, DrawRectangleArgs, DrawTriangleArgs, LineShading,
+    CpuVramBlitArgs, DrawLineArgs, DrawRectangleArgs, DrawTriangleArgs, FrameSize, LineShading,
     RasterizerInterface, RectangleTextureMapping, TextureMappingMode, TriangleShading,
     TriangleTextureMapping, VramVramBlitArgs, cross_product_z, software, swap_vertices,
     vertices_valid,
 };
 use crate::gpu::registers::Registers;
-use crate::gpu::{Color, Vertex, Vram, VramArray, WgpuResources};
+use crate::gpu::{Color, Vertex, Vram, VramArray};
 use std::cmp;
-use wgpu::Texture;
 
 const DITHER_TABLE: &[[i8; 4]; 4] =
     &[[-4, 0, -3, 1], [2, -2, 3, -1], [-3, 1, -4, 0], [3, -1, 2, -2]];
@@ -77,13 +79,13 @@ pub struct NaiveSoftwareRasterizer {
 }
 
 impl NaiveSoftwareRasterizer {
-    pub fn new(device: &wgpu::Device) -> Self {
-        Self { vram: Vram::new(), renderer: SoftwareRenderer::new(device) }
+    pub fn new() -> Self {
+        Self { vram: Vram::new(), renderer: SoftwareRenderer::new() }
     }
 
-    pub fn from_vram(device: &wgpu::Device, vram: &Vram) -> Self {
+    pub fn from_vram(vram: &Vram) -> Self {
         let vram_array: Box<VramArray> = vram.to_vec().into_boxed_slice().try_into().unwrap();
-        Self { vram: vram_array.into(), renderer: SoftwareRenderer::new(device) }
+        Self { vram: vram_array.into(), renderer: SoftwareRenderer::new() }
     }
 }
 
@@ -469,12 +471,22 @@ impl RasterizerInterface for NaiveSoftwareRasterizer {
         software::vram_to_vram_blit(&mut self.vram, args);
     }
 
+    #[cfg(feature = "wgpu")]
     fn generate_frame_texture(
         &mut self,
         registers: &Registers,
+        display_config: DisplayConfig,
         wgpu_resources: &mut WgpuResources,
-    ) -> &Texture {
-        self.renderer.generate_frame_texture(registers, wgpu_resources, &self.vram)
+    ) -> &wgpu::Texture {
+        self.renderer.generate_frame_texture(registers, display_config, wgpu_resources, &self.vram)
+    }
+
+    fn generate_frame_buffer(
+        &mut self,
+        registers: &Registers,
+        display_config: DisplayConfig,
+    ) -> (FrameSize, &[u8]) {
+        self.renderer.generate_frame_buffer(registers, display_config, &self.vram)
     }
 
     fn clone_vram(&mut self) -> Vram {
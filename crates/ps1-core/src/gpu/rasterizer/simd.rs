@@ -4,14 +4,18 @@
 
 mod avx2;
 
+use crate::api::DisplayConfig;
+#[cfg(feature = "wgpu")]
+use crate::gpu::WgpuResources;
 use crate::gpu::gp0::DrawSettings;
 use crate::gpu::rasterizer::software::SoftwareRenderer;
 use crate::gpu::rasterizer::{
-    CpuVramBlitArgs, DrawLineArgs, DrawRectangleArgs, DrawTriangleArgs, RasterizerInterface,
-    VramVramBlitArgs, cross_product_z, software, swap_vertices, vertices_valid,
+    CpuVramBlitArgs, DrawLineArgs, DrawRectangleArgs, DrawTriangleArgs, FrameSize,
+    RasterizerInterface, VramVramBlitArgs, cross_product_z, software, swap_vertices,
+    vertices_valid,
 };
 use crate::gpu::registers::Registers;
-use crate::gpu::{Color, Vertex, Vram, VramArray, WgpuResources};
+use crate::gpu::{Color, Vertex, Vram, VramArray};
 use std::cmp;
 use std::ops::{Deref, DerefMut};
 
@@ -54,16 +58,16 @@ pub struct SimdSoftwareRasterizer {
 
 impl SimdSoftwareRasterizer {
     #[allow(clippy::large_stack_arrays)]
-    pub fn new(device: &wgpu::Device) -> Self {
-        Self { vram: AlignedVram::new_on_heap(), renderer: SoftwareRenderer::new(device) }
+    pub fn new() -> Self {
+        Self { vram: AlignedVram::new_on_heap(), renderer: SoftwareRenderer::new() }
     }
 
     #[allow(clippy::large_stack_arrays)]
-    pub fn from_vram(device: &wgpu::Device, vram: &Vram) -> Self {
+    pub fn from_vram(vram: &Vram) -> Self {
         let mut aligned_vram = AlignedVram::new_on_heap();
         aligned_vram.0.copy_from_slice(vram.as_ref());
 
-        Self { vram: aligned_vram, renderer: SoftwareRenderer::new(device) }
+        Self { vram: aligned_vram, renderer: SoftwareRenderer::new() }
     }
 }
 
@@ -223,12 +227,22 @@ impl RasterizerInterface for SimdSoftwareRasterizer {
         software::vram_to_vram_blit(&mut self.vram, args);
     }
 
+    #[cfg(feature = "wgpu")]
     fn generate_frame_texture(
         &mut self,
         registers: &Registers,
+        display_config: DisplayConfig,
         wgpu_resources: &mut WgpuResources,
     ) -> &wgpu::Texture {
-        self.renderer.generate_frame_texture(registers, wgpu_resources, &self.vram)
+        self.renderer.generate_frame_texture(registers, display_config, wgpu_resources, &self.vram)
+    }
+
+    fn generate_frame_buffer(
+        &mut self,
+        registers: &Registers,
+        display_config: DisplayConfig,
+    ) -> (FrameSize, &[u8]) {
+        self.renderer.generate_frame_buffer(registers, display_config, &self.vram)
     }
 
     fn clone_vram(&mut self) -> Vram {
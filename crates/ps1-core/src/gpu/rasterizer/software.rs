@@ -1,12 +1,14 @@
-use crate::api::ColorDepthBits;
+use crate::api::{ColorDepthBits, DisplayConfig};
+#[cfg(feature = "wgpu")]
+use crate::gpu::WgpuResources;
 use crate::gpu::rasterizer::{
-    ClearPipeline, CpuVramBlitArgs, FrameCoords, FrameSize, ScreenSize, VramVramBlitArgs,
+    CpuVramBlitArgs, FrameCoords, FrameSize, ScreenSize, VramVramBlitArgs,
 };
 use crate::gpu::registers::Registers;
-use crate::gpu::{Color, VramArray, WgpuResources, rasterizer};
+use crate::gpu::{Color, VramArray, rasterizer};
 use bytemuck::{Pod, Zeroable};
+#[cfg(feature = "wgpu")]
 use std::collections::HashMap;
-use wgpu::CommandBuffer;
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Zeroable)]
@@ -28,40 +30,82 @@ impl RgbaColor {
 const FRAME_BUFFER_LEN: usize =
     (1024 * 2 * (ScreenSize::PAL.bottom - ScreenSize::PAL.top)) as usize;
 
+// Rows are tightly packed, i.e. the row stride is equal to the current frame width
 type FrameBuffer = [RgbaColor; FRAME_BUFFER_LEN];
 
 #[derive(Debug)]
 pub struct SoftwareRenderer {
     frame_buffer: Box<FrameBuffer>,
+    frame_size: FrameSize,
+    #[cfg(feature = "wgpu")]
     frame_textures: HashMap<FrameSize, wgpu::Texture>,
-    clear_pipeline: ClearPipeline,
 }
 
 impl SoftwareRenderer {
-    pub fn new(device: &wgpu::Device) -> Self {
-        let clear_pipeline = ClearPipeline::new(device, wgpu::TextureFormat::Rgba8UnormSrgb);
-
+    pub fn new() -> Self {
         Self {
             frame_buffer: vec![RgbaColor::BLACK; FRAME_BUFFER_LEN]
                 .into_boxed_slice()
                 .try_into()
                 .unwrap(),
+            frame_size: FrameSize { width: 0, height: 0 },
+            #[cfg(feature = "wgpu")]
             frame_textures: HashMap::new(),
-            clear_pipeline,
         }
     }
 
+    pub fn generate_frame_buffer(
+        &mut self,
+        registers: &Registers,
+        display_config: DisplayConfig,
+        vram: &VramArray,
+    ) -> (FrameSize, &[u8]) {
+        self.populate_frame(registers, display_config, vram);
+
+        let len = (self.frame_size.width * self.frame_size.height) as usize;
+        (self.frame_size, bytemuck::cast_slice(&self.frame_buffer[..len]))
+    }
+
+    #[cfg(feature = "wgpu")]
     pub fn generate_frame_texture(
         &mut self,
         registers: &Registers,
-        wgpu_resources: &mut WgpuResources,
+        display_config: DisplayConfig,
+        wgpu_resources: &WgpuResources,
         vram: &VramArray,
     ) -> &wgpu::Texture {
-        if wgpu_resources.display_config.dump_vram {
-            return self.write_frame(
-                &wgpu_resources.device,
-                &wgpu_resources.queue,
-                FrameSize { width: 1024, height: 512 },
+        self.populate_frame(registers, display_config, vram);
+
+        let frame_texture = get_or_create_frame_texture(
+            &wgpu_resources.device,
+            self.frame_size,
+            &mut self.frame_textures,
+        );
+
+        wgpu_resources.queue.write_texture(
+            frame_texture.as_image_copy(),
+            bytemuck::cast_slice(self.frame_buffer.as_ref()),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * self.frame_size.width),
+                rows_per_image: None,
+            },
+            frame_texture.size(),
+        );
+
+        frame_texture
+    }
+
+    fn populate_frame(
+        &mut self,
+        registers: &Registers,
+        display_config: DisplayConfig,
+        vram: &VramArray,
+    ) {
+        if display_config.dump_vram {
+            self.frame_size = FrameSize { width: 1024, height: 512 };
+            populate_frame_buffer(
+                self.frame_size,
                 FrameCoords {
                     frame_x: 0,
                     frame_y: 0,
@@ -74,17 +118,18 @@ impl SoftwareRenderer {
                 },
                 ColorDepthBits::Fifteen,
                 vram,
+                &mut self.frame_buffer,
             );
+            return;
         }
 
         let (frame_coords, frame_size) =
-            rasterizer::compute_frame_location(registers, wgpu_resources.display_config);
+            rasterizer::compute_frame_location(registers, display_config);
+        self.frame_size = frame_size;
+
         let Some(frame_coords) = frame_coords else {
-            return self.clear_frame(
-                &wgpu_resources.device,
-                &mut wgpu_resources.queued_command_buffers,
-                frame_size,
-            );
+            self.clear_frame();
+            return;
         };
 
         log::debug!(
@@ -99,71 +144,26 @@ impl SoftwareRenderer {
         );
 
         if !registers.display_enabled {
-            return self.clear_frame(
-                &wgpu_resources.device,
-                &mut wgpu_resources.queued_command_buffers,
-                frame_size,
-            );
+            self.clear_frame();
+            return;
         }
 
-        return self.write_frame(
-            &wgpu_resources.device,
-            &wgpu_resources.queue,
+        populate_frame_buffer(
             frame_size,
             frame_coords,
             registers.display_area_color_depth,
             vram,
+            &mut self.frame_buffer,
         );
     }
 
-    fn clear_frame(
-        &mut self,
-        device: &wgpu::Device,
-        command_buffers: &mut Vec<CommandBuffer>,
-        frame_size: FrameSize,
-    ) -> &wgpu::Texture {
-        let texture = get_or_create_frame_texture(device, frame_size, &mut self.frame_textures);
-
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: "clear_encoder".into(),
-        });
-
-        self.clear_pipeline.draw(texture, &mut encoder);
-
-        command_buffers.push(encoder.finish());
-
-        texture
-    }
-
-    fn write_frame(
-        &mut self,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        frame_size: FrameSize,
-        frame_coords: FrameCoords,
-        color_depth: ColorDepthBits,
-        vram: &VramArray,
-    ) -> &wgpu::Texture {
-        populate_frame_buffer(frame_size, frame_coords, color_depth, vram, &mut self.frame_buffer);
-
-        let frame_texture =
-            get_or_create_frame_texture(device, frame_size, &mut self.frame_textures);
-
-        queue.write_texture(
-            frame_texture.as_image_copy(),
-            bytemuck::cast_slice(self.frame_buffer.as_ref()),
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(1024 * 4),
-                rows_per_image: None,
-            },
-            frame_texture.size(),
-        );
-
-        frame_texture
+    fn clear_frame(&mut self) {
+        let len = (self.frame_size.width * self.frame_size.height) as usize;
+        self.frame_buffer[..len].fill(RgbaColor::BLACK);
     }
 }
 
+#[cfg(feature = "wgpu")]
 fn get_or_create_frame_texture<'a>(
     device: &wgpu::Device,
     frame_size: FrameSize,
@@ -209,9 +209,13 @@ fn populate_frame_buffer(
     let y_range =
         frame_coords.display_y_start..frame_coords.display_y_start + frame_coords.display_height;
 
+    let frame_width = frame_size.width as usize;
+
     for y in 0..frame_size.height {
+        let fb_row_addr = frame_width * y as usize;
+
         if !y_range.contains(&y) {
-            frame_buffer[1024 * y as usize..1024 * (y + 1) as usize].fill(RgbaColor::BLACK);
+            frame_buffer[fb_row_addr..fb_row_addr + frame_width].fill(RgbaColor::BLACK);
             continue;
         }
 
@@ -221,9 +225,8 @@ fn populate_frame_buffer(
         let vram_row_addr = (1024 * vram_y) as usize;
 
         // Fill pixels outside of the horizontal display range with solid black
-        let fb_row_addr = 1024 * y as usize;
         frame_buffer[fb_row_addr..fb_row_addr + x_range.start as usize].fill(RgbaColor::BLACK);
-        frame_buffer[fb_row_addr + x_range.end as usize..fb_row_addr + frame_size.width as usize]
+        frame_buffer[fb_row_addr + x_range.end as usize..fb_row_addr + frame_width]
             .fill(RgbaColor::BLACK);
 
         for x in x_range.clone() {
@@ -4,7 +4,7 @@ mod hazards;
 mod sync;
 mod twentyfour;
 
-use crate::api::ColorDepthBits;
+use crate::api::{ColorDepthBits, DisplayConfig};
 use crate::gpu::gp0::{DrawSettings, SemiTransparencyMode, TextureColorDepthBits, TexturePage};
 use crate::gpu::rasterizer::wgpuhardware::blit::{
     CpuVramBlitPipeline, VramCopyPipeline, VramCpuBlitter, VramFillPipeline,
@@ -25,7 +25,7 @@ use crate::gpu::{Color, Vertex, Vram, WgpuResources, rasterizer};
 use std::collections::HashMap;
 use std::ops::{BitOr, BitOrAssign, Range};
 use std::sync::Arc;
-use std::{array, cmp, iter};
+use std::{array, cmp, iter, mem};
 use wgpu::{
     BindGroup, Buffer, BufferDescriptor, BufferUsages, CommandBuffer, CommandEncoder,
     CommandEncoderDescriptor, ComputePassDescriptor, Device, Extent3d, ImageCopyBuffer,
@@ -195,6 +195,7 @@ pub struct WgpuRasterizer {
     // rgba8unorm at scaled resolution; used as a no-op render attachment with some mask bit shaders
     dummy_vram: Texture,
     frame_textures: HashMap<(FrameSize, u32), Texture>,
+    cpu_frame_buffer: Vec<u8>,
     hazard_tracker: HazardTracker,
     clear_pipeline: ClearPipeline,
     render_24bpp_pipeline: TwentyFourBppPipeline,
@@ -318,6 +319,7 @@ impl WgpuRasterizer {
             native_vram,
             dummy_vram,
             frame_textures: HashMap::with_capacity(20),
+            cpu_frame_buffer: Vec::new(),
             hazard_tracker: HazardTracker::new(),
             clear_pipeline,
             render_24bpp_pipeline,
@@ -432,6 +434,85 @@ impl WgpuRasterizer {
         frame
     }
 
+    fn render_frame_texture(
+        &mut self,
+        registers: &Registers,
+        display_config: DisplayConfig,
+        command_buffers: &mut Vec<CommandBuffer>,
+    ) -> &Texture {
+        log::debug!("Rendering frame to display");
+
+        if let Some(command_buffer) = self.flush_draw_commands() {
+            command_buffers.push(command_buffer);
+        }
+
+        if display_config.dump_vram {
+            return &self.scaled_vram;
+        }
+
+        let (frame_coords, frame_size) =
+            rasterizer::compute_frame_location(registers, display_config);
+        let Some(frame_coords) = frame_coords else {
+            return self.get_and_clear_frame(frame_size, command_buffers);
+        };
+
+        if !registers.display_enabled {
+            return self.get_and_clear_frame(frame_size, command_buffers);
+        }
+
+        log::debug!("  Frame size {frame_size:?}, frame coords {frame_coords:?}");
+
+        if registers.display_area_color_depth == ColorDepthBits::TwentyFour {
+            return self.render_24bpp(frame_coords, frame_size, command_buffers);
+        }
+
+        let resolution_scale = self.config.resolution_scale;
+        let frame = get_or_create_frame_texture(
+            &self.device,
+            frame_size,
+            resolution_scale,
+            &mut self.frame_textures,
+        );
+
+        let mut encoder = self.device.create_command_encoder(&CommandEncoderDescriptor::default());
+        self.clear_pipeline.draw(frame, &mut encoder);
+
+        // TODO bounds check
+        let source_x = frame_coords.frame_x + frame_coords.display_x_offset;
+        let source_y = frame_coords.frame_y + frame_coords.display_y_offset;
+        encoder.copy_texture_to_texture(
+            ImageCopyTexture {
+                texture: &self.scaled_vram,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: resolution_scale * source_x,
+                    y: resolution_scale * source_y,
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            ImageCopyTexture {
+                texture: frame,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: resolution_scale * frame_coords.display_x_start,
+                    y: resolution_scale * frame_coords.display_y_start,
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            Extent3d {
+                width: resolution_scale * frame_coords.display_width,
+                height: resolution_scale * frame_coords.display_height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        command_buffers.push(encoder.finish());
+
+        frame
+    }
+
     fn flush_draw_commands(&mut self) -> Option<CommandBuffer> {
         if self.draw_commands.is_empty() {
             return None;
@@ -1119,9 +1200,11 @@ impl RasterizerInterface for WgpuRasterizer {
 
     fn cpu_to_vram_blit(&mut self, args: CpuVramBlitArgs, data: &[u16]) {
         let buffer_bind_group = self.cpu_vram_blit_pipeline.prepare(&self.device, &args, data);
-        let sync_vertex_buffer =
-            self.native_scaled_sync_pipeline
-                .prepare(&self.device, [args.x, args.y], [args.width, args.height]);
+        let sync_vertex_buffer = self.native_scaled_sync_pipeline.prepare(
+            &self.device,
+            [args.x, args.y],
+            [args.width, args.height],
+        );
 
         self.draw_commands.push(DrawCommand::CpuVramBlit {
             args,
@@ -1161,85 +1244,32 @@ impl RasterizerInterface for WgpuRasterizer {
     fn generate_frame_texture(
         &mut self,
         registers: &Registers,
+        display_config: DisplayConfig,
         wgpu_resources: &mut WgpuResources,
     ) -> &Texture {
-        log::debug!("Rendering frame to display");
-
-        if let Some(command_buffer) = self.flush_draw_commands() {
-            wgpu_resources.queued_command_buffers.push(command_buffer);
-        }
-
-        if wgpu_resources.display_config.dump_vram {
-            return &self.scaled_vram;
-        }
-
-        let (frame_coords, frame_size) =
-            rasterizer::compute_frame_location(registers, wgpu_resources.display_config);
-        let Some(frame_coords) = frame_coords else {
-            return self
-                .get_and_clear_frame(frame_size, &mut wgpu_resources.queued_command_buffers);
-        };
-
-        if !registers.display_enabled {
-            return self
-                .get_and_clear_frame(frame_size, &mut wgpu_resources.queued_command_buffers);
-        }
-
-        log::debug!("  Frame size {frame_size:?}, frame coords {frame_coords:?}");
-
-        if registers.display_area_color_depth == ColorDepthBits::TwentyFour {
-            return self.render_24bpp(
-                frame_coords,
-                frame_size,
-                &mut wgpu_resources.queued_command_buffers,
-            );
-        }
-
-        let resolution_scale = self.config.resolution_scale;
-        let frame = get_or_create_frame_texture(
-            &self.device,
-            frame_size,
-            resolution_scale,
-            &mut self.frame_textures,
-        );
-
-        let mut encoder = self.device.create_command_encoder(&CommandEncoderDescriptor::default());
-        self.clear_pipeline.draw(frame, &mut encoder);
-
-        // TODO bounds check
-        let source_x = frame_coords.frame_x + frame_coords.display_x_offset;
-        let source_y = frame_coords.frame_y + frame_coords.display_y_offset;
-        encoder.copy_texture_to_texture(
-            ImageCopyTexture {
-                texture: &self.scaled_vram,
-                mip_level: 0,
-                origin: Origin3d {
-                    x: resolution_scale * source_x,
-                    y: resolution_scale * source_y,
-                    z: 0,
-                },
-                aspect: TextureAspect::All,
-            },
-            ImageCopyTexture {
-                texture: frame,
-                mip_level: 0,
-                origin: Origin3d {
-                    x: resolution_scale * frame_coords.display_x_start,
-                    y: resolution_scale * frame_coords.display_y_start,
-                    z: 0,
-                },
-                aspect: TextureAspect::All,
-            },
-            Extent3d {
-                width: resolution_scale * frame_coords.display_width,
-                height: resolution_scale * frame_coords.display_height,
-                depth_or_array_layers: 1,
-            },
-        );
-
-        wgpu_resources.queued_command_buffers.push(encoder.finish());
+        self.render_frame_texture(
+            registers,
+            display_config,
+            &mut wgpu_resources.queued_command_buffers,
+        )
+    }
 
-        frame
+    fn generate_frame_buffer(
+        &mut self,
+        registers: &Registers,
+        display_config: DisplayConfig,
+    ) -> (FrameSize, &[u8]) {
+        let device = Arc::clone(&self.device);
+        let queue = Arc::clone(&self.queue);
+        let mut frame_buffer = mem::take(&mut self.cpu_frame_buffer);
+
+        let mut command_buffers = Vec::new();
+        let frame = self.render_frame_texture(registers, display_config, &mut command_buffers);
+        let frame_size = FrameSize { width: frame.width(), height: frame.height() };
+        read_back_frame(&device, &queue, frame, command_buffers, &mut frame_buffer);
+
+        self.cpu_frame_buffer = frame_buffer;
+        (frame_size, &self.cpu_frame_buffer)
     }
 
     fn clone_vram(&mut self) -> Vram {
@@ -1421,6 +1451,57 @@ fn expand_tiny_triangle(
     }
 }
 
+// Copy a frame texture into host RAM as tightly packed RGBA8 rows, with alpha forced to 255
+fn read_back_frame(
+    device: &Device,
+    queue: &Queue,
+    frame: &Texture,
+    command_buffers: Vec<CommandBuffer>,
+    out: &mut Vec<u8>,
+) {
+    let row_len = 4 * frame.width();
+    let padded_row_len = row_len.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let readback_buffer = device.create_buffer(&BufferDescriptor {
+        label: "frame_readback_buffer".into(),
+        size: (padded_row_len * frame.height()).into(),
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+    encoder.copy_texture_to_buffer(
+        frame.as_image_copy(),
+        ImageCopyBuffer {
+            buffer: &readback_buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_len),
+                rows_per_image: None,
+            },
+        },
+        frame.size(),
+    );
+
+    queue.submit(command_buffers.into_iter().chain(iter::once(encoder.finish())));
+
+    let readback_buffer_slice = readback_buffer.slice(..);
+    readback_buffer_slice.map_async(MapMode::Read, Result::unwrap);
+    device.poll(Maintain::Wait);
+
+    out.clear();
+    {
+        let readback_buffer_view = readback_buffer_slice.get_mapped_range();
+        for row in readback_buffer_view.chunks_exact(padded_row_len as usize) {
+            for pixel in row[..row_len as usize].chunks_exact(4) {
+                out.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 255]);
+            }
+        }
+    }
+
+    readback_buffer.unmap();
+}
+
 fn get_or_create_frame_texture<'a>(
     device: &Device,
     frame_size: FrameSize,
@@ -0,0 +1,528 @@
+//! High-level emulation (HLE) of the BIOS kernel, for running software without a BIOS ROM
+//!
+//! Kernel function calls and exceptions are handled in Rust instead of by executing BIOS code.
+//! The kernel sets up low RAM the same way the real BIOS does: $A0/$B0/$C0 jump to dispatchers
+//! that load a function address from a table in RAM, and the exception vector at $80 jumps into
+//! ROM. Every table entry points to a unique stub address in the (otherwise empty) BIOS ROM
+//! region, and the emulator calls [`HleBios::dispatch`] instead of executing an instruction
+//! whenever the CPU jumps into ROM. Because the tables live in RAM, software that patches table
+//! entries (e.g. through `GetB0Table()`) sees the same behavior as with a real BIOS.
+//!
+//! When the kernel needs to call guest code (interrupt handler chains, event callbacks, `Exec()`),
+//! it points the return address at another stub and records what it was doing as a
+//! [`kernel::Continuation`], so guest code always runs in the normal CPU loop and never nested
+//! inside a kernel call.
+//!
+//! Kernel data that software is known to read directly (exception chains, the process control
+//! block, thread and event control blocks) is kept in RAM in the same layout as the real BIOS.
+
+mod boot;
+mod disc;
+mod files;
+mod kernel;
+mod libc;
+
+use crate::bus::Bus;
+use crate::cpu::R3000;
+use crate::debug::{Cop0Register, CpuRegister};
+use crate::memory::BIOS_ROM_LEN;
+use bincode::{Decode, Encode};
+use files::Files;
+use kernel::KernelState;
+use libc::Libc;
+use std::array;
+
+pub(crate) use boot::SystemConfig;
+
+// Register numbers
+const V0: u32 = 2;
+const A0: u32 = 4;
+const A1: u32 = 5;
+const A2: u32 = 6;
+const A3: u32 = 7;
+const S0: u32 = 16;
+const GP: u32 = 28;
+const SP: u32 = 29;
+const FP: u32 = 30;
+const RA: u32 = 31;
+
+// Stub addresses in the ROM region. Nothing is ever executed from these addresses
+const RESET_VECTOR: u32 = 0xBFC0_0000;
+const BEV_EXCEPTION_VECTOR: u32 = 0xBFC0_0180;
+const A_STUBS: u32 = 0xBFC1_0000;
+const B_STUBS: u32 = 0xBFC1_0400;
+const C_STUBS: u32 = 0xBFC1_0800;
+const EXCEPTION_HANDLER: u32 = 0xBFC1_0C00;
+const RETURN_TRAP: u32 = 0xBFC1_0C04;
+const EXE_RETURNED: u32 = 0xBFC1_0C08;
+const HALTED: u32 = 0xBFC1_0C0C;
+const PAD_HANDLER: u32 = 0xBFC1_0C10;
+const RCNT_HANDLERS: u32 = 0xBFC1_0C14;
+const STUBS_END: u32 = RCNT_HANDLERS + 4 * 4;
+
+// Function tables and dispatchers in low RAM, at the same addresses as the real BIOS tables
+const A_TABLE: u32 = 0x0200;
+const A_TABLE_LEN: u32 = 0xC0;
+const B_TABLE: u32 = 0x0874;
+const B_TABLE_LEN: u32 = 0x60;
+const C_TABLE: u32 = 0x0674;
+const C_TABLE_LEN: u32 = 0x20;
+const A_DISPATCHER: u32 = 0x0500;
+const B_DISPATCHER: u32 = 0x0520;
+const C_DISPATCHER: u32 = 0x0540;
+
+const VERSION_STRING_ADDR: u32 = 0xBFC7_FF32;
+const VERSION_STRING: &[u8] = b"CoffeePSX HLE BIOS\0";
+
+// Real BIOS functions take anywhere from tens to many thousands of cycles; use a flat cost
+const CALL_CYCLES: u32 = 50;
+
+/// Generate BIOS ROM contents for HLE mode. Code in ROM is never executed, but software can still
+/// read from ROM, e.g. the version string returned by `GetSystemInfo()`.
+pub fn generate_rom() -> Vec<u8> {
+    let mut rom = vec![0; BIOS_ROM_LEN];
+
+    let offset = (VERSION_STRING_ADDR & 0x7FFFF) as usize;
+    rom[offset..offset + VERSION_STRING.len()].copy_from_slice(VERSION_STRING);
+
+    rom
+}
+
+/// Whether the HLE kernel should handle execution at the given PC instead of the CPU, which is
+/// true for any address in the BIOS ROM region.
+pub fn is_hle_address(pc: u32) -> bool {
+    pc & 0x1FF8_0000 == 0x1FC0_0000
+}
+
+/// Result of an HLE kernel function
+enum Flow {
+    /// Return to the caller with the given value in V0
+    Return(u32),
+    /// Block, then call the function again through the table dispatcher. Executing the dispatcher
+    /// gives the CPU a chance to take interrupts between attempts
+    Retry,
+    /// The function already set the PC, e.g. to call guest code or to not return
+    Jump,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct HleBios {
+    kernel: KernelState,
+    files: Files,
+    libc: Libc,
+}
+
+impl HleBios {
+    pub fn new() -> Self {
+        Self { kernel: KernelState::new(), files: Files::new(), libc: Libc::new() }
+    }
+
+    /// Handle execution at an address in the BIOS ROM region, in place of executing an
+    /// instruction. Returns the number of CPU cycles taken.
+    pub fn dispatch(&mut self, cpu: &mut R3000, bus: &mut Bus<'_>) -> u32 {
+        let mut g = Guest { cpu, bus };
+
+        let pc = 0xA000_0000 | (g.cpu.pc() & 0x1FFF_FFFF);
+        let (flow, retry_pc) = match pc {
+            RESET_VECTOR => {
+                self.boot(&mut g, true);
+                (Flow::Jump, pc)
+            }
+            A_STUBS..B_STUBS => (self.call_a(&mut g, (pc - A_STUBS) / 4), 0xA0),
+            B_STUBS..C_STUBS => (self.call_b(&mut g, (pc - B_STUBS) / 4), 0xB0),
+            C_STUBS..EXCEPTION_HANDLER => (self.call_c(&mut g, (pc - C_STUBS) / 4), 0xC0),
+            EXCEPTION_HANDLER | BEV_EXCEPTION_VECTOR => (self.handle_exception(&mut g), pc),
+            RETURN_TRAP => (self.resume(&mut g), pc),
+            EXE_RETURNED => {
+                log::info!("Executable returned to the HLE BIOS with {:08X}; halting", g.reg(V0));
+                g.cpu.set_pc(HALTED);
+                (Flow::Jump, pc)
+            }
+            HALTED => (Flow::Retry, HALTED),
+            PAD_HANDLER => (self.pad_handler(&mut g), pc),
+            RCNT_HANDLERS..STUBS_END => (self.rcnt_handler(&mut g, (pc - RCNT_HANDLERS) / 4), pc),
+            _ => {
+                log::error!("Jumped to unmapped HLE BIOS address {pc:08X}; halting");
+                g.cpu.set_pc(HALTED);
+                (Flow::Jump, pc)
+            }
+        };
+
+        match flow {
+            Flow::Return(value) => {
+                g.set_reg(V0, value);
+                g.cpu.set_pc(g.reg(RA));
+            }
+            Flow::Retry => g.cpu.set_pc(retry_pc),
+            Flow::Jump => {}
+        }
+
+        CALL_CYCLES
+    }
+
+    /// Initialize the kernel as if the BIOS had just finished booting, then either boot the disc
+    /// or idle at $80030000 where the shell would normally start (the point at which EXEs are
+    /// sideloaded).
+    pub fn boot_kernel(&mut self, cpu: &mut R3000, bus: &mut Bus<'_>, boot_disc: bool) {
+        self.boot(&mut Guest { cpu, bus }, boot_disc);
+    }
+
+    #[allow(clippy::match_same_arms)]
+    fn call_a(&mut self, g: &mut Guest<'_, '_>, function: u32) -> Flow {
+        log::trace!("A({function:02X}) called with args {:08X?}", g.args());
+
+        match function {
+            0x00 => self.open(g),
+            0x01 => self.lseek(g),
+            0x02 => self.read(g),
+            0x03 => self.write(g),
+            0x04 => self.close(g),
+            0x05 => Flow::Return(0), // ioctl
+            0x06 => Self::exit(g),
+            0x07 => self.isatty(g),
+            0x08 => self.getc(g),
+            0x09 => self.putc(g),
+            0x0A => Libc::todigit(g),
+            0x0C | 0x0D => Libc::strtol(g),
+            0x0E | 0x0F => Libc::abs(g),
+            0x10 | 0x11 => Libc::atoi(g),
+            0x12 => Libc::atob(g),
+            0x13 => Libc::setjmp(g),
+            0x14 => Libc::longjmp(g),
+            0x15 => Libc::strcat(g),
+            0x16 => Libc::strncat(g),
+            0x17 => Libc::strcmp(g),
+            0x18 => Libc::strncmp(g),
+            0x19 => Libc::strcpy(g),
+            0x1A => Libc::strncpy(g),
+            0x1B => Libc::strlen(g),
+            0x1C | 0x1E => Libc::strchr(g),
+            0x1D | 0x1F => Libc::strrchr(g),
+            0x20 => Libc::strpbrk(g),
+            0x21 => Libc::strspn(g, true),
+            0x22 => Libc::strspn(g, false),
+            0x23 => self.libc.strtok(g),
+            0x24 => Libc::strstr(g),
+            0x25 => Libc::toupper(g),
+            0x26 => Libc::tolower(g),
+            0x27 => Libc::bcopy(g),
+            0x28 => Libc::bzero(g),
+            0x29 | 0x2D => Libc::memcmp(g),
+            0x2A | 0x2C => Libc::memmove(g),
+            0x2B => Libc::memset(g),
+            0x2E => Libc::memchr(g),
+            0x2F => self.libc.rand(),
+            0x30 => self.libc.srand(g),
+            0x33 => self.libc.malloc(g),
+            0x34 => self.libc.free(g),
+            0x37 => self.libc.calloc(g),
+            0x38 => self.libc.realloc(g),
+            0x39 => self.libc.init_heap(g),
+            0x3A | 0x40 | 0x4F | 0x50 | 0x53 | 0xA1 => Self::system_error(g, 'A', function),
+            0x3B => Flow::Return(u32::MAX), // getchar; there is no TTY input
+            // putchar; output is already captured at the A0/B0 entry point when TTY is enabled
+            0x3C => Flow::Return(g.arg(0)),
+            0x3D => Flow::Return(0), // gets
+            0x3E => Libc::puts(g),
+            0x3F => Libc::printf(g),
+            0x41 => Self::load_test(g),
+            0x42 => Self::load(g),
+            0x43 => self.exec(g),
+            0x44 => {
+                g.cpu.flush_i_cache();
+                Flow::Return(0)
+            }
+            0x45 | 0x9E | 0x9F | 0xA2 | 0xA3 => Flow::Return(0),
+            0x46 | 0x47 => kernel::gpu_dw(g),
+            0x48 => kernel::gpu_gp1(g),
+            0x49 => kernel::gpu_cw(g),
+            0x4A => kernel::gpu_cwp(g),
+            0x4B => kernel::gpu_send_linked_list(g),
+            0x4C | 0x4E => Flow::Return(0), // gpu_abort_dma, gpu_sync
+            0x4D => kernel::gpu_status(g),
+            0x51 => Self::load_exec(g),
+            0x52 => Flow::Return(self.kernel.stack_top),
+            // _bu_init, CdInit, CdRemove, and miscellaneous no-ops
+            0x54..=0x5A | 0x70..=0x77 | 0x79..=0x7B | 0x7D | 0x7F | 0x80 | 0x82..=0x8F => {
+                Flow::Return(0)
+            }
+            0x9C => self.set_conf(g),
+            0x9D => self.get_conf(g),
+            0xA0 => {
+                self.boot(g, true);
+                Flow::Jump
+            }
+            0xA4 => Self::cd_get_lbn(g),
+            0xA5 => Self::cd_read_sector(g),
+            0xA6 => Flow::Return(0x02), // CdGetStatus: motor on
+            0xA7..=0xAA | 0xAE | 0xB0 | 0xB1 | 0xB3 => Flow::Return(0),
+            0xAB | 0xAC | 0xAF => self.card_info(g),
+            0xAD => Flow::Return(0), // _card_auto
+            0xB4 => Self::get_system_info(g),
+            _ => {
+                log::warn!("Unimplemented HLE BIOS function A({function:02X})");
+                Flow::Return(0)
+            }
+        }
+    }
+
+    #[allow(clippy::match_same_arms)]
+    fn call_b(&mut self, g: &mut Guest<'_, '_>, function: u32) -> Flow {
+        log::trace!("B({function:02X}) called with args {:08X?}", g.args());
+
+        match function {
+            0x00 => self.alloc_kernel_memory(g),
+            0x01 => Flow::Return(0), // free_kernel_memory
+            0x02 => kernel::set_rcnt(g),
+            0x03 => kernel::get_rcnt(g),
+            0x04 => kernel::start_rcnt(g),
+            0x05 => kernel::stop_rcnt(g),
+            0x06 => kernel::reset_rcnt(g),
+            0x07 => self.deliver_event(g),
+            0x08 => self.open_event(g),
+            0x09 => self.close_event(g),
+            0x0A => self.wait_event(g),
+            0x0B => self.test_event(g),
+            0x0C => self.enable_event(g),
+            0x0D => self.disable_event(g),
+            0x0E => self.open_thread(g),
+            0x0F => self.close_thread(g),
+            0x10 => self.change_thread(g),
+            0x11 => {
+                g.cpu.set_pc(0);
+                Flow::Jump
+            }
+            0x12 => self.init_pad(g),
+            0x13 => self.start_pad(g),
+            0x14 => self.stop_pad(),
+            0x15 => self.outdated_pad_init(g),
+            0x16 => self.outdated_pad_get_buttons(g),
+            0x17 => {
+                self.return_from_exception(g);
+                Flow::Jump
+            }
+            0x18 => {
+                self.kernel.custom_exit = 0;
+                Flow::Return(0)
+            }
+            0x19 => {
+                self.kernel.custom_exit = g.arg(0);
+                Flow::Return(0)
+            }
+            0x1A..=0x1F | 0x21..=0x31 | 0x52 | 0x5A => Self::system_error(g, 'B', function),
+            0x20 => self.undeliver_event(g),
+            0x32 => self.open(g),
+            0x33 => self.lseek(g),
+            0x34 => self.read(g),
+            0x35 => self.write(g),
+            0x36 => self.close(g),
+            0x37 => Flow::Return(0), // ioctl
+            0x38 => Self::exit(g),
+            0x39 => self.isatty(g),
+            0x3A => self.getc(g),
+            0x3B => self.putc(g),
+            0x3C => Flow::Return(u32::MAX), // getchar
+            0x3D => Flow::Return(g.arg(0)), // putchar; see A($3C)
+            0x3E => Flow::Return(0),        // gets
+            0x3F => Libc::puts(g),
+            0x40 => Self::chdir(g),
+            0x41 => self.format(g),
+            0x42 => self.first_file(g),
+            0x43 => self.next_file(g),
+            0x44 => self.rename(g),
+            0x45 => self.erase(g),
+            0x46 => Flow::Return(0), // undelete
+            0x47..=0x49 => Flow::Return(0),
+            0x4A => Flow::Return(0), // InitCard
+            0x4B | 0x4C => Flow::Return(1),
+            0x4D => self.card_info(g),
+            0x4E => self.card_write(g),
+            0x4F => self.card_read(g),
+            0x50 => Flow::Return(1), // allow_new_card
+            0x54 => Flow::Return(self.files.last_error()),
+            0x55 => Flow::Return(self.files.last_file_error(g.arg(0))),
+            0x56 => Flow::Return(C_TABLE),
+            0x57 => Flow::Return(B_TABLE),
+            0x58 => Flow::Return(self.kernel.card_port),
+            0x5B => {
+                self.kernel.clear_pad = g.arg(0) != 0;
+                Flow::Return(0)
+            }
+            0x5C | 0x5D => Flow::Return(1), // card status: ready
+            _ => {
+                log::warn!("Unimplemented HLE BIOS function B({function:02X})");
+                Flow::Return(0)
+            }
+        }
+    }
+
+    #[allow(clippy::match_same_arms)]
+    fn call_c(&mut self, g: &mut Guest<'_, '_>, function: u32) -> Flow {
+        log::trace!("C({function:02X}) called with args {:08X?}", g.args());
+
+        match function {
+            0x00 => Self::enqueue_rcnt_handlers(g),
+            0x02 => Self::enqueue_interrupt_handler(g),
+            0x03 => Self::dequeue_interrupt_handler(g),
+            0x04 => self.free_event_slot(g),
+            0x05 => self.free_thread_slot(g),
+            0x0A => self.change_clear_rcnt(g),
+            // Kernel initialization functions; the HLE kernel is always initialized
+            0x01 | 0x07..=0x09 | 0x0C | 0x12 | 0x13 | 0x1C => Flow::Return(0),
+            0x0B | 0x14 => Self::system_error(g, 'C', function),
+            0x1A => {
+                self.files.card_find_mode = g.arg(0);
+                Flow::Return(0)
+            }
+            0x1D => Flow::Return(self.files.card_find_mode),
+            _ => {
+                log::warn!("Unimplemented HLE BIOS function C({function:02X})");
+                Flow::Return(0)
+            }
+        }
+    }
+
+    fn system_error(g: &mut Guest<'_, '_>, table: char, function: u32) -> Flow {
+        log::error!(
+            "{table}({function:02X}) system error called from {:08X} with args {:08X?}; halting",
+            g.reg(RA),
+            g.args()
+        );
+        g.cpu.set_pc(HALTED);
+        Flow::Jump
+    }
+
+    fn exit(g: &mut Guest<'_, '_>) -> Flow {
+        log::info!("exit({}) called from {:08X}; halting", g.arg(0) as i32, g.reg(RA));
+        g.cpu.set_pc(HALTED);
+        Flow::Jump
+    }
+
+    fn get_system_info(g: &mut Guest<'_, '_>) -> Flow {
+        match g.arg(0) {
+            // Kernel date, in BCD
+            0 => Flow::Return(0x1995_1204),
+            // Kernel version string
+            2 => Flow::Return(VERSION_STRING_ADDR),
+            // RAM size in KB
+            5 => Flow::Return(2048),
+            index => {
+                log::warn!("Unimplemented GetSystemInfo({index})");
+                Flow::Return(0)
+            }
+        }
+    }
+}
+
+/// Access to CPU registers and guest memory for kernel functions
+struct Guest<'a, 'bus> {
+    cpu: &'a mut R3000,
+    bus: &'a mut Bus<'bus>,
+}
+
+impl Guest<'_, '_> {
+    fn reg(&self, register: u32) -> u32 {
+        self.cpu.get_gpr(register)
+    }
+
+    fn set_reg(&mut self, register: u32, value: u32) {
+        self.cpu.set_gpr(register, value);
+    }
+
+    fn cop0(&self, register: Cop0Register) -> u32 {
+        self.cpu.read_debug_register(CpuRegister::Cop0(register))
+    }
+
+    fn set_cop0(&mut self, register: Cop0Register, value: u32) {
+        self.cpu.write_debug_register(CpuRegister::Cop0(register), value);
+    }
+
+    /// Function argument N; arguments past the fourth are passed on the stack
+    fn arg(&self, n: u32) -> u32 {
+        if n < 4 { self.reg(A0 + n) } else { self.read_u32(self.reg(SP).wrapping_add(4 * n)) }
+    }
+
+    fn args(&self) -> [u32; 4] {
+        [self.reg(A0), self.reg(A1), self.reg(A2), self.reg(A3)]
+    }
+
+    fn read_u8(&self, address: u32) -> u8 {
+        self.bus.memory.peek_u8(address).unwrap_or_else(|| {
+            log::warn!("HLE BIOS read from unmapped address {address:08X}");
+            0
+        })
+    }
+
+    fn read_u32(&self, address: u32) -> u32 {
+        u32::from_le_bytes(array::from_fn(|i| self.read_u8(address.wrapping_add(i as u32))))
+    }
+
+    fn write_u8(&mut self, address: u32, value: u8) {
+        if !self.bus.memory.poke_u8(address, value) {
+            log::warn!("HLE BIOS write to unmapped address {address:08X}");
+        }
+    }
+
+    fn write_u32(&mut self, address: u32, value: u32) {
+        self.write_bytes(address, &value.to_le_bytes());
+    }
+
+    fn read_bytes(&self, address: u32, len: u32) -> Vec<u8> {
+        (0..len).map(|i| self.read_u8(address.wrapping_add(i))).collect()
+    }
+
+    fn write_bytes(&mut self, address: u32, bytes: &[u8]) {
+        for (i, &byte) in bytes.iter().enumerate() {
+            self.write_u8(address.wrapping_add(i as u32), byte);
+        }
+    }
+
+    fn fill(&mut self, address: u32, value: u8, len: u32) {
+        for i in 0..len {
+            self.write_u8(address.wrapping_add(i), value);
+        }
+    }
+
+    /// Read a NUL-terminated string, not including the terminator
+    fn read_cstring(&self, address: u32) -> Vec<u8> {
+        // Guard against unterminated strings
+        const MAX_LEN: u32 = 64 * 1024;
+
+        (0..MAX_LEN)
+            .map(|i| self.read_u8(address.wrapping_add(i)))
+            .take_while(|&byte| byte != 0)
+            .collect()
+    }
+
+    /// Write a string followed by a NUL terminator
+    fn write_cstring(&mut self, address: u32, string: &[u8]) {
+        self.write_bytes(address, string);
+        self.write_u8(address.wrapping_add(string.len() as u32), 0);
+    }
+}
+
+/// Run a closure with a guest backed by a freshly created HLE-mode emulator
+#[cfg(test)]
+fn with_test_guest<R>(f: impl FnOnce(&mut Guest<'_, '_>) -> R) -> R {
+    use crate::api::{
+        BiosMode, GraphicsBackend, LoadedMemoryCards, MemoryCardsEnabled, Ps1Emulator,
+        Ps1EmulatorConfig,
+    };
+
+    let config = Ps1EmulatorConfig {
+        bios_mode: BiosMode::Hle,
+        ram_seed: Some(0),
+        ..Ps1EmulatorConfig::default()
+    };
+    let mut emulator = Ps1Emulator::new(
+        Vec::new(),
+        GraphicsBackend::Headless,
+        config,
+        MemoryCardsEnabled { slot_1: false, slot_2: false },
+        LoadedMemoryCards { slot_1: None, slot_2: None },
+        None,
+    )
+    .unwrap();
+
+    emulator.with_cpu_and_bus(|cpu, bus| f(&mut Guest { cpu, bus }))
+}
@@ -0,0 +1,408 @@
+//! Kernel initialization, SYSTEM.CNF parsing, and loading/executing EXEs
+
+use crate::cd::CdController;
+use crate::debug::Cop0Register;
+use crate::hle::disc;
+use crate::hle::files::{Device, split_device};
+use crate::hle::kernel::{
+    Continuation, DEFAULT_EVENT_COUNT, DEFAULT_STACK_TOP, DEFAULT_THREAD_COUNT,
+};
+use crate::hle::{
+    A_DISPATCHER, A_STUBS, A_TABLE, A_TABLE_LEN, B_DISPATCHER, B_STUBS, B_TABLE, B_TABLE_LEN,
+    C_DISPATCHER, C_STUBS, C_TABLE, C_TABLE_LEN, EXCEPTION_HANDLER, EXE_RETURNED, FP, Flow, GP,
+    Guest, HleBios, RA, S0, SP,
+};
+use cdrom::iso9660::DirectoryEntry;
+
+// Memory control register values set by the BIOS during boot
+const MEMORY_CONTROL_INIT: &[(u32, u32)] = &[
+    (0x1F80_1000, 0x1F00_0000),
+    (0x1F80_1004, 0x1F80_2000),
+    (0x1F80_1008, 0x0013_243F),
+    (0x1F80_100C, 0x0000_3022),
+    (0x1F80_1010, 0x0013_243F),
+    (0x1F80_1014, 0x2009_31E1),
+    (0x1F80_1018, 0x0002_0843),
+    (0x1F80_101C, 0x0007_0777),
+    (0x1F80_1020, 0x0003_1125),
+    (0x1F80_1060, 0x0000_0B88),
+];
+
+// The first 64KB of RAM is reserved for the kernel
+const KERNEL_RAM_LEN: u32 = 0x10000;
+
+const EXCEPTION_VECTOR: u32 = 0x0080;
+
+// Where the BIOS would start the shell, and where sideloaded EXEs take over
+const SHELL_ENTRY: u32 = 0x8003_0000;
+
+// Header buffer used when booting the disc and by LoadExec()
+const BOOT_EXE_HEADER: u32 = 0x8000_DF80;
+
+// EXE header fields, relative to the header buffer (which starts at offset $10 in the EXE file)
+const HEADER_LEN: u32 = 0x3C;
+const HEADER_PC: u32 = 0x00;
+const HEADER_GP: u32 = 0x04;
+const HEADER_T_ADDR: u32 = 0x08;
+const HEADER_T_SIZE: u32 = 0x0C;
+const HEADER_B_ADDR: u32 = 0x18;
+const HEADER_B_SIZE: u32 = 0x1C;
+const HEADER_S_ADDR: u32 = 0x20;
+const HEADER_S_SIZE: u32 = 0x24;
+const HEADER_SAVED_REGS: u32 = 0x28;
+const SAVED_REGS: [u32; 5] = [SP, FP, GP, RA, S0];
+
+const EXE_DATA_OFFSET: u32 = 0x800;
+
+// Status register IEc and IM2 bits, which are cleared inside a critical section. EXEs are started
+// inside a critical section, but the shell runs with interrupts enabled
+const SR_INTERRUPTS_ENABLED: u32 = 0x0000_0401;
+
+fn j(target: u32) -> u32 {
+    0x0800_0000 | ((target & 0x0FFF_FFFF) >> 2)
+}
+
+const NOP: u32 = 0;
+
+/// Boot settings from a disc's SYSTEM.CNF file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SystemConfig {
+    pub boot: Vec<u8>,
+    pub threads: u32,
+    pub events: u32,
+    pub stack: u32,
+}
+
+impl Default for SystemConfig {
+    fn default() -> Self {
+        Self {
+            boot: b"cdrom:PSX.EXE;1".to_vec(),
+            threads: DEFAULT_THREAD_COUNT,
+            events: DEFAULT_EVENT_COUNT,
+            stack: DEFAULT_STACK_TOP,
+        }
+    }
+}
+
+impl SystemConfig {
+    /// Read SYSTEM.CNF from the disc, falling back to the default configuration if the disc does
+    /// not have one
+    pub(crate) fn read(cd: &mut CdController) -> Self {
+        let Some(file) = disc::find(cd, b"SYSTEM.CNF") else {
+            log::info!("No SYSTEM.CNF on disc; using default boot configuration");
+            return Self::default();
+        };
+
+        match disc::read_file(cd, &file, 0, file.size) {
+            Some(contents) => Self::parse(&contents),
+            None => Self::default(),
+        }
+    }
+
+    /// Read the entire boot EXE file from the disc, including the 2KB header
+    pub(crate) fn read_boot_exe(&self, cd: &mut CdController) -> Option<Vec<u8>> {
+        let file = disc::find(cd, disc_path(&self.boot))?;
+        disc::read_file(cd, &file, 0, file.size)
+    }
+
+    fn parse(contents: &[u8]) -> Self {
+        let mut config = Self::default();
+
+        for line in contents.split(|&b| b == b'\n' || b == b'\r') {
+            let Some(equals) = line.iter().position(|&b| b == b'=') else { continue };
+            let key = line[..equals].trim_ascii();
+            let value = line[equals + 1..].trim_ascii();
+
+            // Numeric values are always hexadecimal
+            let number =
+                || std::str::from_utf8(value).ok().and_then(|s| u32::from_str_radix(s, 16).ok());
+
+            match key {
+                b"BOOT" => config.boot = value.to_vec(),
+                b"TCB" => config.threads = number().unwrap_or(config.threads),
+                b"EVENT" => config.events = number().unwrap_or(config.events),
+                b"STACK" => config.stack = number().unwrap_or(config.stack),
+                _ => log::warn!("Unrecognized SYSTEM.CNF line: {}", String::from_utf8_lossy(line)),
+            }
+        }
+
+        config
+    }
+}
+
+fn install_vectors(g: &mut Guest<'_, '_>) {
+    // Exception vector: jump to the exception handler stub
+    let exception_vector = [
+        0x3C1A_0000 | (EXCEPTION_HANDLER >> 16), // lui k0, hi(handler)
+        0x375A_0000 | (EXCEPTION_HANDLER & 0xFFFF), // ori k0, k0, lo(handler)
+        0x0340_0008,                             // jr k0
+        NOP,
+    ];
+    for (i, &opcode) in exception_vector.iter().enumerate() {
+        g.write_u32(EXCEPTION_VECTOR + 4 * i as u32, opcode);
+    }
+
+    for (vector, dispatcher, table, table_len, stubs) in [
+        (0xA0, A_DISPATCHER, A_TABLE, A_TABLE_LEN, A_STUBS),
+        (0xB0, B_DISPATCHER, B_TABLE, B_TABLE_LEN, B_STUBS),
+        (0xC0, C_DISPATCHER, C_TABLE, C_TABLE_LEN, C_STUBS),
+    ] {
+        g.write_u32(vector, j(dispatcher));
+        g.write_u32(vector + 4, NOP);
+
+        // Load the function address from the table using the function number in T1
+        let code = [
+            0x2408_0000 | table, // addiu t0, zero, table
+            0x0009_5080,         // sll t2, t1, 2
+            0x010A_4021,         // addu t0, t0, t2
+            0x8D08_0000,         // lw t0, 0(t0)
+            NOP,
+            0x0100_0008, // jr t0
+            NOP,
+        ];
+        for (i, &opcode) in code.iter().enumerate() {
+            g.write_u32(dispatcher + 4 * i as u32, opcode);
+        }
+
+        for i in 0..table_len {
+            g.write_u32(table + 4 * i, stubs + 4 * i);
+        }
+    }
+}
+
+impl HleBios {
+    /// Initialize the kernel and then boot the disc if `boot_disc` is set, otherwise idle at the
+    /// shell entry point
+    pub(super) fn boot(&mut self, g: &mut Guest<'_, '_>, boot_disc: bool) {
+        *self = Self::new();
+
+        for &(address, value) in MEMORY_CONTROL_INIT {
+            g.bus.write_u32(address, value);
+        }
+
+        g.fill(0x8000_0000, 0, KERNEL_RAM_LEN);
+        install_vectors(g);
+
+        let config = if boot_disc {
+            SystemConfig::read(g.bus.cd_controller)
+        } else {
+            SystemConfig::default()
+        };
+        self.kernel.stack_top = config.stack;
+        self.init_kernel(g, config.events, config.threads);
+
+        g.bus.interrupt_registers.write_interrupt_mask(0);
+        g.bus.interrupt_registers.write_interrupt_status(0);
+
+        if boot_disc {
+            log::info!("HLE BIOS booting '{}'", String::from_utf8_lossy(&config.boot));
+            if Self::load_exe(g, &config.boot, BOOT_EXE_HEADER) {
+                g.set_cop0(Cop0Register::Status, 0);
+                Self::start_exe(g, BOOT_EXE_HEADER, config.stack);
+                g.set_reg(RA, EXE_RETURNED);
+                return;
+            }
+
+            log::warn!("Unable to boot disc with HLE BIOS");
+        }
+
+        // Nothing to run; idle where the shell would start
+        g.write_u32(SHELL_ENTRY, j(SHELL_ENTRY));
+        g.write_u32(SHELL_ENTRY + 4, NOP);
+        g.cpu.flush_i_cache();
+        g.set_reg(SP, config.stack);
+        g.set_reg(FP, config.stack);
+        g.set_cop0(Cop0Register::Status, SR_INTERRUPTS_ENABLED);
+        g.cpu.set_pc(SHELL_ENTRY);
+    }
+
+    /// Load an EXE from disc, writing its header to `header` and its contents to RAM
+    fn load_exe(g: &mut Guest<'_, '_>, path: &[u8], header: u32) -> bool {
+        let Some(exe) = read_exe_header(g, path) else { return false };
+        g.write_bytes(header, &exe.header);
+
+        let t_addr = g.read_u32(header + HEADER_T_ADDR);
+        let t_size = g.read_u32(header + HEADER_T_SIZE);
+        let Some(data) = disc::read_file(g.bus.cd_controller, &exe.file, EXE_DATA_OFFSET, t_size)
+        else {
+            log::error!("Failed to read EXE '{}' from disc", String::from_utf8_lossy(path));
+            return false;
+        };
+        g.write_bytes(t_addr, &data);
+        g.cpu.flush_i_cache();
+
+        true
+    }
+
+    /// Clear BSS and set up registers for an EXE whose header has been loaded, then jump to it
+    fn start_exe(g: &mut Guest<'_, '_>, header: u32, default_stack: u32) {
+        let b_addr = g.read_u32(header + HEADER_B_ADDR);
+        let b_size = g.read_u32(header + HEADER_B_SIZE);
+        if b_size != 0 {
+            g.fill(b_addr, 0, b_size);
+        }
+
+        let s_addr = g.read_u32(header + HEADER_S_ADDR);
+        let stack = if s_addr != 0 {
+            s_addr.wrapping_add(g.read_u32(header + HEADER_S_SIZE))
+        } else {
+            default_stack
+        };
+        g.set_reg(SP, stack);
+        g.set_reg(FP, stack);
+        g.set_reg(GP, g.read_u32(header + HEADER_GP));
+
+        g.cpu.set_pc(g.read_u32(header + HEADER_PC));
+    }
+
+    /// `LoadTest(filename, headerbuf)`: Read an EXE's header without loading it
+    pub(super) fn load_test(g: &mut Guest<'_, '_>) -> Flow {
+        let (path, header) = (g.read_cstring(g.arg(0)), g.arg(1));
+        let Some(exe) = read_exe_header(g, &path) else { return Flow::Return(0) };
+        g.write_bytes(header, &exe.header);
+        Flow::Return(g.read_u32(header + HEADER_PC))
+    }
+
+    /// `Load(filename, headerbuf)`
+    pub(super) fn load(g: &mut Guest<'_, '_>) -> Flow {
+        let (path, header) = (g.read_cstring(g.arg(0)), g.arg(1));
+        Flow::Return(Self::load_exe(g, &path, header).into())
+    }
+
+    /// `Exec(headerbuf, param1, param2)`: Run a loaded EXE, returning 1 if it returns
+    pub(super) fn exec(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (header, param1, param2) = (g.arg(0), g.arg(1), g.arg(2));
+
+        for (i, register) in SAVED_REGS.into_iter().enumerate() {
+            g.write_u32(header + HEADER_SAVED_REGS + 4 * i as u32, g.reg(register));
+        }
+
+        Self::start_exe(g, header, g.reg(SP));
+        let pc = g.cpu.pc();
+        self.call_guest(g, pc, &[param1, param2], Continuation::Exec { header });
+        Flow::Jump
+    }
+
+    pub(super) fn exec_returned(g: &mut Guest<'_, '_>, header: u32) -> Flow {
+        for (i, register) in SAVED_REGS.into_iter().enumerate() {
+            g.set_reg(register, g.read_u32(header + HEADER_SAVED_REGS + 4 * i as u32));
+        }
+        Flow::Return(1)
+    }
+
+    /// `LoadExec(filename, stackbase, stackoffset)`: Load and run an EXE, which never returns
+    pub(super) fn load_exec(g: &mut Guest<'_, '_>) -> Flow {
+        let (path, stack_base, stack_offset) = (g.read_cstring(g.arg(0)), g.arg(1), g.arg(2));
+        if !Self::load_exe(g, &path, BOOT_EXE_HEADER) {
+            return Self::system_error(g, 'A', 0x51);
+        }
+
+        g.write_u32(BOOT_EXE_HEADER + HEADER_S_ADDR, stack_base);
+        g.write_u32(BOOT_EXE_HEADER + HEADER_S_SIZE, stack_offset);
+
+        let sr = g.cop0(Cop0Register::Status);
+        g.set_cop0(Cop0Register::Status, sr & !SR_INTERRUPTS_ENABLED);
+
+        let stack = g.reg(SP);
+        Self::start_exe(g, BOOT_EXE_HEADER, stack);
+        g.set_reg(RA, EXE_RETURNED);
+        Flow::Jump
+    }
+
+    /// `CdGetLbn(filename)`: Returns the first sector of a file, or -1 if not found
+    pub(super) fn cd_get_lbn(g: &mut Guest<'_, '_>) -> Flow {
+        let path = g.read_cstring(g.arg(0));
+        match disc::find(g.bus.cd_controller, disc_path(&path)) {
+            Some(file) => Flow::Return(file.lba),
+            None => Flow::Return(u32::MAX),
+        }
+    }
+
+    /// `CdReadSector(count, sector, buffer)`: Returns the number of sectors read, or -1 on error
+    pub(super) fn cd_read_sector(g: &mut Guest<'_, '_>) -> Flow {
+        let (count, lba, buffer) = (g.arg(0), g.arg(1), g.arg(2));
+
+        let mut sector = [0; 2048];
+        for i in 0..count {
+            if !disc::read_user_data(g.bus.cd_controller, lba + i, &mut sector) {
+                return Flow::Return(u32::MAX);
+            }
+            g.write_bytes(buffer + 2048 * i, &sector);
+        }
+
+        Flow::Return(count)
+    }
+}
+
+struct ExeHeader {
+    file: DirectoryEntry,
+    header: Vec<u8>,
+}
+
+fn read_exe_header(g: &mut Guest<'_, '_>, path: &[u8]) -> Option<ExeHeader> {
+    let Some((Device::Cdrom, file_path)) = split_device(path) else {
+        log::error!(
+            "Cannot load EXE '{}'; only cdrom: is supported",
+            String::from_utf8_lossy(path)
+        );
+        return None;
+    };
+
+    let cd = &mut *g.bus.cd_controller;
+    let Some(file) = disc::find(cd, file_path) else {
+        log::error!("EXE '{}' not found on disc", String::from_utf8_lossy(path));
+        return None;
+    };
+
+    let first_sector = disc::read_file(cd, &file, 0, EXE_DATA_OFFSET)?;
+    if first_sector.len() < EXE_DATA_OFFSET as usize || &first_sector[..8] != b"PS-X EXE" {
+        log::warn!("'{}' does not have a PS-X EXE header", String::from_utf8_lossy(path));
+    }
+    let header = first_sector.get(0x10..(0x10 + HEADER_LEN) as usize)?.to_vec();
+
+    Some(ExeHeader { file, header })
+}
+
+/// Strip the device prefix (e.g. `cdrom:`) from a path, if present
+fn disc_path(path: &[u8]) -> &[u8] {
+    split_device(path).map_or(path, |(_, file_path)| file_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_system_cnf() {
+        let config = SystemConfig::parse(
+            b"BOOT = cdrom:\\SLUS_000.01;1\r\nTCB = 4\r\nEVENT = 10\r\nSTACK = 801FFF00\r\n",
+        );
+        assert_eq!(
+            config,
+            SystemConfig {
+                boot: b"cdrom:\\SLUS_000.01;1".to_vec(),
+                threads: 4,
+                events: 0x10,
+                stack: 0x801F_FF00,
+            }
+        );
+        assert_eq!(disc_path(&config.boot), b"\\SLUS_000.01;1");
+    }
+
+    #[test]
+    fn parse_system_cnf_defaults() {
+        // Missing, malformed, and unrecognized lines leave the defaults in place
+        let config =
+            SystemConfig::parse(b"BOOT=cdrom:GAME.EXE;1\nTCB=zz\nSTACK\nFOO=1\n\n\tEVENT =  8 \n");
+        assert_eq!(
+            config,
+            SystemConfig {
+                boot: b"cdrom:GAME.EXE;1".to_vec(),
+                events: 8,
+                ..SystemConfig::default()
+            }
+        );
+
+        assert_eq!(SystemConfig::parse(b""), SystemConfig::default());
+    }
+}
@@ -0,0 +1,46 @@
+//! Disc filesystem access for the HLE kernel. Errors are logged and reported to the guest as
+//! missing files
+
+use crate::cd::CdController;
+use cdrom::CdRomResult;
+use cdrom::iso9660::{self, DirectoryEntry, IsoFilesystem};
+use cdrom::reader::CdRom;
+
+fn with_disc<T>(
+    cd: &mut CdController,
+    f: impl FnOnce(&mut CdRom) -> CdRomResult<Option<T>>,
+) -> Option<T> {
+    let disc = cd.disc_mut()?;
+    f(disc).unwrap_or_else(|err| {
+        log::error!("Error reading from disc: {err}");
+        None
+    })
+}
+
+/// Find a file or directory by path, e.g. `\SLUS_000.01;1`
+pub fn find(cd: &mut CdController, path: &[u8]) -> Option<DirectoryEntry> {
+    let path = String::from_utf8_lossy(path);
+    with_disc(cd, |disc| IsoFilesystem::open(disc)?.find(disc, &path))
+}
+
+/// Read `len` bytes starting at `offset` within a file
+pub fn read_file(
+    cd: &mut CdController,
+    file: &DirectoryEntry,
+    offset: u32,
+    len: u32,
+) -> Option<Vec<u8>> {
+    with_disc(cd, |disc| file.read_range(disc, offset, len).map(Some))
+}
+
+pub fn list_directory(
+    cd: &mut CdController,
+    directory: &DirectoryEntry,
+) -> Option<Vec<DirectoryEntry>> {
+    with_disc(cd, |disc| directory.list(disc).map(Some))
+}
+
+/// Read the 2048 bytes of user data from a data sector. `lba` is relative to 00:02:00
+pub fn read_user_data(cd: &mut CdController, lba: u32, out: &mut [u8; 2048]) -> bool {
+    with_disc(cd, |disc| iso9660::read_user_data(disc, lba, out).map(Some)).is_some()
+}
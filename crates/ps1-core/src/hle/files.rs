@@ -0,0 +1,698 @@
+//! File I/O functions for the `cdrom:`, `buXX:` (memory card), and `tty:` devices
+
+use crate::hle::kernel::{card_slot, with_card};
+use crate::hle::{Flow, Guest, HleBios, disc};
+use crate::sio::memcard::MemoryCard;
+use crate::tty::TtySource;
+use bincode::{Decode, Encode};
+use cdrom::iso9660::DirectoryEntry;
+
+const MAX_FILES: usize = 16;
+
+// Error codes returned by GetLastError() and GetLastFileError()
+const ENOENT: u32 = 2;
+const EBADF: u32 = 9;
+const EEXIST: u32 = 17;
+const ENODEV: u32 = 19;
+const EINVAL: u32 = 22;
+const ENOSPC: u32 = 28;
+
+// open() mode flags
+const O_READ: u32 = 0x0001;
+const O_WRITE: u32 = 0x0002;
+const O_CREATE: u32 = 0x0200;
+const O_ASYNC: u32 = 0x8000;
+
+// Memory card layout: block 0 holds the directory, one 128-byte frame per data block
+const CARD_BLOCK_LEN: u32 = 0x2000;
+const CARD_BLOCKS: u32 = 15;
+const FRAME_LEN: u32 = 0x80;
+const FRAME_STATE: usize = 0x00;
+const FRAME_SIZE: usize = 0x04;
+const FRAME_NEXT: usize = 0x08;
+const FRAME_NAME: usize = 0x0A;
+const FRAME_NAME_LEN: usize = 20;
+const FRAME_CHECKSUM: usize = 0x7F;
+const FRAME_NO_NEXT: u16 = 0xFFFF;
+
+const BLOCK_FIRST: u32 = 0x51;
+const BLOCK_MIDDLE: u32 = 0x52;
+const BLOCK_LAST: u32 = 0x53;
+const BLOCK_FREE: u32 = 0xA0;
+// Deleting a file changes each of its frame states from $5x to $Ax
+const BLOCK_DELETED: u32 = 0x50;
+
+// Directory entry structure returned by firstfile()/nextfile()
+const DIRENTRY_NAME_LEN: usize = 20;
+const DIRENTRY_ATTR: u32 = 0x14;
+const DIRENTRY_SIZE: u32 = 0x18;
+const DIRENTRY_NEXT: u32 = 0x1C;
+const DIRENTRY_HEAD: u32 = 0x20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum Device {
+    Cdrom,
+    Card { port: u32 },
+    Tty,
+}
+
+/// Split a path like `bu10:FOO` into its device and file path. Paths without a device are on the
+/// CD. Returns `None` for unknown devices.
+pub fn split_device(path: &[u8]) -> Option<(Device, &[u8])> {
+    let Some(colon) = path.iter().position(|&b| b == b':') else {
+        return Some((Device::Cdrom, path));
+    };
+
+    let name = path[..colon].to_ascii_lowercase();
+    let file_path = &path[colon + 1..];
+
+    let device = if name.starts_with(b"cdrom") {
+        Device::Cdrom
+    } else if name.starts_with(b"tty") {
+        Device::Tty
+    } else if let Some(port) = name.strip_prefix(b"bu") {
+        let port = std::str::from_utf8(port).ok().and_then(|s| u32::from_str_radix(s, 16).ok())?;
+        Device::Card { port }
+    } else {
+        return None;
+    };
+
+    Some((device, file_path))
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+enum FileData {
+    Disc(DirectoryEntry),
+    Card { slot: usize, blocks: Vec<u32> },
+    Tty,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+struct OpenFile {
+    data: FileData,
+    mode: u32,
+    position: u32,
+    size: u32,
+    error: u32,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+struct FindState {
+    device: Device,
+    pattern: Vec<u8>,
+    next_index: usize,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct Files {
+    files: [Option<OpenFile>; MAX_FILES],
+    last_error: u32,
+    find: Option<FindState>,
+    pub card_find_mode: u32,
+}
+
+impl Files {
+    pub fn new() -> Self {
+        let mut files: [Option<OpenFile>; MAX_FILES] = Default::default();
+
+        // stdin and stdout
+        for file in &mut files[..2] {
+            *file = Some(OpenFile {
+                data: FileData::Tty,
+                mode: O_READ | O_WRITE,
+                position: 0,
+                size: 0,
+                error: 0,
+            });
+        }
+
+        Self { files, last_error: 0, find: None, card_find_mode: 0 }
+    }
+
+    pub fn last_error(&self) -> u32 {
+        self.last_error
+    }
+
+    pub fn last_file_error(&self, fd: u32) -> u32 {
+        match self.files.get(fd as usize) {
+            Some(Some(file)) => file.error,
+            _ => EBADF,
+        }
+    }
+
+    fn error(&mut self, error: u32) -> Flow {
+        self.last_error = error;
+        Flow::Return(u32::MAX)
+    }
+
+    fn file_mut(&mut self, fd: u32) -> Option<&mut OpenFile> {
+        self.files.get_mut(fd as usize).and_then(Option::as_mut)
+    }
+}
+
+/// A file in a memory card directory, identified by its first block (1-15)
+struct CardFile {
+    name: Vec<u8>,
+    size: u32,
+    blocks: Vec<u32>,
+}
+
+fn frame_state(directory: &[u8], block: u32) -> u32 {
+    let offset = (block * FRAME_LEN) as usize;
+    u32::from_le_bytes(
+        directory[offset + FRAME_STATE..offset + FRAME_STATE + 4].try_into().unwrap(),
+    )
+}
+
+fn read_card_directory(g: &mut Guest<'_, '_>, slot: usize) -> Option<Vec<u8>> {
+    let mut directory = vec![0; ((CARD_BLOCKS + 1) * FRAME_LEN) as usize];
+    with_card(g, slot, |card| card.read_bytes(0, &mut directory))?;
+    Some(directory)
+}
+
+fn write_card_frame(g: &mut Guest<'_, '_>, slot: usize, block: u32, frame: &mut [u8]) {
+    frame[FRAME_CHECKSUM] = frame[..FRAME_CHECKSUM].iter().fold(0, |checksum, &b| checksum ^ b);
+    with_card(g, slot, |card| card.write_bytes((block * FRAME_LEN) as usize, frame));
+}
+
+fn card_files(directory: &[u8]) -> Vec<(u32, CardFile)> {
+    (1..=CARD_BLOCKS)
+        .filter(|&block| frame_state(directory, block) == BLOCK_FIRST)
+        .map(|block| {
+            let offset = (block * FRAME_LEN) as usize;
+            let frame = &directory[offset..offset + FRAME_LEN as usize];
+
+            let name = &frame[FRAME_NAME..FRAME_NAME + FRAME_NAME_LEN];
+            let name = name.iter().position(|&b| b == 0).map_or(name, |len| &name[..len]);
+            let size = u32::from_le_bytes(frame[FRAME_SIZE..FRAME_SIZE + 4].try_into().unwrap());
+
+            // Follow the block chain, guarding against loops
+            let mut blocks = vec![block];
+            let mut current = offset;
+            while blocks.len() < CARD_BLOCKS as usize {
+                let next = u16::from_le_bytes([
+                    directory[current + FRAME_NEXT],
+                    directory[current + FRAME_NEXT + 1],
+                ]);
+                if next == FRAME_NO_NEXT || u32::from(next) >= CARD_BLOCKS {
+                    break;
+                }
+                let next_block = u32::from(next) + 1;
+                blocks.push(next_block);
+                current = (next_block * FRAME_LEN) as usize;
+            }
+
+            (block, CardFile { name: name.to_vec(), size, blocks })
+        })
+        .collect()
+}
+
+/// Match a name against a `firstfile()` pattern, where `?` matches any character and `*` matches
+/// the rest of the name
+fn matches_pattern(name: &[u8], pattern: &[u8]) -> bool {
+    let mut name = name.iter();
+    for &p in pattern {
+        match p {
+            b'*' => return true,
+            b'?' => {
+                if name.next().is_none() {
+                    return false;
+                }
+            }
+            _ => {
+                if name.next() != Some(&p) {
+                    return false;
+                }
+            }
+        }
+    }
+    name.next().is_none()
+}
+
+fn card_path(path: &[u8]) -> &[u8] {
+    let start = path.iter().position(|&b| b != b'\\' && b != b'/').unwrap_or(path.len());
+    &path[start..]
+}
+
+fn write_direntry(g: &mut Guest<'_, '_>, address: u32, name: &[u8], size: u32, head: u32) {
+    let mut name_field = [0; DIRENTRY_NAME_LEN];
+    let len = name.len().min(DIRENTRY_NAME_LEN - 1);
+    name_field[..len].copy_from_slice(&name[..len]);
+
+    g.write_bytes(address, &name_field);
+    g.write_u32(address + DIRENTRY_ATTR, 0x50);
+    g.write_u32(address + DIRENTRY_SIZE, size);
+    g.write_u32(address + DIRENTRY_NEXT, 0);
+    g.write_u32(address + DIRENTRY_HEAD, head);
+}
+
+impl HleBios {
+    /// `open(filename, mode)`: Returns a file descriptor, or -1 on error
+    pub(super) fn open(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (path, mode) = (g.read_cstring(g.arg(0)), g.arg(1));
+
+        let Some(fd) = self.files.files.iter().position(Option::is_none) else {
+            log::warn!("open('{}') failed: too many open files", String::from_utf8_lossy(&path));
+            return self.files.error(EBADF);
+        };
+
+        let Some((device, file_path)) = split_device(&path) else {
+            return self.files.error(ENODEV);
+        };
+
+        let (data, size) = match device {
+            Device::Cdrom => {
+                if mode & O_WRITE != 0 {
+                    return self.files.error(EINVAL);
+                }
+                let Some(file) = disc::find(g.bus.cd_controller, file_path) else {
+                    return self.files.error(ENOENT);
+                };
+                let size = file.size;
+                (FileData::Disc(file), size)
+            }
+            Device::Card { port } => {
+                let Some(slot) = card_slot(port) else { return self.files.error(ENODEV) };
+                match Self::open_card_file(g, slot, card_path(file_path), mode) {
+                    Ok(opened) => opened,
+                    Err(error) => return self.files.error(error),
+                }
+            }
+            Device::Tty => (FileData::Tty, 0),
+        };
+
+        log::debug!("open('{}', {mode:X}) = {fd}", String::from_utf8_lossy(&path));
+        self.files.files[fd] = Some(OpenFile { data, mode, position: 0, size, error: 0 });
+        self.files.last_error = 0;
+        Flow::Return(fd as u32)
+    }
+
+    fn open_card_file(
+        g: &mut Guest<'_, '_>,
+        slot: usize,
+        name: &[u8],
+        mode: u32,
+    ) -> Result<(FileData, u32), u32> {
+        let mut directory = read_card_directory(g, slot).ok_or(ENODEV)?;
+        let existing = card_files(&directory).into_iter().find(|(_, file)| file.name == name);
+
+        if mode & O_CREATE == 0 {
+            let (_, file) = existing.ok_or(ENOENT)?;
+            return Ok((FileData::Card { slot, blocks: file.blocks }, file.size));
+        }
+
+        if existing.is_some() {
+            return Err(EEXIST);
+        }
+
+        let block_count = ((mode >> 16) & 0xF).max(1);
+        let free: Vec<u32> = (1..=CARD_BLOCKS)
+            .filter(|&block| frame_state(&directory, block) & 0xF0 == BLOCK_FREE)
+            .take(block_count as usize)
+            .collect();
+        if free.len() != block_count as usize {
+            return Err(ENOSPC);
+        }
+
+        let size = block_count * CARD_BLOCK_LEN;
+        for (i, &block) in free.iter().enumerate() {
+            let state = match i {
+                0 => BLOCK_FIRST,
+                _ if i == free.len() - 1 => BLOCK_LAST,
+                _ => BLOCK_MIDDLE,
+            };
+            let next = free.get(i + 1).map_or(FRAME_NO_NEXT, |&next| (next - 1) as u16);
+
+            let offset = (block * FRAME_LEN) as usize;
+            let frame = &mut directory[offset..offset + FRAME_LEN as usize];
+            frame.fill(0);
+            frame[FRAME_STATE..FRAME_STATE + 4].copy_from_slice(&state.to_le_bytes());
+            frame[FRAME_NEXT..FRAME_NEXT + 2].copy_from_slice(&next.to_le_bytes());
+            if i == 0 {
+                frame[FRAME_SIZE..FRAME_SIZE + 4].copy_from_slice(&size.to_le_bytes());
+                let len = name.len().min(FRAME_NAME_LEN);
+                frame[FRAME_NAME..FRAME_NAME + len].copy_from_slice(&name[..len]);
+            }
+            write_card_frame(g, slot, block, frame);
+        }
+
+        Ok((FileData::Card { slot, blocks: free }, size))
+    }
+
+    /// `lseek(fd, offset, seektype)`: Returns the new position, or -1 on error
+    pub(super) fn lseek(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (fd, offset, seek_type) = (g.arg(0), g.arg(1), g.arg(2));
+        let Some(file) = self.files.file_mut(fd) else { return self.files.error(EBADF) };
+
+        let position = match seek_type {
+            0 => offset,
+            1 => file.position.wrapping_add(offset),
+            2 => file.size.wrapping_add(offset),
+            _ => {
+                file.error = EINVAL;
+                return self.files.error(EINVAL);
+            }
+        };
+        file.position = position;
+
+        Flow::Return(position)
+    }
+
+    /// `read(fd, dst, length)`: Returns the number of bytes read, or -1 on error
+    pub(super) fn read(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (fd, dst, len) = (g.arg(0), g.arg(1), g.arg(2));
+        let Some(file) = self.files.file_mut(fd) else { return self.files.error(EBADF) };
+
+        let position = file.position;
+        let len = len.min(file.size.saturating_sub(position));
+        let mode = file.mode;
+
+        let data = match &file.data {
+            FileData::Disc(disc_file) => {
+                disc::read_file(g.bus.cd_controller, disc_file, position, len)
+            }
+            FileData::Card { slot, blocks } => read_card_file(g, *slot, blocks, position, len),
+            FileData::Tty => Some(Vec::new()),
+        };
+
+        let Some(data) = data else {
+            file.error = ENODEV;
+            return self.files.error(ENODEV);
+        };
+        let is_card = matches!(file.data, FileData::Card { .. });
+        file.position += data.len() as u32;
+        g.write_bytes(dst, &data);
+
+        let count = data.len() as u32;
+        if is_card && mode & O_ASYNC != 0 {
+            return self.card_io_complete(g, count);
+        }
+        Flow::Return(count)
+    }
+
+    /// `write(fd, src, length)`: Returns the number of bytes written, or -1 on error
+    pub(super) fn write(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (fd, src, len) = (g.arg(0), g.arg(1), g.arg(2));
+        let Some(file) = self.files.file_mut(fd) else { return self.files.error(EBADF) };
+
+        match &file.data {
+            FileData::Tty => {
+                for c in g.read_bytes(src, len) {
+                    g.bus.tty.putchar(TtySource::Bios, c);
+                }
+                Flow::Return(len)
+            }
+            FileData::Disc { .. } => {
+                file.error = EBADF;
+                self.files.error(EBADF)
+            }
+            FileData::Card { slot, blocks } => {
+                let position = file.position;
+                let len = len.min(file.size.saturating_sub(position));
+                let data = g.read_bytes(src, len);
+                if !write_card_file(g, *slot, blocks, position, &data) {
+                    file.error = ENODEV;
+                    return self.files.error(ENODEV);
+                }
+
+                file.position += len;
+                if file.mode & O_ASYNC != 0 {
+                    return self.card_io_complete(g, len);
+                }
+                Flow::Return(len)
+            }
+        }
+    }
+
+    /// `close(fd)`: Returns the file descriptor, or -1 on error
+    pub(super) fn close(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let fd = g.arg(0);
+        match self.files.files.get_mut(fd as usize).and_then(Option::take) {
+            Some(_) => Flow::Return(fd),
+            None => self.files.error(EBADF),
+        }
+    }
+
+    pub(super) fn isatty(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        match self.files.file_mut(g.arg(0)) {
+            Some(file) => Flow::Return(matches!(file.data, FileData::Tty).into()),
+            None => self.files.error(EBADF),
+        }
+    }
+
+    /// `getc(fd)`: Read a single byte
+    pub(super) fn getc(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let fd = g.arg(0);
+        let Some(file) = self.files.file_mut(fd) else { return self.files.error(EBADF) };
+        if file.position >= file.size {
+            return Flow::Return(u32::MAX);
+        }
+
+        let position = file.position;
+        let byte = match &file.data {
+            FileData::Disc(disc_file) => {
+                disc::read_file(g.bus.cd_controller, disc_file, position, 1)
+            }
+            FileData::Card { slot, blocks } => read_card_file(g, *slot, blocks, position, 1),
+            FileData::Tty => None,
+        };
+
+        match byte.and_then(|data| data.first().copied()) {
+            Some(byte) => {
+                file.position += 1;
+                Flow::Return(byte.into())
+            }
+            None => Flow::Return(u32::MAX),
+        }
+    }
+
+    /// `putc(char, fd)`: Write a single byte
+    pub(super) fn putc(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (c, fd) = (g.arg(0), g.arg(1));
+        match self.files.file_mut(fd).map(|file| &file.data) {
+            Some(FileData::Tty) => {
+                g.bus.tty.putchar(TtySource::Bios, c as u8);
+                Flow::Return(c)
+            }
+            Some(_) => {
+                log::warn!("putc() to non-TTY file descriptor {fd} is not supported");
+                Flow::Return(u32::MAX)
+            }
+            None => self.files.error(EBADF),
+        }
+    }
+
+    /// `chdir(name)`: Only the root directory is supported
+    pub(super) fn chdir(g: &mut Guest<'_, '_>) -> Flow {
+        let path = g.read_cstring(g.arg(0));
+        log::warn!("Ignoring chdir('{}')", String::from_utf8_lossy(&path));
+        Flow::Return(1)
+    }
+
+    /// `FormatDevice(devicename)`
+    pub(super) fn format(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let path = g.read_cstring(g.arg(0));
+        let Some((Device::Card { port }, _)) = split_device(&path) else {
+            self.files.last_error = ENODEV;
+            return Flow::Return(0);
+        };
+
+        match card_slot(port).and_then(|slot| with_card(g, slot, MemoryCard::format)) {
+            Some(()) => Flow::Return(1),
+            None => {
+                self.files.last_error = ENODEV;
+                Flow::Return(0)
+            }
+        }
+    }
+
+    /// `firstfile(filename, direntry)`: Returns `direntry` if a matching file was found, or 0
+    pub(super) fn first_file(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let path = g.read_cstring(g.arg(0));
+        let Some((device, pattern)) = split_device(&path) else {
+            self.files.last_error = ENODEV;
+            return Flow::Return(0);
+        };
+
+        let pattern = match device {
+            Device::Card { .. } => card_path(pattern),
+            _ => pattern,
+        };
+        self.files.find = Some(FindState { device, pattern: pattern.to_vec(), next_index: 0 });
+        self.find_next_file(g, g.arg(1))
+    }
+
+    /// `nextfile(direntry)`: Continue the search started by `firstfile()`
+    pub(super) fn next_file(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        self.find_next_file(g, g.arg(0))
+    }
+
+    fn find_next_file(&mut self, g: &mut Guest<'_, '_>, direntry: u32) -> Flow {
+        let Some(find) = &mut self.files.find else { return Flow::Return(0) };
+
+        let found = match find.device {
+            Device::Card { port } => card_slot(port)
+                .and_then(|slot| read_card_directory(g, slot))
+                .and_then(|directory| {
+                    card_files(&directory)
+                        .into_iter()
+                        .enumerate()
+                        .skip(find.next_index)
+                        .find(|(_, (_, file))| matches_pattern(&file.name, &find.pattern))
+                        .map(|(i, (block, file))| (i, file.name, file.size, block))
+                }),
+            Device::Cdrom => {
+                // Split the pattern into a directory path and a file name pattern
+                let split = find.pattern.iter().rposition(|&b| b == b'\\' || b == b'/');
+                let (directory_path, name_pattern) = match split {
+                    Some(i) => (&find.pattern[..i], &find.pattern[i + 1..]),
+                    None => (&[][..], find.pattern.as_slice()),
+                };
+                let name_pattern = name_pattern.split(|&b| b == b';').next().unwrap_or(&[]);
+
+                let cd = &mut *g.bus.cd_controller;
+                disc::find(cd, directory_path)
+                    .filter(|directory| directory.is_directory)
+                    .and_then(|directory| disc::list_directory(cd, &directory))
+                    .and_then(|entries| {
+                        entries
+                            .into_iter()
+                            .enumerate()
+                            .skip(find.next_index)
+                            .find(|(_, file)| {
+                                matches_pattern(
+                                    &file.name.to_ascii_uppercase().into_bytes(),
+                                    &name_pattern.to_ascii_uppercase(),
+                                )
+                            })
+                            .map(|(i, file)| (i, file.name.into_bytes(), file.size, file.lba))
+                    })
+            }
+            Device::Tty => None,
+        };
+
+        match found {
+            Some((index, name, size, head)) => {
+                find.next_index = index + 1;
+                write_direntry(g, direntry, &name, size, head);
+                Flow::Return(direntry)
+            }
+            None => {
+                self.files.find = None;
+                Flow::Return(0)
+            }
+        }
+    }
+
+    /// `rename(old_filename, new_filename)`: Only supported for memory card files
+    pub(super) fn rename(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let old_path = g.read_cstring(g.arg(0));
+        let new_path = g.read_cstring(g.arg(1));
+
+        let (Some((Device::Card { port }, old_name)), Some((_, new_name))) =
+            (split_device(&old_path), split_device(&new_path))
+        else {
+            self.files.last_error = ENODEV;
+            return Flow::Return(0);
+        };
+        let (old_name, new_name) = (card_path(old_name), card_path(new_name));
+
+        let Some(slot) = card_slot(port) else { return Flow::Return(0) };
+        let Some(mut directory) = read_card_directory(g, slot) else {
+            self.files.last_error = ENODEV;
+            return Flow::Return(0);
+        };
+
+        let files = card_files(&directory);
+        if files.iter().any(|(_, file)| file.name == new_name) {
+            self.files.last_error = EEXIST;
+            return Flow::Return(0);
+        }
+        let Some(&(block, _)) = files.iter().find(|(_, file)| file.name == old_name) else {
+            self.files.last_error = ENOENT;
+            return Flow::Return(0);
+        };
+
+        let offset = (block * FRAME_LEN) as usize;
+        let frame = &mut directory[offset..offset + FRAME_LEN as usize];
+        frame[FRAME_NAME..FRAME_NAME + FRAME_NAME_LEN].fill(0);
+        let len = new_name.len().min(FRAME_NAME_LEN);
+        frame[FRAME_NAME..FRAME_NAME + len].copy_from_slice(&new_name[..len]);
+        write_card_frame(g, slot, block, frame);
+
+        Flow::Return(1)
+    }
+
+    /// `erase(filename)`: Only supported for memory card files
+    pub(super) fn erase(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let path = g.read_cstring(g.arg(0));
+        let Some((Device::Card { port }, name)) = split_device(&path) else {
+            self.files.last_error = ENODEV;
+            return Flow::Return(0);
+        };
+        let name = card_path(name);
+
+        let Some(slot) = card_slot(port) else { return Flow::Return(0) };
+        let Some(mut directory) = read_card_directory(g, slot) else {
+            self.files.last_error = ENODEV;
+            return Flow::Return(0);
+        };
+
+        let Some((_, file)) =
+            card_files(&directory).into_iter().find(|(_, file)| file.name == name)
+        else {
+            self.files.last_error = ENOENT;
+            return Flow::Return(0);
+        };
+
+        for block in file.blocks {
+            let state = frame_state(&directory, block) + BLOCK_DELETED;
+            let offset = (block * FRAME_LEN) as usize;
+            let frame = &mut directory[offset..offset + FRAME_LEN as usize];
+            frame[FRAME_STATE..FRAME_STATE + 4].copy_from_slice(&state.to_le_bytes());
+            write_card_frame(g, slot, block, frame);
+        }
+
+        Flow::Return(1)
+    }
+}
+
+fn card_file_offset(blocks: &[u32], position: u32) -> Option<usize> {
+    let block = blocks.get((position / CARD_BLOCK_LEN) as usize)?;
+    Some((block * CARD_BLOCK_LEN + position % CARD_BLOCK_LEN) as usize)
+}
+
+fn read_card_file(
+    g: &mut Guest<'_, '_>,
+    slot: usize,
+    blocks: &[u32],
+    position: u32,
+    len: u32,
+) -> Option<Vec<u8>> {
+    with_card(g, slot, |card| {
+        let mut data = vec![0; len as usize];
+        for (i, byte) in data.iter_mut().enumerate() {
+            if let Some(offset) = card_file_offset(blocks, position + i as u32) {
+                card.read_bytes(offset, std::slice::from_mut(byte));
+            }
+        }
+        data
+    })
+}
+
+fn write_card_file(
+    g: &mut Guest<'_, '_>,
+    slot: usize,
+    blocks: &[u32],
+    position: u32,
+    data: &[u8],
+) -> bool {
+    with_card(g, slot, |card| {
+        for (i, &byte) in data.iter().enumerate() {
+            if let Some(offset) = card_file_offset(blocks, position + i as u32) {
+                card.write_bytes(offset, &[byte]);
+            }
+        }
+    })
+    .is_some()
+}
@@ -0,0 +1,1010 @@
+//! Kernel internals: exceptions and interrupt handler chains, events, threads, root counters,
+//! controllers, memory card sector access, and GPU helper functions
+
+use crate::debug::{Cop0Register, CpuRegister};
+use crate::hle::libc::Libc;
+use crate::hle::{
+    A0, A1, FP, Flow, GP, Guest, HALTED, HleBios, PAD_HANDLER, RA, RCNT_HANDLERS, RETURN_TRAP, SP,
+    V0,
+};
+use crate::sio::Port;
+use crate::sio::memcard::MemoryCard;
+use bincode::{Decode, Encode};
+
+// Pointers to kernel data structures in low RAM, each followed by the structure's size in bytes
+const EXCB_PTR: u32 = 0x0100;
+const PCB_PTR: u32 = 0x0108;
+const TCB_PTR: u32 = 0x0110;
+const EVCB_PTR: u32 = 0x0120;
+
+const KERNEL_HEAP_START: u32 = 0x8000_E000;
+const KERNEL_HEAP_END: u32 = 0x8001_0000;
+
+// Interrupt handlers run on a kernel stack rather than on the interrupted code's stack
+const EXCEPTION_STACK_TOP: u32 = 0x8000_6FF0;
+
+// Interrupt chain entries for the kernel's own handlers: +0 next, +4 func2, +8 func1
+const DEFAULT_HANDLERS: u32 = 0x8000_7000;
+const CHAIN_ENTRY_LEN: u32 = 0x10;
+const PAD_HANDLER_PRIORITY: u32 = 2;
+const RCNT_HANDLER_PRIORITY: u32 = 1;
+
+const EXCB_LEN: u32 = 4 * 8;
+const PCB_LEN: u32 = 4;
+const TCB_LEN: u32 = 0xC0;
+const EVCB_LEN: u32 = 0x1C;
+
+const TCB_STATUS: u32 = 0x00;
+const TCB_REGS: u32 = 0x08;
+const TCB_EPC: u32 = 0x88;
+const TCB_HI: u32 = 0x8C;
+const TCB_LO: u32 = 0x90;
+const TCB_SR: u32 = 0x94;
+const TCB_CAUSE: u32 = 0x98;
+
+const THREAD_FREE: u32 = 0x1000;
+const THREAD_USED: u32 = 0x4000;
+const THREAD_HANDLE_BASE: u32 = 0xFF00_0000;
+
+const EVCB_CLASS: u32 = 0x00;
+const EVCB_STATUS: u32 = 0x04;
+const EVCB_SPEC: u32 = 0x08;
+const EVCB_MODE: u32 = 0x0C;
+const EVCB_FUNC: u32 = 0x10;
+
+const EVENT_FREE: u32 = 0x0000;
+const EVENT_DISABLED: u32 = 0x1000;
+const EVENT_ENABLED: u32 = 0x2000;
+const EVENT_READY: u32 = 0x4000;
+
+const EVENT_MODE_CALLBACK: u32 = 0x1000;
+const EVENT_MODE_NO_CALLBACK: u32 = 0x2000;
+
+const EVENT_HANDLE_BASE: u32 = 0xF100_0000;
+
+const RCNT_CLASS_BASE: u32 = 0xF200_0000;
+const HW_CARD_CLASS: u32 = 0xF000_0011;
+const SW_CARD_CLASS: u32 = 0xF400_0001;
+
+const SPEC_INTERRUPT: u32 = 0x0002;
+const SPEC_IO_END: u32 = 0x0004;
+const SPEC_TIMEOUT: u32 = 0x0100;
+const SPEC_ERROR: u32 = 0x8000;
+
+const EXCEPTION_INTERRUPT: u32 = 0;
+const EXCEPTION_SYSCALL: u32 = 8;
+
+// Status register bits IEp and IM2; after RFE these become interrupts enabled + IRQ unmasked
+const SR_INTERRUPT_BITS: u32 = 0x0404;
+
+const VBLANK_IRQ: u32 = 1 << 0;
+
+const GPU_GP0: u32 = 0x1F80_1810;
+const GPU_GP1: u32 = 0x1F80_1814;
+
+pub const DEFAULT_THREAD_COUNT: u32 = 4;
+pub const DEFAULT_EVENT_COUNT: u32 = 16;
+pub const DEFAULT_STACK_TOP: u32 = 0x801F_FFF0;
+
+// Each pending guest call adds a continuation; this only overflows if software repeatedly leaves
+// callbacks without returning
+const MAX_CONTINUATIONS: usize = 64;
+
+/// What the kernel was doing when it called guest code, resumed when the call returns
+#[derive(Debug, Clone, Encode, Decode)]
+pub enum Continuation {
+    /// Walking the interrupt handler chains; `entry` is the chain entry whose function was called
+    InterruptChain { priority: u32, entry: u32, called_func2: bool },
+    /// Delivering events; `events` lists the (class, spec) pairs still to deliver, and
+    /// `next_event` is the index of the next event control block to check for the first pair
+    DeliverEvents { events: Vec<(u32, u32)>, next_event: u32, ra: u32, return_value: u32 },
+    /// Running an EXE started by `Exec()`; caller registers are saved in the header
+    Exec { header: u32 },
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct KernelState {
+    continuations: Vec<Continuation>,
+    exception_depth: usize,
+    pub custom_exit: u32,
+    clear_rcnt: [bool; 4],
+    pub clear_pad: bool,
+    pad_buffers: [(u32, u32); 2],
+    pad_started: bool,
+    outdated_pad_buffer: u32,
+    pub card_port: u32,
+    kernel_heap: u32,
+    event_count: u32,
+    thread_count: u32,
+    pub stack_top: u32,
+}
+
+impl KernelState {
+    pub fn new() -> Self {
+        Self {
+            continuations: Vec::new(),
+            exception_depth: 0,
+            custom_exit: 0,
+            clear_rcnt: [true; 4],
+            clear_pad: true,
+            pad_buffers: [(0, 0); 2],
+            pad_started: false,
+            outdated_pad_buffer: 0,
+            card_port: 0,
+            kernel_heap: KERNEL_HEAP_START,
+            event_count: DEFAULT_EVENT_COUNT,
+            thread_count: DEFAULT_THREAD_COUNT,
+            stack_top: DEFAULT_STACK_TOP,
+        }
+    }
+}
+
+fn current_tcb(g: &Guest<'_, '_>) -> u32 {
+    g.read_u32(g.read_u32(PCB_PTR))
+}
+
+fn chain_head(g: &Guest<'_, '_>, priority: u32) -> u32 {
+    g.read_u32(g.read_u32(EXCB_PTR) + 8 * priority)
+}
+
+fn save_context(g: &mut Guest<'_, '_>, tcb: u32, return_address: u32, sr: u32) {
+    for register in 0..32 {
+        g.write_u32(tcb + TCB_REGS + 4 * register, g.reg(register));
+    }
+    g.write_u32(tcb + TCB_EPC, return_address);
+    g.write_u32(tcb + TCB_HI, g.cpu.read_debug_register(CpuRegister::Hi));
+    g.write_u32(tcb + TCB_LO, g.cpu.read_debug_register(CpuRegister::Lo));
+    g.write_u32(tcb + TCB_SR, sr);
+}
+
+fn restore_context(g: &mut Guest<'_, '_>, tcb: u32) {
+    for register in 1..32 {
+        g.set_reg(register, g.read_u32(tcb + TCB_REGS + 4 * register));
+    }
+    g.cpu.write_debug_register(CpuRegister::Hi, g.read_u32(tcb + TCB_HI));
+    g.cpu.write_debug_register(CpuRegister::Lo, g.read_u32(tcb + TCB_LO));
+
+    // The saved SR has the interrupt enable stack pushed, as it was on exception entry
+    g.set_cop0(Cop0Register::Status, g.read_u32(tcb + TCB_SR));
+    g.cpu.return_from_exception();
+
+    g.cpu.set_pc(g.read_u32(tcb + TCB_EPC));
+}
+
+fn rcnt_interrupt_bit(rcnt: u32) -> u32 {
+    // Root counter 3 is VBlank
+    if rcnt == 3 { VBLANK_IRQ } else { 1 << (4 + rcnt) }
+}
+
+fn pending_interrupts(g: &Guest<'_, '_>) -> u32 {
+    let registers = &g.bus.interrupt_registers;
+    registers.read_interrupt_status() & registers.read_interrupt_mask()
+}
+
+fn acknowledge_interrupt(g: &mut Guest<'_, '_>, bit: u32) {
+    g.bus.interrupt_registers.write_interrupt_status(!bit);
+}
+
+fn enable_interrupt(g: &mut Guest<'_, '_>, bit: u32) {
+    let mask = g.bus.interrupt_registers.read_interrupt_mask();
+    g.bus.interrupt_registers.write_interrupt_mask(mask | bit);
+}
+
+impl HleBios {
+    /// Allocate and initialize the exception chains and process/thread/event control blocks
+    pub(super) fn init_kernel(
+        &mut self,
+        g: &mut Guest<'_, '_>,
+        event_count: u32,
+        thread_count: u32,
+    ) {
+        self.kernel.kernel_heap = KERNEL_HEAP_START;
+        self.kernel.continuations.clear();
+
+        let excb = self.alloc_kernel(g, EXCB_LEN);
+        let pcb = self.alloc_kernel(g, PCB_LEN);
+
+        let mut thread_count = thread_count.max(1);
+        let mut event_count = event_count;
+        let available = KERNEL_HEAP_END - self.kernel.kernel_heap;
+        if thread_count * TCB_LEN + event_count * EVCB_LEN > available {
+            log::warn!(
+                "Too many threads ({thread_count}) and events ({event_count}) requested; using defaults"
+            );
+            thread_count = DEFAULT_THREAD_COUNT;
+            event_count = DEFAULT_EVENT_COUNT;
+        }
+
+        let tcbs = self.alloc_kernel(g, thread_count * TCB_LEN);
+        let evcbs = self.alloc_kernel(g, event_count * EVCB_LEN);
+        self.kernel.thread_count = thread_count;
+        self.kernel.event_count = event_count;
+
+        for (ptr, address, len) in [
+            (EXCB_PTR, excb, EXCB_LEN),
+            (PCB_PTR, pcb, PCB_LEN),
+            (TCB_PTR, tcbs, thread_count * TCB_LEN),
+            (EVCB_PTR, evcbs, event_count * EVCB_LEN),
+        ] {
+            g.write_u32(ptr, address);
+            g.write_u32(ptr + 4, len);
+        }
+
+        for i in 0..thread_count {
+            g.write_u32(tcbs + i * TCB_LEN + TCB_STATUS, THREAD_FREE);
+        }
+
+        // The boot thread is always thread 0
+        g.write_u32(tcbs + TCB_STATUS, THREAD_USED);
+        g.write_u32(pcb, tcbs);
+
+        // The kernel's own interrupt handlers
+        let pad_entry = DEFAULT_HANDLERS + 4 * CHAIN_ENTRY_LEN;
+        g.write_u32(pad_entry + 8, PAD_HANDLER);
+        Self::enqueue_handler(g, PAD_HANDLER_PRIORITY, pad_entry);
+        Self::enqueue_rcnt_handlers_at(g, RCNT_HANDLER_PRIORITY);
+    }
+
+    fn alloc_kernel(&mut self, g: &mut Guest<'_, '_>, len: u32) -> u32 {
+        let len = len.next_multiple_of(4);
+        if KERNEL_HEAP_END - self.kernel.kernel_heap < len {
+            log::error!("HLE BIOS kernel memory exhausted allocating {len} bytes");
+            return 0;
+        }
+
+        let address = self.kernel.kernel_heap;
+        self.kernel.kernel_heap += len;
+        g.fill(address, 0, len);
+        address
+    }
+
+    pub(super) fn alloc_kernel_memory(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        Flow::Return(self.alloc_kernel(g, g.arg(0)))
+    }
+
+    pub(super) fn set_conf(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (event_count, thread_count, stack_top) = (g.arg(0), g.arg(1), g.arg(2));
+        self.kernel.stack_top = stack_top;
+        self.init_kernel(g, event_count, thread_count);
+        Flow::Return(0)
+    }
+
+    pub(super) fn get_conf(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (event_count_ptr, thread_count_ptr, stack_top_ptr) = (g.arg(0), g.arg(1), g.arg(2));
+        g.write_u32(event_count_ptr, self.kernel.event_count);
+        g.write_u32(thread_count_ptr, self.kernel.thread_count);
+        g.write_u32(stack_top_ptr, self.kernel.stack_top);
+        Flow::Return(0)
+    }
+
+    /// Call guest code with the return address set to the return trap. `continuation` is resumed
+    /// when the call returns.
+    pub(super) fn call_guest(
+        &mut self,
+        g: &mut Guest<'_, '_>,
+        function: u32,
+        args: &[u32],
+        continuation: Continuation,
+    ) {
+        if self.kernel.continuations.len() == MAX_CONTINUATIONS {
+            log::error!("Too many nested HLE BIOS calls into guest code; discarding pending calls");
+            self.kernel.continuations.clear();
+        }
+        self.kernel.continuations.push(continuation);
+
+        for (i, &arg) in args.iter().enumerate() {
+            g.set_reg(A0 + i as u32, arg);
+        }
+        g.set_reg(RA, RETURN_TRAP);
+        g.cpu.set_pc(function);
+    }
+
+    /// Handle a return from guest code called through [`Self::call_guest`]
+    pub(super) fn resume(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let Some(continuation) = self.kernel.continuations.pop() else {
+            log::error!("Guest code returned to the HLE BIOS with no pending call; halting");
+            g.cpu.set_pc(HALTED);
+            return Flow::Jump;
+        };
+
+        let value = g.reg(V0);
+        match continuation {
+            Continuation::InterruptChain { priority, entry, called_func2 } => {
+                let func2 = g.read_u32(entry + 4);
+                if !called_func2 && value != 0 && func2 != 0 {
+                    let continuation =
+                        Continuation::InterruptChain { priority, entry, called_func2: true };
+                    self.call_guest(g, func2, &[value], continuation);
+                    return Flow::Jump;
+                }
+
+                let next = g.read_u32(entry);
+                self.continue_interrupt_chain(g, priority, next)
+            }
+            Continuation::DeliverEvents { events, next_event, ra, return_value } => {
+                self.continue_delivering(g, events, next_event, ra, return_value)
+            }
+            Continuation::Exec { header } => Self::exec_returned(g, header),
+        }
+    }
+
+    pub(super) fn handle_exception(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let cause = g.cop0(Cop0Register::Cause);
+        let epc = g.cop0(Cop0Register::Epc);
+        let code = (cause >> 2) & 0x1F;
+        log::trace!("Exception code {code} at {epc:08X}, cause {cause:08X}");
+
+        let tcb = current_tcb(g);
+        save_context(g, tcb, epc, g.cop0(Cop0Register::Status));
+        g.write_u32(tcb + TCB_CAUSE, cause);
+        self.kernel.exception_depth = self.kernel.continuations.len();
+
+        match code {
+            EXCEPTION_INTERRUPT => {
+                g.set_reg(SP, EXCEPTION_STACK_TOP);
+                let entry = chain_head(g, 0);
+                self.continue_interrupt_chain(g, 0, entry)
+            }
+            EXCEPTION_SYSCALL => {
+                g.write_u32(tcb + TCB_EPC, epc.wrapping_add(4));
+                Self::syscall(g, tcb);
+                self.return_from_exception(g);
+                Flow::Jump
+            }
+            _ => {
+                log::error!(
+                    "Unhandled exception (code {code}) at {epc:08X}, cause {cause:08X}; skipping instruction"
+                );
+                g.write_u32(tcb + TCB_EPC, epc.wrapping_add(4));
+                self.return_from_exception(g);
+                Flow::Jump
+            }
+        }
+    }
+
+    fn syscall(g: &mut Guest<'_, '_>, tcb: u32) {
+        let sr = g.read_u32(tcb + TCB_SR);
+        let saved_v0 = tcb + TCB_REGS + 4 * V0;
+
+        match g.reg(A0) {
+            0 => {}
+            // EnterCriticalSection: returns whether interrupts were enabled
+            1 => {
+                g.write_u32(saved_v0, u32::from(sr & SR_INTERRUPT_BITS == SR_INTERRUPT_BITS));
+                g.write_u32(tcb + TCB_SR, sr & !SR_INTERRUPT_BITS);
+            }
+            // ExitCriticalSection
+            2 => g.write_u32(tcb + TCB_SR, sr | SR_INTERRUPT_BITS),
+            // ChangeThreadSubFunction: A1 is the new TCB address
+            3 => {
+                g.write_u32(saved_v0, 1);
+                let pcb = g.read_u32(PCB_PTR);
+                g.write_u32(pcb, g.reg(A1));
+            }
+            function => log::warn!("Unimplemented HLE BIOS syscall({function})"),
+        }
+    }
+
+    fn continue_interrupt_chain(
+        &mut self,
+        g: &mut Guest<'_, '_>,
+        mut priority: u32,
+        mut entry: u32,
+    ) -> Flow {
+        loop {
+            while entry == 0 {
+                priority += 1;
+                if priority == 4 {
+                    return self.exit_exception(g);
+                }
+                entry = chain_head(g, priority);
+            }
+
+            let func1 = g.read_u32(entry + 8);
+            if func1 != 0 {
+                let continuation =
+                    Continuation::InterruptChain { priority, entry, called_func2: false };
+                self.call_guest(g, func1, &[], continuation);
+                return Flow::Jump;
+            }
+
+            entry = g.read_u32(entry);
+        }
+    }
+
+    fn exit_exception(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        if self.kernel.custom_exit != 0 {
+            Libc::restore_jmp_buf(g, self.kernel.custom_exit, 1);
+        } else {
+            self.return_from_exception(g);
+        }
+        Flow::Jump
+    }
+
+    pub(super) fn return_from_exception(&mut self, g: &mut Guest<'_, '_>) {
+        // Anything called from inside the exception handler is abandoned
+        self.kernel.continuations.truncate(self.kernel.exception_depth);
+        restore_context(g, current_tcb(g));
+    }
+
+    fn enqueue_handler(g: &mut Guest<'_, '_>, priority: u32, entry: u32) {
+        let head = g.read_u32(EXCB_PTR) + 8 * (priority & 3);
+        g.write_u32(entry, g.read_u32(head));
+        g.write_u32(head, entry);
+    }
+
+    fn enqueue_rcnt_handlers_at(g: &mut Guest<'_, '_>, priority: u32) {
+        for rcnt in 0..4 {
+            let entry = DEFAULT_HANDLERS + rcnt * CHAIN_ENTRY_LEN;
+            g.write_u32(entry + 8, RCNT_HANDLERS + 4 * rcnt);
+            g.write_u32(entry + 4, 0);
+            Self::enqueue_handler(g, priority, entry);
+        }
+    }
+
+    pub(super) fn enqueue_rcnt_handlers(g: &mut Guest<'_, '_>) -> Flow {
+        Self::enqueue_rcnt_handlers_at(g, g.arg(0));
+        Flow::Return(0)
+    }
+
+    pub(super) fn enqueue_interrupt_handler(g: &mut Guest<'_, '_>) -> Flow {
+        Self::enqueue_handler(g, g.arg(0), g.arg(1));
+        Flow::Return(0)
+    }
+
+    pub(super) fn dequeue_interrupt_handler(g: &mut Guest<'_, '_>) -> Flow {
+        let (priority, entry) = (g.arg(0), g.arg(1));
+
+        let mut link = g.read_u32(EXCB_PTR) + 8 * (priority & 3);
+        loop {
+            let next = g.read_u32(link);
+            if next == 0 {
+                return Flow::Return(0);
+            }
+            if next == entry {
+                g.write_u32(link, g.read_u32(entry));
+                return Flow::Return(0);
+            }
+            link = next;
+        }
+    }
+
+    pub(super) fn change_clear_rcnt(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (rcnt, clear) = (g.arg(0) & 3, g.arg(1) != 0);
+        let previous = self.kernel.clear_rcnt[rcnt as usize];
+        self.kernel.clear_rcnt[rcnt as usize] = clear;
+        Flow::Return(previous.into())
+    }
+
+    pub(super) fn rcnt_handler(&mut self, g: &mut Guest<'_, '_>, rcnt: u32) -> Flow {
+        let bit = rcnt_interrupt_bit(rcnt);
+        if pending_interrupts(g) & bit == 0 {
+            return Flow::Return(0);
+        }
+
+        if self.kernel.clear_rcnt[rcnt as usize] {
+            acknowledge_interrupt(g, bit);
+        }
+
+        let ra = g.reg(RA);
+        self.deliver_events(g, vec![(RCNT_CLASS_BASE + rcnt, SPEC_INTERRUPT)], ra, 0)
+    }
+
+    // Events
+
+    fn evcb(&self, g: &Guest<'_, '_>, handle: u32) -> Option<u32> {
+        let index = handle & 0xFFFF;
+        (handle & 0xFFFF_0000 == EVENT_HANDLE_BASE && index < self.kernel.event_count)
+            .then(|| g.read_u32(EVCB_PTR) + index * EVCB_LEN)
+    }
+
+    fn deliver_events(
+        &mut self,
+        g: &mut Guest<'_, '_>,
+        events: Vec<(u32, u32)>,
+        ra: u32,
+        return_value: u32,
+    ) -> Flow {
+        self.continue_delivering(g, events, 0, ra, return_value)
+    }
+
+    fn continue_delivering(
+        &mut self,
+        g: &mut Guest<'_, '_>,
+        mut events: Vec<(u32, u32)>,
+        mut next_event: u32,
+        ra: u32,
+        return_value: u32,
+    ) -> Flow {
+        let evcbs = g.read_u32(EVCB_PTR);
+
+        while let Some(&(class, spec)) = events.first() {
+            while next_event < self.kernel.event_count {
+                let evcb = evcbs + next_event * EVCB_LEN;
+                next_event += 1;
+
+                if g.read_u32(evcb + EVCB_CLASS) != class
+                    || g.read_u32(evcb + EVCB_SPEC) != spec
+                    || g.read_u32(evcb + EVCB_STATUS) != EVENT_ENABLED
+                {
+                    continue;
+                }
+
+                match g.read_u32(evcb + EVCB_MODE) {
+                    EVENT_MODE_NO_CALLBACK => g.write_u32(evcb + EVCB_STATUS, EVENT_READY),
+                    EVENT_MODE_CALLBACK => {
+                        let function = g.read_u32(evcb + EVCB_FUNC);
+                        if function != 0 {
+                            let continuation = Continuation::DeliverEvents {
+                                events,
+                                next_event,
+                                ra,
+                                return_value,
+                            };
+                            self.call_guest(g, function, &[], continuation);
+                            return Flow::Jump;
+                        }
+                    }
+                    mode => log::warn!("Event {class:08X}/{spec:04X} has invalid mode {mode:04X}"),
+                }
+            }
+
+            events.remove(0);
+            next_event = 0;
+        }
+
+        g.set_reg(V0, return_value);
+        g.cpu.set_pc(ra);
+        Flow::Jump
+    }
+
+    pub(super) fn deliver_event(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let ra = g.reg(RA);
+        self.deliver_events(g, vec![(g.arg(0), g.arg(1))], ra, 0)
+    }
+
+    pub(super) fn undeliver_event(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (class, spec) = (g.arg(0), g.arg(1));
+
+        let evcbs = g.read_u32(EVCB_PTR);
+        for i in 0..self.kernel.event_count {
+            let evcb = evcbs + i * EVCB_LEN;
+            if g.read_u32(evcb + EVCB_CLASS) == class
+                && g.read_u32(evcb + EVCB_SPEC) == spec
+                && g.read_u32(evcb + EVCB_STATUS) == EVENT_READY
+                && g.read_u32(evcb + EVCB_MODE) == EVENT_MODE_NO_CALLBACK
+            {
+                g.write_u32(evcb + EVCB_STATUS, EVENT_ENABLED);
+            }
+        }
+
+        Flow::Return(0)
+    }
+
+    pub(super) fn free_event_slot(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let evcbs = g.read_u32(EVCB_PTR);
+        let slot = (0..self.kernel.event_count)
+            .find(|&i| g.read_u32(evcbs + i * EVCB_LEN + EVCB_STATUS) == EVENT_FREE);
+        Flow::Return(slot.unwrap_or(u32::MAX))
+    }
+
+    pub(super) fn open_event(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (class, spec, mode, function) = (g.arg(0), g.arg(1), g.arg(2), g.arg(3));
+
+        let Flow::Return(slot) = self.free_event_slot(g) else { unreachable!() };
+        if slot == u32::MAX {
+            log::warn!("OpenEvent({class:08X}, {spec:04X}) failed: no free event slots");
+            return Flow::Return(u32::MAX);
+        }
+
+        let evcb = g.read_u32(EVCB_PTR) + slot * EVCB_LEN;
+        g.write_u32(evcb + EVCB_CLASS, class);
+        g.write_u32(evcb + EVCB_STATUS, EVENT_DISABLED);
+        g.write_u32(evcb + EVCB_SPEC, spec);
+        g.write_u32(evcb + EVCB_MODE, mode);
+        g.write_u32(evcb + EVCB_FUNC, function);
+
+        Flow::Return(EVENT_HANDLE_BASE | slot)
+    }
+
+    pub(super) fn close_event(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let Some(evcb) = self.evcb(g, g.arg(0)) else { return Flow::Return(0) };
+        g.write_u32(evcb + EVCB_STATUS, EVENT_FREE);
+        Flow::Return(1)
+    }
+
+    pub(super) fn wait_event(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let Some(evcb) = self.evcb(g, g.arg(0)) else { return Flow::Return(0) };
+        match g.read_u32(evcb + EVCB_STATUS) {
+            EVENT_READY => {
+                g.write_u32(evcb + EVCB_STATUS, EVENT_ENABLED);
+                Flow::Return(1)
+            }
+            EVENT_ENABLED => Flow::Retry,
+            _ => Flow::Return(0),
+        }
+    }
+
+    pub(super) fn test_event(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let Some(evcb) = self.evcb(g, g.arg(0)) else { return Flow::Return(0) };
+        if g.read_u32(evcb + EVCB_STATUS) == EVENT_READY {
+            g.write_u32(evcb + EVCB_STATUS, EVENT_ENABLED);
+            Flow::Return(1)
+        } else {
+            Flow::Return(0)
+        }
+    }
+
+    pub(super) fn enable_event(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        self.set_event_status(g, EVENT_ENABLED)
+    }
+
+    pub(super) fn disable_event(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        self.set_event_status(g, EVENT_DISABLED)
+    }
+
+    fn set_event_status(&mut self, g: &mut Guest<'_, '_>, status: u32) -> Flow {
+        let Some(evcb) = self.evcb(g, g.arg(0)) else { return Flow::Return(0) };
+        if g.read_u32(evcb + EVCB_STATUS) != EVENT_FREE {
+            g.write_u32(evcb + EVCB_STATUS, status);
+        }
+        Flow::Return(1)
+    }
+
+    // Threads
+
+    fn tcb(&self, g: &Guest<'_, '_>, handle: u32) -> Option<u32> {
+        let index = handle & 0xFFFF;
+        (handle & 0xFFFF_0000 == THREAD_HANDLE_BASE && index < self.kernel.thread_count)
+            .then(|| g.read_u32(TCB_PTR) + index * TCB_LEN)
+    }
+
+    pub(super) fn free_thread_slot(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let tcbs = g.read_u32(TCB_PTR);
+        let slot = (0..self.kernel.thread_count)
+            .find(|&i| g.read_u32(tcbs + i * TCB_LEN + TCB_STATUS) != THREAD_USED);
+        Flow::Return(slot.unwrap_or(u32::MAX))
+    }
+
+    pub(super) fn open_thread(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (pc, sp, gp) = (g.arg(0), g.arg(1), g.arg(2));
+
+        let Flow::Return(slot) = self.free_thread_slot(g) else { unreachable!() };
+        if slot == u32::MAX {
+            log::warn!("OpenThread({pc:08X}) failed: no free thread slots");
+            return Flow::Return(u32::MAX);
+        }
+
+        let tcb = g.read_u32(TCB_PTR) + slot * TCB_LEN;
+        g.fill(tcb, 0, TCB_LEN);
+        g.write_u32(tcb + TCB_STATUS, THREAD_USED);
+        g.write_u32(tcb + TCB_EPC, pc);
+        g.write_u32(tcb + TCB_REGS + 4 * SP, sp);
+        g.write_u32(tcb + TCB_REGS + 4 * FP, sp);
+        g.write_u32(tcb + TCB_REGS + 4 * GP, gp);
+        g.write_u32(tcb + TCB_SR, SR_INTERRUPT_BITS);
+
+        Flow::Return(THREAD_HANDLE_BASE | slot)
+    }
+
+    pub(super) fn close_thread(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let Some(tcb) = self.tcb(g, g.arg(0)) else { return Flow::Return(0) };
+        g.write_u32(tcb + TCB_STATUS, THREAD_FREE);
+        Flow::Return(1)
+    }
+
+    pub(super) fn change_thread(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let Some(new_tcb) =
+            self.tcb(g, g.arg(0)).filter(|&tcb| g.read_u32(tcb + TCB_STATUS) == THREAD_USED)
+        else {
+            return Flow::Return(0);
+        };
+
+        // Save SR with the interrupt enable stack pushed so that it is restored the same way as
+        // a context saved on exception entry
+        let sr = g.cop0(Cop0Register::Status);
+        let pushed_sr = (sr & !0x3F) | ((sr << 2) & 0x3C);
+
+        let tcb = current_tcb(g);
+        save_context(g, tcb, g.reg(RA), pushed_sr);
+        g.write_u32(tcb + TCB_REGS + 4 * V0, 1);
+
+        let pcb = g.read_u32(PCB_PTR);
+        g.write_u32(pcb, new_tcb);
+        restore_context(g, new_tcb);
+
+        Flow::Jump
+    }
+
+    // Controllers
+
+    pub(super) fn init_pad(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        self.kernel.pad_buffers = [(g.arg(0), g.arg(1)), (g.arg(2), g.arg(3))];
+        for (address, size) in self.kernel.pad_buffers {
+            g.fill(address, 0xFF, size);
+        }
+        Flow::Return(1)
+    }
+
+    pub(super) fn start_pad(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        self.kernel.pad_started = true;
+        enable_interrupt(g, VBLANK_IRQ);
+        Flow::Return(1)
+    }
+
+    pub(super) fn stop_pad(&mut self) -> Flow {
+        self.kernel.pad_started = false;
+        Flow::Return(1)
+    }
+
+    pub(super) fn outdated_pad_init(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        self.kernel.outdated_pad_buffer = g.arg(1);
+        self.kernel.pad_started = true;
+        enable_interrupt(g, VBLANK_IRQ);
+        Flow::Return(2)
+    }
+
+    pub(super) fn outdated_pad_get_buttons(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        match self.kernel.outdated_pad_buffer {
+            0 => Flow::Return(u32::MAX),
+            address => Flow::Return(g.read_u32(address)),
+        }
+    }
+
+    pub(super) fn pad_handler(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        if pending_interrupts(g) & VBLANK_IRQ == 0 {
+            return Flow::Return(0);
+        }
+
+        if self.kernel.pad_started {
+            self.update_pads(g);
+        }
+
+        if self.kernel.clear_pad {
+            acknowledge_interrupt(g, VBLANK_IRQ);
+        }
+
+        Flow::Return(0)
+    }
+
+    fn update_pads(&mut self, g: &mut Guest<'_, '_>) {
+        let mut buttons = [0xFFFF_u16; 2];
+
+        for (i, port) in [Port::One, Port::Two].into_iter().enumerate() {
+            // Buffer contents: status ($00 = OK, $FF = no controller), then the poll response
+            let data = match g.bus.sio0.poll_controller(port) {
+                Some(response) => {
+                    buttons[i] = u16::from_le_bytes([response[1], response[2]]);
+                    [&[0x00], response.as_slice()].concat()
+                }
+                None => vec![0xFF],
+            };
+
+            let (address, size) = self.kernel.pad_buffers[i];
+            if address != 0 {
+                g.write_bytes(address, &data[..data.len().min(size as usize)]);
+            }
+        }
+
+        if self.kernel.outdated_pad_buffer != 0 {
+            let value = u32::from(buttons[0]) | (u32::from(buttons[1]) << 16);
+            g.write_u32(self.kernel.outdated_pad_buffer, value);
+        }
+    }
+
+    // Memory cards
+
+    fn card_result(&mut self, g: &mut Guest<'_, '_>, spec: u32) -> Flow {
+        let ra = g.reg(RA);
+        let return_value = u32::from(spec == SPEC_IO_END);
+        self.deliver_events(g, vec![(SW_CARD_CLASS, spec), (HW_CARD_CLASS, spec)], ra, return_value)
+    }
+
+    /// Deliver the card I/O completion events for asynchronous file operations
+    pub(super) fn card_io_complete(&mut self, g: &mut Guest<'_, '_>, return_value: u32) -> Flow {
+        let ra = g.reg(RA);
+        let events = vec![(SW_CARD_CLASS, SPEC_IO_END), (HW_CARD_CLASS, SPEC_IO_END)];
+        self.deliver_events(g, events, ra, return_value)
+    }
+
+    pub(super) fn card_info(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let port = g.arg(0);
+        self.kernel.card_port = port;
+
+        let spec = if card_slot(port).is_some_and(|slot| card_present(g, slot)) {
+            SPEC_IO_END
+        } else {
+            SPEC_TIMEOUT
+        };
+        self.card_result(g, spec)
+    }
+
+    pub(super) fn card_read(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (port, sector, dst) = (g.arg(0), g.arg(1), g.arg(2));
+        self.kernel.card_port = port;
+
+        let mut data = [0; CARD_SECTOR_LEN];
+        let spec = card_sector_access(g, port, sector, |card, offset| {
+            card.read_bytes(offset, &mut data);
+        });
+        if spec == SPEC_IO_END {
+            g.write_bytes(dst, &data);
+        }
+        self.card_result(g, spec)
+    }
+
+    pub(super) fn card_write(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (port, sector, src) = (g.arg(0), g.arg(1), g.arg(2));
+        self.kernel.card_port = port;
+
+        let data = g.read_bytes(src, CARD_SECTOR_LEN as u32);
+        let spec = card_sector_access(g, port, sector, |card, offset| {
+            card.write_bytes(offset, &data);
+        });
+        self.card_result(g, spec)
+    }
+}
+
+const CARD_SECTOR_LEN: usize = 128;
+const CARD_SECTORS: u32 = 1024;
+
+fn card_sector_access(
+    g: &mut Guest<'_, '_>,
+    port: u32,
+    sector: u32,
+    f: impl FnOnce(&mut MemoryCard, usize),
+) -> u32 {
+    if sector >= CARD_SECTORS {
+        return SPEC_ERROR;
+    }
+
+    let offset = sector as usize * CARD_SECTOR_LEN;
+    match card_slot(port).and_then(|slot| with_card(g, slot, |card| f(card, offset))) {
+        Some(()) => SPEC_IO_END,
+        None => SPEC_TIMEOUT,
+    }
+}
+
+/// Memory card slot (0 or 1) for a BIOS card port number ($00 or $10); multitap ports are not
+/// supported
+pub fn card_slot(port: u32) -> Option<usize> {
+    (port & 0x0F == 0).then_some(((port >> 4) & 1) as usize)
+}
+
+fn card_present(g: &mut Guest<'_, '_>, slot: usize) -> bool {
+    with_card(g, slot, |_| ()).is_some()
+}
+
+/// Run a function on the memory card in the given slot, if one is inserted
+pub fn with_card<T>(
+    g: &mut Guest<'_, '_>,
+    slot: usize,
+    f: impl FnOnce(&mut MemoryCard) -> T,
+) -> Option<T> {
+    let (card_1, card_2) = g.bus.sio0.memory_cards();
+    let card = if slot == 0 { card_1 } else { card_2 };
+    card.map(f)
+}
+
+// Root counters
+
+fn rcnt_registers(g: &Guest<'_, '_>) -> Option<u32> {
+    let rcnt = g.arg(0) & 3;
+    (rcnt != 3).then_some(0x1F80_1100 + 0x10 * rcnt)
+}
+
+pub fn set_rcnt(g: &mut Guest<'_, '_>) -> Flow {
+    let Some(registers) = rcnt_registers(g) else { return Flow::Return(0) };
+    let (rcnt, target, flags) = (g.arg(0) & 3, g.arg(1), g.arg(2));
+
+    let mut mode = 0;
+    if flags & 0x1000 != 0 {
+        // IRQ on target, repeatedly
+        mode |= 0x0050;
+    }
+    if flags & 0x0100 != 0 {
+        // Reset counter on target
+        mode |= 0x0008;
+    }
+    if flags & 0x0010 != 0 {
+        // Sync enable
+        mode |= 0x0001;
+    }
+    if flags & 0x0001 != 0 {
+        // Alternate clock source
+        mode |= if rcnt == 2 { 0x0200 } else { 0x0100 };
+    }
+
+    g.bus.write_u32(registers + 8, target & 0xFFFF);
+    g.bus.write_u32(registers + 4, mode);
+
+    Flow::Return(1)
+}
+
+pub fn get_rcnt(g: &mut Guest<'_, '_>) -> Flow {
+    let Some(registers) = rcnt_registers(g) else { return Flow::Return(0) };
+    Flow::Return(g.bus.read_u32(registers) & 0xFFFF)
+}
+
+pub fn start_rcnt(g: &mut Guest<'_, '_>) -> Flow {
+    enable_interrupt(g, rcnt_interrupt_bit(g.arg(0) & 3));
+    Flow::Return(1)
+}
+
+pub fn stop_rcnt(g: &mut Guest<'_, '_>) -> Flow {
+    let mask = g.bus.interrupt_registers.read_interrupt_mask();
+    let bit = rcnt_interrupt_bit(g.arg(0) & 3);
+    g.bus.interrupt_registers.write_interrupt_mask(mask & !bit);
+    Flow::Return(1)
+}
+
+pub fn reset_rcnt(g: &mut Guest<'_, '_>) -> Flow {
+    let Some(registers) = rcnt_registers(g) else { return Flow::Return(0) };
+    g.bus.write_u32(registers, 0);
+    Flow::Return(1)
+}
+
+// GPU
+
+/// `GPU_dw(x, y, width, height, src)`: Copy an image from RAM to VRAM using GP0 writes. Also
+/// used for `gpu_send_dma()`, which takes the same arguments
+pub fn gpu_dw(g: &mut Guest<'_, '_>) -> Flow {
+    let (x, y, width, height, src) = (g.arg(0), g.arg(1), g.arg(2), g.arg(3), g.arg(4));
+
+    g.bus.write_u32(GPU_GP0, 0xA000_0000);
+    g.bus.write_u32(GPU_GP0, (x & 0xFFFF) | (y << 16));
+    g.bus.write_u32(GPU_GP0, (width & 0xFFFF) | (height << 16));
+
+    let words = (width * height).div_ceil(2);
+    for i in 0..words {
+        let word = g.read_u32(src + 4 * i);
+        g.bus.write_u32(GPU_GP0, word);
+    }
+
+    Flow::Return(0)
+}
+
+pub fn gpu_gp1(g: &mut Guest<'_, '_>) -> Flow {
+    g.bus.write_u32(GPU_GP1, g.arg(0));
+    Flow::Return(0)
+}
+
+pub fn gpu_cw(g: &mut Guest<'_, '_>) -> Flow {
+    g.bus.write_u32(GPU_GP0, g.arg(0));
+    Flow::Return(0)
+}
+
+pub fn gpu_cwp(g: &mut Guest<'_, '_>) -> Flow {
+    let (src, words) = (g.arg(0), g.arg(1));
+    for i in 0..words {
+        let word = g.read_u32(src + 4 * i);
+        g.bus.write_u32(GPU_GP0, word);
+    }
+    Flow::Return(0)
+}
+
+/// Send a GPU linked list (ordering table) using GP0 writes
+pub fn gpu_send_linked_list(g: &mut Guest<'_, '_>) -> Flow {
+    // Guard against circular lists
+    const MAX_PACKETS: u32 = 1 << 20;
+
+    let mut address = g.arg(0) & 0x00FF_FFFF;
+    for _ in 0..MAX_PACKETS {
+        let header = g.read_u32(address);
+        for i in 0..header >> 24 {
+            let word = g.read_u32(address + 4 * (i + 1));
+            g.bus.write_u32(GPU_GP0, word);
+        }
+
+        if header & 0x0080_0000 != 0 {
+            break;
+        }
+        address = header & 0x00FF_FFFF;
+    }
+
+    Flow::Return(0)
+}
+
+pub fn gpu_status(g: &mut Guest<'_, '_>) -> Flow {
+    Flow::Return(g.bus.read_u32(GPU_GP1))
+}
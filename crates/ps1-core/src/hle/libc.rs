@@ -0,0 +1,827 @@
+//! C standard library functions in the A0 table: strings, memory, heap, printf, and setjmp
+
+use crate::hle::{FP, Flow, GP, Guest, RA, S0, SP, V0};
+use crate::tty::TtySource;
+use bincode::{Decode, Encode};
+
+// jmp_buf layout: RA, SP, FP, S0-S7, GP
+const JMP_BUF_REGS: [u32; 12] =
+    [RA, SP, FP, S0, S0 + 1, S0 + 2, S0 + 3, S0 + 4, S0 + 5, S0 + 6, S0 + 7, GP];
+
+// Heap block header: size of the data area in bytes, then whether the block is in use
+const HEAP_HEADER_LEN: u32 = 8;
+const HEAP_MIN_SPLIT: u32 = HEAP_HEADER_LEN + 8;
+
+// Returned by todigit() for characters that are not digits
+const NOT_A_DIGIT: u32 = 0x0098_9680;
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct Libc {
+    heap_start: u32,
+    heap_end: u32,
+    rand_seed: u32,
+    strtok_next: u32,
+}
+
+impl Libc {
+    pub fn new() -> Self {
+        Self { heap_start: 0, heap_end: 0, rand_seed: 0, strtok_next: 0 }
+    }
+
+    pub fn todigit(g: &mut Guest<'_, '_>) -> Flow {
+        let c = g.arg(0) as u8;
+        let digit = match c {
+            b'0'..=b'9' => u32::from(c - b'0'),
+            b'a'..=b'z' => u32::from(c - b'a') + 10,
+            b'A'..=b'Z' => u32::from(c - b'A') + 10,
+            _ => NOT_A_DIGIT,
+        };
+        Flow::Return(digit)
+    }
+
+    fn parse_integer(g: &Guest<'_, '_>, src: u32, base: u32) -> (u32, u32) {
+        let mut address = src;
+        while g.read_u8(address).is_ascii_whitespace() {
+            address += 1;
+        }
+
+        let negative = match g.read_u8(address) {
+            b'-' => {
+                address += 1;
+                true
+            }
+            b'+' => {
+                address += 1;
+                false
+            }
+            _ => false,
+        };
+
+        let has_hex_prefix = g.read_u8(address) == b'0'
+            && g.read_u8(address + 1).eq_ignore_ascii_case(&b'x')
+            && g.read_u8(address + 2).is_ascii_hexdigit();
+        let base = match base {
+            0 if has_hex_prefix => 16,
+            0 if g.read_u8(address) == b'0' => 8,
+            0 => 10,
+            base => base,
+        };
+        if base == 16 && has_hex_prefix {
+            address += 2;
+        }
+
+        let mut value: u32 = 0;
+        while let Some(digit) = (g.read_u8(address) as char).to_digit(36).filter(|&d| d < base) {
+            value = value.wrapping_mul(base).wrapping_add(digit);
+            address += 1;
+        }
+
+        (if negative { value.wrapping_neg() } else { value }, address)
+    }
+
+    /// `strtoul(src, src_end, base)` and `strtol(src, src_end, base)`, which only differ in how
+    /// the result is interpreted
+    pub fn strtol(g: &mut Guest<'_, '_>) -> Flow {
+        let (src, end_ptr, base) = (g.arg(0), g.arg(1), g.arg(2));
+        if src == 0 {
+            return Flow::Return(0);
+        }
+
+        let (value, end) = Self::parse_integer(g, src, base);
+        if end_ptr != 0 {
+            g.write_u32(end_ptr, end);
+        }
+        Flow::Return(value)
+    }
+
+    pub fn abs(g: &mut Guest<'_, '_>) -> Flow {
+        Flow::Return((g.arg(0) as i32).unsigned_abs())
+    }
+
+    pub fn atoi(g: &mut Guest<'_, '_>) -> Flow {
+        let src = g.arg(0);
+        if src == 0 {
+            return Flow::Return(0);
+        }
+        Flow::Return(Self::parse_integer(g, src, 10).0)
+    }
+
+    /// `atob(src, num_dst)`: Returns a pointer to the end of the number
+    pub fn atob(g: &mut Guest<'_, '_>) -> Flow {
+        let (src, dst) = (g.arg(0), g.arg(1));
+        let (value, end) = Self::parse_integer(g, src, 10);
+        g.write_u32(dst, value);
+        Flow::Return(end)
+    }
+
+    pub fn setjmp(g: &mut Guest<'_, '_>) -> Flow {
+        let buf = g.arg(0);
+        for (i, register) in JMP_BUF_REGS.into_iter().enumerate() {
+            g.write_u32(buf + 4 * i as u32, g.reg(register));
+        }
+        Flow::Return(0)
+    }
+
+    pub fn longjmp(g: &mut Guest<'_, '_>) -> Flow {
+        Self::restore_jmp_buf(g, g.arg(0), g.arg(1));
+        Flow::Jump
+    }
+
+    /// Restore registers from a `jmp_buf` and return from the matching `setjmp()` call with the
+    /// given value
+    pub fn restore_jmp_buf(g: &mut Guest<'_, '_>, buf: u32, value: u32) {
+        for (i, register) in JMP_BUF_REGS.into_iter().enumerate() {
+            g.set_reg(register, g.read_u32(buf + 4 * i as u32));
+        }
+        g.set_reg(V0, value);
+        g.cpu.set_pc(g.reg(RA));
+    }
+
+    fn strlen_at(g: &Guest<'_, '_>, s: u32) -> u32 {
+        g.read_cstring(s).len() as u32
+    }
+
+    pub fn strcat(g: &mut Guest<'_, '_>) -> Flow {
+        let (dst, src) = (g.arg(0), g.arg(1));
+        if dst == 0 || src == 0 {
+            return Flow::Return(0);
+        }
+        let string = g.read_cstring(src);
+        let end = dst + Self::strlen_at(g, dst);
+        g.write_cstring(end, &string);
+        Flow::Return(dst)
+    }
+
+    pub fn strncat(g: &mut Guest<'_, '_>) -> Flow {
+        let (dst, src, max_len) = (g.arg(0), g.arg(1), g.arg(2));
+        if dst == 0 || src == 0 {
+            return Flow::Return(0);
+        }
+        let mut string = g.read_cstring(src);
+        string.truncate(max_len as usize);
+        let end = dst + Self::strlen_at(g, dst);
+        g.write_cstring(end, &string);
+        Flow::Return(dst)
+    }
+
+    fn compare(a: &[u8], b: &[u8]) -> u32 {
+        // Compare including the terminators, like the C function
+        let a = a.iter().copied().chain([0]);
+        let b = b.iter().copied().chain([0]);
+        a.zip(b)
+            .map(|(a, b)| i32::from(a) - i32::from(b))
+            .find(|&difference| difference != 0)
+            .unwrap_or(0) as u32
+    }
+
+    pub fn strcmp(g: &mut Guest<'_, '_>) -> Flow {
+        let (a, b) = (g.arg(0), g.arg(1));
+        if a == 0 || b == 0 {
+            return Flow::Return(i32::from(a != 0).wrapping_sub(i32::from(b != 0)) as u32);
+        }
+        Flow::Return(Self::compare(&g.read_cstring(a), &g.read_cstring(b)))
+    }
+
+    pub fn strncmp(g: &mut Guest<'_, '_>) -> Flow {
+        let (a, b, max_len) = (g.arg(0), g.arg(1), g.arg(2) as usize);
+        if a == 0 || b == 0 {
+            return Flow::Return(i32::from(a != 0).wrapping_sub(i32::from(b != 0)) as u32);
+        }
+
+        let mut a = g.read_cstring(a);
+        let mut b = g.read_cstring(b);
+        a.truncate(max_len);
+        b.truncate(max_len);
+        Flow::Return(Self::compare(&a, &b))
+    }
+
+    pub fn strcpy(g: &mut Guest<'_, '_>) -> Flow {
+        let (dst, src) = (g.arg(0), g.arg(1));
+        if dst == 0 || src == 0 {
+            return Flow::Return(0);
+        }
+        let string = g.read_cstring(src);
+        g.write_cstring(dst, &string);
+        Flow::Return(dst)
+    }
+
+    pub fn strncpy(g: &mut Guest<'_, '_>) -> Flow {
+        let (dst, src, max_len) = (g.arg(0), g.arg(1), g.arg(2));
+        if dst == 0 || src == 0 {
+            return Flow::Return(0);
+        }
+        let mut string = g.read_cstring(src);
+        string.resize(max_len as usize, 0);
+        g.write_bytes(dst, &string);
+        Flow::Return(dst)
+    }
+
+    pub fn strlen(g: &mut Guest<'_, '_>) -> Flow {
+        let s = g.arg(0);
+        if s == 0 {
+            return Flow::Return(0);
+        }
+        Flow::Return(Self::strlen_at(g, s))
+    }
+
+    fn find_in_string(s: u32, position: Option<usize>) -> Flow {
+        Flow::Return(position.map_or(0, |i| s + i as u32))
+    }
+
+    pub fn strchr(g: &mut Guest<'_, '_>) -> Flow {
+        let (s, c) = (g.arg(0), g.arg(1) as u8);
+        if s == 0 {
+            return Flow::Return(0);
+        }
+        let string = g.read_cstring(s);
+        let position =
+            if c == 0 { Some(string.len()) } else { string.iter().position(|&b| b == c) };
+        Self::find_in_string(s, position)
+    }
+
+    pub fn strrchr(g: &mut Guest<'_, '_>) -> Flow {
+        let (s, c) = (g.arg(0), g.arg(1) as u8);
+        if s == 0 {
+            return Flow::Return(0);
+        }
+        let string = g.read_cstring(s);
+        let position =
+            if c == 0 { Some(string.len()) } else { string.iter().rposition(|&b| b == c) };
+        Self::find_in_string(s, position)
+    }
+
+    pub fn strpbrk(g: &mut Guest<'_, '_>) -> Flow {
+        let (s, set) = (g.arg(0), g.arg(1));
+        if s == 0 || set == 0 {
+            return Flow::Return(0);
+        }
+        let set = g.read_cstring(set);
+        let position = g.read_cstring(s).iter().position(|b| set.contains(b));
+        Self::find_in_string(s, position)
+    }
+
+    /// `strspn(s, set)` if `in_set` is true, `strcspn(s, set)` if false
+    pub fn strspn(g: &mut Guest<'_, '_>, in_set: bool) -> Flow {
+        let (s, set) = (g.arg(0), g.arg(1));
+        if s == 0 || set == 0 {
+            return Flow::Return(0);
+        }
+        let set = g.read_cstring(set);
+        let count = g.read_cstring(s).iter().take_while(|b| set.contains(b) == in_set).count();
+        Flow::Return(count as u32)
+    }
+
+    pub fn strtok(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (s, delimiters) = (g.arg(0), g.arg(1));
+        let start = if s != 0 { s } else { self.strtok_next };
+        if start == 0 || delimiters == 0 {
+            return Flow::Return(0);
+        }
+
+        let delimiters = g.read_cstring(delimiters);
+        let string = g.read_cstring(start);
+
+        let Some(token_start) = string.iter().position(|b| !delimiters.contains(b)) else {
+            self.strtok_next = 0;
+            return Flow::Return(0);
+        };
+
+        match string[token_start..].iter().position(|b| delimiters.contains(b)) {
+            Some(len) => {
+                let token_end = start + (token_start + len) as u32;
+                g.write_u8(token_end, 0);
+                self.strtok_next = token_end + 1;
+            }
+            None => self.strtok_next = 0,
+        }
+
+        Flow::Return(start + token_start as u32)
+    }
+
+    pub fn strstr(g: &mut Guest<'_, '_>) -> Flow {
+        let (s, sub) = (g.arg(0), g.arg(1));
+        if s == 0 || sub == 0 {
+            return Flow::Return(0);
+        }
+        let string = g.read_cstring(s);
+        let sub = g.read_cstring(sub);
+        let position = if sub.is_empty() {
+            Some(0)
+        } else {
+            string.windows(sub.len()).position(|window| window == sub)
+        };
+        Self::find_in_string(s, position)
+    }
+
+    pub fn toupper(g: &mut Guest<'_, '_>) -> Flow {
+        Flow::Return(g.arg(0).to_le_bytes()[0].to_ascii_uppercase().into())
+    }
+
+    pub fn tolower(g: &mut Guest<'_, '_>) -> Flow {
+        Flow::Return(g.arg(0).to_le_bytes()[0].to_ascii_lowercase().into())
+    }
+
+    fn copy(g: &mut Guest<'_, '_>, dst: u32, src: u32, len: u32) {
+        // Reading everything first handles overlapping ranges
+        let data = g.read_bytes(src, len);
+        g.write_bytes(dst, &data);
+    }
+
+    /// `bcopy(src, dst, len)`
+    pub fn bcopy(g: &mut Guest<'_, '_>) -> Flow {
+        let (src, dst, len) = (g.arg(0), g.arg(1), g.arg(2));
+        if src != 0 && dst != 0 && (len as i32) > 0 {
+            Self::copy(g, dst, src, len);
+        }
+        Flow::Return(src)
+    }
+
+    pub fn bzero(g: &mut Guest<'_, '_>) -> Flow {
+        let (dst, len) = (g.arg(0), g.arg(1));
+        if dst == 0 || (len as i32) <= 0 {
+            return Flow::Return(0);
+        }
+        g.fill(dst, 0, len);
+        Flow::Return(dst)
+    }
+
+    pub fn memcmp(g: &mut Guest<'_, '_>) -> Flow {
+        let (a, b, len) = (g.arg(0), g.arg(1), g.arg(2));
+        if a == 0 || b == 0 {
+            return Flow::Return(0);
+        }
+        let a = g.read_bytes(a, len);
+        let b = g.read_bytes(b, len);
+        let difference = a
+            .iter()
+            .zip(&b)
+            .map(|(&a, &b)| i32::from(a) - i32::from(b))
+            .find(|&difference| difference != 0)
+            .unwrap_or(0);
+        Flow::Return(difference as u32)
+    }
+
+    pub fn memmove(g: &mut Guest<'_, '_>) -> Flow {
+        let (dst, src, len) = (g.arg(0), g.arg(1), g.arg(2));
+        if dst == 0 || src == 0 {
+            return Flow::Return(0);
+        }
+        if (len as i32) > 0 {
+            Self::copy(g, dst, src, len);
+        }
+        Flow::Return(dst)
+    }
+
+    pub fn memset(g: &mut Guest<'_, '_>) -> Flow {
+        let (dst, value, len) = (g.arg(0), g.arg(1), g.arg(2));
+        if dst == 0 {
+            return Flow::Return(0);
+        }
+        if (len as i32) > 0 {
+            g.fill(dst, value as u8, len);
+        }
+        Flow::Return(dst)
+    }
+
+    pub fn memchr(g: &mut Guest<'_, '_>) -> Flow {
+        let (s, c, len) = (g.arg(0), g.arg(1) as u8, g.arg(2));
+        if s == 0 || (len as i32) <= 0 {
+            return Flow::Return(0);
+        }
+        let position = g.read_bytes(s, len).iter().position(|&b| b == c);
+        Self::find_in_string(s, position)
+    }
+
+    pub fn rand(&mut self) -> Flow {
+        self.rand_seed = self.rand_seed.wrapping_mul(0x41C6_4E6D).wrapping_add(0x3039);
+        Flow::Return((self.rand_seed >> 16) & 0x7FFF)
+    }
+
+    pub fn srand(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        self.rand_seed = g.arg(0);
+        Flow::Return(0)
+    }
+
+    // Heap
+
+    /// `InitHeap(addr, size)`
+    pub fn init_heap(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (address, size) = (g.arg(0).next_multiple_of(4), g.arg(1) & !3);
+        if size <= HEAP_HEADER_LEN {
+            self.heap_start = 0;
+            self.heap_end = 0;
+            return Flow::Return(0);
+        }
+
+        self.heap_start = address;
+        self.heap_end = address + size;
+        Self::write_block(g, address, size - HEAP_HEADER_LEN, false);
+
+        Flow::Return(0)
+    }
+
+    fn write_block(g: &mut Guest<'_, '_>, block: u32, size: u32, used: bool) {
+        g.write_u32(block, size);
+        g.write_u32(block + 4, used.into());
+    }
+
+    fn allocate(&mut self, g: &mut Guest<'_, '_>, size: u32) -> u32 {
+        if self.heap_start == 0 || size == 0 {
+            return 0;
+        }
+        let size = size.next_multiple_of(4);
+
+        let mut block = self.heap_start;
+        while block + HEAP_HEADER_LEN <= self.heap_end {
+            let mut block_size = g.read_u32(block);
+            let used = g.read_u32(block + 4) != 0;
+
+            if !used {
+                // Merge with any free blocks that follow
+                loop {
+                    let next = block + HEAP_HEADER_LEN + block_size;
+                    if next + HEAP_HEADER_LEN > self.heap_end || g.read_u32(next + 4) != 0 {
+                        break;
+                    }
+                    block_size += HEAP_HEADER_LEN + g.read_u32(next);
+                }
+                Self::write_block(g, block, block_size, false);
+
+                if block_size >= size {
+                    if block_size - size >= HEAP_MIN_SPLIT {
+                        let remainder = block + HEAP_HEADER_LEN + size;
+                        Self::write_block(g, remainder, block_size - size - HEAP_HEADER_LEN, false);
+                        block_size = size;
+                    }
+                    Self::write_block(g, block, block_size, true);
+                    return block + HEAP_HEADER_LEN;
+                }
+            }
+
+            block += HEAP_HEADER_LEN + block_size;
+        }
+
+        log::warn!("HLE BIOS malloc({size}) failed: heap exhausted");
+        0
+    }
+
+    fn block_for(&self, ptr: u32) -> Option<u32> {
+        (ptr >= self.heap_start + HEAP_HEADER_LEN && ptr < self.heap_end)
+            .then_some(ptr - HEAP_HEADER_LEN)
+    }
+
+    pub fn malloc(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        Flow::Return(self.allocate(g, g.arg(0)))
+    }
+
+    pub fn free(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        if let Some(block) = self.block_for(g.arg(0)) {
+            g.write_u32(block + 4, 0);
+        }
+        Flow::Return(0)
+    }
+
+    pub fn calloc(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let size = g.arg(0).wrapping_mul(g.arg(1));
+        let ptr = self.allocate(g, size);
+        if ptr != 0 {
+            g.fill(ptr, 0, size);
+        }
+        Flow::Return(ptr)
+    }
+
+    pub fn realloc(&mut self, g: &mut Guest<'_, '_>) -> Flow {
+        let (old_ptr, size) = (g.arg(0), g.arg(1));
+        let Some(old_block) = self.block_for(old_ptr) else {
+            return Flow::Return(self.allocate(g, size));
+        };
+
+        g.write_u32(old_block + 4, 0);
+        if size == 0 {
+            return Flow::Return(0);
+        }
+
+        // Copy before allocating, since the new block may overlap the old one
+        let old_size = g.read_u32(old_block);
+        let data = g.read_bytes(old_ptr, old_size.min(size));
+        let new_ptr = self.allocate(g, size);
+        if new_ptr == 0 {
+            g.write_u32(old_block + 4, 1);
+            return Flow::Return(0);
+        }
+        g.write_bytes(new_ptr, &data);
+
+        Flow::Return(new_ptr)
+    }
+
+    // Console output
+
+    fn print(g: &mut Guest<'_, '_>, text: &[u8]) {
+        for &c in text {
+            g.bus.tty.putchar(TtySource::Bios, c);
+        }
+    }
+
+    pub fn puts(g: &mut Guest<'_, '_>) -> Flow {
+        let s = g.arg(0);
+        let text = if s == 0 { b"<NULL>".to_vec() } else { g.read_cstring(s) };
+        Self::print(g, &text);
+        Self::print(g, b"\n");
+        Flow::Return(1)
+    }
+
+    pub fn printf(g: &mut Guest<'_, '_>) -> Flow {
+        let format = g.read_cstring(g.arg(0));
+        let output = format_printf(g, &format);
+        Self::print(g, &output);
+        Flow::Return(output.len() as u32)
+    }
+}
+
+#[derive(Debug, Default)]
+struct FormatSpec {
+    left_align: bool,
+    zero_pad: bool,
+    plus_sign: bool,
+    space_sign: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+/// Format a printf string, reading arguments starting from argument 1
+fn format_printf(g: &Guest<'_, '_>, format: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut next_arg = 1;
+    let mut arg = || {
+        next_arg += 1;
+        g.arg(next_arg - 1)
+    };
+
+    let mut chars = format.iter().copied().peekable();
+    while let Some(c) = chars.next() {
+        if c != b'%' {
+            output.push(c);
+            continue;
+        }
+
+        let mut spec = FormatSpec::default();
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                b'-' => spec.left_align = true,
+                b'0' => spec.zero_pad = true,
+                b'+' => spec.plus_sign = true,
+                b' ' => spec.space_sign = true,
+                b'#' => spec.alternate = true,
+                _ => break,
+            }
+            chars.next();
+        }
+
+        if chars.peek() == Some(&b'*') {
+            chars.next();
+            spec.width = arg() as usize;
+        }
+        while let Some(digit) = chars.peek().and_then(|&c| (c as char).to_digit(10)) {
+            spec.width = 10 * spec.width + digit as usize;
+            chars.next();
+        }
+
+        if chars.peek() == Some(&b'.') {
+            chars.next();
+            let mut precision = 0;
+            if chars.peek() == Some(&b'*') {
+                chars.next();
+                precision = arg() as usize;
+            }
+            while let Some(digit) = chars.peek().and_then(|&c| (c as char).to_digit(10)) {
+                precision = 10 * precision + digit as usize;
+                chars.next();
+            }
+            spec.precision = Some(precision);
+        }
+
+        // Length modifiers make no difference with 32-bit ints and longs
+        while chars.next_if(|&c| c == b'l' || c == b'h').is_some() {}
+
+        let Some(conversion) = chars.next() else { break };
+        let (prefix, body): (&[u8], Vec<u8>) = match conversion {
+            b'd' | b'i' => {
+                let value = arg() as i32;
+                let sign: &[u8] = if value < 0 {
+                    b"-"
+                } else if spec.plus_sign {
+                    b"+"
+                } else if spec.space_sign {
+                    b" "
+                } else {
+                    b""
+                };
+                (sign, format_digits(value.unsigned_abs(), 10, false, spec.precision))
+            }
+            b'u' => (b"", format_digits(arg(), 10, false, spec.precision)),
+            b'o' => {
+                let prefix: &[u8] = if spec.alternate { b"0" } else { b"" };
+                (prefix, format_digits(arg(), 8, false, spec.precision))
+            }
+            b'x' | b'X' | b'p' => {
+                let uppercase = conversion == b'X';
+                let value = arg();
+                let prefix: &[u8] = match (spec.alternate && value != 0, uppercase) {
+                    (true, false) => b"0x",
+                    (true, true) => b"0X",
+                    (false, _) => b"",
+                };
+                (prefix, format_digits(value, 16, uppercase, spec.precision))
+            }
+            b'c' => (b"", vec![arg() as u8]),
+            b's' => {
+                let address = arg();
+                let mut string =
+                    if address == 0 { b"<NULL>".to_vec() } else { g.read_cstring(address) };
+                if let Some(precision) = spec.precision {
+                    string.truncate(precision);
+                }
+                (b"", string)
+            }
+            b'%' => (b"", vec![b'%']),
+            _ => {
+                log::warn!("Unsupported printf conversion '%{}'", conversion as char);
+                (b"", vec![b'%', conversion])
+            }
+        };
+
+        let padding = spec.width.saturating_sub(prefix.len() + body.len());
+        let zero_pad = spec.zero_pad
+            && !spec.left_align
+            && spec.precision.is_none()
+            && !matches!(conversion, b'c' | b's' | b'%');
+
+        if spec.left_align {
+            output.extend_from_slice(prefix);
+            output.extend_from_slice(&body);
+            output.resize(output.len() + padding, b' ');
+        } else if zero_pad {
+            output.extend_from_slice(prefix);
+            output.resize(output.len() + padding, b'0');
+            output.extend_from_slice(&body);
+        } else {
+            output.resize(output.len() + padding, b' ');
+            output.extend_from_slice(prefix);
+            output.extend_from_slice(&body);
+        }
+    }
+
+    output
+}
+
+fn format_digits(value: u32, radix: u32, uppercase: bool, precision: Option<usize>) -> Vec<u8> {
+    let digits = match (radix, uppercase) {
+        (8, _) => format!("{value:o}"),
+        (16, false) => format!("{value:x}"),
+        (16, true) => format!("{value:X}"),
+        _ => value.to_string(),
+    };
+
+    let mut output = Vec::new();
+    match precision {
+        // A precision of 0 prints nothing for a value of 0
+        Some(0) if value == 0 => {}
+        Some(precision) => {
+            output.resize(precision.saturating_sub(digits.len()), b'0');
+            output.extend_from_slice(digits.as_bytes());
+        }
+        None => output.extend_from_slice(digits.as_bytes()),
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hle::{A0, with_test_guest};
+
+    const STRING_A: u32 = 0x8001_0000;
+    const STRING_B: u32 = 0x8001_0100;
+    const END_PTR: u32 = 0x8001_0200;
+
+    fn call(g: &mut Guest<'_, '_>, function: fn(&mut Guest<'_, '_>) -> Flow, args: &[u32]) -> u32 {
+        for (i, &arg) in args.iter().enumerate() {
+            g.set_reg(A0 + i as u32, arg);
+        }
+
+        match function(g) {
+            Flow::Return(value) => value,
+            _ => panic!("function did not return a value"),
+        }
+    }
+
+    fn strtol(g: &mut Guest<'_, '_>, s: &[u8], base: u32) -> (i32, u32) {
+        g.write_cstring(STRING_A, s);
+        let value = call(g, Libc::strtol, &[STRING_A, END_PTR, base]);
+        (value as i32, g.read_u32(END_PTR) - STRING_A)
+    }
+
+    #[test]
+    fn strtol_bases() {
+        with_test_guest(|g| {
+            assert_eq!(strtol(g, b"  1234xyz", 10), (1234, 6));
+            assert_eq!(strtol(g, b"-42", 10), (-42, 3));
+            assert_eq!(strtol(g, b"+7", 0), (7, 2));
+            assert_eq!(strtol(g, b"0x1F", 0), (0x1F, 4));
+            assert_eq!(strtol(g, b"0x1F", 16), (0x1F, 4));
+            assert_eq!(strtol(g, b"017", 0), (0o17, 3));
+            assert_eq!(strtol(g, b"zz", 36), (35 * 36 + 35, 2));
+
+            // "0x" without hex digits parses as 0 followed by garbage
+            assert_eq!(strtol(g, b"0xg", 0), (0, 1));
+            assert_eq!(strtol(g, b"abc", 10), (0, 0));
+
+            assert_eq!(call(g, Libc::strtol, &[0, END_PTR, 10]), 0);
+        });
+    }
+
+    fn strcmp(g: &mut Guest<'_, '_>, a: &[u8], b: &[u8]) -> i32 {
+        g.write_cstring(STRING_A, a);
+        g.write_cstring(STRING_B, b);
+        call(g, Libc::strcmp, &[STRING_A, STRING_B]) as i32
+    }
+
+    #[test]
+    fn strcmp_ordering() {
+        with_test_guest(|g| {
+            assert_eq!(strcmp(g, b"abc", b"abc"), 0);
+            assert_eq!(strcmp(g, b"abc", b"abd"), -1);
+            assert_eq!(strcmp(g, b"abd", b"abc"), 1);
+            assert_eq!(strcmp(g, b"ab", b"abc"), -i32::from(b'c'));
+            assert_eq!(strcmp(g, b"abc", b"ab"), i32::from(b'c'));
+            assert_eq!(strcmp(g, b"", b""), 0);
+
+            // NULL sorts before any string
+            assert_eq!(call(g, Libc::strcmp, &[0, STRING_B]) as i32, -1);
+            assert_eq!(call(g, Libc::strcmp, &[STRING_A, 0]) as i32, 1);
+            assert_eq!(call(g, Libc::strcmp, &[0, 0]), 0);
+        });
+    }
+
+    fn printf(g: &mut Guest<'_, '_>, format: &[u8], args: &[u32]) -> String {
+        g.write_cstring(STRING_A, format);
+        for (i, &arg) in args.iter().enumerate() {
+            g.set_reg(A0 + 1 + i as u32, arg);
+        }
+        String::from_utf8(format_printf(g, format)).unwrap()
+    }
+
+    #[test]
+    fn printf_conversions() {
+        with_test_guest(|g| {
+            assert_eq!(
+                printf(g, b"%d %i %u", &[(-5_i32) as u32, 12, u32::MAX]),
+                "-5 12 4294967295"
+            );
+            assert_eq!(printf(g, b"%x %X %#x", &[0xAB, 0xAB, 0xAB]), "ab AB 0xab");
+            assert_eq!(printf(g, b"%o %#o %#x", &[0xAB, 0xAB, 0]), "253 0253 0");
+            assert_eq!(printf(g, b"%c%c%%", &[u32::from(b'o'), u32::from(b'k')]), "ok%");
+
+            g.write_cstring(STRING_B, b"hello");
+            assert_eq!(
+                printf(g, b"[%s] [%.3s] [%s]", &[STRING_B, STRING_B, 0]),
+                "[hello] [hel] [<NULL>]"
+            );
+        });
+    }
+
+    #[test]
+    fn printf_width_and_flags() {
+        with_test_guest(|g| {
+            assert_eq!(
+                printf(g, b"[%5d] [%-5d] [%05d]", &[42, 42, (-42_i32) as u32]),
+                "[   42] [42   ] [-0042]"
+            );
+            assert_eq!(printf(g, b"[%+d] [% d] [%.4d]", &[7, 7, 7]), "[+7] [ 7] [0007]");
+            assert_eq!(printf(g, b"[%*d] [%08.3d]", &[4, 9, 5]), "[   9] [     005]");
+            assert_eq!(printf(g, b"[%#06x] [%ld] [%hd]", &[0xF, 1, 2]), "[0x000f] [1] [2]");
+        });
+    }
+
+    #[test]
+    fn printf_stack_arguments() {
+        with_test_guest(|g| {
+            // Arguments past the fourth are read from the stack
+            let sp = 0x801F_FF00;
+            g.set_reg(SP, sp);
+            g.write_u32(sp + 16, 4);
+            g.write_u32(sp + 20, 5);
+
+            assert_eq!(printf(g, b"%d %d %d %d %d", &[1, 2, 3]), "1 2 3 4 5");
+        });
+    }
+
+    #[test]
+    fn printf_returns_length() {
+        with_test_guest(|g| {
+            g.write_cstring(STRING_A, b"%d apples");
+            assert_eq!(call(g, Libc::printf, &[STRING_A, 12]), 9);
+        });
+    }
+}
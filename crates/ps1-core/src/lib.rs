@@ -1,10 +1,16 @@
 pub mod api;
+mod biostrace;
 mod boxedarray;
 mod bus;
 mod cd;
+pub mod compat;
 mod cpu;
+pub mod debug;
 mod dma;
+mod fastboot;
+pub mod gdb;
 mod gpu;
+mod hle;
 pub mod input;
 mod interrupts;
 mod mdec;
@@ -15,9 +21,11 @@ mod scheduler;
 mod sio;
 mod spu;
 mod timers;
+mod tty;
 
 pub use gpu::RasterizerType;
 
+#[cfg(feature = "wgpu")]
 #[must_use]
 pub fn required_wgpu_features() -> wgpu::Features {
     wgpu::Features::PUSH_CONSTANTS
@@ -25,6 +33,7 @@ pub fn required_wgpu_features() -> wgpu::Features {
         | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
 }
 
+#[cfg(feature = "wgpu")]
 #[must_use]
 pub fn required_wgpu_limits() -> wgpu::Limits {
     wgpu::Limits {
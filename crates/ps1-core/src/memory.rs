@@ -5,8 +5,10 @@ use crate::boxedarray::BoxedArray;
 use crate::num::U32Ext;
 use crate::pgxp::{PgxpMemory, PreciseVertex};
 use bincode::{Decode, Encode};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-const BIOS_ROM_LEN: usize = 512 * 1024;
+pub const BIOS_ROM_LEN: usize = 512 * 1024;
 pub const MAIN_RAM_LEN: usize = 2 * 1024 * 1024;
 pub const SCRATCHPAD_LEN: usize = 1024;
 
@@ -77,18 +79,23 @@ macro_rules! impl_write_u32 {
 }
 
 impl Memory {
-    pub fn new(bios_rom: Vec<u8>) -> Ps1Result<Self> {
+    pub fn new(bios_rom: Vec<u8>, ram_seed: Option<u64>) -> Ps1Result<Self> {
         if bios_rom.len() != BIOS_ROM_LEN {
             return Err(Ps1Error::IncorrectBiosSize { bios_len: bios_rom.len() });
         }
 
         let bios_rom: Box<[u8; BIOS_ROM_LEN]> = bios_rom.into_boxed_slice().try_into().unwrap();
 
+        let mut rng = match ram_seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
         let mut main_ram = MainRam::new();
-        main_ram.fill_with(rand::random);
+        main_ram.fill_with(|| rng.gen());
 
         let mut scratchpad = Scratchpad::new();
-        scratchpad.fill_with(rand::random);
+        scratchpad.fill_with(|| rng.gen());
 
         Ok(Self {
             bios_rom: BiosRom::from(bios_rom),
@@ -114,6 +121,40 @@ impl Memory {
         impl_read_u32!(self.bios_rom, BIOS_ROM_MASK, address)
     }
 
+    /// Read a byte without side effects, returning `None` if the address does not map to main RAM,
+    /// scratchpad, or BIOS ROM.
+    pub fn peek_u8(&self, address: u32) -> Option<u8> {
+        match address & 0x1FFFFFFF {
+            0x00000000..=0x007FFFFF => Some(self.read_main_ram_u8(address)),
+            0x1F800000..=0x1F8003FF => Some(self.read_scratchpad_u8(address)),
+            0x1FC00000..=0x1FFFFFFF => Some(self.read_bios_u8(address)),
+            _ => None,
+        }
+    }
+
+    /// Write a byte to main RAM or scratchpad, returning `false` if the address does not map to
+    /// either.
+    pub fn poke_u8(&mut self, address: u32, value: u8) -> bool {
+        match address & 0x1FFFFFFF {
+            0x00000000..=0x007FFFFF => self.write_main_ram_u8(address, value),
+            0x1F800000..=0x1F8003FF => self.write_scratchpad_u8(address, value),
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Read a word without side effects, returning `None` if the address does not map to main RAM,
+    /// scratchpad, or BIOS ROM.
+    pub fn peek_u32(&self, address: u32) -> Option<u32> {
+        match address & 0x1FFFFFFF {
+            0x00000000..=0x007FFFFF => Some(self.read_main_ram_u32(address)),
+            0x1F800000..=0x1F8003FF => Some(self.read_scratchpad_u32(address)),
+            0x1FC00000..=0x1FFFFFFF => Some(self.read_bios_u32(address)),
+            _ => None,
+        }
+    }
+
     pub fn read_main_ram_u8(&self, address: u32) -> u8 {
         impl_read_u8!(self.main_ram, MAIN_RAM_MASK, address)
     }
@@ -57,6 +57,7 @@ impl PgxpConfig {
         self.enabled && self.precise_nclip
     }
 
+    #[cfg_attr(not(feature = "wgpu"), allow(dead_code))]
     pub(crate) fn perspective_texture_mapping(self) -> bool {
         self.enabled && self.perspective_texture_mapping
     }
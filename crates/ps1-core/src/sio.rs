@@ -9,7 +9,7 @@ pub mod memcard;
 mod rxfifo;
 
 use crate::api::{LoadedMemoryCards, MemoryCardsEnabled};
-use crate::input::{ControllerState, ControllerType, Ps1Inputs};
+use crate::input::{AnalogMode, ControllerState, ControllerType, Ps1Inputs};
 use crate::interrupts::{InterruptRegisters, InterruptType};
 use crate::num::U32Ext;
 use crate::scheduler::{Scheduler, SchedulerEvent, SchedulerEventType};
@@ -310,6 +310,38 @@ impl SerialPort0 {
         (self.devices.memory_card_1.as_mut(), self.devices.memory_card_2.as_mut())
     }
 
+    /// The bytes a controller would send in response to a standard poll (command $42), starting
+    /// with the ID low byte: buttons, then stick axes if in analog mode. Returns `None` if no
+    /// controller is connected.
+    ///
+    /// Used by the HLE BIOS, which reads controllers without going through the SIO0 registers.
+    pub fn poll_controller(&self, port: Port) -> Option<Vec<u8>> {
+        let (state, dualshock_state) = match port {
+            Port::One => (self.devices.p1_joypad_state, &self.devices.p1_dualshock_state),
+            Port::Two => (self.devices.p2_joypad_state, &self.devices.p2_dualshock_state),
+        };
+
+        let buttons = !u16::from(state.digital);
+        let mut response = vec![0x41, buttons as u8, (buttons >> 8) as u8];
+        match state.controller_type {
+            ControllerType::None => return None,
+            ControllerType::Digital => {}
+            ControllerType::DualShock => {
+                if dualshock_state.analog_mode == AnalogMode::Analog {
+                    response[0] = 0x73;
+                    response.extend([
+                        state.analog.right_x,
+                        state.analog.right_y,
+                        state.analog.left_x,
+                        state.analog.left_y,
+                    ]);
+                }
+            }
+        }
+
+        Some(response)
+    }
+
     pub fn update_memory_cards(&mut self, enabled: MemoryCardsEnabled, loaded: LoadedMemoryCards) {
         self.devices.memory_card_1 = enabled.slot_1.then(|| MemoryCard::new(loaded.slot_1));
         self.devices.memory_card_2 = enabled.slot_2.then(|| MemoryCard::new(loaded.slot_2));
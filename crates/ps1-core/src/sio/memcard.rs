@@ -44,6 +44,22 @@ impl MemoryCard {
         &self.data
     }
 
+    /// Read directly from card data, bypassing the memory card protocol. Used by the HLE BIOS.
+    pub fn read_bytes(&self, offset: usize, out: &mut [u8]) {
+        out.copy_from_slice(&self.data[offset..offset + out.len()]);
+    }
+
+    /// Write directly to card data, bypassing the memory card protocol. Used by the HLE BIOS.
+    pub fn write_bytes(&mut self, offset: usize, data: &[u8]) {
+        self.data[offset..offset + data.len()].copy_from_slice(data);
+        self.written_since_load = true;
+        self.dirty = true;
+    }
+
+    pub fn format(&mut self) {
+        self.write_bytes(0, &new_formatted_memory_card());
+    }
+
     fn flag_byte(&self) -> u8 {
         u8::from(!self.written_since_load) << 3
     }
@@ -0,0 +1,216 @@
+//! TTY output capture: BIOS `putchar()` calls and the DTL-H development board debug UART
+//!
+//! The DTL-H dev boards have a SCN2681 DUART in expansion region 2 which software can use as a
+//! serial console. Only enough of the DUART is emulated to transmit on channel A; everything
+//! written to it is captured as TTY output alongside BIOS `putchar()` output.
+
+use crate::cpu::OpSize;
+use proc_macros::SaveState;
+use std::collections::VecDeque;
+
+// Lines are dropped oldest-first if the frontend does not drain output
+const MAX_BUFFERED_LINES: usize = 1000;
+
+// Guard against software that prints large amounts of text without ever printing a newline
+const MAX_LINE_LEN: usize = 4096;
+
+// SCN2681 register offsets within expansion region 2
+const DUART_STATUS_A: u32 = 0x21;
+const DUART_TX_HOLDING_A: u32 = 0x23;
+
+// SRA bits: transmitter ready (bit 2) and transmitter empty (bit 3); transmits are instant
+const DUART_STATUS_TX_READY: u32 = 0x0C;
+
+// Power-on self test 7-segment display on dev boards
+const POST_REGISTER: u32 = 0x41;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TtySource {
+    /// BIOS `putchar()` calls, A($3C) and B($3D)
+    Bios,
+    /// DTL-H debug UART in expansion region 2
+    DebugUart,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TtyLine {
+    pub source: TtySource,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default, SaveState)]
+pub(crate) struct Tty {
+    #[save_state(skip)]
+    enabled: bool,
+    bios_line: String,
+    uart_line: String,
+    #[save_state(skip)]
+    lines: VecDeque<TtyLine>,
+}
+
+impl Tty {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, ..Self::default() }
+    }
+
+    // Partial lines are restored from the save state, while completed lines that have not been
+    // drained yet are kept
+    pub fn from_state(state: TtyState, current: Self) -> Self {
+        Self { bios_line: state.bios_line, uart_line: state.uart_line, ..current }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn putchar(&mut self, source: TtySource, c: u8) {
+        if !self.enabled {
+            return;
+        }
+
+        let line = match source {
+            TtySource::Bios => &mut self.bios_line,
+            TtySource::DebugUart => &mut self.uart_line,
+        };
+
+        match c {
+            b'\n' => {}
+            // Carriage returns usually precede newlines; don't leave them in the captured text
+            b'\r' => return,
+            _ => {
+                line.push(c as char);
+                if line.len() < MAX_LINE_LEN {
+                    return;
+                }
+            }
+        }
+
+        let text = std::mem::take(line);
+        if self.lines.len() == MAX_BUFFERED_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(TtyLine { source, text });
+    }
+
+    pub fn drain_lines(&mut self) -> impl Iterator<Item = TtyLine> + '_ {
+        self.lines.drain(..)
+    }
+
+    pub fn write_expansion_2(&mut self, address: u32, value: u32, size: OpSize) {
+        match address & 0xFF {
+            DUART_TX_HOLDING_A => self.putchar(TtySource::DebugUart, value as u8),
+            POST_REGISTER => log::debug!("POST register write: {:02X}", value & 0xF),
+            _ => {
+                log::debug!("Unhandled {size:?} expansion 2 write: {address:08X} {value:08X}");
+            }
+        }
+    }
+}
+
+pub fn read_expansion_2(address: u32, size: OpSize) -> u32 {
+    match address & 0xFF {
+        DUART_STATUS_A => DUART_STATUS_TX_READY,
+        _ => {
+            log::debug!("Unhandled {size:?} expansion 2 read {address:08X}");
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn print(tty: &mut Tty, source: TtySource, text: &str) {
+        for c in text.bytes() {
+            tty.putchar(source, c);
+        }
+    }
+
+    fn lines(tty: &mut Tty) -> Vec<(TtySource, String)> {
+        tty.drain_lines().map(|line| (line.source, line.text)).collect()
+    }
+
+    #[test]
+    fn line_assembly() {
+        let mut tty = Tty::new(true);
+
+        print(&mut tty, TtySource::Bios, "Hello\r\nwor");
+        assert_eq!(lines(&mut tty), [(TtySource::Bios, "Hello".into())]);
+
+        // Sources are assembled independently
+        print(&mut tty, TtySource::DebugUart, "uart");
+        print(&mut tty, TtySource::Bios, "ld\n\n");
+        tty.write_expansion_2(0x1F802023, u32::from(b'\n'), OpSize::Byte);
+        assert_eq!(
+            lines(&mut tty),
+            [
+                (TtySource::Bios, "world".into()),
+                (TtySource::Bios, String::new()),
+                (TtySource::DebugUart, "uart".into()),
+            ]
+        );
+        assert!(lines(&mut tty).is_empty());
+    }
+
+    #[test]
+    fn long_lines_are_split() {
+        let mut tty = Tty::new(true);
+
+        print(&mut tty, TtySource::Bios, &"a".repeat(MAX_LINE_LEN + 1));
+        assert_eq!(lines(&mut tty), [(TtySource::Bios, "a".repeat(MAX_LINE_LEN))]);
+
+        print(&mut tty, TtySource::Bios, "\n");
+        assert_eq!(lines(&mut tty), [(TtySource::Bios, "a".into())]);
+    }
+
+    #[test]
+    fn oldest_lines_are_dropped() {
+        let mut tty = Tty::new(true);
+        for i in 0..MAX_BUFFERED_LINES + 2 {
+            print(&mut tty, TtySource::Bios, &format!("{i}\n"));
+        }
+
+        let lines = lines(&mut tty);
+        assert_eq!(lines.len(), MAX_BUFFERED_LINES);
+        assert_eq!(lines[0].1, "2");
+    }
+
+    #[test]
+    fn disabled() {
+        let mut tty = Tty::new(false);
+        print(&mut tty, TtySource::Bios, "ignored");
+
+        tty.set_enabled(true);
+        print(&mut tty, TtySource::Bios, "\n");
+        assert_eq!(lines(&mut tty), [(TtySource::Bios, String::new())]);
+    }
+
+    #[test]
+    fn partial_lines_are_saved() {
+        let mut tty = Tty::new(true);
+        print(&mut tty, TtySource::Bios, "before\nsaved");
+        print(&mut tty, TtySource::DebugUart, "uart");
+        let state = tty.save_state();
+
+        print(&mut tty, TtySource::Bios, " and lost\n");
+        let mut tty = Tty::from_state(state, tty);
+        assert!(tty.enabled());
+
+        print(&mut tty, TtySource::Bios, "\n");
+        print(&mut tty, TtySource::DebugUart, "\n");
+        assert_eq!(
+            lines(&mut tty),
+            [
+                (TtySource::Bios, "before".into()),
+                (TtySource::Bios, "saved and lost".into()),
+                (TtySource::Bios, "saved".into()),
+                (TtySource::DebugUart, "uart".into()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,359 @@
+//! Conformance suite for community PS1 test ROMs (CPU, GTE, GPU, timers, DMA, etc.)
+//!
+//! Each test EXE is sideloaded after the BIOS boots and run until it stops printing TTY output,
+//! and the TTY output is scanned for pass/fail results. Test ROMs are not distributed with the
+//! emulator, so the suite only runs when these environment variables are set:
+//! - `PS1_TEST_BIOS`: Path to a BIOS ROM
+//! - `PS1_TEST_ROMS`: Directory containing test EXEs; searched recursively
+//!
+//! Optionally, `PS1_TEST_ROMS/expected-failures.txt` can list tests that are known to fail, one
+//! per line, using the EXE path relative to the test ROM directory without the extension. Known
+//! failures are reported but do not fail the suite, and an unexpected pass is reported as well so
+//! that the list can be updated. ROMs that print no pass/fail output at all (e.g. tests that only
+//! draw to the screen) are reported but not counted as failures. A ROM that makes the emulator
+//! panic is reported as an error, and the suite continues with the next ROM.
+//!
+//! Run with `cargo test --release -p ps1-core --test test_roms [-- <filter>]`; debug builds are
+//! too slow to run more than a handful of ROMs.
+
+use ps1_core::api::{
+    AudioOutput, CpuFrame, CpuRenderer, MemoryCardSlot, MemoryCardsEnabled, Ps1Emulator,
+    Ps1EmulatorBuilder, Ps1EmulatorConfig, SaveWriter, TickEffect, TtyLine,
+};
+use ps1_core::input::Ps1Inputs;
+use std::any::Any;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::ffi::OsStr;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::{env, fs, io};
+
+const BIOS_VAR: &str = "PS1_TEST_BIOS";
+const ROMS_VAR: &str = "PS1_TEST_ROMS";
+const EXPECTED_FAILURES_FILE: &str = "expected-failures.txt";
+
+// ~60 seconds of emulated time
+const MAX_FRAMES: u32 = 3600;
+
+// Test ROMs generally spin forever after printing results, so stop early once the TTY has been
+// silent for ~5 seconds of emulated time
+const IDLE_FRAMES: u32 = 300;
+
+struct NullOutput;
+
+impl CpuRenderer for NullOutput {
+    type Err = Infallible;
+
+    fn render_frame(&mut self, _frame: CpuFrame<'_>) -> Result<(), Self::Err> {
+        Ok(())
+    }
+}
+
+impl AudioOutput for NullOutput {
+    type Err = Infallible;
+
+    fn queue_samples(&mut self, _samples: &[(i16, i16)]) -> Result<(), Self::Err> {
+        Ok(())
+    }
+}
+
+impl SaveWriter for NullOutput {
+    type Err = Infallible;
+
+    fn save_memory_card(
+        &mut self,
+        _slot: MemoryCardSlot,
+        _card_data: &[u8],
+    ) -> Result<(), Self::Err> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineResult {
+    Pass,
+    Fail,
+    Neither,
+}
+
+const FAIL_WORDS: &[&str] =
+    &["fail", "failed", "failure", "failures", "error", "errors", "mismatch", "wrong"];
+const PASS_WORDS: &[&str] = &["pass", "passed", "ok"];
+
+// Test ROMs don't share an output format, so classify each line by keyword. Summary lines such as
+// "0 failed" or "errors: 0" are not failures
+fn classify_line(line: &str) -> LineResult {
+    let line = line.to_ascii_lowercase();
+    let words: Vec<&str> =
+        line.split(|c: char| !c.is_ascii_alphanumeric()).filter(|word| !word.is_empty()).collect();
+
+    // The count can come before or after the keyword ("0 failed", "errors: 0"); a number after the
+    // keyword takes priority so that "errors: 0, failures: 3" is not read as 0 failures
+    let count_is_zero = |i: usize| match words.get(i + 1) {
+        Some(next) if next.bytes().all(|b| b.is_ascii_digit()) => *next == "0",
+        _ => i > 0 && words[i - 1] == "0",
+    };
+
+    if words.iter().enumerate().any(|(i, word)| FAIL_WORDS.contains(word) && !count_is_zero(i)) {
+        return LineResult::Fail;
+    }
+
+    if words.iter().any(|word| PASS_WORDS.contains(word))
+        || words.iter().enumerate().any(|(i, word)| FAIL_WORDS.contains(word) && count_is_zero(i))
+    {
+        return LineResult::Pass;
+    }
+
+    LineResult::Neither
+}
+
+// This file uses a custom test harness, so the classifier is checked by main() on every run instead
+// of by #[test] functions
+const CLASSIFIER_CASES: &[(&str, LineResult)] = &[
+    ("Test 1: PASS", LineResult::Pass),
+    ("add ... ok", LineResult::Pass),
+    ("All tests passed!", LineResult::Pass),
+    ("0 failed", LineResult::Pass),
+    ("Errors: 0", LineResult::Pass),
+    ("0 errors found", LineResult::Pass),
+    ("Test 3: FAIL", LineResult::Fail),
+    ("GTE RTPS mismatch: expected 00001000, got 00000FFF", LineResult::Fail),
+    ("Errors: 10", LineResult::Fail),
+    ("2 errors", LineResult::Fail),
+    // Any failure outweighs passes on the same line
+    ("12 passed, 1 failed", LineResult::Fail),
+    ("Errors: 0, failures: 3", LineResult::Fail),
+    ("Running GTE tests...", LineResult::Neither),
+    ("", LineResult::Neither),
+    // Keywords must be whole words
+    ("Failsafe mode, okay", LineResult::Neither),
+    ("Passthrough 0", LineResult::Neither),
+];
+
+fn check_classifier() -> Vec<String> {
+    CLASSIFIER_CASES
+        .iter()
+        .filter_map(|&(line, expected)| {
+            let actual = classify_line(line);
+            (actual != expected).then(|| format!("'{line}': expected {expected:?}, got {actual:?}"))
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+enum Outcome {
+    Passed { checks: usize },
+    Failed { failures: Vec<String> },
+    NoResults,
+    Error(String),
+}
+
+fn run_rom(bios: &[u8], exe_path: &Path) -> Outcome {
+    let exe = match fs::read(exe_path) {
+        Ok(exe) => exe,
+        Err(err) => return Outcome::Error(format!("Failed to read EXE: {err}")),
+    };
+
+    let config =
+        Ps1EmulatorConfig { tty_enabled: true, ram_seed: Some(0), ..Ps1EmulatorConfig::default() };
+    let emulator = Ps1EmulatorBuilder::new(bios.to_vec())
+        .with_config(config)
+        .with_memory_cards_enabled(MemoryCardsEnabled { slot_1: false, slot_2: false })
+        .build();
+    let mut emulator = match emulator {
+        Ok(emulator) => emulator,
+        Err(err) => return Outcome::Error(format!("Failed to create emulator: {err}")),
+    };
+
+    if let Err(err) = emulator.run_until_exe_sideloaded(&exe) {
+        return Outcome::Error(format!("Failed to sideload EXE: {err}"));
+    }
+
+    let lines = match run_until_idle(&mut emulator) {
+        Ok(lines) => lines,
+        Err(err) => return Outcome::Error(format!("Emulation error: {err}")),
+    };
+
+    let mut checks = 0;
+    let mut failures = Vec::new();
+    for line in lines {
+        match classify_line(&line.text) {
+            LineResult::Pass => checks += 1,
+            LineResult::Fail => failures.push(line.text),
+            LineResult::Neither => {}
+        }
+    }
+
+    if !failures.is_empty() {
+        Outcome::Failed { failures }
+    } else if checks != 0 {
+        Outcome::Passed { checks }
+    } else {
+        Outcome::NoResults
+    }
+}
+
+fn run_until_idle(emulator: &mut Ps1Emulator) -> Result<Vec<TtyLine>, String> {
+    let mut lines = Vec::new();
+    let mut frame = 0;
+    let mut last_output_frame = 0;
+
+    while frame < MAX_FRAMES && (lines.is_empty() || frame - last_output_frame < IDLE_FRAMES) {
+        let effect = emulator
+            .tick_with_cpu_renderer(
+                Ps1Inputs::default(),
+                &mut NullOutput,
+                &mut NullOutput,
+                &mut NullOutput,
+            )
+            .map_err(|err| err.to_string())?;
+
+        if effect == TickEffect::FrameRendered {
+            frame += 1;
+
+            let len = lines.len();
+            lines.extend(emulator.drain_tty_output());
+            if lines.len() != len {
+                last_output_frame = frame;
+            }
+        }
+    }
+
+    Ok(lines)
+}
+
+fn find_exes(dir: &Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_exes(&path, out)?;
+        } else if path
+            .extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("exe") || ext.eq_ignore_ascii_case("psx"))
+        {
+            out.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn test_name(roms_dir: &Path, exe_path: &Path) -> String {
+    let relative = exe_path.strip_prefix(roms_dir).unwrap_or(exe_path).with_extension("");
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+fn read_expected_failures(roms_dir: &Path) -> HashSet<String> {
+    let Ok(contents) = fs::read_to_string(roms_dir.join(EXPECTED_FAILURES_FILE)) else {
+        return HashSet::new();
+    };
+
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic payload")
+}
+
+fn main() -> ExitCode {
+    let classifier_errors = check_classifier();
+    if !classifier_errors.is_empty() {
+        for error in &classifier_errors {
+            eprintln!("Line classifier check failed for {error}");
+        }
+        return ExitCode::FAILURE;
+    }
+
+    let (Some(bios_path), Some(roms_dir)) = (env::var_os(BIOS_VAR), env::var_os(ROMS_VAR)) else {
+        println!("Skipping test ROM suite; set {BIOS_VAR} and {ROMS_VAR} to run it");
+        return ExitCode::SUCCESS;
+    };
+    let roms_dir = PathBuf::from(roms_dir);
+
+    // Ignore libtest flags passed through by cargo test; treat anything else as a name filter
+    let filter = env::args().skip(1).find(|arg| !arg.starts_with('-'));
+
+    let bios = match fs::read(&bios_path) {
+        Ok(bios) => bios,
+        Err(err) => {
+            eprintln!("Failed to read BIOS from '{}': {err}", Path::new(&bios_path).display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut exe_paths = Vec::new();
+    if let Err(err) = find_exes(&roms_dir, &mut exe_paths) {
+        eprintln!("Failed to search test ROM directory '{}': {err}", roms_dir.display());
+        return ExitCode::FAILURE;
+    }
+    exe_paths.sort();
+
+    let expected_failures = read_expected_failures(&roms_dir);
+
+    let mut failed = Vec::new();
+    let mut unexpected_passes = Vec::new();
+    let mut run_count = 0;
+    for exe_path in &exe_paths {
+        let name = test_name(&roms_dir, exe_path);
+        if filter.as_ref().is_some_and(|filter| !name.contains(filter.as_str())) {
+            continue;
+        }
+        run_count += 1;
+
+        // A panic in the emulator only fails the ROM that triggered it
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| run_rom(&bios, exe_path)))
+            .unwrap_or_else(|payload| {
+                Outcome::Error(format!("panicked: {}", panic_message(&*payload)))
+            });
+        let expected_failure = expected_failures.contains(&name);
+
+        match (&outcome, expected_failure) {
+            (Outcome::Passed { checks }, false) => println!("{name} ... ok ({checks} passed)"),
+            (Outcome::Passed { checks }, true) => {
+                println!("{name} ... ok ({checks} passed), but listed as an expected failure");
+                unexpected_passes.push(name);
+            }
+            (Outcome::Failed { failures }, _) => {
+                let label = if expected_failure { "FAILED (expected)" } else { "FAILED" };
+                println!("{name} ... {label}");
+                for failure in failures {
+                    println!("    {failure}");
+                }
+
+                if !expected_failure {
+                    failed.push(name);
+                }
+            }
+            (Outcome::NoResults, _) => println!("{name} ... no pass/fail output"),
+            (Outcome::Error(err), _) => {
+                println!("{name} ... ERROR: {err}");
+                failed.push(name);
+            }
+        }
+    }
+
+    println!();
+    println!("{} run, {} failed", run_count, failed.len());
+    if !unexpected_passes.is_empty() {
+        println!("Unexpected passes: {}", unexpected_passes.join(", "));
+    }
+
+    if failed.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        println!("Failed tests: {}", failed.join(", "));
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,224 @@
+//! Headless regression test runner.
+//!
+//! Boots each test case in a manifest with the software rasterizer, runs it for a fixed number of
+//! frames with scripted inputs, and compares hashes of all rendered frames and audio samples
+//! against the golden hashes stored in the manifest.
+
+mod manifest;
+mod runner;
+
+use crate::manifest::{Manifest, TestCase};
+use crate::runner::{FinalFrame, RunnerConfig, TestOutput};
+use anyhow::{Context, anyhow};
+use clap::Parser;
+use env_logger::Env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// Path to the test manifest (TOML)
+    manifest: PathBuf,
+
+    /// BIOS path to use instead of the path in the manifest
+    #[arg(long)]
+    bios: Option<PathBuf>,
+
+    /// Only run tests whose name contains this string
+    #[arg(long)]
+    filter: Option<String>,
+
+    /// Record the current output as the golden hashes for every test that was run. Rewrites the
+    /// manifest file; comments are not preserved
+    #[arg(long, default_value_t)]
+    bless: bool,
+
+    /// Directory to write the final frame of each failed test to, as a PPM image
+    #[arg(long)]
+    dump_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TestResult {
+    Passed,
+    Failed,
+    Blessed,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_env(Env::default().default_filter_or("warn")).init();
+
+    let args = Args::parse();
+
+    let mut manifest = Manifest::load(&args.manifest)?;
+    let base_dir = args.manifest.parent().unwrap_or(Path::new("."));
+
+    let bios_path = args.bios.clone().unwrap_or_else(|| base_dir.join(&manifest.bios));
+    let bios = fs::read(&bios_path)
+        .with_context(|| format!("Failed to read BIOS from '{}'", bios_path.display()))?;
+
+    let runner_config =
+        RunnerConfig { bios, rasterizer: manifest.rasterizer, ram_seed: manifest.ram_seed };
+
+    if let Some(dump_dir) = &args.dump_dir {
+        fs::create_dir_all(dump_dir)
+            .with_context(|| format!("Failed to create dump directory '{}'", dump_dir.display()))?;
+    }
+
+    let mut failed = Vec::new();
+    let mut run_count = 0;
+    for test in &mut manifest.tests {
+        if args.filter.as_ref().is_some_and(|filter| !test.name.contains(filter.as_str())) {
+            continue;
+        }
+        run_count += 1;
+
+        print!("{} ... ", test.name);
+        let _ = std::io::stdout().flush();
+
+        let start = Instant::now();
+        let output = match runner::run_test(&runner_config, test, base_dir) {
+            Ok(output) => output,
+            Err(err) => {
+                println!("ERROR: {err:#}");
+                failed.push(test.name.clone());
+                continue;
+            }
+        };
+        let elapsed = start.elapsed();
+
+        let result = if args.bless {
+            test.video_hash = Some(output.video_hash.clone());
+            test.audio_hash = Some(output.audio_hash.clone());
+            TestResult::Blessed
+        } else if hashes_match(test, &output) {
+            TestResult::Passed
+        } else {
+            TestResult::Failed
+        };
+
+        match result {
+            TestResult::Passed => println!("ok ({elapsed:.2?})"),
+            TestResult::Blessed => println!(
+                "blessed (video={}, audio={}) ({elapsed:.2?})",
+                output.video_hash, output.audio_hash
+            ),
+            TestResult::Failed => {
+                println!("FAILED ({elapsed:.2?})");
+                let mismatches = [
+                    hash_mismatch("video", test.video_hash.as_deref(), &output.video_hash),
+                    hash_mismatch("audio", test.audio_hash.as_deref(), &output.audio_hash),
+                ];
+                for mismatch in mismatches.into_iter().flatten() {
+                    println!("  {mismatch}");
+                }
+                failed.push(test.name.clone());
+
+                if let (Some(dump_dir), Some(frame)) = (&args.dump_dir, &output.final_frame) {
+                    let path = dump_dir.join(format!("{}.ppm", test.name));
+                    write_ppm(&path, frame)?;
+                    println!("  final frame written to '{}'", path.display());
+                }
+            }
+        }
+    }
+
+    if args.bless {
+        manifest.save(&args.manifest)?;
+    }
+
+    println!("{} passed, {} failed", run_count - failed.len(), failed.len());
+
+    if !failed.is_empty() {
+        return Err(anyhow!("Failed tests: {}", failed.join(", ")));
+    }
+
+    Ok(())
+}
+
+// A test without golden hashes never passes
+fn hashes_match(test: &TestCase, output: &TestOutput) -> bool {
+    test.video_hash.as_ref() == Some(&output.video_hash)
+        && test.audio_hash.as_ref() == Some(&output.audio_hash)
+}
+
+fn hash_mismatch(label: &str, expected: Option<&str>, actual: &str) -> Option<String> {
+    match expected {
+        Some(expected) if expected == actual => None,
+        Some(expected) => Some(format!("{label} hash mismatch: expected {expected}, got {actual}")),
+        None => Some(format!("no golden {label} hash; got {actual}")),
+    }
+}
+
+fn write_ppm(path: &Path, frame: &FinalFrame) -> anyhow::Result<()> {
+    fs::write(path, to_ppm(frame))
+        .with_context(|| format!("Failed to write frame to '{}'", path.display()))
+}
+
+fn to_ppm(frame: &FinalFrame) -> Vec<u8> {
+    let mut contents = format!("P6\n{} {}\n255\n", frame.width, frame.height).into_bytes();
+    contents.extend_from_slice(&frame.rgb);
+    contents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_case(video_hash: Option<&str>, audio_hash: Option<&str>) -> TestCase {
+        TestCase {
+            name: "test".into(),
+            file: None,
+            frames: 1,
+            inputs: Vec::new(),
+            video_hash: video_hash.map(String::from),
+            audio_hash: audio_hash.map(String::from),
+        }
+    }
+
+    fn output(video_hash: &str, audio_hash: &str) -> TestOutput {
+        TestOutput {
+            video_hash: video_hash.into(),
+            audio_hash: audio_hash.into(),
+            final_frame: None,
+        }
+    }
+
+    #[test]
+    fn hash_comparison() {
+        let output = output("0123456789abcdef", "fedcba9876543210");
+
+        let test = test_case(Some("0123456789abcdef"), Some("fedcba9876543210"));
+        assert!(hashes_match(&test, &output));
+
+        let test = test_case(Some("0123456789abcdef"), Some("0000000000000000"));
+        assert!(!hashes_match(&test, &output));
+
+        let test = test_case(Some("0000000000000000"), Some("fedcba9876543210"));
+        assert!(!hashes_match(&test, &output));
+
+        let test = test_case(None, Some("fedcba9876543210"));
+        assert!(!hashes_match(&test, &output));
+    }
+
+    #[test]
+    fn mismatch_report() {
+        assert_eq!(hash_mismatch("video", Some("aa"), "aa"), None);
+        assert_eq!(
+            hash_mismatch("video", Some("aa"), "bb").as_deref(),
+            Some("video hash mismatch: expected aa, got bb")
+        );
+        assert_eq!(
+            hash_mismatch("audio", None, "bb").as_deref(),
+            Some("no golden audio hash; got bb")
+        );
+    }
+
+    #[test]
+    fn ppm_format() {
+        let frame = FinalFrame { rgb: vec![1, 2, 3, 4, 5, 6], width: 2, height: 1 };
+        assert_eq!(to_ppm(&frame), b"P6\n2 1\n255\n\x01\x02\x03\x04\x05\x06");
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::Context;
+use ps1_core::input::DigitalJoypadState;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Rasterizer {
+    // The naive software rasterizer is the default because it is available on every host; the
+    // SIMD rasterizer requires AVX2
+    #[default]
+    NaiveSoftware,
+    SimdSoftware,
+}
+
+impl Rasterizer {
+    pub fn to_rasterizer_type(self) -> ps1_core::RasterizerType {
+        match self {
+            Self::NaiveSoftware => ps1_core::RasterizerType::NaiveSoftware,
+            Self::SimdSoftware => ps1_core::RasterizerType::SimdSoftware,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Button {
+    Up,
+    Down,
+    Left,
+    Right,
+    Cross,
+    Circle,
+    Square,
+    Triangle,
+    L1,
+    L2,
+    R1,
+    R2,
+    Start,
+    Select,
+}
+
+pub fn buttons_to_joypad_state(buttons: &[Button]) -> DigitalJoypadState {
+    let mut state = DigitalJoypadState::default();
+
+    for &button in buttons {
+        state = match button {
+            Button::Up => state.with_up(true),
+            Button::Down => state.with_down(true),
+            Button::Left => state.with_left(true),
+            Button::Right => state.with_right(true),
+            Button::Cross => state.with_cross(true),
+            Button::Circle => state.with_circle(true),
+            Button::Square => state.with_square(true),
+            Button::Triangle => state.with_triangle(true),
+            Button::L1 => state.with_l1(true),
+            Button::L2 => state.with_l2(true),
+            Button::R1 => state.with_r1(true),
+            Button::R2 => state.with_r2(true),
+            Button::Start => state.with_start(true),
+            Button::Select => state.with_select(true),
+        };
+    }
+
+    state
+}
+
+/// Change in controller state at the start of the given frame. Buttons remain held until the next
+/// input event that changes them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputEvent {
+    pub frame: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub p1: Vec<Button>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub p2: Vec<Button>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestCase {
+    pub name: String,
+    /// CUE, CHD, or EXE file; relative paths are resolved relative to the manifest. Will boot
+    /// the BIOS with no disc if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    pub frames: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<InputEvent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub video_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Relative paths are resolved relative to the manifest
+    pub bios: PathBuf,
+    #[serde(default)]
+    pub rasterizer: Rasterizer,
+    #[serde(default)]
+    pub ram_seed: u64,
+    #[serde(rename = "test")]
+    pub tests: Vec<TestCase>,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest from '{}'", path.display()))?;
+        let manifest: Self = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse manifest at '{}'", path.display()))?;
+
+        for test in &manifest.tests {
+            if !test.inputs.is_sorted_by_key(|input| input.frame) {
+                anyhow::bail!("Input events for test '{}' are not sorted by frame", test.name);
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let contents = toml::to_string_pretty(self).context("Failed to serialize manifest")?;
+        fs::write(path, contents)
+            .with_context(|| format!("Failed to write manifest to '{}'", path.display()))
+    }
+}
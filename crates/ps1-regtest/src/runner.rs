@@ -0,0 +1,227 @@
+use crate::manifest::{self, Rasterizer, TestCase};
+use anyhow::{Context, anyhow};
+use cdrom::reader::{CdRom, CdRomFileFormat};
+use crc::{CRC_64_XZ, Crc, Digest};
+use ps1_core::api::{
+    AudioOutput, CpuFrame, CpuRenderer, DisplayConfig, MemoryCardSlot, MemoryCardsEnabled,
+    Ps1EmulatorBuilder, Ps1EmulatorConfig, SaveWriter, TickEffect,
+};
+use ps1_core::input::{ControllerType, Ps1Inputs};
+use std::convert::Infallible;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+
+static HASH_CRC: Crc<u64> = Crc::<u64>::new(&CRC_64_XZ);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestOutput {
+    pub video_hash: String,
+    pub audio_hash: String,
+    pub final_frame: Option<FinalFrame>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinalFrame {
+    pub rgb: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+struct HashingRenderer {
+    digest: Digest<'static, u64>,
+    // Set before the tick that renders the last frame; earlier frames are only hashed
+    capture_frame: bool,
+    final_frame: Option<FinalFrame>,
+}
+
+impl HashingRenderer {
+    fn new() -> Self {
+        Self { digest: HASH_CRC.digest(), capture_frame: false, final_frame: None }
+    }
+}
+
+impl CpuRenderer for HashingRenderer {
+    type Err = Infallible;
+
+    fn render_frame(&mut self, frame: CpuFrame<'_>) -> Result<(), Self::Err> {
+        self.digest.update(&frame.width.to_le_bytes());
+        self.digest.update(&frame.height.to_le_bytes());
+        self.digest.update(frame.rgba);
+
+        if self.capture_frame {
+            self.final_frame = Some(FinalFrame {
+                rgb: frame.to_rgb24(),
+                width: frame.width,
+                height: frame.height,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+struct HashingAudioOutput {
+    digest: Digest<'static, u64>,
+}
+
+impl AudioOutput for HashingAudioOutput {
+    type Err = Infallible;
+
+    fn queue_samples(&mut self, samples: &[(i16, i16)]) -> Result<(), Self::Err> {
+        for &(l, r) in samples {
+            self.digest.update(&l.to_le_bytes());
+            self.digest.update(&r.to_le_bytes());
+        }
+
+        Ok(())
+    }
+}
+
+// Memory cards are disabled during regression tests
+struct NullSaveWriter;
+
+impl SaveWriter for NullSaveWriter {
+    type Err = Infallible;
+
+    fn save_memory_card(
+        &mut self,
+        _slot: MemoryCardSlot,
+        _card_data: &[u8],
+    ) -> Result<(), Self::Err> {
+        Ok(())
+    }
+}
+
+pub struct RunnerConfig {
+    pub bios: Vec<u8>,
+    pub rasterizer: Rasterizer,
+    pub ram_seed: u64,
+}
+
+impl RunnerConfig {
+    fn to_emulator_config(&self) -> Ps1EmulatorConfig {
+        Ps1EmulatorConfig {
+            display: DisplayConfig {
+                rasterizer_type: self.rasterizer.to_rasterizer_type(),
+                ..DisplayConfig::default()
+            },
+            ram_seed: Some(self.ram_seed),
+            ..Ps1EmulatorConfig::default()
+        }
+    }
+}
+
+pub fn run_test(
+    config: &RunnerConfig,
+    test: &TestCase,
+    base_dir: &Path,
+) -> anyhow::Result<TestOutput> {
+    let builder = Ps1EmulatorBuilder::new(config.bios.clone())
+        .with_config(config.to_emulator_config())
+        .with_memory_cards_enabled(MemoryCardsEnabled { slot_1: false, slot_2: false });
+
+    let mut emulator = match &test.file {
+        Some(file) => {
+            let path = base_dir.join(file);
+            match path.extension().and_then(OsStr::to_str) {
+                Some(ext) if ext.eq_ignore_ascii_case("exe") => {
+                    let exe = fs::read(&path)
+                        .with_context(|| format!("Failed to read EXE from '{}'", path.display()))?;
+
+                    let mut emulator = builder.build()?;
+                    emulator.run_until_exe_sideloaded(&exe)?;
+                    emulator
+                }
+                _ => {
+                    let format = CdRomFileFormat::from_file_path(&path).ok_or_else(|| {
+                        anyhow!("Unsupported file extension for '{}'", path.display())
+                    })?;
+                    let disc = CdRom::open(&path, format).with_context(|| {
+                        format!("Failed to open disc image '{}'", path.display())
+                    })?;
+                    builder.with_disc(disc).build()?
+                }
+            }
+        }
+        None => builder.build()?,
+    };
+
+    let mut renderer = HashingRenderer::new();
+    let mut audio_output = HashingAudioOutput { digest: HASH_CRC.digest() };
+    let mut save_writer = NullSaveWriter;
+
+    let mut inputs = Ps1Inputs::default();
+    if test.inputs.iter().any(|event| !event.p2.is_empty()) {
+        inputs.p2.controller_type = ControllerType::Digital;
+    }
+
+    let mut input_events = test.inputs.iter().peekable();
+
+    let mut frame = 0;
+    while frame < test.frames {
+        while let Some(event) = input_events.next_if(|event| event.frame <= frame) {
+            inputs.p1.digital = manifest::buttons_to_joypad_state(&event.p1);
+            inputs.p2.digital = manifest::buttons_to_joypad_state(&event.p2);
+        }
+
+        renderer.capture_frame = frame + 1 == test.frames;
+        if emulator.tick_with_cpu_renderer(
+            inputs,
+            &mut renderer,
+            &mut audio_output,
+            &mut save_writer,
+        )? == TickEffect::FrameRendered
+        {
+            frame += 1;
+        }
+    }
+
+    Ok(TestOutput {
+        video_hash: format!("{:016x}", renderer.digest.finalize()),
+        audio_hash: format!("{:016x}", audio_output.digest.finalize()),
+        final_frame: renderer.final_frame,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(renderer: &mut HashingRenderer, rgba: &[u8]) {
+        let frame = CpuFrame { rgba, width: 2, height: 1, pixel_aspect_ratio: 1.0 };
+        renderer.render_frame(frame).unwrap();
+    }
+
+    #[test]
+    fn only_final_frame_is_captured() {
+        let mut renderer = HashingRenderer::new();
+        render(&mut renderer, &[1, 2, 3, 255, 4, 5, 6, 255]);
+        assert_eq!(renderer.final_frame, None);
+
+        renderer.capture_frame = true;
+        render(&mut renderer, &[7, 8, 9, 255, 10, 11, 12, 255]);
+        assert_eq!(
+            renderer.final_frame,
+            Some(FinalFrame { rgb: vec![7, 8, 9, 10, 11, 12], width: 2, height: 1 })
+        );
+    }
+
+    #[test]
+    fn video_hash_covers_every_frame() {
+        let hash = |frames: &[&[u8]]| {
+            let mut renderer = HashingRenderer::new();
+            for frame in frames {
+                render(&mut renderer, frame);
+            }
+            renderer.digest.finalize()
+        };
+
+        let a: &[u8] = &[0; 8];
+        let b: &[u8] = &[0, 0, 0, 0, 0, 0, 1, 0];
+        assert_eq!(hash(&[a, b]), hash(&[a, b]));
+        assert_ne!(hash(&[a, b]), hash(&[b, a]));
+        assert_ne!(hash(&[a, b]), hash(&[a, a]));
+        assert_ne!(hash(&[a]), hash(&[a, a]));
+    }
+}